@@ -0,0 +1,48 @@
+//! Compares `ArcDefer` with `ArcDeferGuard`.
+//!
+//! Run with `cargo bench --bench arc_defer`.
+
+use defer_heavy::{ArcDefer, ArcDeferGuard};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+const CLONES: usize = 8;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    //Warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>8.2} ns/iter",
+        name,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    bench("ArcDeferGuard", || {
+        let guard = ArcDeferGuard::new(|| {
+            black_box(());
+        });
+        let clones: [_; CLONES] = core::array::from_fn(|_| guard.clone());
+        drop(black_box(guard));
+        drop(black_box(clones));
+    });
+
+    bench("ArcDefer", || {
+        let guard = ArcDefer::new(|| {
+            black_box(());
+        });
+        let clones: [_; CLONES] = core::array::from_fn(|_| guard.clone());
+        drop(black_box(guard));
+        drop(black_box(clones));
+    });
+}
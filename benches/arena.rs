@@ -0,0 +1,50 @@
+//! Compares `DeferArena` with `DeferStack` in a loop that reuses them.
+//!
+//! Run with `cargo bench --bench arena`.
+
+use defer_heavy::{DeferArena, DeferStack};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+const CLOSURES: u64 = 8;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    //Warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>8.2} ns/iter",
+        name,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let mut stack = DeferStack::new();
+    bench("DeferStack", || {
+        for i in 0..CLOSURES {
+            stack.push(move || {
+                black_box(i);
+            });
+        }
+        stack.run_all();
+    });
+
+    let mut arena = DeferArena::with_capacity(CLOSURES as usize, 16);
+    bench("DeferArena", || {
+        for i in 0..CLOSURES {
+            arena.push(move || {
+                black_box(i);
+            });
+        }
+        arena.run_all();
+    });
+}
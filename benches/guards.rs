@@ -0,0 +1,102 @@
+//! Compares the guards with hand written `Drop` implementations.
+//!
+//! Run with `cargo bench --bench guards`.
+//! To compare a change, run `cargo bench --bench guards -- --save-baseline main` without the change
+//! and `cargo bench --bench guards -- --baseline main` with it, on the same machine.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use defer_heavy::{defer, ArcDeferGuard, DeferGuard};
+use std::hint::black_box;
+
+const CLONES: usize = 8;
+
+struct ManualGuard;
+
+impl Drop for ManualGuard {
+    fn drop(&mut self) {
+        black_box(());
+    }
+}
+
+struct ManualCaptureGuard(Vec<u8>);
+
+impl Drop for ManualCaptureGuard {
+    fn drop(&mut self) {
+        black_box(self.0.len());
+    }
+}
+
+fn zst() {
+    black_box(());
+}
+
+//A guard with a zero sized closure only stores whether the closure is still pending.
+#[cfg(not(any(
+    feature = "leak-detect",
+    feature = "stats",
+    feature = "slow-defer-warning"
+)))]
+const _: () = {
+    const fn size_of_guard<F: FnOnce()>(_: &F) -> usize {
+        size_of::<DeferGuard<F>>()
+    }
+
+    assert!(size_of_guard(&zst) == size_of::<bool>());
+};
+
+fn nested(depth: u32) {
+    if depth == 0 {
+        return;
+    }
+
+    defer! { black_box(depth); }
+    nested(depth - 1);
+}
+
+fn guards(c: &mut Criterion) {
+    let mut group = c.benchmark_group("guards");
+
+    group.bench_function("manual_drop_zst", |b| {
+        b.iter(|| drop(black_box(ManualGuard)))
+    });
+
+    group.bench_function("defer_guard_zst", |b| {
+        b.iter(|| drop(black_box(DeferGuard::new(zst))))
+    });
+
+    group.bench_function("manual_drop_vec", |b| {
+        b.iter(|| drop(black_box(ManualCaptureGuard(black_box(vec![1, 2, 3])))))
+    });
+
+    group.bench_function("defer_guard_vec", |b| {
+        b.iter(|| {
+            let data = black_box(vec![1u8, 2, 3]);
+            drop(black_box(DeferGuard::new(move || {
+                black_box(data.len());
+            })));
+        })
+    });
+
+    group.bench_function("defer_guard_cancel_ref", |b| {
+        b.iter(|| {
+            let mut guard = black_box(DeferGuard::new(zst));
+            black_box(guard.cancel_ref());
+        })
+    });
+
+    group.bench_function("arc_defer_guard_clone_drop", |b| {
+        b.iter(|| {
+            let guard = ArcDeferGuard::new(zst);
+            let clones: [_; CLONES] = core::array::from_fn(|_| guard.clone());
+            drop(black_box(clones));
+            drop(black_box(guard));
+        })
+    });
+
+    group.bench_function("defer_nested_10", |b| b.iter(|| nested(black_box(10))));
+
+    group.finish();
+}
+
+criterion_group!(benches, guards);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+//! Compares the scope macros with a hand written `Drop` implementation in tight loops.
+//!
+//! Run with `cargo bench-all` or `cargo bench --bench macros`.
+//!
+//! In release builds `defer!` compiles to the same machine code as the hand written `Drop` implementation,
+//! the `Option` of the guard is removed because the closure is known to be present on the drop path.
+//! `defer_guard!` followed by a conditional `cancel` and `defer_conditioned!` only add the branch on the condition itself.
+//! Enabling `tracing`, `stats` or `leak-detect` adds the work of these features to every guard.
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use defer_heavy::{defer, defer_conditioned, defer_guard};
+use std::hint::black_box;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn increment() {
+    COUNTER.fetch_add(1, Relaxed);
+}
+
+struct ManualGuard;
+
+impl Drop for ManualGuard {
+    fn drop(&mut self) {
+        increment();
+    }
+}
+
+///
+/// Benchmarks the closure with a different value in every iteration,
+/// so the conditions cannot be hoisted out of the loop.
+///
+fn bench_counted(group: &mut BenchmarkGroup<'_, WallTime>, id: &str, mut f: impl FnMut(u32)) {
+    group.bench_function(id, |b| {
+        let mut i = 0u32;
+        b.iter(|| {
+            i = i.wrapping_add(1);
+            f(i);
+        })
+    });
+}
+
+fn macros(c: &mut Criterion) {
+    let mut group = c.benchmark_group("macros");
+
+    bench_counted(&mut group, "manual_drop", |i| {
+        let _guard = ManualGuard;
+        black_box(i);
+    });
+
+    bench_counted(&mut group, "defer", |i| {
+        defer! { increment(); }
+        black_box(i);
+    });
+
+    bench_counted(&mut group, "defer_conditioned_true", |i| {
+        let _guard = defer_conditioned!(black_box(true), {
+            increment();
+        });
+        black_box(i);
+    });
+
+    bench_counted(&mut group, "defer_guard_never_canceled", |i| {
+        let _guard = defer_guard! { increment(); };
+        black_box(i);
+    });
+
+    bench_counted(&mut group, "defer_guard_canceled_on_odd", |i| {
+        let guard = defer_guard! { increment(); };
+        if black_box(i) % 2 == 1 {
+            guard.cancel();
+        }
+    });
+
+    group.finish();
+    black_box(COUNTER.load(Relaxed));
+}
+
+criterion_group!(benches, macros);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-env-changed=RUSTC");
+    println!("cargo::rustc-check-cfg=cfg(defer_heavy_nightly)");
+
+    //The attributes that need nightly are only used by the lint-attrs feature.
+    if env::var_os("CARGO_FEATURE_LINT_ATTRS").is_none() {
+        return;
+    }
+
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let Ok(output) = Command::new(rustc).arg("--version").output() else {
+        return;
+    };
+
+    let version = String::from_utf8_lossy(&output.stdout);
+    if version.contains("-nightly") || version.contains("-dev") {
+        println!("cargo::rustc-cfg=defer_heavy_nightly");
+    }
+}
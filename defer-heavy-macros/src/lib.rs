@@ -0,0 +1,78 @@
+//! Procedural macros for the `defer-heavy` crate.
+//!
+//! Use them through the `proc-macro` feature of `defer-heavy`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, Ident, ImplItem, ItemImpl};
+
+///
+/// Implements `Drop` by calling a method of the annotated impl block.
+///
+/// See the documentation of `defer_heavy::defer_drop`.
+///
+#[proc_macro_attribute]
+pub fn defer_drop(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let method = parse_macro_input!(attr as Ident);
+    let item = parse_macro_input!(item as ItemImpl);
+    expand(method, item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(method: Ident, item: ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "`#[defer_drop]` must be applied to an inherent impl block",
+        ));
+    }
+
+    let func = item
+        .items
+        .iter()
+        .find_map(|item| match item {
+            ImplItem::Fn(func) if func.sig.ident == method => Some(func),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            Error::new(
+                method.span(),
+                format!("no method `{}` found in this impl block", method),
+            )
+        })?;
+
+    let sig = &func.sig;
+    let by_reference = sig.receiver().is_some_and(|r| r.reference.is_some());
+    if !by_reference || sig.inputs.len() != 1 {
+        return Err(Error::new(
+            sig.inputs.span(),
+            format!(
+                "`{}` must take `&mut self` or `&self` and no other arguments",
+                method
+            ),
+        ));
+    }
+
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.span(),
+            format!("`{}` must not be async or generic", method),
+        ));
+    }
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        #[automatically_derived]
+        impl #impl_generics ::core::ops::Drop for #self_ty #where_clause {
+            fn drop(&mut self) {
+                Self::#method(self);
+            }
+        }
+    })
+}
@@ -0,0 +1,76 @@
+#ifndef DEFER_HEAVY_H
+#define DEFER_HEAVY_H
+
+/* Generated with cbindgen. Do not edit this file manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ *
+ * Cancels the closure and releases the reference held by the pointer.
+ * The closure will not be called anymore, even if other references to the guard exist.
+ * Does nothing if the pointer is null.
+ *
+ * # Safety
+ * The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+ * The pointer must not be used afterward.
+ *
+ */
+void defer_arc_cancel(const void *ptr);
+
+/**
+ *
+ * Releases the reference held by the pointer.
+ * If this was the last reference and the guard was not canceled, the closure is called.
+ * Does nothing if the pointer is null.
+ *
+ * # Safety
+ * The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+ * The pointer must not be used afterward.
+ *
+ */
+void defer_arc_destroy(const void *ptr);
+
+/**
+ *
+ * Creates a new reference to the guard.
+ *
+ * # Returns
+ * A pointer that holds the new reference or null if the pointer is null.
+ *
+ * # Safety
+ * The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`
+ * and must not have been released yet.
+ *
+ */
+const void *defer_arc_clone(const void *ptr);
+
+/**
+ *
+ * Releases the reference held by the pointer without calling the closure.
+ * If this was the last reference, the closure is dropped without being called.
+ * Does nothing if the pointer is null.
+ *
+ * With the `triomphe` feature the closure may still be called if another thread
+ * releases the second to last reference at the same time.
+ * Without it, this function never calls the closure.
+ *
+ * # Safety
+ * The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+ * The pointer must not be used afterward.
+ *
+ */
+void defer_arc_free(const void *ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DEFER_HEAVY_H */
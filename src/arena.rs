@@ -0,0 +1,251 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of, MaybeUninit};
+
+///
+/// Unit of the inline storage of a `DeferArena`.
+/// Closures with a higher alignment are boxed.
+///
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct Chunk([MaybeUninit<u8>; 16]);
+
+const CHUNK: usize = size_of::<Chunk>();
+
+enum ArenaEntry<'a> {
+    Inline {
+        slot: usize,
+        call: unsafe fn(*mut u8),
+        drop: unsafe fn(*mut u8),
+    },
+    Boxed(Box<dyn FnOnce() + 'a>),
+}
+
+unsafe fn call_inline<F: FnOnce()>(ptr: *mut u8) {
+    //SAFETY: the caller guarantees that the slot holds an F that is not used afterward.
+    let func = unsafe { core::ptr::read(ptr as *mut F) };
+    func();
+}
+
+unsafe fn drop_inline<F: FnOnce()>(ptr: *mut u8) {
+    //SAFETY: the caller guarantees that the slot holds an F that is not used afterward.
+    unsafe { core::ptr::drop_in_place(ptr as *mut F) };
+}
+
+///
+/// A stack of deferred closures with different types that are stored in preallocated slots.
+///
+/// Unlike `DeferStack`, pushing a closure does not allocate if it fits into a slot.
+/// Closures that are larger than the slot size, need an alignment of more than 16 bytes
+/// or do not fit because all slots are used are boxed by `push`.
+/// `try_push` returns the closure instead.
+///
+/// The closures are called in reverse order of registration when `run_all` is called or when the arena is dropped.
+/// The arena is empty afterward and keeps its slots, so it can be reused without allocating.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferArena;
+///
+/// let mut arena = DeferArena::with_capacity(4, 32);
+/// for request in 0..3 {
+///     arena.push(move || println!("Cleanup second {}", request));
+///     arena.push(move || println!("Cleanup first {}", request));
+///     //Handle request...
+///     arena.run_all();
+/// }
+/// assert_eq!(arena.boxed(), 0);
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the arena is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferArena<'a> {
+    slots: Box<[Chunk]>,
+    chunks_per_slot: usize,
+    slot_count: usize,
+    entries: Vec<ArenaEntry<'a>>,
+    boxed: usize,
+    //The slots hold closures that may be neither Send nor Sync.
+    _marker: PhantomData<Box<dyn FnOnce() + 'a>>,
+}
+
+impl<'a> DeferArena<'a> {
+    ///
+    /// Allocates `slots` slots that can each hold a closure of up to `max_closure_bytes` bytes.
+    ///
+    #[must_use]
+    pub fn with_capacity(slots: usize, max_closure_bytes: usize) -> Self {
+        let chunks_per_slot = max_closure_bytes.div_ceil(CHUNK);
+        let uninit = Chunk([MaybeUninit::uninit(); CHUNK]);
+        Self {
+            slots: alloc::vec![uninit; slots * chunks_per_slot].into_boxed_slice(),
+            chunks_per_slot,
+            slot_count: slots,
+            entries: Vec::with_capacity(slots),
+            boxed: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    fn fits<F: FnOnce()>(&self) -> bool {
+        self.entries.len() < self.slot_count
+            && size_of::<F>() <= self.chunks_per_slot * CHUNK
+            && align_of::<F>() <= align_of::<Chunk>()
+    }
+
+    #[inline(always)]
+    fn slot_ptr(&mut self, slot: usize) -> *mut u8 {
+        //SAFETY: slot is smaller than slot_count, so the offset is inside the allocation.
+        unsafe {
+            self.slots
+                .as_mut_ptr()
+                .add(slot * self.chunks_per_slot)
+                .cast()
+        }
+    }
+
+    ///
+    /// Pushes a closure. The closure is boxed if it does not fit into a free slot.
+    ///
+    pub fn push(&mut self, func: impl FnOnce() + 'a) {
+        if let Err(func) = self.try_push(func) {
+            self.boxed += 1;
+            self.entries.push(ArenaEntry::Boxed(Box::new(func)));
+        }
+    }
+
+    ///
+    /// Pushes a closure without allocating.
+    ///
+    /// # Returns
+    /// * Ok: the closure was stored in a slot.
+    /// * Err: the closure is too large or all slots are used, the closure is returned.
+    ///
+    pub fn try_push<F: FnOnce() + 'a>(&mut self, func: F) -> Result<(), F> {
+        if !self.fits::<F>() {
+            return Err(func);
+        }
+
+        //Entries are only removed from the end, so the slot with the index of the entry is free.
+        let slot = self.entries.len();
+        //SAFETY: the slot is free, large enough and aligned for F.
+        unsafe { core::ptr::write(self.slot_ptr(slot) as *mut F, func) };
+        self.entries.push(ArenaEntry::Inline {
+            slot,
+            call: call_inline::<F>,
+            drop: drop_inline::<F>,
+        });
+        Ok(())
+    }
+
+    ///
+    /// Will call all closures now in reverse order of registration.
+    /// The arena is empty afterward and can be reused.
+    ///
+    pub fn run_all(&mut self) {
+        //The entry is removed before its closure is called,
+        //so if a closure panics the remaining closures are called when the arena is dropped.
+        while let Some(entry) = self.entries.pop() {
+            match entry {
+                ArenaEntry::Inline { slot, call, .. } => {
+                    let ptr = self.slot_ptr(slot);
+                    //SAFETY: the slot holds the closure of this entry and the entry was removed.
+                    unsafe { call(ptr) };
+                }
+                ArenaEntry::Boxed(func) => {
+                    self.boxed -= 1;
+                    func();
+                }
+            }
+        }
+    }
+
+    ///
+    /// Drops all closures without calling them.
+    /// The arena is empty afterward and can be reused.
+    ///
+    pub fn reset(&mut self) {
+        while let Some(entry) = self.entries.pop() {
+            match entry {
+                ArenaEntry::Inline { slot, drop, .. } => {
+                    let ptr = self.slot_ptr(slot);
+                    //SAFETY: the slot holds the closure of this entry and the entry was removed.
+                    unsafe { drop(ptr) };
+                }
+                ArenaEntry::Boxed(func) => {
+                    self.boxed -= 1;
+                    drop(func);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Returns the number of closures in the arena.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///
+    /// Returns true if there are no closures in the arena.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// Returns the number of slots.
+    ///
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.slot_count
+    }
+
+    ///
+    /// Returns the largest closure size in bytes that fits into a slot.
+    ///
+    #[inline(always)]
+    pub fn max_closure_bytes(&self) -> usize {
+        self.chunks_per_slot * CHUNK
+    }
+
+    ///
+    /// Returns the number of closures that are stored in a slot.
+    ///
+    #[inline(always)]
+    pub fn slots_used(&self) -> usize {
+        self.entries.len() - self.boxed
+    }
+
+    ///
+    /// Returns the number of closures that were boxed because they did not fit into a slot.
+    ///
+    #[inline(always)]
+    pub fn boxed(&self) -> usize {
+        self.boxed
+    }
+}
+
+impl Drop for DeferArena<'_> {
+    fn drop(&mut self) {
+        self.run_all();
+    }
+}
+
+impl core::fmt::Debug for DeferArena<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferArena")
+            .field("len", &self.entries.len())
+            .field("capacity", &self.slot_count)
+            .field("boxed", &self.boxed)
+            .finish()
+    }
+}
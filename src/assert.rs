@@ -0,0 +1,61 @@
+use core::fmt::Arguments;
+use core::panic::Location;
+
+///
+/// Guard created by `defer_assert!` and `defer_debug_assert!`.
+/// The closure checks the assertion and receives the location where the guard was created.
+///
+#[doc(hidden)]
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferAssertGuard<F: FnOnce(&'static Location<'static>)>(
+    Option<F>,
+    &'static Location<'static>,
+);
+
+impl<F: FnOnce(&'static Location<'static>)> DeferAssertGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new(func: F) -> Self {
+        Self(Some(func), Location::caller())
+    }
+
+    ///
+    /// Returns the location where the guard was created.
+    ///
+    #[inline(always)]
+    pub fn location(&self) -> &'static Location<'static> {
+        self.1
+    }
+}
+
+impl<F: FnOnce(&'static Location<'static>)> Drop for DeferAssertGuard<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            f(self.1)
+        }
+    }
+}
+
+///
+/// Called when a deferred assertion fails.
+/// If the thread is already panicking the assertion is skipped, because panicking again would abort the process.
+/// With the `std` feature the failure is printed to stderr instead.
+/// Without the `std` feature a panic in progress is detected with the `panic_tracking` module.
+///
+#[cold]
+#[inline(never)]
+pub fn defer_assert_failed(location: &'static Location<'static>, args: Arguments<'_>) {
+    if crate::thread_panicking() {
+        #[cfg(feature = "std")]
+        std::eprintln!("deferred assertion failed at {}: {}", location, args);
+        return;
+    }
+
+    panic!("deferred assertion failed at {}: {}", location, args)
+}
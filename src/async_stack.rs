@@ -0,0 +1,291 @@
+use crate::DeferKey;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::num::NonZeroUsize;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+///
+/// A cleanup future with an erased type.
+///
+pub type BoxCleanup<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+type CleanupFactory<'a> = Box<dyn FnOnce() -> BoxCleanup<'a> + 'a>;
+
+///
+/// Decides what happens to the cleanups of an `AsyncDeferStack` that is dropped without running them.
+///
+pub enum AsyncDropPolicy<'a> {
+    ///
+    /// Panics in debug builds, unless the thread is already panicking.
+    /// In release builds the cleanups are dropped without being run.
+    /// This is the default.
+    ///
+    DebugPanic,
+    ///
+    /// Drops the cleanups without running them.
+    ///
+    Discard,
+    ///
+    /// Passes each cleanup to the closure in the order `run_all` would run them,
+    /// for example to spawn it on an executor.
+    ///
+    Fallback(Box<dyn FnMut(BoxCleanup<'a>) + 'a>),
+}
+
+impl core::fmt::Debug for AsyncDropPolicy<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AsyncDropPolicy::DebugPanic => f.write_str("DebugPanic"),
+            AsyncDropPolicy::Discard => f.write_str("Discard"),
+            AsyncDropPolicy::Fallback(_) => f.write_str("Fallback"),
+        }
+    }
+}
+
+struct AsyncStackEntry<'a> {
+    seq: u64,
+    func: CleanupFactory<'a>,
+}
+
+///
+/// A stack of async cleanups that are awaited in reverse order of registration.
+///
+/// This is the async counterpart of `DeferStack`. The cleanups are created lazily,
+/// the closure passed to `push` is only called when the cleanup is run.
+/// Async code cannot be awaited in `Drop`, so `run_all` must be awaited before the stack is dropped.
+/// What happens to cleanups that were not run is decided by the `AsyncDropPolicy`.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::AsyncDeferStack;
+///
+/// async fn close_stream() {}
+/// async fn send_goodbye() {}
+///
+/// async fn shutdown() {
+///     let mut stack = AsyncDeferStack::new();
+///     stack.push(close_stream);
+///     stack.push(send_goodbye);
+///     //Sends the goodbye, then closes the stream.
+///     stack.run_all().await;
+/// }
+/// ```
+///
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct AsyncDeferStack<'a> {
+    entries: Vec<AsyncStackEntry<'a>>,
+    next_seq: u64,
+    policy: AsyncDropPolicy<'a>,
+}
+
+impl Default for AsyncDeferStack<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> AsyncDeferStack<'a> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_drop_policy(AsyncDropPolicy::DebugPanic)
+    }
+
+    ///
+    /// Creates a stack with the policy for cleanups that were not run when the stack is dropped.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub const fn with_drop_policy(policy: AsyncDropPolicy<'a>) -> Self {
+        Self {
+            entries: Vec::new(),
+            next_seq: 0,
+            policy,
+        }
+    }
+
+    ///
+    /// Replaces the policy for cleanups that were not run when the stack is dropped.
+    ///
+    /// # Returns
+    /// The previous policy.
+    ///
+    #[inline(always)]
+    pub fn set_drop_policy(&mut self, policy: AsyncDropPolicy<'a>) -> AsyncDropPolicy<'a> {
+        core::mem::replace(&mut self.policy, policy)
+    }
+
+    ///
+    /// Pushes a closure that creates the cleanup future when the cleanup is run.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel the cleanup.
+    ///
+    pub fn push<Fut: Future<Output = ()> + 'a>(
+        &mut self,
+        func: impl FnOnce() -> Fut + 'a,
+    ) -> DeferKey {
+        self.push_factory(Box::new(move || Box::pin(func()) as BoxCleanup<'a>))
+    }
+
+    ///
+    /// Pushes a cleanup future that was already created.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel the cleanup.
+    ///
+    pub fn push_boxed(&mut self, cleanup: BoxCleanup<'a>) -> DeferKey {
+        self.push_factory(Box::new(move || cleanup))
+    }
+
+    fn push_factory(&mut self, func: CleanupFactory<'a>) -> DeferKey {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.push(AsyncStackEntry { seq, func });
+        DeferKey(seq)
+    }
+
+    ///
+    /// Will cancel the cleanup, so it is not run anymore.
+    ///
+    /// # Returns
+    /// * true: cleanup was dropped and will not be run anymore.
+    /// * false: there is no cleanup with this key in the stack.
+    ///
+    pub fn cancel(&mut self, key: DeferKey) -> bool {
+        let Some(index) = self.entries.iter().position(|e| e.seq == key.0) else {
+            return false;
+        };
+
+        drop(self.entries.remove(index));
+        true
+    }
+
+    ///
+    /// Will cancel all cleanups.
+    ///
+    #[inline(always)]
+    pub fn cancel_all(&mut self) {
+        self.entries.clear();
+    }
+
+    ///
+    /// Returns true if the cleanup with this key is still in the stack.
+    ///
+    pub fn contains_key(&self, key: DeferKey) -> bool {
+        self.entries.iter().any(|e| e.seq == key.0)
+    }
+
+    ///
+    /// Returns the amount of cleanups in the stack.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///
+    /// Returns true if there are no cleanups in the stack.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// Awaits all cleanups one after the other in reverse order of registration.
+    /// The stack is empty afterward and can be reused.
+    ///
+    /// Each cleanup is removed from the stack before it is awaited.
+    /// If the returned future is dropped early, the cleanups that were not started stay in the stack.
+    ///
+    pub async fn run_all(&mut self) {
+        while let Some(entry) = self.entries.pop() {
+            (entry.func)().await;
+        }
+    }
+
+    ///
+    /// Awaits all cleanups in batches of up to `limit` concurrent cleanups.
+    /// Batches are started in reverse order of registration, the next batch is started when the whole batch completed.
+    /// The stack is empty afterward and can be reused.
+    ///
+    /// If the returned future is dropped early, the cleanups that were not started stay in the stack.
+    ///
+    pub async fn run_all_concurrent(&mut self, limit: NonZeroUsize) {
+        while !self.entries.is_empty() {
+            let start = self.entries.len().saturating_sub(limit.get());
+            let batch = self
+                .entries
+                .drain(start..)
+                .rev()
+                .map(|entry| Some((entry.func)()))
+                .collect();
+            JoinBatch(batch).await;
+        }
+    }
+}
+
+///
+/// Completes when all futures of the batch completed.
+///
+struct JoinBatch<'a>(Vec<Option<BoxCleanup<'a>>>);
+
+impl Future for JoinBatch<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut pending = false;
+        for slot in self.0.iter_mut() {
+            let Some(future) = slot else {
+                continue;
+            };
+
+            match future.as_mut().poll(cx) {
+                Poll::Ready(()) => *slot = None,
+                Poll::Pending => pending = true,
+            }
+        }
+
+        if pending {
+            return Poll::Pending;
+        }
+
+        Poll::Ready(())
+    }
+}
+
+impl Drop for AsyncDeferStack<'_> {
+    fn drop(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        match &mut self.policy {
+            AsyncDropPolicy::DebugPanic => {
+                let count = self.entries.len();
+                self.entries.clear();
+                if cfg!(debug_assertions) && !crate::thread_panicking() {
+                    panic!("AsyncDeferStack dropped with {count} cleanups that were not run");
+                }
+            }
+            AsyncDropPolicy::Discard => self.entries.clear(),
+            AsyncDropPolicy::Fallback(fallback) => {
+                while let Some(entry) = self.entries.pop() {
+                    fallback((entry.func)());
+                }
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for AsyncDeferStack<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AsyncDeferStack")
+            .field("len", &self.entries.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
@@ -0,0 +1,86 @@
+use crate::DeferGuard;
+use alloc::vec::Vec;
+
+///
+/// Will cancel all guards in reverse order.
+///
+pub fn cancel_vec<F: FnOnce()>(mut guards: Vec<DeferGuard<F>>) {
+    while let Some(mut guard) = guards.pop() {
+        guard.cancel_ref();
+    }
+}
+
+///
+/// Will call the closures of all guards now in reverse order.
+/// Guards that were already canceled or executed are skipped.
+///
+pub fn execute_vec<F: FnOnce()>(guards: Vec<DeferGuard<F>>) {
+    DeferIterator::new(guards).for_each(drop);
+}
+
+///
+/// Iterator that calls the closures of a `Vec` of guards in reverse order.
+///
+/// Each call to `next` calls the closure of the next guard that was not canceled or executed yet.
+/// Guards that were already canceled or executed are skipped.
+/// When the iterator is dropped, the closures of the remaining guards are called in reverse order.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{DeferGuard, DeferIterator};
+///
+/// fn test() {
+///     let guards: Vec<_> = ["Third", "Second", "First"]
+///         .into_iter()
+///         .map(|msg| DeferGuard::new(move || println!("{}", msg)))
+///         .collect();
+///     let executed = DeferIterator::new(guards).count();
+///     assert_eq!(executed, 3);
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the iterator is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferIterator<F: FnOnce()>(Vec<DeferGuard<F>>);
+
+impl<F: FnOnce()> DeferIterator<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(guards: Vec<DeferGuard<F>>) -> Self {
+        Self(guards)
+    }
+}
+
+impl<F: FnOnce()> Iterator for DeferIterator<F> {
+    type Item = ();
+
+    fn next(&mut self) -> Option<()> {
+        while let Some(guard) = self.0.pop() {
+            if guard.destroy() {
+                return Some(());
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.0.len()))
+    }
+}
+
+impl<F: FnOnce()> From<Vec<DeferGuard<F>>> for DeferIterator<F> {
+    fn from(value: Vec<DeferGuard<F>>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<F: FnOnce()> Drop for DeferIterator<F> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
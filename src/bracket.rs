@@ -0,0 +1,83 @@
+use crate::Defer;
+
+///
+/// Acquires a resource, passes it to the body and releases it afterward.
+///
+/// The resource is released even if the body panics.
+/// Changes the body makes to the resource are visible to the release closure.
+///
+/// # Examples
+/// ```rust
+/// fn test() {
+///     let len = defer_heavy::bracket(
+///         || Vec::new(),
+///         |v: Vec<u8>| println!("Releasing {} bytes", v.len()),
+///         |v| {
+///             v.push(1);
+///             v.len()
+///         },
+///     );
+///     assert_eq!(len, 1);
+/// }
+/// ```
+///
+pub fn bracket<A, T>(
+    acquire: impl FnOnce() -> A,
+    release: impl FnOnce(A),
+    body: impl FnOnce(&mut A) -> T,
+) -> T {
+    let mut resource = Defer::new(acquire(), release);
+    body(&mut resource)
+}
+
+///
+/// Same as `bracket` but acquire and body may fail.
+///
+/// If acquire fails, its error is returned and release is not called, because nothing was acquired.
+/// Otherwise release is always called, even if the body returns an error or panics.
+///
+/// # Examples
+/// ```rust
+/// fn test() -> Result<usize, String> {
+///     defer_heavy::try_bracket(
+///         || Ok(Vec::new()),
+///         |v: Vec<u8>| println!("Releasing {} bytes", v.len()),
+///         |v| {
+///             v.push(1);
+///             Err("write failed".to_string())
+///         },
+///     )
+/// }
+/// ```
+///
+pub fn try_bracket<A, T, E>(
+    acquire: impl FnOnce() -> Result<A, E>,
+    release: impl FnOnce(A),
+    body: impl FnOnce(&mut A) -> Result<T, E>,
+) -> Result<T, E> {
+    let mut resource = Defer::new(acquire()?, release);
+    body(&mut resource)
+}
+
+///
+/// Acquires a resource and returns a guard that releases it when dropped.
+///
+/// This is the same as `bracket` without the body closure,
+/// for cases where borrowing inside a closure is too restrictive.
+/// The resource can be used through `Deref` and `DerefMut` of the guard.
+///
+/// # Examples
+/// ```rust
+/// fn test() {
+///     let mut buffer = defer_heavy::acquire_deferred(
+///         || Vec::new(),
+///         |v: Vec<u8>| println!("Releasing {} bytes", v.len()),
+///     );
+///     buffer.push(1);
+/// }
+/// ```
+///
+#[must_use]
+pub fn acquire_deferred<A, F: FnOnce(A)>(acquire: impl FnOnce() -> A, release: F) -> Defer<A, F> {
+    Defer::new(acquire(), release)
+}
@@ -0,0 +1,234 @@
+use core::ffi::c_void;
+
+///
+/// Guard that calls a C cleanup function with a context pointer.
+///
+/// Unlike `DeferGuard` its type does not depend on a closure,
+/// so it can be embedded in structs that are shared with C code.
+/// The guard is neither `Send` nor `Sync`, use `assert_send` if the context may be used from another thread.
+///
+/// # Examples
+/// ```rust
+/// use core::ffi::c_void;
+/// use defer_heavy::CDeferGuard;
+///
+/// unsafe extern "C" fn destroy(ctx: *mut c_void) {
+///     println!("Destroying {:?}", ctx);
+/// }
+///
+/// fn test(ctx: *mut c_void) {
+///     let _guard = unsafe { CDeferGuard::new(destroy, ctx) };
+/// }
+/// ```
+///
+#[repr(C)]
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the cleanup function is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct CDeferGuard {
+    func: Option<unsafe extern "C" fn(*mut c_void)>,
+    ctx: *mut c_void,
+}
+
+impl CDeferGuard {
+    ///
+    /// Creates a guard that calls `func(ctx)` when dropped.
+    ///
+    /// # Safety
+    /// Calling `func` with `ctx` must be safe for as long as the guard exists.
+    /// Usually this means that `ctx` must stay valid until the guard is dropped, destroyed or canceled.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub unsafe fn new(func: unsafe extern "C" fn(*mut c_void), ctx: *mut c_void) -> Self {
+        Self {
+            func: Some(func),
+            ctx,
+        }
+    }
+
+    ///
+    /// Returns the context pointer that is passed to the function.
+    ///
+    #[inline(always)]
+    pub fn context(&self) -> *mut c_void {
+        self.ctx
+    }
+
+    ///
+    /// Will call the function now.
+    ///
+    /// # Returns
+    /// * true: function was called.
+    /// * false: function was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the function now.
+    ///
+    /// # Returns
+    /// * true: function was called.
+    /// * false: function was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.func.take() else {
+            return false;
+        };
+
+        //SAFETY: guaranteed by the caller of new.
+        unsafe { func(self.ctx) };
+        true
+    }
+
+    ///
+    /// Will cancel calling the function, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: function will not be called anymore.
+    /// * false: function was already canceled or called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel calling the function, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: function will not be called anymore.
+    /// * false: function was already canceled or called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.func.take().is_some()
+    }
+
+    ///
+    /// Marks the guard as `Send`.
+    ///
+    /// # Safety
+    /// Calling the function with the context pointer must be safe from any thread.
+    ///
+    #[inline(always)]
+    pub unsafe fn assert_send(self) -> SendCDeferGuard {
+        SendCDeferGuard(self)
+    }
+}
+
+impl crate::Deferrable for CDeferGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        CDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        CDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.func.is_some()
+    }
+}
+
+impl Drop for CDeferGuard {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+///
+/// `CDeferGuard` that can be sent to another thread.
+/// Created by `CDeferGuard::assert_send`.
+///
+#[repr(transparent)]
+#[derive(Debug)]
+pub struct SendCDeferGuard(CDeferGuard);
+
+//SAFETY: guaranteed by the caller of CDeferGuard::assert_send.
+unsafe impl Send for SendCDeferGuard {}
+
+impl SendCDeferGuard {
+    ///
+    /// Returns the context pointer that is passed to the function.
+    ///
+    #[inline(always)]
+    pub fn context(&self) -> *mut c_void {
+        self.0.context()
+    }
+
+    ///
+    /// Will call the function now.
+    ///
+    /// # Returns
+    /// * true: function was called.
+    /// * false: function was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(self) -> bool {
+        self.0.destroy()
+    }
+
+    ///
+    /// Will call the function now.
+    ///
+    /// # Returns
+    /// * true: function was called.
+    /// * false: function was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        self.0.destroy_ref()
+    }
+
+    ///
+    /// Will cancel calling the function, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: function will not be called anymore.
+    /// * false: function was already canceled or called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(self) -> bool {
+        self.0.cancel()
+    }
+
+    ///
+    /// Will cancel calling the function, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: function will not be called anymore.
+    /// * false: function was already canceled or called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.cancel_ref()
+    }
+}
+
+impl crate::Deferrable for SendCDeferGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        SendCDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        SendCDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.func.is_some()
+    }
+}
@@ -0,0 +1,130 @@
+use core::panic::Location;
+
+///
+/// Guard that panics when dropped unless `resolve` or `defuse` was called.
+///
+/// This is the inverse of a defer: it does not perform the cleanup itself,
+/// but ensures that the cleanup was performed explicitly on every path.
+///
+/// If the checkpoint is dropped while the thread is already panicking,
+/// the failure is reported instead of panicking again, which would abort the process.
+/// This requires the `std` feature.
+/// With the `checkpoint_lenient` feature and without debug assertions, the failure is always reported instead of panicking.
+/// The failure is reported with the `log` crate if the `log` feature is enabled, otherwise it is printed to stderr if the `std` feature is enabled.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::Checkpoint;
+///
+/// struct Context {
+///     open: u32,
+/// }
+///
+/// fn test(ctx: &mut Context) {
+///     ctx.open += 1;
+///     let checkpoint = Checkpoint::new("close connection");
+///     //Work...
+///     ctx.open -= 1;
+///     checkpoint.resolve();
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[must_use = "dropping a Checkpoint without calling resolve or defuse panics"]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the checkpoint panics if the future is dropped at the suspension before it was resolved"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct Checkpoint {
+    label: &'static str,
+    location: &'static Location<'static>,
+}
+
+impl Checkpoint {
+    #[inline(always)]
+    #[track_caller]
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            location: Location::caller(),
+        }
+    }
+
+    ///
+    /// Returns the label of the checkpoint.
+    ///
+    #[inline(always)]
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    ///
+    /// Returns the location where the checkpoint was created.
+    ///
+    #[inline(always)]
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    ///
+    /// Marks the cleanup as performed.
+    ///
+    #[inline(always)]
+    pub fn resolve(self) {
+        core::mem::forget(self)
+    }
+
+    ///
+    /// Marks the cleanup as intentionally not performed.
+    ///
+    #[inline(always)]
+    pub fn defuse(self) {
+        core::mem::forget(self)
+    }
+}
+
+impl Drop for Checkpoint {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            report(self);
+            return;
+        }
+
+        #[cfg(all(feature = "checkpoint_lenient", not(debug_assertions)))]
+        {
+            report(self);
+            return;
+        }
+
+        #[allow(unreachable_code)]
+        {
+            panic!(
+                "checkpoint `{}` created at {} was dropped without being resolved",
+                self.label, self.location
+            )
+        }
+    }
+}
+
+#[cold]
+#[allow(dead_code)]
+fn report(checkpoint: &Checkpoint) {
+    #[cfg(feature = "log")]
+    log::error!(
+        "checkpoint `{}` created at {} was dropped without being resolved",
+        checkpoint.label,
+        checkpoint.location
+    );
+
+    #[cfg(all(feature = "std", not(feature = "log")))]
+    std::eprintln!(
+        "checkpoint `{}` created at {} was dropped without being resolved",
+        checkpoint.label,
+        checkpoint.location
+    );
+
+    #[cfg(not(any(feature = "std", feature = "log")))]
+    let _ = checkpoint;
+}
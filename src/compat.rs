@@ -0,0 +1,97 @@
+//! Compatibility layer for migrating from the `scopeguard` crate.
+//!
+//! The functions and macros of this module accept the same arguments as their `scopeguard` counterparts,
+//! so call sites only need to change the crate name.
+//! Existing `scopeguard::ScopeGuard` values can be converted into a `Defer` with `From`.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::compat::{self, defer};
+//!
+//! fn test() {
+//!     defer! { println!("Second"); }
+//!     let mut buffer = compat::guard(Vec::new(), |b: Vec<u8>| println!("Flushing {} bytes", b.len()));
+//!     buffer.push(1);
+//! }
+//! ```
+
+use crate::Defer;
+
+pub use crate::defer;
+
+#[cfg(feature = "std")]
+pub use crate::__compat_defer_on_success as defer_on_success;
+#[cfg(feature = "std")]
+pub use crate::__compat_defer_on_unwind as defer_on_unwind;
+
+///
+/// Creates a guard that passes the value to `dropfn` when dropped.
+/// Same as `scopeguard::guard`.
+///
+#[inline(always)]
+#[must_use]
+pub fn guard<T, F: FnOnce(T)>(value: T, dropfn: F) -> Defer<T, F> {
+    Defer::new(value, dropfn)
+}
+
+///
+/// Creates a guard that passes the value to `dropfn` only if it is dropped while the thread is panicking.
+/// Same as `scopeguard::guard_on_unwind`.
+///
+#[cfg(feature = "std")]
+#[inline(always)]
+#[must_use]
+pub fn guard_on_unwind<T, F: FnOnce(T)>(value: T, dropfn: F) -> Defer<T, F, crate::OnUnwind> {
+    Defer::with_policy(value, dropfn, crate::OnUnwind)
+}
+
+///
+/// Creates a guard that passes the value to `dropfn` only if it is not dropped while the thread is panicking.
+/// Same as `scopeguard::guard_on_success`.
+///
+#[cfg(feature = "std")]
+#[inline(always)]
+#[must_use]
+pub fn guard_on_success<T, F: FnOnce(T)>(value: T, dropfn: F) -> Defer<T, F, crate::OnSuccess> {
+    Defer::with_policy(value, dropfn, crate::OnSuccess)
+}
+
+///
+/// A `scopeguard::ScopeGuard` converted into a `Defer`.
+/// The value of the `Defer` is the scope guard, which is dropped by the closure.
+/// The value of the scope guard can still be used through `Deref`.
+///
+pub type ScopeGuardDefer<T, F> = Defer<
+    scopeguard::ScopeGuard<T, F, scopeguard::Always>,
+    fn(scopeguard::ScopeGuard<T, F, scopeguard::Always>),
+>;
+
+impl<T, F: FnOnce(T)> From<scopeguard::ScopeGuard<T, F, scopeguard::Always>>
+    for ScopeGuardDefer<T, F>
+{
+    fn from(value: scopeguard::ScopeGuard<T, F, scopeguard::Always>) -> Self {
+        Defer::new(value, drop)
+    }
+}
+
+/// Executes a block of code only if the surrounding scope ends because of a panic.
+/// Same as `scopeguard::defer_on_unwind!`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __compat_defer_on_unwind {
+	( $($tt:tt)* ) => {
+		let _deferred = $crate::PolicyGuard::with_policy(|| { $($tt)* }, $crate::OnUnwind);
+	};
+}
+
+/// Executes a block of code only if the surrounding scope ends without a panic.
+/// Same as `scopeguard::defer_on_success!`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __compat_defer_on_success {
+	( $($tt:tt)* ) => {
+		let _deferred = $crate::PolicyGuard::with_policy(|| { $($tt)* }, $crate::OnSuccess);
+	};
+}
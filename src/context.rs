@@ -0,0 +1,164 @@
+use core::ops::{Deref, DerefMut};
+
+///
+/// Guard that passes a mutable reference to a context to the closure when dropped.
+///
+/// The guard holds the only mutable borrow of the context,
+/// the scope uses the context through `Deref` and `DerefMut` while the guard exists.
+/// The closure receives the context when the guard is dropped, so it sees every change made during the scope.
+/// Unlike `defer_with!(var => ...)` the context is only borrowed, so it can be used again after the guard was dropped.
+/// This is the type `defer_with_context!` shadows the variable with.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::ContextDeferGuard;
+///
+/// struct Transaction {
+///     rollback_id: Option<u32>,
+/// }
+///
+/// fn insert(transaction: &mut Transaction) {
+///     let mut transaction = ContextDeferGuard::new(transaction, |transaction: &mut Transaction| {
+///         if let Some(id) = transaction.rollback_id.take() {
+///             println!("Rollback {}", id);
+///         }
+///     });
+///     transaction.rollback_id = Some(7);
+///     //Prints "Rollback 7"
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct ContextDeferGuard<'a, C: ?Sized, F: FnOnce(&mut C)>(&'a mut C, Option<F>);
+
+impl<'a, C: ?Sized, F: FnOnce(&mut C)> ContextDeferGuard<'a, C, F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(context: &'a mut C, func: F) -> Self {
+        Self(context, Some(func))
+    }
+
+    ///
+    /// Will call the closure now with the context.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was canceled.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now with the context.
+    /// The guard still borrows the context until it is dropped.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.1.take() else {
+            return false;
+        };
+
+        func(self.0);
+        true
+    }
+
+    ///
+    /// Will cancel running the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel running the closure.
+    /// The guard still borrows the context until it is dropped.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.1.take().is_some()
+    }
+}
+
+///
+/// Borrows the context variable of `defer_with_context!` mutably.
+/// A method call reborrows a `&mut C` variable and borrows a `C` variable, so the macro accepts both.
+///
+pub trait ContextSlot {
+    fn __defer_heavy_context(&mut self) -> &mut Self;
+}
+
+impl<C: ?Sized> ContextSlot for C {
+    #[inline(always)]
+    fn __defer_heavy_context(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl<C: ?Sized, F: FnOnce(&mut C)> Deref for ContextDeferGuard<'_, C, F> {
+    type Target = C;
+
+    #[inline(always)]
+    fn deref(&self) -> &C {
+        self.0
+    }
+}
+
+impl<C: ?Sized, F: FnOnce(&mut C)> DerefMut for ContextDeferGuard<'_, C, F> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut C {
+        self.0
+    }
+}
+
+impl<C: ?Sized, F: FnOnce(&mut C)> Drop for ContextDeferGuard<'_, C, F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+impl<C: ?Sized + core::fmt::Debug, F: FnOnce(&mut C)> core::fmt::Debug
+    for ContextDeferGuard<'_, C, F>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ContextDeferGuard")
+            .field("context", &self.0)
+            .field("armed", &self.1.is_some())
+            .finish()
+    }
+}
+
+impl<C: ?Sized, F: FnOnce(&mut C)> crate::Deferrable for ContextDeferGuard<'_, C, F> {
+    #[inline(always)]
+    fn cancel(self) {
+        ContextDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        ContextDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.1.is_some()
+    }
+}
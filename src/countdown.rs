@@ -0,0 +1,142 @@
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering::SeqCst;
+use core::sync::atomic::{AtomicBool, AtomicUsize};
+
+struct CountdownInner<F: FnOnce() + Send> {
+    remaining: AtomicUsize,
+    canceled: AtomicBool,
+    func: UnsafeCell<Option<F>>,
+}
+
+// The closure is only ever accessed by the thread that decrements the count to zero,
+// which happens at most once, or in Drop which requires exclusive ownership.
+// The closure may run on any thread holding a handle, so it must be Send.
+unsafe impl<F: FnOnce() + Send> Sync for CountdownInner<F> {}
+
+///
+/// Reference counted guard that executes its closure once a fixed number of handles were dropped.
+///
+/// Unlike `ArcDeferGuard` the closure does not wait for the last handle.
+/// It is called by the N-th drop of any handle, regardless of how many handles exist.
+/// Drops beyond the N-th do nothing.
+/// If all handles are dropped before the count reaches zero, the closure is dropped without being called.
+/// A count of 0 means that the closure is never called.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use defer_heavy::CountdownDeferGuard;
+///
+/// fn test() {
+///     let countdown = CountdownDeferGuard::new(3, || println!("3 workers finished"));
+///     let workers: Vec<_> = (0..3)
+///         .map(|_| {
+///             let countdown = countdown.clone();
+///             thread::spawn(move || drop(countdown.own()))
+///         })
+///         .collect();
+///     for w in workers {
+///         w.join().unwrap();
+///     }
+///     //Does not call the closure a 4th time.
+///     drop(countdown);
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct CountdownDeferGuard<F: FnOnce() + Send>(Arc<CountdownInner<F>>);
+
+impl<F: FnOnce() + Send> CountdownDeferGuard<F> {
+    #[must_use]
+    pub fn new(count: usize, func: F) -> Self {
+        Self(Arc::new(CountdownInner {
+            remaining: AtomicUsize::new(count),
+            canceled: AtomicBool::new(false),
+            func: UnsafeCell::new(Some(func)),
+        }))
+    }
+
+    ///
+    /// Utility function to ensure ownership is transferred to a thread/closure.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub fn own(self) -> Self {
+        self
+    }
+
+    ///
+    /// Returns how many drops are still required until the closure is called.
+    ///
+    #[inline(always)]
+    pub fn remaining(&self) -> usize {
+        self.0.remaining.load(SeqCst)
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once no thread has a reference to it anymore.
+    ///
+    #[inline(always)]
+    pub fn cancel(self) {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once no thread has a reference to it anymore.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.0.canceled.store(true, SeqCst)
+    }
+
+    ///
+    /// Returns true if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.canceled.load(SeqCst)
+    }
+}
+
+impl<F: FnOnce() + Send> Clone for CountdownDeferGuard<F> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F: FnOnce() + Send> Drop for CountdownDeferGuard<F> {
+    fn drop(&mut self) {
+        let inner = &*self.0;
+        if inner
+            .remaining
+            .fetch_update(SeqCst, SeqCst, |c| c.checked_sub(1))
+            != Ok(1)
+        {
+            return;
+        }
+
+        //SAFETY: only the thread that decremented the count to zero gets here.
+        let func = unsafe { (*inner.func.get()).take() };
+        if !inner.canceled.load(SeqCst) {
+            if let Some(f) = func {
+                f()
+            }
+        }
+    }
+}
+
+impl<F: FnOnce() + Send> core::fmt::Debug for CountdownDeferGuard<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CountdownDeferGuard")
+            .field("remaining", &self.remaining())
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
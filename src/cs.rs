@@ -0,0 +1,224 @@
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+struct CsSlotState<F> {
+    func: Option<F>,
+    refs: usize,
+    canceled: bool,
+}
+
+///
+/// Static storage for a `CsDeferGuard`.
+///
+/// The closure type must be nameable to declare the static, this is usually a `fn()` pointer.
+/// The slot can be armed again once all handles of the previous closure were dropped.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{CsDeferGuard, CsDeferSlot};
+///
+/// static SLOT: CsDeferSlot<fn()> = CsDeferSlot::new();
+///
+/// fn power_down_sensor() {
+///     println!("Sensor powered down");
+/// }
+///
+/// let guard = CsDeferGuard::init(&SLOT, power_down_sensor as fn()).unwrap();
+/// let handle = guard.clone(); //For example for an interrupt handler
+/// drop(guard);
+/// drop(handle); //Prints "Sensor powered down"
+/// assert!(SLOT.is_empty());
+/// ```
+///
+pub struct CsDeferSlot<F: FnOnce() + Send + 'static>(Mutex<RefCell<CsSlotState<F>>>);
+
+impl<F: FnOnce() + Send + 'static> CsDeferSlot<F> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Mutex::new(RefCell::new(CsSlotState {
+            func: None,
+            refs: 0,
+            canceled: false,
+        })))
+    }
+
+    ///
+    /// Returns true if no handle to the slot exists, so it can be armed.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.with(|state| state.refs == 0)
+    }
+
+    #[inline(always)]
+    fn with<R>(&self, f: impl FnOnce(&mut CsSlotState<F>) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.0.borrow_ref_mut(cs)))
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Default for CsDeferSlot<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> core::fmt::Debug for CsDeferSlot<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (refs, canceled) = self.with(|state| (state.refs, state.canceled));
+        f.debug_struct("CsDeferSlot")
+            .field("refs", &refs)
+            .field("canceled", &canceled)
+            .finish()
+    }
+}
+
+///
+/// Shared handle to a closure stored in a `CsDeferSlot`.
+///
+/// This works without alloc and without atomics.
+/// The reference count and the cancel flag are changed inside `critical_section::with`,
+/// so the target must provide a `critical-section` implementation.
+/// Cloning a handle increments the reference count of the slot.
+/// The closure is called when the last handle is dropped, unless one of the handles canceled it.
+///
+/// The closure is called outside of the critical section by whatever context dropped the last handle.
+/// If that is an interrupt handler, the closure runs in the interrupt handler.
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct CsDeferGuard<F: FnOnce() + Send + 'static>(&'static CsDeferSlot<F>);
+
+impl<F: FnOnce() + Send + 'static> CsDeferGuard<F> {
+    ///
+    /// Arms the slot with the closure.
+    ///
+    /// # Returns
+    /// * Ok: the slot was armed, this is the first handle.
+    /// * Err: handles of the slot still exist, the closure is returned.
+    ///
+    pub fn init(slot: &'static CsDeferSlot<F>, func: F) -> Result<Self, F> {
+        slot.with(|state| {
+            if state.refs != 0 {
+                return Err(func);
+            }
+
+            state.func = Some(func);
+            state.refs = 1;
+            state.canceled = false;
+            Ok(Self(slot))
+        })
+    }
+
+    ///
+    /// Will call the closure now instead of when the last handle is dropped.
+    /// The slot stays in use until the last handle is dropped.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was already called or canceled.
+    ///
+    pub fn fire_now(self) -> bool {
+        let func = self.0.with(|state| {
+            if state.canceled {
+                return None;
+            }
+
+            state.func.take()
+        });
+
+        //Called outside of the critical section.
+        func.map(|f| f()).is_some()
+    }
+
+    ///
+    /// Will cancel running the closure.
+    /// The closure is dropped and the slot can be armed again once the last handle is dropped.
+    ///
+    #[inline(always)]
+    pub fn cancel(self) {
+        self.cancel_ref();
+    }
+
+    ///
+    /// Will cancel running the closure for all handles.
+    /// The closure is dropped and the slot can be armed again once the last handle is dropped.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.0.with(|state| state.canceled = true);
+    }
+
+    ///
+    /// Returns true if the closure was canceled by one of the handles.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.with(|state| state.canceled)
+    }
+
+    ///
+    /// Returns the number of handles that currently exist for the closure.
+    ///
+    #[inline(always)]
+    pub fn ref_count(&self) -> usize {
+        self.0.with(|state| state.refs)
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Clone for CsDeferGuard<F> {
+    fn clone(&self) -> Self {
+        self.0.with(|state| {
+            state.refs = state
+                .refs
+                .checked_add(1)
+                .expect("CsDeferGuard reference count overflow");
+        });
+        Self(self.0)
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> core::fmt::Debug for CsDeferGuard<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("CsDeferGuard").field(self.0).finish()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> crate::Deferrable for CsDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        CsDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        self.fire_now()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.with(|state| !state.canceled && state.func.is_some())
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Drop for CsDeferGuard<F> {
+    fn drop(&mut self) {
+        let released = self.0.with(|state| {
+            state.refs -= 1;
+            if state.refs != 0 {
+                return None;
+            }
+
+            let canceled = core::mem::take(&mut state.canceled);
+            Some((state.func.take(), canceled))
+        });
+
+        //Called or dropped outside of the critical section.
+        if let Some((Some(func), false)) = released {
+            func();
+        }
+    }
+}
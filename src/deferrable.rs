@@ -0,0 +1,159 @@
+use crate::DeferGuard;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+///
+/// Common interface of the guards, so generic code can accept any kind of deferred closure.
+///
+/// A bare closure is also `Deferrable`, it behaves like a `DeferGuard` that was just created.
+/// `ArcDeferGuard` calls its closure in `destroy` only if this is the last reference,
+/// otherwise `destroy` releases this reference and returns false.
+/// Guards that call their closure when a count reaches zero, like `CountdownDeferGuard` or `PoolDeferGuard`,
+/// are not `Deferrable` because they cannot call the closure early.
+///
+/// Use `DeferrableDyn` to store guards of different types in the same collection.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{defer_guard, Deferrable};
+///
+/// fn with_cleanup<D: Deferrable>(cleanup: D, success: bool) {
+///     if success {
+///         cleanup.cancel();
+///     }
+/// }
+///
+/// fn test() {
+///     with_cleanup(|| println!("Rollback"), false);
+///     with_cleanup(defer_guard! { println!("Rollback"); }, true);
+/// }
+/// ```
+///
+pub trait Deferrable {
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    fn cancel(self)
+    where
+        Self: Sized;
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was already called or canceled, or other handles to it exist.
+    ///
+    fn destroy(self) -> bool
+    where
+        Self: Sized;
+
+    ///
+    /// Returns true if the closure was neither called nor canceled yet.
+    ///
+    fn is_armed(&self) -> bool;
+}
+
+///
+/// Object safe companion of `Deferrable`.
+///
+/// It is implemented for every `Deferrable`, so guards of different types can be stored as `Box<dyn DeferrableDyn>`.
+/// `Box<dyn DeferrableDyn>` is `Deferrable` itself.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{defer_guard, Deferrable, DeferrableDyn, DeferGuard};
+///
+/// fn test() {
+///     let cleanups: Vec<Box<dyn DeferrableDyn>> = vec![
+///         Box::new(defer_guard! { println!("Close file"); }),
+///         Box::new(|| println!("Remove temp dir")),
+///     ];
+///
+///     for cleanup in cleanups {
+///         cleanup.destroy_boxed();
+///     }
+/// }
+/// ```
+///
+#[cfg(feature = "alloc")]
+pub trait DeferrableDyn: Deferrable {
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    fn cancel_boxed(self: Box<Self>);
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// See `Deferrable::destroy`.
+    ///
+    fn destroy_boxed(self: Box<Self>) -> bool;
+}
+
+#[cfg(feature = "alloc")]
+impl<D: Deferrable> DeferrableDyn for D {
+    #[inline(always)]
+    fn cancel_boxed(self: Box<Self>) {
+        (*self).cancel()
+    }
+
+    #[inline(always)]
+    fn destroy_boxed(self: Box<Self>) -> bool {
+        (*self).destroy()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deferrable for Box<dyn DeferrableDyn + '_> {
+    #[inline(always)]
+    fn cancel(self) {
+        self.cancel_boxed()
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        self.destroy_boxed()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        (**self).is_armed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deferrable for Box<dyn DeferrableDyn + Send + '_> {
+    #[inline(always)]
+    fn cancel(self) {
+        self.cancel_boxed()
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        self.destroy_boxed()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        (**self).is_armed()
+    }
+}
+
+impl<F: FnOnce()> Deferrable for F {
+    #[inline(always)]
+    fn cancel(self) {
+        DeferGuard::new(self).cancel();
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        DeferGuard::new(self).destroy()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,120 @@
+use crate::DeferGuard;
+
+///
+/// Guard that must be canceled or destroyed explicitly.
+///
+/// Created by `DeferGuard::ensure` and `defer_ensure!`.
+/// In debug builds dropping the guard while its closure is still pending calls the closure and then panics,
+/// unless the thread is already panicking. Without the `std` feature this is detected with the `panic_tracking` module.
+/// The check is done by the type itself, so the wrapped `DeferGuard` does not need to remember it
+/// and the guard is exactly as large as a `DeferGuard`.
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct EnsureDeferGuard<F: FnOnce()>(DeferGuard<F>);
+
+impl<F: FnOnce()> EnsureDeferGuard<F> {
+    #[inline(always)]
+    pub(crate) fn new(guard: DeferGuard<F>) -> Self {
+        Self(guard)
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was canceled.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.0.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure, the guard counts as handled afterwards.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        self.0.destroy_ref()
+    }
+
+    ///
+    /// Will cancel running the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel(mut self) -> bool {
+        self.0.cancel_ref()
+    }
+
+    ///
+    /// Will cancel running the closure.
+    /// This drops the closure, the guard counts as handled afterwards.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.cancel_ref()
+    }
+
+    ///
+    /// Returns a reference to the closure.
+    ///
+    /// # Returns
+    /// * Some: the closure was not called or canceled yet.
+    /// * None: the closure was called or canceled.
+    ///
+    #[inline(always)]
+    pub fn peek(&self) -> Option<&F> {
+        self.0.peek()
+    }
+}
+
+impl<F: FnOnce()> Drop for EnsureDeferGuard<F> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        let unhandled = cfg!(debug_assertions) && self.0.peek().is_some();
+        self.0.destroy_ref();
+        if unhandled && !crate::thread_panicking() {
+            panic!(
+                "DeferGuard created with ensure was dropped without being canceled or destroyed"
+            );
+        }
+    }
+}
+
+impl<F: FnOnce()> crate::Deferrable for EnsureDeferGuard<F> {
+    #[inline(always)]
+    #[track_caller]
+    fn cancel(self) {
+        EnsureDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        EnsureDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.peek().is_some()
+    }
+}
@@ -0,0 +1,93 @@
+//! Receives errors of deferred closures that have no caller to return them to.
+//!
+//! Fallible cleanups that run when their owner is dropped, like the fallible entries of a `DeferStack`,
+//! pass their errors to the hook instead of discarding them.
+//! The default hook prints the error to stderr with the `std` feature and ignores it otherwise.
+//!
+//! The hook is global. Without atomic pointer sized integers it cannot be replaced.
+//!
+//! # Examples
+//! ```rust
+//! use core::fmt::Debug;
+//! use defer_heavy::error_hook;
+//!
+//! fn log_cleanup_error(error: &dyn Debug) {
+//!     println!("Cleanup failed: {:?}", error);
+//! }
+//!
+//! error_hook::set(log_cleanup_error);
+//! ```
+
+use core::fmt::Debug;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::AtomicPtr;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::Ordering::SeqCst;
+
+///
+/// Signature of an error hook.
+///
+pub type ErrorHook = fn(&dyn Debug);
+
+#[cfg(target_has_atomic = "ptr")]
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+///
+/// The hook that is used until `set` is called.
+/// Prints the error to stderr with the `std` feature, ignores it otherwise.
+///
+#[allow(unused_variables)]
+pub fn default_hook(error: &dyn Debug) {
+    #[cfg(feature = "std")]
+    std::eprintln!("deferred closure failed: {:?}", error);
+}
+
+///
+/// Replaces the error hook.
+///
+/// # Returns
+/// The previous hook.
+///
+#[cfg(target_has_atomic = "ptr")]
+pub fn set(hook: ErrorHook) -> ErrorHook {
+    let previous = HOOK.swap(hook as *mut (), SeqCst);
+    from_ptr(previous)
+}
+
+///
+/// Restores the default hook.
+///
+#[cfg(target_has_atomic = "ptr")]
+pub fn reset() {
+    HOOK.store(core::ptr::null_mut(), SeqCst);
+}
+
+///
+/// Returns the current error hook.
+///
+pub fn get() -> ErrorHook {
+    #[cfg(target_has_atomic = "ptr")]
+    return from_ptr(HOOK.load(SeqCst));
+
+    #[cfg(not(target_has_atomic = "ptr"))]
+    return default_hook;
+}
+
+///
+/// Passes the error to the current error hook.
+///
+#[inline(always)]
+pub fn report(error: &dyn Debug) {
+    get()(error)
+}
+
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+fn from_ptr(ptr: *mut ()) -> ErrorHook {
+    if ptr.is_null() {
+        return default_hook;
+    }
+
+    //SAFETY: only ErrorHook function pointers are stored in HOOK.
+    unsafe { core::mem::transmute::<*mut (), ErrorHook>(ptr) }
+}
@@ -0,0 +1,78 @@
+use crate::ErasedArcDeferGuard as FfiGuard;
+
+///
+/// Cancels the closure and releases the reference held by the pointer.
+/// The closure will not be called anymore, even if other references to the guard exist.
+/// Does nothing if the pointer is null.
+///
+/// # Safety
+/// The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+/// The pointer must not be used afterward.
+///
+#[no_mangle]
+pub unsafe extern "C" fn defer_arc_cancel(ptr: *const ()) {
+    if ptr.is_null() {
+        return;
+    }
+
+    FfiGuard::from_raw(ptr).cancel();
+}
+
+///
+/// Releases the reference held by the pointer.
+/// If this was the last reference and the guard was not canceled, the closure is called.
+/// Does nothing if the pointer is null.
+///
+/// # Safety
+/// The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+/// The pointer must not be used afterward.
+///
+#[no_mangle]
+pub unsafe extern "C" fn defer_arc_destroy(ptr: *const ()) {
+    if ptr.is_null() {
+        return;
+    }
+
+    drop(FfiGuard::from_raw(ptr));
+}
+
+///
+/// Creates a new reference to the guard.
+///
+/// # Returns
+/// A pointer that holds the new reference or null if the pointer is null.
+///
+/// # Safety
+/// The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`
+/// and must not have been released yet.
+///
+#[no_mangle]
+pub unsafe extern "C" fn defer_arc_clone(ptr: *const ()) -> *const () {
+    if ptr.is_null() {
+        return ptr;
+    }
+
+    FfiGuard::clone_from_raw(ptr).into_raw()
+}
+
+///
+/// Releases the reference held by the pointer without calling the closure.
+/// If this was the last reference, the closure is dropped without being called.
+/// Does nothing if the pointer is null.
+///
+/// With the `triomphe` feature the closure may still be called if another thread
+/// releases the second to last reference at the same time.
+/// Without it, this function never calls the closure.
+///
+/// # Safety
+/// The pointer must be null or have been returned by `ErasedArcDeferGuard::into_raw` or `defer_arc_clone`.
+/// The pointer must not be used afterward.
+///
+#[no_mangle]
+pub unsafe extern "C" fn defer_arc_free(ptr: *const ()) {
+    if ptr.is_null() {
+        return;
+    }
+
+    FfiGuard::from_raw(ptr).release();
+}
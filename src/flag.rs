@@ -0,0 +1,322 @@
+use core::cell::Cell;
+#[cfg(target_has_atomic = "8")]
+use core::sync::atomic::AtomicBool;
+#[cfg(target_has_atomic = "8")]
+use core::sync::atomic::Ordering::{self, AcqRel, Acquire, Relaxed, Release, SeqCst};
+
+///
+/// Guard that sets a flag while it is alive and clears it when dropped.
+///
+/// Created by `flag_guard`, `try_flag_guard`, `FlagGuard::new` and `FlagGuard::try_new`.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::AtomicBool;
+/// use defer_heavy::try_flag_guard;
+///
+/// static IN_HANDLER: AtomicBool = AtomicBool::new(false);
+///
+/// fn handle_event() {
+///     let Some(_busy) = try_flag_guard(&IN_HANDLER) else {
+///         //Called again while the handler is running.
+///         return;
+///     };
+///     //Handle event...
+/// }
+/// ```
+///
+#[cfg(target_has_atomic = "8")]
+#[derive(Debug)]
+#[must_use = "the flag is cleared immediately if the guard is not used"]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the flag stays set until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct FlagGuard<'a>(Option<&'a AtomicBool>, Ordering);
+
+#[cfg(target_has_atomic = "8")]
+impl<'a> FlagGuard<'a> {
+    ///
+    /// Stores true in the flag. The guard stores false when it is dropped.
+    /// Both stores use the given ordering.
+    ///
+    /// # Panics
+    /// If the ordering is `Acquire` or `AcqRel`, like `AtomicBool::store`.
+    ///
+    #[inline(always)]
+    pub fn new(flag: &'a AtomicBool, ordering: Ordering) -> Self {
+        flag.store(true, ordering);
+        Self(Some(flag), ordering)
+    }
+
+    ///
+    /// Stores true in the flag if it is false.
+    /// The guard stores false when it is dropped using the given ordering.
+    ///
+    /// # Returns
+    /// * Some: the flag was false and is now set.
+    /// * None: the flag was already set, it is not changed.
+    ///
+    /// # Panics
+    /// If the ordering is `Acquire` or `AcqRel`, like `AtomicBool::store`.
+    ///
+    #[inline(always)]
+    pub fn try_new(flag: &'a AtomicBool, ordering: Ordering) -> Option<Self> {
+        //Checked here, so the guard does not panic when it is dropped.
+        assert!(
+            !matches!(ordering, Acquire | AcqRel),
+            "the ordering must be valid for a store"
+        );
+        flag.compare_exchange(false, true, ordering, Relaxed).ok()?;
+        Some(Self(Some(flag), ordering))
+    }
+
+    ///
+    /// Clears the flag now.
+    ///
+    #[inline(always)]
+    pub fn release(mut self) {
+        self.release_ref();
+    }
+
+    ///
+    /// Drops the guard without clearing the flag.
+    ///
+    #[inline(always)]
+    pub fn forget(mut self) {
+        self.0 = None;
+    }
+
+    #[inline(always)]
+    fn release_ref(&mut self) {
+        if let Some(flag) = self.0.take() {
+            flag.store(false, self.1);
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl Drop for FlagGuard<'_> {
+    fn drop(&mut self) {
+        self.release_ref();
+    }
+}
+
+///
+/// Stores true in the flag and returns a guard that stores false when it is dropped.
+/// Both stores use `SeqCst`, use `FlagGuard::new` for a different ordering.
+///
+#[cfg(target_has_atomic = "8")]
+#[inline(always)]
+pub fn flag_guard(flag: &AtomicBool) -> FlagGuard<'_> {
+    FlagGuard::new(flag, SeqCst)
+}
+
+///
+/// Stores true in the flag if it is false and returns a guard that stores false when it is dropped.
+/// Both operations use `SeqCst`, use `FlagGuard::try_new` for a different ordering.
+///
+/// # Returns
+/// * Some: the flag was false and is now set.
+/// * None: the flag was already set, for example because the caller was entered again.
+///
+#[cfg(target_has_atomic = "8")]
+#[inline(always)]
+pub fn try_flag_guard(flag: &AtomicBool) -> Option<FlagGuard<'_>> {
+    FlagGuard::try_new(flag, SeqCst)
+}
+
+///
+/// Guard that calls the closure when dropped unless a "done" flag is set.
+///
+/// The flag is borrowed, so the code that completes the work can set it,
+/// for example on another thread, to skip the cleanup.
+/// The flag is checked with `Acquire` when the guard is dropped.
+/// Created by `defer_guarded_by!` and `DoneFlagDeferGuard::new`.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::AtomicBool;
+/// use defer_heavy::defer_guarded_by;
+///
+/// fn upload(done: &AtomicBool) {
+///     let _rollback = defer_guarded_by!(done, {
+///         println!("Delete partial upload");
+///     });
+///     //Upload, the worker sets done once the upload completed...
+/// }
+/// ```
+///
+#[cfg(target_has_atomic = "8")]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DoneFlagDeferGuard<'a, F: FnOnce()>(Option<F>, &'a AtomicBool);
+
+#[cfg(target_has_atomic = "8")]
+impl<'a, F: FnOnce()> DoneFlagDeferGuard<'a, F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(done: &'a AtomicBool, func: F) -> Self {
+        Self(Some(func), done)
+    }
+
+    ///
+    /// Returns true if the flag is set, so the closure will not be called.
+    ///
+    #[inline(always)]
+    pub fn is_done(&self) -> bool {
+        self.1.load(Acquire)
+    }
+
+    ///
+    /// Sets the flag with `Release` and cancels the closure.
+    /// Other guards that observe the same flag are canceled too.
+    ///
+    #[inline(always)]
+    pub fn cancel_and_mark(mut self) {
+        self.0 = None;
+        self.1.store(true, Release);
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore. The flag is not changed.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: the closure was already dropped by `cancel_ref`.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore. The flag is not changed.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: the closure was already dropped by `cancel_ref`.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<F: FnOnce()> Drop for DoneFlagDeferGuard<'_, F> {
+    fn drop(&mut self) {
+        let Some(func) = self.0.take() else {
+            return;
+        };
+
+        if !self.1.load(Acquire) {
+            func();
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<F: FnOnce()> core::fmt::Debug for DoneFlagDeferGuard<'_, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DoneFlagDeferGuard")
+            .field("pending", &self.0.is_some())
+            .field("done", &self.is_done())
+            .finish()
+    }
+}
+
+///
+/// Guard that sets a `Cell<bool>` while it is alive and clears it when dropped.
+///
+/// This is the single threaded version of `FlagGuard`.
+/// Created by `cell_flag_guard` and `try_cell_flag_guard`.
+///
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use defer_heavy::try_cell_flag_guard;
+///
+/// struct Widget {
+///     redrawing: Cell<bool>,
+/// }
+///
+/// impl Widget {
+///     fn redraw(&self) {
+///         let Some(_busy) = try_cell_flag_guard(&self.redrawing) else {
+///             return;
+///         };
+///         //Redraw, which may call redraw again...
+///     }
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[must_use = "the flag is cleared immediately if the guard is not used"]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the flag stays set until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct CellFlagGuard<'a>(Option<&'a Cell<bool>>);
+
+impl CellFlagGuard<'_> {
+    ///
+    /// Clears the flag now.
+    ///
+    #[inline(always)]
+    pub fn release(mut self) {
+        self.release_ref();
+    }
+
+    ///
+    /// Drops the guard without clearing the flag.
+    ///
+    #[inline(always)]
+    pub fn forget(mut self) {
+        self.0 = None;
+    }
+
+    #[inline(always)]
+    fn release_ref(&mut self) {
+        if let Some(flag) = self.0.take() {
+            flag.set(false);
+        }
+    }
+}
+
+impl Drop for CellFlagGuard<'_> {
+    fn drop(&mut self) {
+        self.release_ref();
+    }
+}
+
+///
+/// Sets the flag and returns a guard that clears it when it is dropped.
+///
+#[inline(always)]
+pub fn cell_flag_guard(flag: &Cell<bool>) -> CellFlagGuard<'_> {
+    flag.set(true);
+    CellFlagGuard(Some(flag))
+}
+
+///
+/// Sets the flag if it is not set and returns a guard that clears it when it is dropped.
+///
+/// # Returns
+/// * Some: the flag was not set and is now set.
+/// * None: the flag was already set, for example because the caller was entered again.
+///
+#[inline(always)]
+pub fn try_cell_flag_guard(flag: &Cell<bool>) -> Option<CellFlagGuard<'_>> {
+    if flag.replace(true) {
+        return None;
+    }
+
+    Some(CellFlagGuard(Some(flag)))
+}
@@ -0,0 +1,234 @@
+///
+/// Guard for a `FnMut` closure.
+///
+/// Unlike `DeferGuard` the closure is kept after it was called or canceled,
+/// which allows the guard to be armed again with `rearm` or `reset`.
+/// The closure is called when the guard is dropped while it is armed.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::FnMutDeferGuard;
+///
+/// let mut guard = FnMutDeferGuard::new(|| println!("Cleanup"));
+/// for _ in 0..3 {
+///     //Do work...
+///     guard.destroy_ref(); //Prints "Cleanup"
+///     guard.rearm();
+/// }
+/// //Prints "Cleanup" once more when the guard is dropped
+/// assert_eq!(guard.call_count(), 3);
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct FnMutDeferGuard<F: FnMut()>(F, bool, u32, Option<u32>);
+
+impl<F: FnMut()> FnMutDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(func, true, 0, None)
+    }
+
+    ///
+    /// Creates a guard that can call the closure at most `max_calls` times.
+    /// After the closure was called `max_calls` times, the guard is disarmed and `rearm` has no effect
+    /// until `reset_count` is called.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::FnMutDeferGuard;
+    ///
+    /// let mut guard = FnMutDeferGuard::with_max_calls(|| println!("Retry"), 2);
+    /// assert!(guard.destroy_ref()); //Prints "Retry"
+    /// assert!(guard.rearm());
+    /// assert!(guard.destroy_ref()); //Prints "Retry"
+    /// assert!(!guard.rearm());
+    /// assert!(!guard.is_armed());
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub fn with_max_calls(func: F, max_calls: u32) -> Self {
+        Self(func, max_calls != 0, 0, Some(max_calls))
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because the guard is not armed.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now and disarm the guard.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because the guard is not armed.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        if !self.1 {
+            return false;
+        }
+
+        self.1 = false;
+        self.2 = self.2.wrapping_add(1);
+        (self.0)();
+        true
+    }
+
+    ///
+    /// Will call the closure now without disarming the guard.
+    /// If the guard is armed, the closure is called again when the guard is dropped.
+    /// The guard is disarmed if this call reaches the maximum number of calls.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because the maximum number of calls was reached.
+    ///
+    pub fn call_now(&mut self) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+
+        self.2 = self.2.wrapping_add(1);
+        if self.is_exhausted() {
+            self.1 = false;
+        }
+
+        (self.0)();
+        true
+    }
+
+    ///
+    /// Calls `f` with mutable access to the closure, for example to update its captured state.
+    /// Neither the call count nor whether the guard is armed are changed.
+    ///
+    #[inline(always)]
+    pub fn with_fn_mut_access<R>(&mut self, f: impl FnOnce(&mut F) -> R) -> R {
+        f(&mut self.0)
+    }
+
+    ///
+    /// Will cancel running the closure.
+    ///
+    /// # Returns
+    /// * true: guard was disarmed and the closure will not be called anymore.
+    /// * false: guard was already not armed.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will disarm the guard so the closure is not called when the guard is dropped.
+    /// The closure is kept and the guard can be armed again with `rearm`.
+    ///
+    /// # Returns
+    /// * true: guard was disarmed.
+    /// * false: guard was already not armed.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        core::mem::replace(&mut self.1, false)
+    }
+
+    ///
+    /// Arms the guard again with its current closure.
+    /// The guard stays disarmed if the closure was already called the maximum number of times.
+    ///
+    /// # Returns
+    /// * true: the guard was not armed previously and is armed now.
+    /// * false: the guard was already armed or the maximum number of calls was reached.
+    ///
+    #[inline(always)]
+    pub fn rearm(&mut self) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+
+        !core::mem::replace(&mut self.1, true)
+    }
+
+    ///
+    /// Replaces the closure and arms the guard again.
+    /// The previous closure is dropped without being called.
+    /// The call count is kept, so the guard stays disarmed if the maximum number of calls was reached.
+    ///
+    /// # Returns
+    /// * true: the guard was not armed previously and is armed now.
+    /// * false: an armed closure was replaced without being called or the maximum number of calls was reached.
+    ///
+    #[inline(always)]
+    pub fn reset(&mut self, new_fn: F) -> bool {
+        self.0 = new_fn;
+        self.rearm()
+    }
+
+    ///
+    /// Returns true if the closure will be called when the guard is dropped.
+    ///
+    #[inline(always)]
+    pub fn is_armed(&self) -> bool {
+        self.1
+    }
+
+    ///
+    /// Returns how often the closure was called.
+    /// The count wraps around on overflow.
+    ///
+    #[inline(always)]
+    pub fn call_count(&self) -> u32 {
+        self.2
+    }
+
+    ///
+    /// Resets the call count to 0.
+    /// The closure and whether the guard is armed are not changed,
+    /// but a guard created with `with_max_calls` can be armed again.
+    ///
+    #[inline(always)]
+    pub fn reset_count(&mut self) {
+        self.2 = 0;
+    }
+
+    #[inline(always)]
+    fn is_exhausted(&self) -> bool {
+        self.3.is_some_and(|max| self.2 >= max)
+    }
+}
+
+impl<F: FnMut()> crate::Deferrable for FnMutDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        FnMutDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        FnMutDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        FnMutDeferGuard::is_armed(self)
+    }
+}
+
+impl<F: FnMut()> Drop for FnMutDeferGuard<F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
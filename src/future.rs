@@ -0,0 +1,137 @@
+use crate::DeferGuard;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+///
+/// Future that calls the closure if it is dropped before the inner future completed.
+///
+/// Created by `FutureDeferExt::defer_on_cancel`.
+/// The closure is called after the inner future was dropped.
+///
+#[must_use = "futures do nothing unless polled"]
+pub struct OnCancel<Fut: Future, F: FnOnce()> {
+    future: Fut,
+    guard: DeferGuard<F>,
+}
+
+///
+/// Future that calls the closure when it is dropped after the inner future completed.
+///
+/// Created by `FutureDeferExt::defer_on_complete`.
+/// The closure is called after the inner future was dropped.
+/// If the future is dropped before it completed, the closure is dropped without being called.
+///
+#[must_use = "futures do nothing unless polled"]
+pub struct OnComplete<Fut: Future, F: FnOnce()> {
+    future: Fut,
+    guard: DeferGuard<F>,
+    completed: bool,
+}
+
+// The closure is never pinned, only the future is.
+impl<Fut: Future + Unpin, F: FnOnce()> Unpin for OnCancel<Fut, F> {}
+impl<Fut: Future + Unpin, F: FnOnce()> Unpin for OnComplete<Fut, F> {}
+
+impl<Fut: Future, F: FnOnce()> Future for OnCancel<Fut, F> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        //SAFETY: the future is never moved out of self, the guard is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let result = future.poll(cx);
+        if result.is_ready() {
+            this.guard.cancel_ref();
+        }
+
+        result
+    }
+}
+
+impl<Fut: Future, F: FnOnce()> Future for OnComplete<Fut, F> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        //SAFETY: the future is never moved out of self, the guard is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let result = future.poll(cx);
+        if result.is_ready() {
+            this.completed = true;
+        }
+
+        result
+    }
+}
+
+impl<Fut: Future, F: FnOnce()> Drop for OnComplete<Fut, F> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.guard.cancel_ref();
+        }
+    }
+}
+
+impl<Fut: Future, F: FnOnce()> core::fmt::Debug for OnCancel<Fut, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OnCancel")
+            .field("pending", &self.guard.peek().is_some())
+            .finish()
+    }
+}
+
+impl<Fut: Future, F: FnOnce()> core::fmt::Debug for OnComplete<Fut, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OnComplete")
+            .field("completed", &self.completed)
+            .finish()
+    }
+}
+
+///
+/// Extension trait for futures to run a closure depending on whether the future completed.
+///
+/// Async code is usually canceled by dropping its future, for example when another branch of a `select!` wins.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::FutureDeferExt;
+///
+/// async fn initialize() {
+///     //Half initialized resources are released if this future is dropped early.
+///     let setup = async {
+///         //Setup...
+///     };
+///     setup.defer_on_cancel(|| println!("Setup was canceled")).await;
+/// }
+/// ```
+///
+pub trait FutureDeferExt: Future + Sized {
+    ///
+    /// Calls the closure if the returned future is dropped before it completed.
+    /// If the future completed, the closure is dropped without being called.
+    ///
+    #[track_caller]
+    fn defer_on_cancel<F: FnOnce()>(self, func: F) -> OnCancel<Self, F> {
+        OnCancel {
+            future: self,
+            guard: DeferGuard::new(func),
+        }
+    }
+
+    ///
+    /// Calls the closure when the returned future is dropped after it completed.
+    /// If the future is dropped before it completed, the closure is dropped without being called.
+    ///
+    #[track_caller]
+    fn defer_on_complete<F: FnOnce()>(self, func: F) -> OnComplete<Self, F> {
+        OnComplete {
+            future: self,
+            guard: DeferGuard::new(func),
+            completed: false,
+        }
+    }
+}
+
+impl<Fut: Future> FutureDeferExt for Fut {}
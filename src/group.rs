@@ -0,0 +1,158 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+type GroupFunc<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+///
+/// A group of independent deferred closures that can be sent to other threads.
+///
+/// The closures are called in reverse order of registration when `destroy_all` is called or when the group is dropped.
+/// With the `std` feature `destroy_all_parallel` calls them concurrently instead.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferGroup;
+///
+/// fn shutdown(sessions: &[String]) {
+///     let mut group = DeferGroup::new();
+///     for session in sessions {
+///         group.add(move || println!("Closing {}", session));
+///     }
+///     group.destroy_all();
+/// }
+/// ```
+///
+#[derive(Default)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the group is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferGroup<'a>(Vec<GroupFunc<'a>>);
+
+impl<'a> DeferGroup<'a> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    ///
+    /// Adds a closure to the group.
+    ///
+    #[inline(always)]
+    pub fn add(&mut self, func: impl FnOnce() + Send + 'a) {
+        self.0.push(Box::new(func));
+    }
+
+    ///
+    /// Returns the number of closures in the group.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    ///
+    /// Returns true if there are no closures in the group.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    ///
+    /// Will call all closures now in reverse order of registration.
+    /// The group is empty afterward and can be reused.
+    ///
+    pub fn destroy_all(&mut self) {
+        //The closure is removed before it is called,
+        //so if it panics the remaining closures are called when the group is dropped.
+        while let Some(func) = self.0.pop() {
+            func();
+        }
+    }
+
+    ///
+    /// Will cancel all closures, so they cannot be called anymore.
+    ///
+    #[inline(always)]
+    pub fn cancel_all(&mut self) {
+        self.0.clear();
+    }
+
+    ///
+    /// Will call all closures now, concurrently, and return once all of them returned.
+    ///
+    /// The order in which the closures are called is not specified.
+    /// The closures are called by at most `max_threads` threads, including the calling thread.
+    /// With `None`, every closure gets its own thread.
+    ///
+    /// If closures panic, the remaining closures are still called.
+    /// The first panic is re-raised after all closures returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::num::NonZeroUsize;
+    /// use defer_heavy::DeferGroup;
+    ///
+    /// fn shutdown(sessions: &[String]) {
+    ///     let mut group = DeferGroup::new();
+    ///     for session in sessions {
+    ///         group.add(move || println!("Closing {}", session));
+    ///     }
+    ///     //Closes the sessions on up to 4 threads.
+    ///     group.destroy_all_parallel(NonZeroUsize::new(4));
+    /// }
+    /// ```
+    ///
+    #[cfg(feature = "std")]
+    pub fn destroy_all_parallel(mut self, max_threads: Option<core::num::NonZeroUsize>) {
+        use std::sync::Mutex;
+
+        let jobs = core::mem::take(&mut self.0);
+        let threads = max_threads.map_or(jobs.len(), |max| max.get().min(jobs.len()));
+        let jobs = Mutex::new(jobs);
+        let first_panic = Mutex::new(None);
+
+        let work = || loop {
+            //The lock is released before the closure is called.
+            let Some(func) = jobs.lock().unwrap_or_else(|e| e.into_inner()).pop() else {
+                return;
+            };
+
+            if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(func)) {
+                first_panic
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert(payload);
+            }
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 1..threads {
+                scope.spawn(work);
+            }
+
+            work();
+        });
+
+        if let Some(payload) = first_panic.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for DeferGroup<'_> {
+    fn drop(&mut self) {
+        self.destroy_all();
+    }
+}
+
+impl core::fmt::Debug for DeferGroup<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferGroup")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
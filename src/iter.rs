@@ -0,0 +1,130 @@
+use core::iter::FusedIterator;
+
+///
+/// Why the closure of a `DeferIter` was called.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IterEnd {
+    /// The iterator returned `None`.
+    Exhausted,
+    /// The iterator was dropped before it returned `None`.
+    Dropped,
+}
+
+///
+/// Iterator that calls the closure exactly once, either when the inner iterator returns `None`
+/// or when it is dropped before that.
+///
+/// Created by `IterDeferExt::defer_cleanup`.
+/// The iterator is fused: after the inner iterator returned `None` it is not polled again.
+///
+pub struct DeferIter<I: Iterator, F: FnOnce(IterEnd)> {
+    iter: I,
+    func: Option<F>,
+}
+
+impl<I: Iterator, F: FnOnce(IterEnd)> DeferIter<I, F> {
+    ///
+    /// Returns true if the inner iterator returned `None` and the closure was called.
+    ///
+    #[inline(always)]
+    pub fn is_exhausted(&self) -> bool {
+        self.func.is_none()
+    }
+
+    #[inline(always)]
+    fn check(&mut self, item: Option<I::Item>) -> Option<I::Item> {
+        if item.is_none() {
+            if let Some(func) = self.func.take() {
+                func(IterEnd::Exhausted);
+            }
+        }
+
+        item
+    }
+}
+
+impl<I: Iterator, F: FnOnce(IterEnd)> Iterator for DeferIter<I, F> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_exhausted() {
+            return None;
+        }
+
+        let item = self.iter.next();
+        self.check(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.is_exhausted() {
+            return (0, Some(0));
+        }
+
+        self.iter.size_hint()
+    }
+}
+
+impl<I: DoubleEndedIterator, F: FnOnce(IterEnd)> DoubleEndedIterator for DeferIter<I, F> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.is_exhausted() {
+            return None;
+        }
+
+        let item = self.iter.next_back();
+        self.check(item)
+    }
+}
+
+impl<I: ExactSizeIterator, F: FnOnce(IterEnd)> ExactSizeIterator for DeferIter<I, F> {}
+
+impl<I: Iterator, F: FnOnce(IterEnd)> FusedIterator for DeferIter<I, F> {}
+
+impl<I: Iterator, F: FnOnce(IterEnd)> Drop for DeferIter<I, F> {
+    fn drop(&mut self) {
+        if let Some(func) = self.func.take() {
+            func(IterEnd::Dropped);
+        }
+    }
+}
+
+impl<I: Iterator + core::fmt::Debug, F: FnOnce(IterEnd)> core::fmt::Debug for DeferIter<I, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferIter")
+            .field("iter", &self.iter)
+            .field("exhausted", &self.is_exhausted())
+            .finish()
+    }
+}
+
+///
+/// Extension trait for iterators that are backed by a resource that must be cleaned up.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{IterDeferExt, IterEnd};
+///
+/// let lines = ["a", "b", "c"]
+///     .into_iter()
+///     .defer_cleanup(|end| println!("Cursor closed: {:?}", end));
+///
+/// for line in lines.take(2) {
+///     println!("{}", line);
+/// }
+/// //Prints "Cursor closed: Dropped"
+/// ```
+///
+pub trait IterDeferExt: Iterator + Sized {
+    ///
+    /// Calls the closure when the iterator returns `None` or when it is dropped before that.
+    ///
+    #[inline(always)]
+    fn defer_cleanup<F: FnOnce(IterEnd)>(self, func: F) -> DeferIter<Self, F> {
+        DeferIter {
+            iter: self,
+            func: Some(func),
+        }
+    }
+}
+
+impl<I: Iterator> IterDeferExt for I {}
@@ -0,0 +1,148 @@
+//! Records which guards were executed, canceled or forgotten.
+//!
+//! Every `DeferGuard` and `ArcDeferGuard` is recorded by the location where it was created.
+//! This is intended for tests that assert that no cleanup was silently discarded.
+//! The records are global, so tests using them should not run concurrently with other tests that create guards.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::{defer, leakcheck};
+//!
+//! fn test() {
+//!     leakcheck::reset();
+//!     {
+//!         defer! { println!("Cleanup"); }
+//!     }
+//!     leakcheck::assert_all_executed();
+//! }
+//! ```
+
+use crate::tracker::Outcome;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::panic::Location;
+use std::sync::{Mutex, MutexGuard};
+
+///
+/// Counts of the guards created at one location.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocationReport {
+    /// The location where the guards were created.
+    pub location: &'static Location<'static>,
+    /// Number of guards created at this location.
+    pub created: u64,
+    /// Number of guards whose closure was called.
+    pub executed: u64,
+    /// Number of guards whose closure was canceled.
+    pub canceled: u64,
+}
+
+impl LocationReport {
+    ///
+    /// Number of guards that were neither executed nor canceled.
+    /// These were forgotten or are still alive.
+    ///
+    #[inline(always)]
+    pub fn forgotten(&self) -> u64 {
+        self.created
+            .saturating_sub(self.executed)
+            .saturating_sub(self.canceled)
+    }
+}
+
+///
+/// Summary of all recorded guards, sorted by location.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    pub locations: Vec<LocationReport>,
+}
+
+impl LeakReport {
+    ///
+    /// Returns the counts for the guards created at the location.
+    ///
+    pub fn at(&self, location: &Location<'_>) -> Option<&LocationReport> {
+        self.locations.iter().find(|l| {
+            l.location.file() == location.file()
+                && l.location.line() == location.line()
+                && l.location.column() == location.column()
+        })
+    }
+
+    ///
+    /// Returns the counts of the guards that were canceled or forgotten.
+    ///
+    pub fn failures(&self) -> impl Iterator<Item = &LocationReport> {
+        self.locations
+            .iter()
+            .filter(|l| l.canceled > 0 || l.forgotten() > 0)
+    }
+}
+
+static RECORDS: Mutex<BTreeMap<&'static Location<'static>, LocationReport>> =
+    Mutex::new(BTreeMap::new());
+
+fn lock() -> MutexGuard<'static, BTreeMap<&'static Location<'static>, LocationReport>> {
+    RECORDS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn record(location: &'static Location<'static>, outcome: Outcome) {
+    let mut records = lock();
+    let entry = records.entry(location).or_insert(LocationReport {
+        location,
+        created: 0,
+        executed: 0,
+        canceled: 0,
+    });
+
+    match outcome {
+        Outcome::Created => entry.created += 1,
+        Outcome::Executed => entry.executed += 1,
+        Outcome::Canceled => entry.canceled += 1,
+        Outcome::Untracked => entry.created = entry.created.saturating_sub(1),
+    }
+}
+
+///
+/// Returns the counts of all recorded guards.
+///
+pub fn report() -> LeakReport {
+    LeakReport {
+        locations: lock().values().copied().collect(),
+    }
+}
+
+///
+/// Removes all records.
+///
+pub fn reset() {
+    lock().clear();
+}
+
+///
+/// Panics if any recorded guard was canceled or forgotten.
+/// The panic message lists the locations where these guards were created.
+///
+#[track_caller]
+pub fn assert_all_executed() {
+    let report = report();
+    let mut failures = report.failures().peekable();
+    if failures.peek().is_none() {
+        return;
+    }
+
+    let mut msg = alloc::string::String::from("not all deferred closures were executed:");
+    for failure in failures {
+        use core::fmt::Write;
+        let _ = write!(
+            msg,
+            "\n  {}: {} canceled, {} forgotten",
+            failure.location,
+            failure.canceled,
+            failure.forgotten()
+        );
+    }
+    panic!("{}", msg);
+}
@@ -3,6 +3,7 @@
 //! This crate is compatible uses `no_std`.
 //! The default features use `alloc`
 //! To disable alloc set default-features to false in cargo.toml.
+//! APIs that need the standard library require the `std` feature.
 //!
 //! This crates provides 6 macros for different use cases of deferment:
 //! 1. `defer!` simple deferment. Will execute when current scope ends.
@@ -18,12 +19,303 @@
 //!     - Execution can be canceled or preempted.
 //!     - Closure must be `Send`
 //!     - Target must support Arc & AtomicBool.
+//!     - The `parking_lot` feature uses a `parking_lot::Mutex` instead of the AtomicBool.
+//!     - The `triomphe` feature uses `triomphe::Arc` instead of `alloc::sync::Arc`.
+//!       Weak references are not available with this feature, `ArcDeferGuard::downgrade_ref`,
+//!       `WeakArcDeferGuard` and `ArcDeferGuard::cancel_token` do not exist with it.
 //!     - Target must support alloc
 //!     - can be disabled with `default-features=false` in Cargo.toml
 //!
 //! 6. `defer_move_arc!` Same as `defer_arc!` but moves local variables into the closure.
 //!     - All used local variables must be `Send`.
 //!
+//! The functions `scoped`, `guard` and `arc_guard` are alternatives to the macros for code that avoids statement macros.
+//!     - `scoped` returns an opaque guard that calls the closure when the surrounding scope ends.
+//!     - `guard` returns a `DeferGuard` and `arc_guard` returns an `ArcDeferGuard`.
+//!
+//! `defer_arc_final!` Returns a reference counted guard that cannot be canceled.
+//!     - Executes when the last reference is dropped.
+//!     - Smaller allocation and cheaper drop than `defer_arc!`.
+//!     - `defer_move_arc_final!` moves local variables into the closure.
+//!
+//! `defer_assert!` Asserts a condition when the current scope ends.
+//!     - Does not panic again if the scope ends because of a panic, without `std` this relies on `panic_tracking`.
+//!     - `defer_debug_assert!` is only enabled with debug assertions.
+//!
+//! `Checkpoint` panics when dropped unless the cleanup was marked as performed.
+//!     - The inverse of a defer, for cleanups that the guard cannot perform itself.
+//!     - The `checkpoint_lenient` feature only reports the failure in builds without debug assertions.
+//!
+//! `defer_ensure!` and `DeferGuard::ensure` return an `EnsureDeferGuard` that must be canceled or destroyed explicitly.
+//!     - Dropping it while the closure is pending calls the closure and panics in debug builds.
+//!     - Behaves like `defer_guard!` in release builds.
+//!
+//! `CountdownDeferGuard` Executes after a fixed number of its handles were dropped.
+//!     - Handles can be cloned and shared with other threads.
+//!     - Requires the `mt` feature.
+//!
+//! `flag_guard` sets an `AtomicBool` and clears it when the guard is dropped.
+//!     - `try_flag_guard` returns `None` if the flag is already set, for reentrancy checks.
+//!     - `cell_flag_guard` and `try_cell_flag_guard` do the same for a `Cell<bool>`.
+//!     - `defer_guarded_by!` executes unless an `AtomicBool` "done" flag was set when the guard is dropped.
+//!
+//! `FnMutDeferGuard` holds a `FnMut` closure.
+//!     - The guard can be armed again after it was executed or canceled.
+//!
+//! `MultiDeferGuard` holds up to a fixed number of closures of the same type without allocating.
+//!     - All closures can be canceled at once.
+//!
+//! `PinnedArcDeferGuard` is a shared guard that always executes on the thread that created it.
+//!     - If the last reference is dropped on another thread, the closure is sent to the home thread.
+//!     - Requires the `std` feature.
+//!
+//! `PolicyGuard` decides whether to execute the closure using a `DeferPolicy`.
+//!     - `Always`, `Never`, `OnUnwind` and `OnSuccess` are provided.
+//!     - `OnUnwind` and `OnSuccess` require the `std` feature or the hooks of the `panic_tracking` module.
+//!
+//! `Defer` owns a value and passes it to a closure when dropped.
+//!     - The value can be used through `Deref` and recovered with `into_inner()`.
+//!     - `DeferExt` creates the guard in method position: `value.defer(|v| ...)`.
+//!
+//! `defer_on_drop_of!` executes when the value of a variable is dropped.
+//!     - The variable is shadowed by a `WithDefer` that pairs the value with the guard.
+//!
+//! `bracket` acquires a resource, runs a body with it and releases it afterward.
+//!     - The resource is released even if the body panics.
+//!     - `try_bracket` accepts fallible closures, `acquire_deferred` returns the guard instead of taking a body.
+//!
+//! `defer_with_status!` Returns a guard whose closure receives an `ExitKind`.
+//!     - The closure can tell whether the scope ended normally, by panic or by an explicit `destroy()`.
+//!     - Requires the `std` feature or the hooks of the `panic_tracking` module.
+//!
+//! `defer_retry!` Executes a block again if it panics, up to a fixed number of attempts.
+//!     - The last panic is re-raised unless another panic is active.
+//!     - Requires the `std` feature.
+//!
+//! `PanicSafeDeferGuard` catches a panic of its closure.
+//!     - The panic is only re-raised if no other panic is active, so the process does not abort.
+//!     - Requires the `std` feature.
+//!
+//! `defer_with!` Returns a guard whose closure receives the last value set during the scope.
+//!     - `defer_with!(var => ...)` lets the scope and the deferred block both mutate `var`.
+//!
+//! `defer_with_context!` passes a mutable reference to a context variable to the deferred block.
+//!     - The scope keeps using the context through the returned `ContextDeferGuard`.
+//!     - The context is only borrowed, so it can be used again after the scope.
+//!
+//! `defer_guard_by_ref!` Returns a guard that only captures the listed variables by `&` or `&mut`.
+//!
+//! `defer_weak!` Executes with the target of a `Weak` if the target is still alive when the scope ends.
+//!     - The guard does not keep the target alive, see `DeferGuard::new_with_weak`.
+//!
+//! `defer_scope_guard!` Collects the `defer!` registrations of its body into a single `ScopeDeferGuard`.
+//!     - All of them can be canceled or executed at once.
+//!     - `defer_outer!` is equivalent to `defer!` in the body and does not compile outside of it.
+//!     - Requires the `alloc` feature.
+//!
+//! `defer_result_guard!` Returns a guard whose block returns a `Result`.
+//!     - An error is written to a `&mut Option<E>` of the enclosing scope.
+//!
+//! `defer_time!` Returns a guard that passes the time since its creation to the closure.
+//!     - Requires the `std` feature.
+//!
+//! `defer_timeout!` Returns a guard that executes when its scope ends or when a deadline passes.
+//!     - Whichever comes first, the closure executes exactly once.
+//!     - `defer_expiring!` instead cancels the closure when the deadline passes.
+//!     - Requires the `std` feature.
+//!
+//! `ErasedDeferGuard` and `ErasedArcDeferGuard` hold a boxed closure.
+//!     - Guards with different closures can be stored in the same `Vec`.
+//!     - Requires the `alloc` feature.
+//!
+//! `DeferIterator` calls the closures of a `Vec` of guards in reverse order.
+//!     - `cancel_vec` and `execute_vec` cancel or call all guards at once.
+//!     - Requires the `alloc` feature.
+//!
+//! `DeferSlot` starts disarmed and can be armed with a closure later.
+//!     - Can be used as a struct field because it does not depend on the type of the closure.
+//!     - `SendDeferSlot` only accepts `Send` closures.
+//!     - Requires the `alloc` feature.
+//!
+//! `CancelToken` cancels a guard without owning it.
+//!     - Created by `cancel_token()` of `CancelableDeferGuard` or `ArcDeferGuard`.
+//!     - `DeferGuard::with_token` converts a guard into a `CancelableDeferGuard`,
+//!       so plain guards do not carry the cancel state.
+//!     - Tokens do not keep the closure alive.
+//!     - Requires the `alloc` feature.
+//!
+//! `DeferStack` holds closures of different types.
+//!     - Closures can be pushed with a priority that decides the order of execution.
+//!     - Fallible closures can be pushed, their errors can be collected or are passed to the `error_hook`.
+//!     - A panicking closure does not prevent the remaining closures from being called.
+//!     - Requires the `alloc` feature.
+//!
+//! `link` links two guards, so at most one of them calls its closure.
+//!     - The first guard that is dropped or destroyed calls its closure, the other one drops it.
+//!     - `link_arc` does the same for guards that are sent to other threads, it requires the `mt` feature.
+//!     - Requires the `alloc` feature.
+//!
+//! `DeferGroup` holds independent `Send` closures.
+//!     - `destroy_all_parallel` calls them concurrently, this requires the `std` feature.
+//!     - Requires the `alloc` feature.
+//!
+//! `run_with_defers` runs a body and then the closures it registered, which can modify the return value.
+//!     - `run_with_defers_try` does the same for a `Result`, for example to add context to an error.
+//!     - Requires the `alloc` feature.
+//!
+//! `DeferArena` holds closures of different types in preallocated slots.
+//!     - Pushing a closure that fits into a slot does not allocate, the arena can be reused.
+//!     - Requires the `alloc` feature.
+//!
+//! `ScopedDeferGuard` can be shared with threads spawned by `std::thread::scope`.
+//!     - Does not require alloc and the closure does not need to be `'static`.
+//!     - Execution can be canceled.
+//!
+//! `scoped_defer!` Returns a guard that is bound to a `std::thread::scope`.
+//!     - The closure only has to live as long as the scope.
+//!     - It runs after the threads spawned through the guard finished.
+//!     - Requires the `std` feature.
+//!
+//! `StaticDeferGuard` stores the closure in a `static` `DeferSlotCell`.
+//!     - Does not require alloc, only core atomics.
+//!     - Handles are `Copy` and can cancel or fire the closure.
+//!
+//! `PoolDeferGuard` stores the closure in a slot of a `static` `DeferPool`.
+//!     - Does not require alloc, only core atomics.
+//!     - Handles can be cloned and the closure is called when the last handle is dropped.
+//!     - The slot is returned to the pool afterwards.
+//!     - Requires the `pool` feature.
+//!
+//! `CsDeferGuard` is a shared guard for targets without atomics and without alloc.
+//!     - The closure is stored in a `static` `CsDeferSlot`, the reference count is changed in critical sections.
+//!     - Requires the `critical-section` feature and a `critical-section` implementation for the target.
+//!
+//! `CDeferGuard` calls an `extern "C"` function with a context pointer.
+//!     - `#[repr(C)]`, so it can be embedded in structs that are shared with C code.
+//!     - `assert_send` makes it `Send` if the context is thread safe.
+//!
+//! `#[defer_drop(method)]` implements `Drop` by calling a method.
+//!     - Requires the `proc-macro` feature.
+//!
+//! `IterDeferExt` calls a closure when an iterator is exhausted or dropped early.
+//!     - The closure receives an `IterEnd` and is called exactly once.
+//!
+//! `FutureDeferExt` calls a closure if a future is dropped before or after it completed.
+//!     - `defer_on_cancel` cleans up after futures that were canceled, for example by `select!`.
+//!     - Requires the `async` feature.
+//!
+//! `AsyncDeferStack` holds async cleanups that are awaited in reverse order by `run_all`.
+//!     - An `AsyncDropPolicy` decides what happens to cleanups that were not run when the stack is dropped.
+//!     - Requires the `async` and `alloc` features.
+//!
+//! `TokioDeferGuard` passes a blocking closure to `tokio::task::spawn_blocking` when it is dropped.
+//!     - Keeps blocking cleanup off the executor threads.
+//!     - Requires the `tokio` feature.
+//!
+//! `ArcDeferGuard::new_with_completion` returns a `futures-channel` oneshot receiver
+//! that resolves once the last reference to the guard was dropped.
+//!     - Works with any executor.
+//!     - Requires the `futures-channel` feature.
+//!
+//! `Deferrable` is implemented by the guards and by closures, so generic code can accept any of them.
+//!     - `DeferrableDyn` is its object safe companion, it requires the `alloc` feature.
+//!
+//! The `scopeguard-compat` feature provides the `compat` module to migrate from the `scopeguard` crate.
+//!
+//! # Thread safety
+//! The guards are `Send` if the closure is `Send`.
+//! `ArcDeferGuard` and `WeakArcDeferGuard` are `Send` and `Sync` for every `Send` closure,
+//! even if the closure is not `Sync`, because the closure is never accessed through a shared reference.
+//! This allows storing an `ArcDeferGuard` in a `static` and canceling it from multiple threads.
+//!
+//! A guard with a closure that is not `Send` cannot be sent to another thread:
+//! ```rust,compile_fail
+//! use std::rc::Rc;
+//! use defer_heavy::defer_move_guard;
+//!
+//! fn test() {
+//!     let rc = Rc::new(1);
+//!     let guard = defer_move_guard! { println!("{}", rc); };
+//!     std::thread::spawn(move || drop(guard));
+//! }
+//! ```
+//!
+//! A `DeferGuard` is only `Sync` if the closure is `Sync`:
+//! ```rust,compile_fail
+//! use std::cell::Cell;
+//! use defer_heavy::defer_move_guard;
+//!
+//! fn require_sync<T: Sync>(_: &T) {}
+//!
+//! fn test() {
+//!     let cell = Cell::new(1);
+//!     let guard = defer_move_guard! { cell.set(2); };
+//!     require_sync(&guard);
+//! }
+//! ```
+//!
+//! # Leak detection
+//! The `leak-detect` feature records every `DeferGuard` and `ArcDeferGuard` by the location where it was created.
+//! Tests can use `leakcheck::assert_all_executed()` to ensure that no closure was canceled or forgotten.
+//! Without the feature nothing is recorded.
+//!
+//! # Statistics
+//! The `stats` feature counts how many guards were created, executed and canceled and how many are still alive.
+//! `stats::snapshot()` returns the current counts, for example to export them to a metrics system.
+//! Without the feature nothing is counted.
+//!
+//! # Slow closures
+//! The `slow-defer-warning` feature measures the closures of `DeferGuard`s that have a time budget
+//! and reports closures that take longer to the hook of the `slow_defer` module.
+//! The budget is set per guard with `DeferGuard::new_with_budget` or for all guards with `slow_defer::set_threshold`.
+//! Without the feature nothing is measured.
+//!
+//! # Panic tracking without std
+//! Without the `std` feature the crate cannot ask the standard library whether a panic is in progress.
+//! `OnUnwind`, `OnSuccess` and `StatusDeferGuard` use the counter of the `panic_tracking` module instead,
+//! which the panic handler or unwinding shim of the target has to maintain.
+//! See the `panic_tracking` module for the integration contract.
+//!
+//! # Error hook
+//! Errors of fallible cleanups that run when their owner is dropped are passed to the hook of the `error_hook` module.
+//! The default hook prints them to stderr with the `std` feature and ignores them otherwise.
+//!
+//! # Operating system resources
+//! The `os` module has guards that remove files and directories, close file descriptors and handles,
+//! or restore environment variables and the working directory.
+//! Errors of the removal or of restoring the working directory are passed to the `error_hook`. Requires the `std` feature.
+//!
+//! # Testing
+//! The `test-util` feature provides the `testing` module with helpers for tests,
+//! like recording the order in which closures were called or counting dropped closures.
+//! Enable it only for `dev-dependencies`.
+//!
+//! # Tracing
+//! The `tracing` feature emits `tracing` events when a closure created by one of the macros
+//! is registered, executed, or canceled.
+//! The events contain the file and line of the macro invocation.
+//! Without the feature the macros do not wrap the closures at all.
+//!
+//! # Logging
+//! The `log` feature provides the `defer_log!` macro which logs a message with the `log` crate
+//! when the deferred closure is executed or canceled. It does not require `std`.
+//!
+//! # FFI
+//! The `ffi` feature provides `extern "C"` functions to share an `ArcDeferGuard` with C code.
+//! The guard must hold a `Box<dyn FnOnce() + Send>` and is converted with `ErasedArcDeferGuard::into_raw`.
+//! The C declarations are in `include/defer_heavy.h`.
+//!
+//! # Lints
+//! The `lint-attrs` feature marks the guards with `#[clippy::has_significant_drop]`,
+//! so `clippy::significant_drop_tightening` and `clippy::significant_drop_in_scrutinee` report them.
+//! On a nightly compiler the guards are additionally marked with `#[must_not_suspend]`,
+//! so `#![warn(must_not_suspend)]` reports guards that are held across an `.await`.
+//! The build script detects the nightly compiler, the feature only uses stable attributes on other compilers.
+//! Only types with their own `Drop` implementation are marked.
+//! clippy still reports wrappers such as `ArcDeferGuard` or `WithDefer` through the marked type they contain.
+//! `StaticDeferGuard` is not marked, dropping the handle does nothing.
+//! `AsyncDeferStack` and `TokioDeferGuard` are meant to be held across an `.await`, they are not marked with `#[must_not_suspend]`.
+//!
 //! # Usage
 //!
 //! Add the dependency in your `Cargo.toml`:
@@ -41,38 +333,323 @@
 //!
 
 #![no_std]
+#![cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    feature(must_not_suspend)
+)]
 
-#[cfg(target_has_atomic = "8")]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
 #[cfg(target_has_atomic = "ptr")]
 #[cfg(feature = "mt")]
 mod mt {
     extern crate alloc;
+    use crate::tracker::Tracker;
     use crate::DeferGuard;
-    use alloc::sync::Arc;
+    #[cfg(not(feature = "triomphe"))]
+    use alloc::sync::{Arc, Weak};
+    use core::mem::ManuallyDrop;
+    #[cfg(target_has_atomic = "8")]
     use core::sync::atomic::AtomicBool;
+    #[cfg(target_has_atomic = "8")]
     use core::sync::atomic::Ordering::SeqCst;
+    #[cfg(feature = "triomphe")]
+    use triomphe::Arc;
 
-    #[doc(hidden)]
-    #[derive(Debug, Clone)]
+    ///
+    /// Flag of another crate that is checked in addition to our own flag.
+    ///
+    #[cfg(target_has_atomic = "8")]
+    type ExternalFlag = Option<alloc::sync::Arc<AtomicBool>>;
+
+    #[cfg(target_has_atomic = "8")]
+    #[inline(always)]
+    fn is_external_set(flag: &ExternalFlag) -> bool {
+        flag.as_ref().is_some_and(|flag| flag.load(SeqCst))
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    #[derive(Debug)]
+    struct CancelFlag(AtomicBool, ExternalFlag);
+
+    #[cfg(not(feature = "parking_lot"))]
+    impl CancelFlag {
+        #[inline(always)]
+        fn new(canceled: bool) -> Self {
+            Self(AtomicBool::new(canceled), None)
+        }
+
+        #[inline(always)]
+        fn is_set(&self) -> bool {
+            self.0.load(SeqCst) || is_external_set(&self.1)
+        }
+
+        #[inline(always)]
+        fn set(&self) {
+            self.0.store(true, SeqCst)
+        }
+    }
+
+    #[cfg(feature = "parking_lot")]
+    #[derive(Debug)]
+    struct CancelFlag(
+        parking_lot::Mutex<bool>,
+        #[cfg(target_has_atomic = "8")] ExternalFlag,
+    );
+
+    #[cfg(feature = "parking_lot")]
+    impl CancelFlag {
+        #[inline(always)]
+        fn new(canceled: bool) -> Self {
+            Self(
+                parking_lot::Mutex::new(canceled),
+                #[cfg(target_has_atomic = "8")]
+                None,
+            )
+        }
+
+        #[inline(always)]
+        fn is_set(&self) -> bool {
+            #[cfg(target_has_atomic = "8")]
+            if is_external_set(&self.1) {
+                return true;
+            }
+
+            *self.0.lock()
+        }
+
+        #[inline(always)]
+        fn set(&self) {
+            *self.0.lock() = true
+        }
+    }
+
+    ///
+    /// Snapshot of the state of an `ArcDeferGuard`, returned by `ArcDeferGuard::inspect_inner`.
+    ///
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ArcDeferGuardState {
+        ///
+        /// Number of references to the guard.
+        ///
+        pub strong_count: usize,
+        ///
+        /// True if the guard was canceled by one of its references or its external cancel flag.
+        ///
+        pub is_canceled: bool,
+        ///
+        /// False if the guard was created without a closure, for example by erasing a canceled guard.
+        ///
+        pub has_closure: bool,
+    }
+
+    impl core::fmt::Display for ArcDeferGuardState {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(
+                f,
+                "ArcDeferGuard {{ references: {}, canceled: {}, closure: {} }}",
+                self.strong_count, self.is_canceled, self.has_closure
+            )
+        }
+    }
+
+    ///
+    /// Shared guard that calls the closure when the last reference to it is dropped.
+    ///
+    /// References can be cloned and sent to other threads, so the closure must be `Send`.
+    /// Any reference can cancel the closure for all references.
+    /// This is the type returned by `defer_arc!`, `defer_move_arc!` and `arc_guard`.
+    ///
+    /// # Execution
+    /// The closure is owned by the shared allocation and called when the allocation is dropped,
+    /// which is when the reference count reaches 0.
+    /// Decrementing the reference count is a single atomic operation,
+    /// so if multiple threads drop their references at the same time exactly one of them observes 0
+    /// and calls the closure, exactly once.
+    /// Which thread that is depends on the order of the drops and is not specified.
+    /// Methods that consume a reference, like `try_destroy`, use `Arc::try_unwrap` and only succeed
+    /// for the last reference, so they cannot call the closure a second time either.
+    ///
+    /// Unlike multiple `defer!` in the same scope, there is no order between different `ArcDeferGuard`s
+    /// that are dropped on different threads.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::thread;
+    /// use defer_heavy::ArcDeferGuard;
+    ///
+    /// let guard = ArcDeferGuard::new(|| println!("All workers finished"));
+    /// let workers: Vec<_> = (0..4)
+    ///     .map(|_| {
+    ///         let guard = guard.clone();
+    ///         thread::spawn(move || {
+    ///             let _guard = guard.own();
+    ///             //Do work...
+    ///         })
+    ///     })
+    ///     .collect();
+    /// drop(guard);
+    /// for worker in workers {
+    ///     worker.join().unwrap();
+    /// }
+    /// //Printed "All workers finished" once the last worker dropped its reference.
+    /// ```
+    ///
     pub struct ArcDeferGuard<F: FnOnce() + Send>(Arc<ArcDeferGuardInner<F>>);
 
-    #[doc(hidden)]
+    impl<F: FnOnce() + Send> core::fmt::Debug for ArcDeferGuard<F> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_tuple("ArcDeferGuard").field(&*self.0).finish()
+        }
+    }
+
+    impl<F: FnOnce() + Send> Clone for ArcDeferGuard<F> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    ///
+    /// Guards are equal if they are references to the same guard.
+    /// This compares identity, not the closures.
+    ///
+    impl<F: FnOnce() + Send> PartialEq for ArcDeferGuard<F> {
+        fn eq(&self, other: &Self) -> bool {
+            self.ptr_eq(other)
+        }
+    }
+
+    impl<F: FnOnce() + Send> Eq for ArcDeferGuard<F> {}
+
+    ///
+    /// Hashes the identity of the guard, see `ArcDeferGuard::id`.
+    ///
+    impl<F: FnOnce() + Send> core::hash::Hash for ArcDeferGuard<F> {
+        fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+            self.id().hash(state)
+        }
+    }
+
+    impl<F: FnOnce() + Send> crate::Deferrable for ArcDeferGuard<F> {
+        #[inline(always)]
+        #[track_caller]
+        fn cancel(self) {
+            ArcDeferGuard::cancel(self)
+        }
+
+        #[inline(always)]
+        fn destroy(self) -> bool {
+            self.try_execute_if_sole()
+        }
+
+        #[inline(always)]
+        fn is_armed(&self) -> bool {
+            let state = self.inspect_inner();
+            !state.is_canceled && state.has_closure
+        }
+    }
+
     impl<F: FnOnce() + Send> ArcDeferGuard<F> {
+        ///
+        /// Creates a guard that calls the closure when the last reference is dropped.
+        ///
         #[inline(always)]
         #[must_use]
+        #[track_caller]
         pub fn new(func: F) -> Self {
-            Self(Arc::new(ArcDeferGuardInner(
-                AtomicBool::new(false),
+            Self(Arc::new(ArcDeferGuardInner::new(
                 Some(func),
+                Tracker::new(),
             )))
         }
 
+        ///
+        /// Creates a guard that is also canceled once the external `flag` is set to true.
+        /// The closure is skipped if either this guard was canceled or the `flag` is true
+        /// when the last reference to the guard is dropped.
+        ///
+        /// This allows tying the guard to cancellation tokens of other crates that use an `Arc<AtomicBool>`.
+        ///
+        /// # Examples
+        /// ```rust
+        /// use std::sync::atomic::{AtomicBool, Ordering};
+        /// use std::sync::Arc;
+        /// use defer_heavy::ArcDeferGuard;
+        ///
+        /// let token = Arc::new(AtomicBool::new(false));
+        /// let guard = ArcDeferGuard::with_external_cancel(|| unreachable!(), token.clone());
+        /// token.store(true, Ordering::SeqCst);
+        /// drop(guard);
+        /// ```
+        ///
+        #[cfg(target_has_atomic = "8")]
+        #[must_use]
+        #[track_caller]
+        pub fn with_external_cancel(func: F, flag: alloc::sync::Arc<AtomicBool>) -> Self {
+            let mut inner = ArcDeferGuardInner::new(Some(func), Tracker::new());
+            inner.0 .1 = Some(flag);
+            Self(Arc::new(inner))
+        }
+
+        ///
+        /// Creates a guard and a receiver that resolves once the last reference to the guard was dropped.
+        ///
+        /// This is the async counterpart of `wait_for_completion` and works with any executor.
+        /// The receiver resolves regardless of whether the closure was canceled, called or moved out of the guard.
+        /// If the closure is called, it resolves after the closure returned.
+        ///
+        /// # Examples
+        /// ```rust
+        /// use defer_heavy::ArcDeferGuard;
+        ///
+        /// async fn shutdown() {
+        ///     let (guard, done) = ArcDeferGuard::new_with_completion(|| println!("Pool closed"));
+        ///     let worker = guard.clone();
+        ///     std::thread::spawn(move || drop(worker));
+        ///     drop(guard);
+        ///     done.await.unwrap();
+        /// }
+        /// ```
+        ///
+        #[cfg(feature = "futures-channel")]
+        #[track_caller]
+        pub fn new_with_completion(func: F) -> (Self, futures_channel::oneshot::Receiver<()>) {
+            let (sender, receiver) = futures_channel::oneshot::channel();
+            let mut inner = ArcDeferGuardInner::new(Some(func), Tracker::new());
+            inner.3.sender = Some(sender);
+            (Self(Arc::new(inner)), receiver)
+        }
+
         #[inline(always)]
-        pub(crate) fn new_opt(func: Option<F>) -> Self {
-            Self(Arc::new(ArcDeferGuardInner(
-                AtomicBool::new(func.is_none()),
-                func,
-            )))
+        pub(crate) fn new_opt(func: Option<F>, tracker: Tracker) -> Self {
+            Self(Arc::new(ArcDeferGuardInner::new(func, tracker)))
+        }
+
+        #[cfg(target_has_atomic = "8")]
+        #[inline(always)]
+        pub(crate) fn set_external_flag(&mut self, flag: Option<alloc::sync::Arc<AtomicBool>>) {
+            if let Some(inner) = Arc::get_mut(&mut self.0) {
+                inner.0 .1 = flag;
+            }
+        }
+
+        ///
+        /// Returns a token that can cancel this guard without keeping the closure alive.
+        /// This is not available with the `triomphe` feature, because it requires weak references.
+        ///
+        #[cfg(target_has_atomic = "8")]
+        #[cfg(not(feature = "triomphe"))]
+        pub fn cancel_token(&self) -> crate::CancelToken
+        where
+            F: 'static,
+        {
+            let weak: Weak<ArcDeferGuardInner<F>> = Arc::downgrade(&self.0);
+            crate::CancelToken::shared(weak)
         }
 
         ///
@@ -84,6 +661,50 @@ mod mt {
             self
         }
 
+        ///
+        /// Returns true if both are references to the same guard.
+        ///
+        #[inline(always)]
+        pub fn ptr_eq(&self, other: &Self) -> bool {
+            Arc::ptr_eq(&self.0, &other.0)
+        }
+
+        ///
+        /// Returns a snapshot of the state of the guard for debugging.
+        /// Other threads may change the state right after this returns.
+        ///
+        /// # Examples
+        /// ```rust
+        /// use defer_heavy::ArcDeferGuard;
+        ///
+        /// let guard = ArcDeferGuard::new(|| println!("Cleanup"));
+        /// let clone = guard.clone();
+        /// clone.cancel_ref();
+        /// //Prints "ArcDeferGuard { references: 2, canceled: true, closure: true }"
+        /// println!("{}", guard.inspect_inner());
+        /// ```
+        ///
+        #[inline(always)]
+        pub fn inspect_inner(&self) -> ArcDeferGuardState {
+            ArcDeferGuardState {
+                strong_count: Arc::strong_count(&self.0),
+                is_canceled: self.0 .0.is_set(),
+                //Only the discriminant is read, the closure itself is never accessed through a shared reference.
+                has_closure: self.0 .1.is_some(),
+            }
+        }
+
+        ///
+        /// Returns an id that identifies the guard.
+        /// All references to the same guard have the same id.
+        /// The id stays the same as long as a reference to the guard exists,
+        /// afterward it may be reused by another guard.
+        ///
+        #[inline(always)]
+        pub fn id(&self) -> usize {
+            Arc::as_ptr(&self.0) as *const () as usize
+        }
+
         ///
         /// Downgrade the guard to a non reference counted guard.
         ///
@@ -94,11 +715,12 @@ mod mt {
         #[inline(always)]
         pub fn try_downgrade(self) -> Result<DeferGuard<F>, Self> {
             let mut inner = Arc::try_unwrap(self.0).map_err(|a| ArcDeferGuard(a))?;
-            if !inner.0.load(SeqCst) {
-                return Ok(DeferGuard(inner.1.take()));
+            if !inner.0.is_set() {
+                return Ok(DeferGuard(inner.1.take(), inner.2.take()));
             }
 
-            Ok(DeferGuard(None))
+            //The canceled closure is recorded when inner is dropped.
+            Ok(DeferGuard(None, Tracker::empty()))
         }
 
         ///
@@ -115,7 +737,138 @@ mod mt {
         pub fn try_destroy(self) -> Result<bool, Self> {
             let inner = Arc::try_unwrap(self.0).map_err(|a| ArcDeferGuard(a))?;
             //DROP inner which calls the closure if inner.0 (canceled flag) is not true.
-            Ok(!inner.0.load(SeqCst))
+            Ok(!inner.0.is_set())
+        }
+
+        ///
+        /// Calls the closure now if the predicate returns true and this is the only reference to the guard.
+        /// The predicate is called first, even if the guard is canceled.
+        /// If the closure is not called now, this reference is dropped normally.
+        ///
+        /// # Returns
+        /// * true: closure was called.
+        /// * false: the predicate returned false, the guard is canceled or there is more than 1 reference to the guard.
+        ///
+        pub fn execute_if(self, pred: impl FnOnce() -> bool) -> bool {
+            if !pred() {
+                return false;
+            }
+
+            self.try_execute_if_sole()
+        }
+
+        ///
+        /// Calls the closure now if this is the only reference to the guard.
+        /// Otherwise this reference is dropped and the closure is called when the last reference is dropped.
+        ///
+        /// # Returns
+        /// * true: closure was called.
+        /// * false: the guard is canceled or there is more than 1 reference to the guard.
+        ///
+        #[inline(always)]
+        pub fn try_execute_if_sole(self) -> bool {
+            self.try_destroy().unwrap_or(false)
+        }
+
+        ///
+        /// Will leak the closure, so it is neither called nor dropped.
+        /// None of the values captured by the closure are dropped.
+        /// This will succeed if no other references to it exist.
+        ///
+        /// # Returns
+        /// * Ok: closure was leaked or already dropped.
+        /// * Err: there is still more than 1 reference to the guard.
+        ///
+        pub fn try_forget(self) -> Result<(), Self> {
+            let mut inner = Arc::try_unwrap(self.0).map_err(|a| ArcDeferGuard(a))?;
+            if let Some(f) = inner.1.take() {
+                core::mem::forget(f);
+            }
+            Ok(())
+        }
+
+        ///
+        /// Boxes the closure, so guards with different closures have the same type.
+        /// This will succeed if no other references to it exist.
+        /// A canceled guard stays canceled.
+        ///
+        /// # Returns
+        /// * Ok: the erased guard.
+        /// * Err: there is still more than 1 reference to the guard.
+        ///
+        #[inline(always)]
+        pub fn try_erase(self) -> Result<ErasedArcDeferGuard, Self>
+        where
+            F: 'static,
+        {
+            self.try_map(|f| alloc::boxed::Box::new(f) as alloc::boxed::Box<dyn FnOnce() + Send>)
+        }
+
+        ///
+        /// Replaces the closure with the closure returned by `map`, which receives the current closure.
+        /// This will succeed if no other references to it exist.
+        /// A canceled guard stays canceled, `map` is not called in that case.
+        /// The new guard keeps the external cancel flag and the waiters of this guard.
+        ///
+        /// # Returns
+        /// * Ok: the guard with the new closure.
+        /// * Err: there is still more than 1 reference to the guard.
+        ///
+        /// # Examples
+        /// ```rust
+        /// use defer_heavy::ArcDeferGuard;
+        ///
+        /// let guard = ArcDeferGuard::new(|| println!("Release connection"));
+        /// let worker = guard.clone();
+        /// //... the worker checked in
+        /// drop(worker);
+        ///
+        /// let guard = guard
+        ///     .try_map(|release| move || {
+        ///         println!("Flush connection");
+        ///         release();
+        ///     })
+        ///     .unwrap();
+        /// ```
+        ///
+        pub fn try_map<G: FnOnce() + Send>(
+            self,
+            map: impl FnOnce(F) -> G,
+        ) -> Result<ArcDeferGuard<G>, Self> {
+            let mut inner = Arc::try_unwrap(self.0).map_err(ArcDeferGuard)?;
+            let func = if inner.0.is_set() {
+                None
+            } else {
+                inner.1.take().map(map)
+            };
+
+            #[allow(unused_mut)]
+            let mut mapped = ArcDeferGuardInner::new(func, inner.2.take());
+            #[cfg(target_has_atomic = "8")]
+            {
+                mapped.0 .1 = inner.0 .1.take();
+            }
+            //Waiters of the old guard now wait for the new guard.
+            mapped.3 = core::mem::take(&mut inner.3);
+            Ok(ArcDeferGuard(Arc::new(mapped)))
+        }
+
+        ///
+        /// Returns a reference to the closure if this is the only reference to the guard.
+        /// This requires a mutable reference because the guard is `Sync` even if the closure is not.
+        ///
+        /// # Returns
+        /// * Some(Some): the closure is not canceled.
+        /// * Some(None): the closure is canceled.
+        /// * None: there is more than 1 reference to the guard.
+        ///
+        pub fn try_peek(&mut self) -> Option<Option<&F>> {
+            let inner = Arc::get_mut(&mut self.0)?;
+            if inner.0.is_set() {
+                return Some(None);
+            }
+
+            Some(inner.1.as_ref())
         }
 
         ///
@@ -124,8 +877,10 @@ mod mt {
         /// however it is guaranteed to not get called anymore.
         ///
         #[inline(always)]
+        #[track_caller]
         pub fn cancel(self) {
-            self.0 .0.store(true, SeqCst)
+            crate::__private::trace_cancel("defer_arc", core::panic::Location::caller());
+            self.0 .0.set()
         }
 
         ///
@@ -134,408 +889,2921 @@ mod mt {
         /// however it is guaranteed to not get called anymore.
         ///
         #[inline(always)]
+        #[track_caller]
         pub fn cancel_ref(&self) {
-            self.0 .0.store(true, SeqCst)
+            crate::__private::trace_cancel("defer_arc", core::panic::Location::caller());
+            self.0 .0.set()
         }
-    }
 
-    impl<T: FnOnce() + Send> TryFrom<ArcDeferGuard<T>> for DeferGuard<T> {
-        type Error = ArcDeferGuard<T>;
+        ///
+        /// Returns a handle that can be used to block until the closure was called or dropped.
+        /// This happens once the last reference to the guard is dropped,
+        /// regardless of whether the closure was canceled, called or moved out of the guard.
+        ///
+        #[cfg(feature = "std")]
+        #[must_use]
+        pub fn wait_for_completion(&self) -> CompletionHandle {
+            CompletionHandle(self.0 .3.completion.get_or_init(Default::default).clone())
+        }
 
-        fn try_from(value: ArcDeferGuard<T>) -> Result<Self, Self::Error> {
-            value.try_downgrade()
+        ///
+        /// Converts the guard into a raw pointer, for example to pass it as user data to a C callback.
+        /// The reference held by this guard is transferred to the pointer.
+        ///
+        /// Every call must be balanced by exactly one call to `from_raw`.
+        /// If `from_raw` is never called, the reference is leaked and the closure is neither called nor dropped.
+        /// Canceling another reference to the guard still prevents the closure from being called
+        /// once the pointer was converted back and dropped.
+        ///
+        #[inline(always)]
+        #[must_use]
+        pub fn into_raw(self) -> *const () {
+            Arc::into_raw(self.0).cast()
         }
-    }
 
-    impl<T: FnOnce() + Send> From<DeferGuard<T>> for ArcDeferGuard<T> {
-        fn from(value: DeferGuard<T>) -> Self {
-            value.upgrade()
+        ///
+        /// Converts a raw pointer back into the guard.
+        /// The reference held by the pointer is transferred to the guard.
+        ///
+        /// # Safety
+        /// The pointer must have been returned by `into_raw` of a guard with the same closure type
+        /// and its reference must not have been released yet.
+        /// The pointer must not be used afterward.
+        ///
+        #[inline(always)]
+        pub unsafe fn from_raw(ptr: *const ()) -> Self {
+            Self(Arc::from_raw(ptr.cast()))
+        }
+
+        ///
+        /// Creates a new reference to the guard from a raw pointer without consuming the pointer.
+        /// This is useful for callbacks that may be called multiple times.
+        ///
+        /// # Safety
+        /// The pointer must have been returned by `into_raw` of a guard with the same closure type
+        /// and its reference must not have been released yet.
+        /// The pointer stays valid and must still be passed to `from_raw` eventually.
+        ///
+        #[inline(always)]
+        pub unsafe fn clone_from_raw(ptr: *const ()) -> Self {
+            let guard = ManuallyDrop::new(Self::from_raw(ptr));
+            (*guard).clone()
+        }
+
+        ///
+        /// Creates a weak reference to the guard without consuming this guard.
+        /// The weak reference does not keep the closure from executing.
+        /// Not available with the `triomphe` feature, because `triomphe::Arc` has no weak references.
+        ///
+        #[cfg(not(feature = "triomphe"))]
+        #[inline(always)]
+        #[must_use]
+        pub fn downgrade_ref(&self) -> WeakArcDeferGuard<F> {
+            WeakArcDeferGuard(Arc::downgrade(&self.0))
         }
     }
 
-    #[derive(Debug)]
-    struct ArcDeferGuardInner<F: FnOnce() + Send>(AtomicBool, Option<F>);
+    ///
+    /// An `ArcDeferGuard` with a boxed closure.
+    /// Guards with different closures can be stored in the same collection after calling `try_erase`.
+    ///
+    /// All erased guards share a single instance of the shared state and its drop glue,
+    /// instead of one instance per closure type.
+    /// In exchange, the closure needs a second allocation next to the shared state.
+    ///
+    pub type ErasedArcDeferGuard = ArcDeferGuard<alloc::boxed::Box<dyn FnOnce() + Send>>;
 
-    impl<F: FnOnce() + Send> Drop for ArcDeferGuardInner<F> {
-        fn drop(&mut self) {
-            if !self.0.load(SeqCst) {
-                if let Some(f) = self.1.take() {
-                    f()
-                }
-            }
+    impl ErasedArcDeferGuard {
+        ///
+        /// Boxes the closure and creates an erased guard.
+        ///
+        /// # Examples
+        /// ```rust
+        /// use defer_heavy::ErasedArcDeferGuard;
+        ///
+        /// let guards = vec![
+        ///     ErasedArcDeferGuard::boxed(|| println!("First")),
+        ///     ErasedArcDeferGuard::boxed(move || println!("Second")),
+        /// ];
+        /// drop(guards);
+        /// ```
+        ///
+        #[inline(always)]
+        #[must_use]
+        #[track_caller]
+        pub fn boxed<G: FnOnce() + Send + 'static>(func: G) -> Self {
+            Self::new(alloc::boxed::Box::new(func))
         }
     }
-}
 
-#[doc(hidden)]
-#[derive(Debug)]
-pub struct DeferGuard<F: FnOnce()>(Option<F>);
+    #[cfg(feature = "ffi")]
+    impl ErasedArcDeferGuard {
+        ///
+        /// Releases this reference without calling the closure.
+        /// If this was the last reference, the closure is dropped without being called.
+        /// With the `triomphe` feature the closure may still be called if another thread
+        /// releases the second to last reference at the same time.
+        ///
+        pub(crate) fn release(self) {
+            #[cfg(not(feature = "triomphe"))]
+            if let Some(inner) = Arc::into_inner(self.0) {
+                inner.0.set();
+            }
 
-impl<F: FnOnce()> DeferGuard<F> {
-    #[inline(always)]
-    #[must_use]
-    pub fn new(func: F) -> Self {
-        Self(Some(func))
+            #[cfg(feature = "triomphe")]
+            if let Ok(inner) = Arc::try_unwrap(self.0) {
+                inner.0.set();
+            }
+        }
     }
 
     ///
-    /// Upgrade the guard to a reference counted one.
-    /// This function is only available if the closure is `Send`
+    /// Reference counted guard that executes its closure when the last reference is dropped.
     ///
-    /// # Returns
-    /// The reference counted guard.
+    /// Unlike `ArcDeferGuard` it cannot be canceled. It does not store a cancel flag,
+    /// so the shared allocation only contains the reference counts and the closure.
+    /// Dropping the last reference only decrements the reference count and calls the closure,
+    /// there is no additional atomic load of a cancel flag.
     ///
-    #[cfg(target_has_atomic = "8")]
-    #[cfg(target_has_atomic = "ptr")]
-    #[cfg(feature = "mt")]
-    pub fn upgrade(mut self) -> ArcDeferGuard<F>
-    where
-        F: FnOnce() + Send,
-    {
-        ArcDeferGuard::new_opt(self.0.take())
+    #[doc(hidden)]
+    pub struct ArcDefer<F: FnOnce() + Send>(Arc<ArcDeferInner<F>>);
+
+    impl<F: FnOnce() + Send> Clone for ArcDefer<F> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
     }
 
-    ///
-    /// Will call the closure now.
-    ///
-    /// # Returns
-    /// * true: closure was called.
-    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
-    ///
-    #[inline(always)]
-    pub fn destroy(mut self) -> bool {
-        self.0.take().map(|f| f()).is_some()
+    #[doc(hidden)]
+    impl<F: FnOnce() + Send> ArcDefer<F> {
+        #[inline(always)]
+        #[must_use]
+        pub fn new(func: F) -> Self {
+            Self(Arc::new(ArcDeferInner(ManuallyDrop::new(func))))
+        }
+
+        ///
+        /// Utility function to ensure ownership is transferred to a thread/closure.
+        ///
+        #[inline(always)]
+        #[must_use]
+        pub fn own(self) -> Self {
+            self
+        }
+
+        ///
+        /// Try to call the closure.
+        /// This will succeed if no other references to it exist.
+        ///
+        /// # Returns
+        /// * Ok: closure was called
+        /// * Err: there is still more than 1 reference to the guard.
+        ///
+        pub fn try_destroy(self) -> Result<(), Self> {
+            //DROP inner which calls the closure.
+            Arc::try_unwrap(self.0).map(drop).map_err(ArcDefer)
+        }
+    }
+
+    impl<F: FnOnce() + Send> core::fmt::Debug for ArcDefer<F> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("ArcDefer").finish_non_exhaustive()
+        }
+    }
+
+    #[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+    struct ArcDeferInner<F: FnOnce() + Send>(ManuallyDrop<F>);
+
+    // The closure is only ever accessed in `Drop`, which requires exclusive ownership.
+    unsafe impl<F: FnOnce() + Send> Sync for ArcDeferInner<F> {}
+
+    impl<F: FnOnce() + Send> Drop for ArcDeferInner<F> {
+        fn drop(&mut self) {
+            //SAFETY: the closure is taken exactly once, the field is never used afterward.
+            let f = unsafe { ManuallyDrop::take(&mut self.0) };
+            f()
+        }
     }
 
     ///
-    /// Will call the closure now.
-    /// This drops the closure.
-    ///
-    /// # Returns
-    /// * true: closure was called.
-    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    /// Weak reference to an `ArcDeferGuard`.
+    /// Not available with the `triomphe` feature.
+    /// It can be used to check whether the closure is still pending without delaying its execution.
     ///
-    #[inline(always)]
-    pub fn destroy_ref(&mut self) -> bool {
-        self.0.take().map(|f| f()).is_some()
+    #[cfg(not(feature = "triomphe"))]
+    #[derive(Debug)]
+    pub struct WeakArcDeferGuard<F: FnOnce() + Send>(Weak<ArcDeferGuardInner<F>>);
+
+    #[cfg(not(feature = "triomphe"))]
+    impl<F: FnOnce() + Send> WeakArcDeferGuard<F> {
+        ///
+        /// Upgrade the weak reference to a guard.
+        ///
+        /// # Returns
+        /// * Some: the guard still exists.
+        /// * None: the closure was already called or dropped.
+        ///
+        #[inline(always)]
+        pub fn upgrade(&self) -> Option<ArcDeferGuard<F>> {
+            self.0.upgrade().map(ArcDeferGuard)
+        }
+
+        ///
+        /// Returns true if the closure has not been called or dropped yet.
+        /// This is also true if the closure was canceled but there are still references to the guard.
+        ///
+        #[inline(always)]
+        pub fn is_pending(&self) -> bool {
+            self.0.strong_count() > 0
+        }
     }
 
+    #[cfg(not(feature = "triomphe"))]
+    impl<F: FnOnce() + Send> Clone for WeakArcDeferGuard<F> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T: FnOnce() + Send> TryFrom<ArcDeferGuard<T>> for DeferGuard<T> {
+        type Error = ArcDeferGuard<T>;
+
+        fn try_from(value: ArcDeferGuard<T>) -> Result<Self, Self::Error> {
+            value.try_downgrade()
+        }
+    }
+
+    //The closure type of the guard is not nameable, this impl only exists so the function can be called as `ArcDeferGuard::new_with_weak`.
+    impl ArcDeferGuard<fn()> {
+        ///
+        /// Creates a guard that calls the closure with the target of the `Weak` if it is still alive when the guard executes.
+        ///
+        /// The `Weak` is upgraded when the last reference to the guard is dropped or the guard is destroyed.
+        /// The guard does not keep the target alive.
+        ///
+        #[inline(always)]
+        #[must_use]
+        #[track_caller]
+        pub fn new_with_weak<T: Send + Sync, G: FnOnce(alloc::sync::Arc<T>) + Send>(
+            weak: alloc::sync::Weak<T>,
+            func: G,
+        ) -> ArcDeferGuard<impl FnOnce() + Send> {
+            ArcDeferGuard::new(move || {
+                if let Some(target) = weak.upgrade() {
+                    func(target)
+                }
+            })
+        }
+    }
+
+    impl<T: FnOnce() + Send> From<DeferGuard<T>> for ArcDeferGuard<T> {
+        fn from(value: DeferGuard<T>) -> Self {
+            value.upgrade()
+        }
+    }
+
+    #[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+    struct ArcDeferGuardInner<F: FnOnce() + Send>(CancelFlag, Option<F>, Tracker, CompletionNotify);
+
     ///
-    /// Will cancel running the closure, so it cannot be called anymore.
-    ///
-    /// # Returns
-    /// * true: closure was dropped and will not be called anymore.
-    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    /// Notifies waiters once the inner guard is dropped.
     ///
-    #[inline(always)]
-    pub fn cancel(mut self) -> bool {
-        self.0.take().is_some()
+    #[derive(Default)]
+    struct CompletionNotify {
+        #[cfg(feature = "std")]
+        completion: std::sync::OnceLock<Arc<Completion>>,
+        #[cfg(feature = "futures-channel")]
+        sender: Option<futures_channel::oneshot::Sender<()>>,
+    }
+
+    impl<F: FnOnce() + Send> ArcDeferGuardInner<F> {
+        #[inline(always)]
+        fn new(func: Option<F>, tracker: Tracker) -> Self {
+            Self(
+                CancelFlag::new(func.is_none()),
+                func,
+                tracker,
+                CompletionNotify::default(),
+            )
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[derive(Debug, Default)]
+    struct Completion(std::sync::Mutex<bool>, std::sync::Condvar);
+
+    #[cfg(feature = "std")]
+    impl Completion {
+        fn lock(&self) -> std::sync::MutexGuard<'_, bool> {
+            self.0.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn notify(&self) {
+            *self.lock() = true;
+            self.1.notify_all();
+        }
     }
 
     ///
-    /// Will cancel the closure, so it cannot be called anymore.
-    /// This drops the closure.
-    ///
-    /// # Returns
-    /// * true: closure was dropped and will not be called anymore.
-    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    /// Handle returned by `ArcDeferGuard::wait_for_completion`.
+    /// It does not keep the closure from executing.
     ///
-    #[inline(always)]
-    pub fn cancel_ref(&mut self) -> bool {
-        self.0.take().is_some()
+    #[cfg(feature = "std")]
+    #[derive(Debug, Clone)]
+    pub struct CompletionHandle(Arc<Completion>);
+
+    #[cfg(feature = "std")]
+    impl CompletionHandle {
+        ///
+        /// Blocks until the last reference to the guard was dropped.
+        /// If the closure is called, this returns after the closure returned.
+        ///
+        pub fn wait(&self) {
+            let mut done = self.0.lock();
+            while !*done {
+                done = self.0 .1.wait(done).unwrap_or_else(|e| e.into_inner());
+            }
+        }
+
+        ///
+        /// Blocks until the last reference to the guard was dropped or the timeout elapsed.
+        ///
+        /// # Returns
+        /// * true: the last reference to the guard was dropped.
+        /// * false: the timeout elapsed.
+        ///
+        pub fn wait_timeout(&self, timeout: core::time::Duration) -> bool {
+            let done = self.0.lock();
+            let (done, _) = self
+                .0
+                 .1
+                .wait_timeout_while(done, timeout, |done| !*done)
+                .unwrap_or_else(|e| e.into_inner());
+            *done
+        }
+
+        ///
+        /// Returns true if the last reference to the guard was dropped.
+        ///
+        pub fn is_complete(&self) -> bool {
+            *self.0.lock()
+        }
     }
-}
 
-impl<F: FnOnce()> Drop for DeferGuard<F> {
-    fn drop(&mut self) {
-        if let Some(f) = self.0.take() {
-            f()
+    // The closure is only ever accessed through `&mut` after `Arc::try_unwrap` or in `Drop`,
+    // both of which require exclusive ownership. Shared references only touch the cancel flag,
+    // which is Sync on its own. The closure may run on any thread holding a reference, so it must be Send.
+    unsafe impl<F: FnOnce() + Send> Sync for ArcDeferGuardInner<F> {}
+
+    impl<F: FnOnce() + Send> core::fmt::Debug for ArcDeferGuardInner<F> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            //The closure must not be accessed through a shared reference, see the Sync impl.
+            f.debug_struct("ArcDeferGuardInner")
+                .field("canceled", &self.0.is_set())
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[cfg(target_has_atomic = "8")]
+    #[cfg(not(feature = "triomphe"))]
+    impl<F: FnOnce() + Send> crate::token::SharedCancel for ArcDeferGuardInner<F> {
+        fn cancel(&self) {
+            self.0.set()
+        }
+
+        fn is_canceled(&self) -> bool {
+            self.0.is_set()
+        }
+    }
+
+    impl<F: FnOnce() + Send> Drop for ArcDeferGuardInner<F> {
+        fn drop(&mut self) {
+            //Waiters are notified even if the closure panics.
+            //This guard is an implementation detail, so it is not tracked.
+            #[cfg(feature = "std")]
+            let _notify = self
+                .3
+                .completion
+                .take()
+                .map(|c| DeferGuard(Some(move || c.notify()), Tracker::empty()));
+            #[cfg(feature = "futures-channel")]
+            let _send = self.3.sender.take().map(|sender| {
+                DeferGuard(
+                    Some(move || {
+                        //The receiver may have been dropped already.
+                        let _ = sender.send(());
+                    }),
+                    Tracker::empty(),
+                )
+            });
+            if self.0.is_set() {
+                self.2.canceled();
+            } else if let Some(f) = self.1.take() {
+                self.2.executed();
+                f()
+            }
         }
     }
 }
 
-/// Executes a block of code when the surrounding scope ends.
-///
-/// # Examples
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+mod countdown;
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+pub use countdown::CountdownDeferGuard;
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "std")]
+mod pinned;
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "std")]
+pub use pinned::{HomeExecutor, PinnedArcDeferGuard};
+
+#[cfg(feature = "critical-section")]
+mod cs;
+
+#[cfg(feature = "critical-section")]
+pub use cs::{CsDeferGuard, CsDeferSlot};
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+mod scoped;
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+pub use scoped::{ScopedDeferGuard, ScopedDeferRef};
+
+#[cfg(feature = "std")]
+mod thread_scope;
+
+#[cfg(feature = "std")]
+pub use thread_scope::ThreadScopeDeferGuard;
+
+#[cfg(target_has_atomic = "ptr")]
+mod static_guard;
+
+#[cfg(target_has_atomic = "ptr")]
+pub use static_guard::{DeferSlotCell, StaticDeferGuard};
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "pool")]
+mod pool;
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "pool")]
+pub use pool::{DeferPool, PoolDeferGuard, PoolFull};
+
+mod assert;
+mod c_guard;
+mod checkpoint;
+mod fn_mut;
+mod policy;
+mod token;
+mod tracker;
+
+#[doc(hidden)]
+pub use assert::DeferAssertGuard;
+pub use c_guard::{CDeferGuard, SendCDeferGuard};
+pub use checkpoint::Checkpoint;
+
+#[cfg(feature = "alloc")]
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+pub use token::{CancelToken, CancelableDeferGuard};
+
+pub use fn_mut::FnMutDeferGuard;
+pub use policy::{Always, DeferPolicy, Never, PolicyGuard};
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub use policy::{OnSuccess, OnUnwind};
+
+mod flag;
+
+#[cfg(target_has_atomic = "8")]
+pub use flag::{flag_guard, try_flag_guard, DoneFlagDeferGuard, FlagGuard};
+
+pub use flag::{cell_flag_guard, try_cell_flag_guard, CellFlagGuard};
+
+mod multi;
+
+pub use multi::MultiDeferGuard;
+
+mod with_defer;
+
+pub use with_defer::WithDefer;
+
+mod with_value;
+
+pub use with_value::DeferWith;
+
+mod context;
+
+pub use context::ContextDeferGuard;
+
+mod ensure;
+
+pub use ensure::EnsureDeferGuard;
+
+mod iter;
+
+pub use iter::{DeferIter, IterDeferExt, IterEnd};
+
+mod value;
+
+pub use value::{Defer, DeferExt};
+
+#[cfg(feature = "async")]
+mod future;
+
+#[cfg(feature = "async")]
+pub use future::{FutureDeferExt, OnCancel, OnComplete};
+
+#[cfg(feature = "tokio")]
+mod tokio_guard;
+
+#[cfg(feature = "tokio")]
+pub use tokio_guard::TokioDeferGuard;
+
+#[cfg(all(feature = "async", feature = "alloc"))]
+mod async_stack;
+
+#[cfg(all(feature = "async", feature = "alloc"))]
+pub use async_stack::{AsyncDeferStack, AsyncDropPolicy, BoxCleanup};
+
+mod deferrable;
+
+pub use deferrable::Deferrable;
+#[cfg(feature = "alloc")]
+pub use deferrable::DeferrableDyn;
+
+mod bracket;
+
+pub use bracket::{acquire_deferred, bracket, try_bracket};
+
+#[cfg(feature = "alloc")]
+mod link;
+
+#[cfg(feature = "alloc")]
+pub use link::{link, LinkedGuard};
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+pub use link::{link_arc, ArcLinkedGuard};
+
+#[cfg(feature = "std")]
+pub use value::OnUnwindValueGuard;
+
+#[cfg(feature = "alloc")]
+mod batch;
+
+#[cfg(feature = "alloc")]
+pub use batch::{cancel_vec, execute_vec, DeferIterator};
+
+#[cfg(feature = "alloc")]
+mod slot;
+
+#[cfg(feature = "alloc")]
+pub use slot::{DeferSlot, SendDeferSlot};
+
+#[cfg(feature = "alloc")]
+mod stack;
+
+#[cfg(feature = "alloc")]
+pub use stack::{DeferKey, DeferStack};
+
+#[cfg(feature = "std")]
+pub use stack::RunReport;
+
+#[cfg(feature = "alloc")]
+mod run;
+
+#[cfg(feature = "alloc")]
+pub use run::{run_with_defers, run_with_defers_try, DeferCtx};
+
+#[cfg(feature = "alloc")]
+mod group;
+
+#[cfg(feature = "alloc")]
+pub use group::DeferGroup;
+
+#[cfg(feature = "alloc")]
+mod scope_guard;
+
+#[cfg(feature = "alloc")]
+pub use scope_guard::ScopeDeferGuard;
+
+#[cfg(feature = "alloc")]
+mod arena;
+
+#[cfg(feature = "alloc")]
+pub use arena::DeferArena;
+
+#[cfg(feature = "log")]
+mod logging;
+
+#[cfg(feature = "log")]
+pub use logging::LogDeferGuard;
+
+#[doc(hidden)]
+#[cfg(feature = "log")]
+pub use log::Level as __LogLevel;
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "ffi")]
+mod ffi;
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "ffi")]
+pub use ffi::{defer_arc_cancel, defer_arc_clone, defer_arc_destroy, defer_arc_free};
+
+///
+/// Implements `Drop` for the type of an impl block by calling one of its methods.
+///
+/// The attribute is applied to the inherent impl block that contains the method.
+/// The method must take `&mut self` or `&self` and no other arguments.
+/// The generics and where clause of the impl block are used for the `Drop` impl,
+/// so they must match the declaration of the type, like for every `Drop` impl.
+///
+/// If the method panics while the thread is already panicking, the process aborts.
+/// Use `PanicSafeDeferGuard` or `std::thread::panicking()` inside the method if it may panic.
+///
+/// Requires the `proc-macro` feature.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_drop;
+///
+/// struct TempDir<T: AsRef<str>>(T);
+///
+/// #[defer_drop(remove)]
+/// impl<T: AsRef<str>> TempDir<T> {
+///     fn remove(&mut self) {
+///         println!("Removing {}", self.0.as_ref());
+///     }
+/// }
+/// ```
+///
+#[cfg(feature = "proc-macro")]
+pub use defer_heavy_macros::defer_drop;
+
+pub mod error_hook;
+
+#[cfg(feature = "scopeguard-compat")]
+pub mod compat;
+
+#[cfg(feature = "leak-detect")]
+pub mod leakcheck;
+
+#[cfg(feature = "stats")]
+pub mod stats;
+
+#[cfg(feature = "slow-defer-warning")]
+pub mod slow_defer;
+
+#[cfg(feature = "std")]
+pub mod os;
+
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+#[cfg(feature = "std")]
+mod panic_safe;
+
+#[cfg(feature = "std")]
+pub use panic_safe::PanicSafeDeferGuard;
+
+#[cfg(feature = "std")]
+mod retry;
+
+#[cfg(feature = "std")]
+pub use retry::RetryDeferGuard;
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub mod panic_tracking;
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+mod status;
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+pub use status::{ExitKind, StatusDeferGuard};
+
+#[cfg(feature = "std")]
+mod time;
+
+#[cfg(feature = "std")]
+pub use time::TimerGuard;
+
+#[cfg(feature = "std")]
+mod timeout;
+
+#[cfg(feature = "std")]
+pub use timeout::TimeoutDeferGuard;
+
+///
+/// Creates a guard that calls the closure when it is dropped.
+///
+/// This is the function behind `defer_guard!` and `defer_move_guard!`,
+/// use it when a plain function call is preferred over a macro.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::guard;
+///
+/// let mut cleanup = guard(|| println!("Cleanup"));
+/// println!("Working");
+/// cleanup.destroy_ref(); //Prints "Cleanup"
+/// ```
+///
+#[inline(always)]
+#[must_use]
+#[track_caller]
+pub fn guard<F: FnOnce()>(func: F) -> DeferGuard<F> {
+    DeferGuard::new(func)
+}
+
+///
+/// Creates a guard that calls the closure when the surrounding scope ends.
+///
+/// This is the function behind `defer!` and `defer_move!`.
+/// The guard can neither be canceled nor executed early,
+/// so its type does not have to be named.
+/// Bind the guard to a variable, `let _ = scoped(..)` calls the closure immediately.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::scoped;
+///
+/// fn test() {
+///     let _cleanup = scoped(|| println!("Second"));
+///     println!("First");
+/// }
+/// ```
+///
+#[inline(always)]
+#[must_use]
+#[track_caller]
+pub fn scoped<F: FnOnce()>(func: F) -> impl Drop {
+    DeferGuard::new(func)
+}
+
+///
+/// Creates a shared guard that calls the closure when the last reference to it is dropped.
+///
+/// This is the function behind `defer_arc!` and `defer_move_arc!`.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use defer_heavy::arc_guard;
+///
+/// let guard = arc_guard(|| println!("Executed"));
+/// let clone = guard.clone();
+/// thread::spawn(move || drop(clone)).join().unwrap();
+/// drop(guard); //Prints "Executed"
+/// ```
+///
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[inline(always)]
+#[must_use]
+#[track_caller]
+pub fn arc_guard<F: FnOnce() + Send>(func: F) -> ArcDeferGuard<F> {
+    ArcDeferGuard::new(func)
+}
+
+///
+/// Guard that calls the closure when it is dropped.
+///
+/// The closure can be called early with `destroy` or canceled with `cancel`.
+/// This is the type returned by `defer_guard!`, `defer_move_guard!` and `guard`.
+/// Without the `leak-detect`, `stats` and `slow-defer-warning` features the guard is as large as `Option<F>`.
+/// Use `with_token` to get a guard that can also be canceled by `CancelToken`s.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferGuard;
+///
+/// fn transfer(commit: bool) {
+///     let rollback = DeferGuard::new(|| println!("Rollback"));
+///     println!("Transfer");
+///     if commit {
+///         rollback.cancel();
+///     }
+///     //Prints "Rollback" here unless the transfer was committed
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferGuard<F: FnOnce()>(Option<F>, tracker::Tracker);
+
+impl<F: FnOnce()> DeferGuard<F> {
+    ///
+    /// Creates a guard that calls the closure when it is dropped.
+    ///
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new(func: F) -> Self {
+        Self(Some(func), tracker::Tracker::new())
+    }
+
+    ///
+    /// Creates a guard that calls the closure when it is dropped
+    /// and reports it to the hook of `slow_defer` if it takes longer than `budget`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// fn handle_request() {
+    ///     let _close = DeferGuard::new_with_budget(Duration::from_millis(5), || {
+    ///         println!("Closing connection");
+    ///     });
+    /// }
+    /// ```
+    ///
+    #[cfg(feature = "slow-defer-warning")]
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new_with_budget(budget: std::time::Duration, func: F) -> Self {
+        let mut guard = Self::new(func);
+        guard.1.set_budget(budget);
+        guard
+    }
+
+    ///
+    /// Creates a guard that must be canceled or destroyed explicitly.
+    ///
+    /// In debug builds dropping the guard while its closure is still pending calls the closure and then panics,
+    /// unless the thread is already panicking. Without the `std` feature this is detected with the `panic_tracking` module.
+    /// This is a runtime checked `#[must_use]` for guards
+    /// that must be committed or rolled back on purpose.
+    /// In release builds the guard behaves exactly like a guard created with `new`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// fn transfer(commit: bool) {
+    ///     let rollback = DeferGuard::ensure(|| println!("Rollback"));
+    ///     //Forgetting one of these in a branch panics in debug builds.
+    ///     if commit {
+    ///         rollback.cancel();
+    ///     } else {
+    ///         rollback.destroy();
+    ///     }
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn ensure(func: F) -> EnsureDeferGuard<F> {
+        EnsureDeferGuard::new(Self::new(func))
+    }
+
+    ///
+    /// Creates a guard without a closure, as if `cancel_ref` was called on it.
+    ///
+    /// Unlike `new` this can be used to initialize a `static` or `const`.
+    /// The guard can be armed later by assigning a new guard to it or with `swap_option`.
+    /// The canceled guard is not recorded by `leakcheck` or `stats`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::Mutex;
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// static SHUTDOWN_GUARD: Mutex<DeferGuard<fn()>> = Mutex::new(DeferGuard::canceled());
+    ///
+    /// fn flush_logs() {
+    ///     println!("Flushing logs");
+    /// }
+    ///
+    /// fn startup() {
+    ///     *SHUTDOWN_GUARD.lock().unwrap() = DeferGuard::new(flush_logs);
+    /// }
+    ///
+    /// fn shutdown() {
+    ///     SHUTDOWN_GUARD.lock().unwrap().destroy_ref();
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub const fn canceled() -> Self {
+        Self(None, tracker::Tracker::empty())
+    }
+
+    ///
+    /// Converts the guard into a guard that can also be canceled by `CancelToken`s.
+    ///
+    /// The cancel state is kept by the returned guard, so guards that never hand out a token do not pay for it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn test() {
+    ///     let mut guard = defer_guard! { unreachable!(); }.with_token();
+    ///     let token = guard.cancel_token();
+    ///     token.cancel();
+    /// }
+    /// ```
+    ///
+    #[cfg(feature = "alloc")]
+    #[cfg(target_has_atomic = "8")]
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline(always)]
+    #[must_use]
+    pub fn with_token(self) -> CancelableDeferGuard<F> {
+        CancelableDeferGuard::new(self)
+    }
+
+    ///
+    /// Upgrade the guard to a reference counted one.
+    /// This function is only available if the closure is `Send`
+    ///
+    /// # Returns
+    /// The reference counted guard.
+    ///
+    #[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+    #[cfg(target_has_atomic = "ptr")]
+    #[cfg(feature = "mt")]
+    pub fn upgrade(mut self) -> ArcDeferGuard<F>
+    where
+        F: FnOnce() + Send,
+    {
+        ArcDeferGuard::new_opt(self.0.take(), self.1.take())
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(f) = self.0.take() else {
+            return false;
+        };
+
+        #[cfg(feature = "slow-defer-warning")]
+        let (budget, location) = self.1.timing();
+        self.1.executed();
+        #[cfg(feature = "slow-defer-warning")]
+        slow_defer::call(budget, location, f);
+        #[cfg(not(feature = "slow-defer-warning"))]
+        f();
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel(mut self) -> bool {
+        __private::trace_cancel("defer_guard", core::panic::Location::caller());
+        self.0.take().map(|_| self.1.canceled()).is_some()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel_ref(&mut self) -> bool {
+        __private::trace_cancel("defer_guard", core::panic::Location::caller());
+        self.0.take().map(|_| self.1.canceled()).is_some()
+    }
+
+    ///
+    /// Will cancel the closure if the condition is true, otherwise nothing happens.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn write() -> Result<(), ()> {
+    ///     Ok(())
+    /// }
+    ///
+    /// let mut rollback = defer_guard! { println!("Rollback"); };
+    /// let result = write();
+    /// rollback.cancel_if(result.is_ok());
+    /// ```
+    ///
+    /// # Returns
+    /// * true: the condition was true and the closure was dropped.
+    /// * false: the condition was false or the closure was already called or canceled.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel_if(&mut self, condition: bool) -> bool {
+        condition && self.cancel_ref()
+    }
+
+    ///
+    /// Will call the closure now if the condition is true, otherwise nothing happens.
+    ///
+    /// # Returns
+    /// * true: the condition was true and the closure was called.
+    /// * false: the condition was false or the closure was already called or canceled.
+    ///
+    #[inline(always)]
+    pub fn destroy_if(&mut self, condition: bool) -> bool {
+        condition && self.destroy_ref()
+    }
+
+    ///
+    /// Will leak the closure, so it is neither called nor dropped.
+    /// None of the values captured by the closure are dropped.
+    /// This should only be used if the captured values must not be dropped,
+    /// for example because their ownership was already transferred by other means.
+    ///
+    /// # Returns
+    /// * true: closure was leaked.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn forget(mut self) -> bool {
+        self.0.take().map(core::mem::forget).is_some()
+    }
+
+    ///
+    /// Boxes the closure, so guards with different closures have the same type.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::{DeferGuard, ErasedDeferGuard};
+    ///
+    /// fn test() {
+    ///     let guards: Vec<ErasedDeferGuard> = vec![
+    ///         DeferGuard::new(|| println!("Second")).erase(),
+    ///         DeferGuard::new(move || println!("First")).erase(),
+    ///     ];
+    /// }
+    /// ```
+    ///
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub fn erase<'a>(mut self) -> ErasedDeferGuard<'a>
+    where
+        F: 'a,
+    {
+        DeferGuard(
+            self.0
+                .take()
+                .map(|f| alloc::boxed::Box::new(f) as alloc::boxed::Box<dyn FnOnce() + 'a>),
+            self.1.take(),
+        )
+    }
+
+    ///
+    /// Returns the closure without calling it.
+    ///
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn into_inner(mut self) -> Option<F> {
+        let func = self.0.take();
+        if func.is_some() {
+            self.1.canceled();
+        }
+        func
+    }
+
+    ///
+    /// Returns a reference to the closure.
+    ///
+    /// # Returns
+    /// * Some: the closure was not called or canceled yet.
+    /// * None: `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn peek(&self) -> Option<&F> {
+        self.0.as_ref()
+    }
+
+    ///
+    /// Combines this guard with another guard into a single guard.
+    ///
+    /// The returned guard is active if at least one of the guards is active and calls the closures of the active guards.
+    /// The closure of this guard is called first. The closure of `other` is also called if it panics.
+    /// The returned guard is only canceled if both guards were canceled or executed.
+    ///
+    /// There is no `unzip`, because the closures are moved into a single closure and cannot be separated again.
+    /// Keep the guards separate as long as they need to be canceled on their own and zip them afterward.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn test(keep_file: bool) {
+    ///     let mut file = defer_guard! { println!("Delete file"); };
+    ///     let dir = defer_guard! { println!("Delete dir"); };
+    ///     file.cancel_if(keep_file);
+    ///     let _cleanup = file.zip(dir);
+    /// }
+    /// ```
+    ///
+    #[must_use]
+    pub fn zip<G: FnOnce()>(mut self, mut other: DeferGuard<G>) -> DeferGuard<impl FnOnce()> {
+        let first = self.0.take();
+        let second = other.0.take();
+        //The returned guard takes over the record of a live guard.
+        let tracker = match (first.is_some(), second.is_some()) {
+            (true, true) => {
+                other.1.untrack();
+                self.1.take()
+            }
+            (true, false) => self.1.take(),
+            (false, true) => other.1.take(),
+            (false, false) => tracker::Tracker::empty(),
+        };
+
+        let live = first.is_some() || second.is_some();
+        let func = move || {
+            let _second = DeferGuard(second, tracker::Tracker::empty());
+            if let Some(first) = first {
+                first();
+            }
+        };
+
+        DeferGuard(live.then_some(func), tracker)
+    }
+
+    ///
+    /// Adds a precondition that is checked when the returned guard is dropped.
+    ///
+    /// The returned guard calls the closure of this guard only if this guard is still active at that time
+    /// and `check` returns true. `check` is not called if this guard was canceled.
+    /// Unlike `defer_conditioned!`, the condition is evaluated lazily on drop and not when the guard is created.
+    /// Canceling the returned guard cancels the closure of this guard, this guard cannot be called on its own anymore.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    /// use std::cell::Cell;
+    ///
+    /// fn test() {
+    ///     let handshake_done = Cell::new(false);
+    ///     let guard = defer_guard! { println!("Send goodbye"); };
+    ///     let _guard = guard.then_if_active(|| handshake_done.get());
+    ///     //Goodbye is only sent if the handshake was completed before the scope ends.
+    ///     handshake_done.set(true);
+    /// }
+    /// ```
+    ///
+    #[must_use]
+    pub fn then_if_active<C: FnOnce() -> bool>(mut self, check: C) -> DeferGuard<impl FnOnce()> {
+        let Some(func) = self.0.take() else {
+            return DeferGuard(None, tracker::Tracker::empty());
+        };
+
+        let func = move || {
+            if check() {
+                func();
+            }
+        };
+
+        DeferGuard(Some(func), self.1.take())
+    }
+
+    ///
+    /// Adds a closure that is called instead of the closure of this guard if it is canceled.
+    ///
+    /// The returned guard calls the closure of this guard when it is dropped or destroyed.
+    /// If this guard was canceled before or if the returned guard is canceled, `on_cancel` is called instead.
+    /// Exactly one of the two closures is called, unless the returned guard is forgotten.
+    /// Calling this multiple times adds multiple hooks, all of them are called if the guard is canceled.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn test(keep_file: bool) {
+    ///     let guard = defer_guard! { println!("Delete file"); };
+    ///     let guard = guard.with_cancel_hook(|| println!("Deleting file was skipped"));
+    ///     if keep_file {
+    ///         guard.cancel();
+    ///     }
+    /// }
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_cancel_hook<G: FnOnce()>(mut self, on_cancel: G) -> DeferGuard<impl FnOnce()> {
+        let hook = CancelHook(self.0.take(), Some(on_cancel));
+        DeferGuard(Some(move || hook.run()), self.1.take())
+    }
+
+    ///
+    /// Checks if both references point to the same guard.
+    /// This compares the address of the guards, not the closures, and is mainly useful for debugging.
+    ///
+    /// # Returns
+    /// * true: both references point to the same guard.
+    /// * false: the guards are different values.
+    ///
+    #[inline(always)]
+    pub fn same_as<G: FnOnce()>(&self, other: &DeferGuard<G>) -> bool {
+        core::ptr::addr_eq(self as *const Self, other as *const DeferGuard<G>)
+    }
+
+    ///
+    /// Converts the guard into a closure that calls the closure of the guard.
+    /// If the guard is canceled at the time of this call, the returned closure does nothing.
+    ///
+    /// The returned closure still owns the guard,
+    /// so the closure of the guard is also called if the returned closure is dropped without being called.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn run_later(callback: impl FnOnce()) {
+    ///     callback();
+    /// }
+    ///
+    /// fn test() {
+    ///     let guard = defer_guard! { println!("Cleanup"); };
+    ///     run_later(guard.into_fn());
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    pub fn into_fn(mut self) -> impl FnOnce() {
+        let guard = DeferGuard(self.0.take(), self.1.take());
+        move || drop(guard)
+    }
+
+    ///
+    /// Returns this guard if its closure was not called or canceled yet,
+    /// otherwise a new guard with the `fallback` closure.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// fn test(guard: DeferGuard<fn()>) {
+    ///     let guard = guard.take_or_else(|| {});
+    ///     assert!(guard.destroy());
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn take_or_else(self, fallback: F) -> Self {
+        if self.peek().is_some() {
+            return self;
+        }
+
+        Self::new(fallback)
+    }
+
+    ///
+    /// Inverts the state of the guard.
+    ///
+    /// If the guard is active, it is canceled and its closure is returned without being called.
+    /// `replacement` is dropped in that case.
+    /// If the guard was canceled or executed, a new active guard with `replacement` is returned.
+    ///
+    /// There is no `!guard`, because `Not` has no way to pass the closure that a canceled guard needs to become active.
+    ///
+    /// # Returns
+    /// * Ok: the guard was inactive, the returned guard calls `replacement`.
+    /// * Err: the guard was active, it is canceled and its closure is returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// fn release_primary() {
+    ///     println!("Release primary");
+    /// }
+    ///
+    /// fn release_fallback() {
+    ///     println!("Release fallback");
+    /// }
+    ///
+    /// fn test(use_fallback: bool) {
+    ///     let mut primary = DeferGuard::new(release_primary as fn());
+    ///     primary.cancel_if(use_fallback);
+    ///     //Exactly one of the two is released.
+    ///     let _guard = match primary.invert_state(release_fallback) {
+    ///         Ok(fallback) => fallback,
+    ///         Err(release_primary) => DeferGuard::new(release_primary),
+    ///     };
+    /// }
+    /// ```
+    ///
+    #[track_caller]
+    pub fn invert_state(mut self, replacement: F) -> Result<Self, F> {
+        let Some(func) = self.0.take() else {
+            return Ok(Self::new(replacement));
+        };
+
+        __private::trace_cancel("defer_guard", core::panic::Location::caller());
+        self.1.canceled();
+        Err(func)
+    }
+
+    ///
+    /// Exchanges the closures of the two guards.
+    /// Each guard executes the closure of the other guard when it is dropped.
+    ///
+    #[inline(always)]
+    pub fn swap(&mut self, other: &mut DeferGuard<F>) {
+        core::mem::swap(&mut self.0, &mut other.0);
+        core::mem::swap(&mut self.1, &mut other.1);
+    }
+
+    ///
+    /// Exchanges the closure of the guard with the content of the option.
+    /// If the option is None, the guard will no longer execute a closure.
+    ///
+    #[inline(always)]
+    pub fn swap_option(&mut self, slot: &mut Option<F>) {
+        core::mem::swap(&mut self.0, slot)
+    }
+}
+
+///
+/// A `DeferGuard` with a boxed closure.
+/// Guards with different closures can be stored in the same collection after calling `erase`.
+///
+#[cfg(feature = "alloc")]
+pub type ErasedDeferGuard<'a> = DeferGuard<alloc::boxed::Box<dyn FnOnce() + 'a>>;
+
+#[cfg(feature = "alloc")]
+impl<'a> ErasedDeferGuard<'a> {
+    ///
+    /// Appends a closure that is called after the current closure.
+    /// The appended closure is also called if the current closure panics.
+    /// If the guard was already canceled or executed, it is armed again with just the appended closure.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// fn setup() {
+    ///     let mut cleanup = DeferGuard::new(Box::new(|| println!("Close first")) as Box<dyn FnOnce()>);
+    ///     cleanup.extend(|| println!("Close second"));
+    /// }
+    /// ```
+    ///
+    pub fn extend<G: FnOnce() + 'a>(&mut self, next: G) {
+        let func: alloc::boxed::Box<dyn FnOnce() + 'a> = match self.0.take() {
+            Some(current) => alloc::boxed::Box::new(move || {
+                let _next = DeferGuard::new(next);
+                current()
+            }),
+            None => alloc::boxed::Box::new(next),
+        };
+        self.0 = Some(func);
+    }
+}
+
+//The closure type of the guard is not nameable, this impl only exists so the function can be called as `DeferGuard::new_with_weak`.
+#[cfg(feature = "alloc")]
+#[cfg(target_has_atomic = "ptr")]
+impl DeferGuard<fn()> {
+    ///
+    /// Creates a guard that calls the closure with the target of the `Weak` if it is still alive when the guard executes.
+    ///
+    /// The `Weak` is upgraded when the guard is dropped or destroyed, not when it is created.
+    /// The guard does not keep the target alive.
+    /// If the target was already dropped, the closure is dropped without being called.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use defer_heavy::DeferGuard;
+    ///
+    /// struct Session;
+    ///
+    /// fn test(session: &Arc<Session>) {
+    ///     let _guard = DeferGuard::new_with_weak(Arc::downgrade(session), |_session| {
+    ///         println!("Session still alive, flushing");
+    ///     });
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new_with_weak<T, G: FnOnce(alloc::sync::Arc<T>)>(
+        weak: alloc::sync::Weak<T>,
+        func: G,
+    ) -> DeferGuard<impl FnOnce()> {
+        DeferGuard::new(move || {
+            if let Some(target) = weak.upgrade() {
+                func(target)
+            }
+        })
+    }
+}
+
+///
+/// Calls the closure or, if it was canceled or is dropped without being called, the cancel hook.
+///
+struct CancelHook<F: FnOnce(), G: FnOnce()>(Option<F>, Option<G>);
+
+impl<F: FnOnce(), G: FnOnce()> CancelHook<F, G> {
+    fn run(mut self) {
+        let Some(func) = self.0.take() else {
+            return;
+        };
+
+        //The hook is dropped without being called.
+        self.1 = None;
+        func();
+    }
+}
+
+impl<F: FnOnce(), G: FnOnce()> Drop for CancelHook<F, G> {
+    fn drop(&mut self) {
+        if let Some(on_cancel) = self.1.take() {
+            on_cancel();
+        }
+    }
+}
+
+impl<F: FnOnce()> Drop for DeferGuard<F> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+///
+/// Returns true if the current thread is panicking.
+/// Always false if this cannot be determined on the target.
+///
+#[inline(always)]
+pub(crate) fn thread_panicking() -> bool {
+    #[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+    return panic_tracking::is_panicking();
+
+    #[cfg(not(any(feature = "std", target_has_atomic = "ptr")))]
+    return false;
+}
+
+impl<F: FnOnce()> Deferrable for DeferGuard<F> {
+    #[inline(always)]
+    #[track_caller]
+    fn cancel(self) {
+        DeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        DeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::assert::defer_assert_failed;
+    pub use crate::context::ContextSlot;
+
+    ///
+    /// Wraps the closure of a macro so its registration and execution are traced.
+    ///
+    #[cfg(feature = "tracing")]
+    #[inline(always)]
+    pub fn trace<F: FnOnce()>(
+        kind: &'static str,
+        file: &'static str,
+        line: u32,
+        func: F,
+    ) -> impl FnOnce() {
+        tracing::trace!(name = kind, file, line, "defer registered");
+        move || {
+            tracing::trace!(name = kind, file, line, "defer executing");
+            func();
+            tracing::trace!(name = kind, file, line, "defer executed");
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    pub fn trace<F: FnOnce()>(_kind: &'static str, _file: &'static str, _line: u32, func: F) -> F {
+        func
+    }
+
+    #[cfg(feature = "tracing")]
+    #[inline(always)]
+    pub fn trace_cancel(kind: &'static str, location: &'static core::panic::Location<'static>) {
+        tracing::trace!(
+            name = kind,
+            file = location.file(),
+            line = location.line(),
+            "defer canceled"
+        );
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    pub fn trace_cancel(_kind: &'static str, _location: &'static core::panic::Location<'static>) {}
+
+    ///
+    /// Wraps a fallible closure so its error is written to the slot.
+    ///
+    #[inline(always)]
+    pub fn store_err<'a, E, F: FnOnce() -> Result<(), E> + 'a>(
+        slot: &'a mut Option<E>,
+        func: F,
+    ) -> impl FnOnce() + 'a {
+        move || {
+            if let Err(err) = func() {
+                *slot = Some(err);
+            }
+        }
+    }
+}
+
+/// Executes a block of code when the surrounding scope ends.
+///
+/// # Examples
+///
+/// Simplest example:
+///
+/// ```rust
+/// use defer_heavy::defer;
+///
+/// fn test() {
+///     defer! { println!("Second"); }
+///     println!("First");
+/// }
+/// ```
+///
+/// Multiple statements:
+///
+/// ```rust
+/// use defer_heavy::defer;
+///
+/// fn test() {
+///     defer! {
+///         println!("Second");
+///         println!("Third");
+///     }
+///     println!("First");
+/// }
+/// ```
+///
+/// In Go, the `defer` code runs when the function exits. In this Rust
+/// implementation, however, the code runs when the surrounding scope ends –
+/// this makes it possible to use `defer` inside loops:
+///
+/// ```rust
+/// use defer_heavy::defer;
+///
+/// fn test() {
+///     defer! { println!("End"); }
+///     println!("Before");
+///
+///     for i in 0..2 {
+///         defer! { println!("Defer {}", i); }
+///         println!("Loop {}", i);
+///     }
+///
+///     println!("After");
+/// }
+/// ```
+#[macro_export]
+macro_rules! defer {
+	( $($tt:tt)* ) => {
+		let _deferred = $crate::scoped($crate::__private::trace("defer", file!(), line!(), || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends.
+/// This macro moves all captured variables.
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_move;
+///
+/// fn test() {
+///     let n = 1;
+///     defer_move! { println!("Second n={}", n); }
+///     println!("First");
+/// }
+/// ```
+#[macro_export]
+macro_rules! defer_move {
+	( $($tt:tt)* ) => {
+		let _deferred = $crate::scoped($crate::__private::trace("defer_move", file!(), line!(), move || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends.
+///
+/// The macro returns a guard that defines the scope of the deferment.
+/// The guard can be used to immediately execute the deferred closure or cancel it and
+/// prevent execution of the closure altogether
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_guard;
+///
+/// fn test() {
+///
+///     let defer1 = defer_guard! { unreachable!("Wont be executed"); };
+///     let defer2 = defer_guard! { println!("Second"); };
+///
+///     println!("First");
+///     defer1.cancel();
+/// }
+/// ```
+///
+/// ```rust
+/// use defer_heavy::defer_guard;
+///
+/// fn test() {
+///
+///     let defer1 = defer_guard! { unreachable!("Wont be executed"); };
+///     let defer2 = defer_guard! { println!("Fourth"); };
+///     let defer3 = defer_guard! { println!("Second"); };
+///
+///     println!("First");
+///     defer3.destroy();
+///     println!("Third");
+///     defer1.cancel();
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! defer_guard {
+	( $($tt:tt)* ) => {
+		$crate::guard($crate::__private::trace("defer_guard", file!(), line!(), || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends and requires the returned guard to be handled explicitly.
+///
+/// The macro returns a guard created with `DeferGuard::ensure`.
+/// In debug builds dropping the guard without calling `cancel` or `destroy` calls the closure and then panics.
+/// In release builds it behaves like `defer_guard!`.
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_ensure;
+///
+/// fn test(commit: bool) {
+///     let rollback = defer_ensure! { println!("Rollback"); };
+///     if commit {
+///         rollback.cancel();
+///     } else {
+///         rollback.destroy();
+///     }
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! defer_ensure {
+	( $($tt:tt)* ) => {
+		$crate::DeferGuard::ensure($crate::__private::trace("defer_ensure", file!(), line!(), || { $($tt)* }))
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends.
+///
+/// The macro returns a guard that defines the scope of the deferment.
+/// The guard can be used to immediately execute the deferred closure or cancel it and
+/// prevent execution of the closure altogether
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_move_guard;
+///
+/// fn test() {
+///     let n = 1;
+///     let defer1 = defer_move_guard! { unreachable!("Wont be executed {}", n); };
+///     let n = 2;
+///     let defer2 = defer_move_guard! { println!("Second {}", n); };
+///
+///     println!("First");
+///     defer1.cancel();
+/// }
+/// ```
+/// Prints:
+/// ```text
+/// First
+/// Second 2
+/// ```
+///
+/// ```rust
+/// use defer_heavy::defer_move_guard;
+///
+/// fn test() {
+///
+///     let n = 1;
+///     let defer1 = defer_move_guard! { unreachable!("Wont be executed {}", n); };
+///     let n = 2;
+///     let defer2 = defer_move_guard! { println!("Fourth {}", n); };
+///     let n = 3;
+///     let defer3 = defer_move_guard! { println!("Second {}", n); };
+///
+///     println!("First");
+///     defer3.destroy(); //Same as drop(defer3);
+///     println!("Third");
+///     defer1.cancel();
+/// }
+/// ```
+/// Prints:
+/// ```text
+/// First
+/// Second 3
+/// Third
+/// Fourth 2
+/// ```
+///
+/// ```rust
+///
+#[macro_export]
+macro_rules! defer_move_guard {
+	( $($tt:tt)* ) => {
+		$crate::guard($crate::__private::trace("defer_move_guard", file!(), line!(), move || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends and lists the captured variables explicitly.
+///
+/// Each variable is captured as `&mut name` or `&name`.
+/// Inside the block the name refers to the reference, so the borrows are visible at the macro invocation.
+/// No other variable is moved into the closure.
+/// The macro returns a `DeferGuard`, like `defer_guard!`.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_guard_by_ref;
+///
+/// fn test() {
+///     let mut log = Vec::new();
+///     let verbose = true;
+///     for i in 0..3 {
+///         let _guard = defer_guard_by_ref!(&mut log, &verbose => {
+///             if *verbose {
+///                 log.push(i);
+///             }
+///         });
+///     }
+///     assert_eq!(log, [0, 1, 2]);
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! defer_guard_by_ref {
+	( @bind [$($binds:tt)*] &mut $name:ident, $($rest:tt)* ) => {
+		$crate::defer_guard_by_ref!(@bind [$($binds)* let $name = &mut $name;] $($rest)*)
+	};
+	( @bind [$($binds:tt)*] &mut $name:ident => $body:block ) => {
+		$crate::defer_guard_by_ref!(@bind [$($binds)* let $name = &mut $name;] => $body)
+	};
+	( @bind [$($binds:tt)*] & $name:ident, $($rest:tt)* ) => {
+		$crate::defer_guard_by_ref!(@bind [$($binds)* let $name = &$name;] $($rest)*)
+	};
+	( @bind [$($binds:tt)*] & $name:ident => $body:block ) => {
+		$crate::defer_guard_by_ref!(@bind [$($binds)* let $name = &$name;] => $body)
+	};
+	( @bind [$($binds:tt)*] => $body:block ) => {
+		{
+			$($binds)*
+			$crate::guard($crate::__private::trace("defer_guard_by_ref", file!(), line!(), move || $body))
+		}
+	};
+	( $($tt:tt)* ) => {
+		$crate::defer_guard_by_ref!(@bind [] $($tt)*)
+	};
+}
+
+/// Collects all `defer!` and `defer_move!` registrations of the body into a single guard.
+///
+/// The macro returns a `ScopeDeferGuard`. The closures are not called when the body ends,
+/// but when the returned guard is dropped or destroyed. `cancel()` cancels all of them.
+///
+/// Inside the body `defer!` and `defer_move!` are replaced by versions that register with the guard,
+/// they do not need to be imported. `defer_outer!` and `defer_move_outer!` are replaced the same way,
+/// in the body they are equivalent to `defer!` and `defer_move!`.
+/// Invocations through a path, like `defer_heavy::defer!`, and other macros are not affected.
+/// The closures may only borrow variables that outlive the returned guard,
+/// so a registration can never outlive the variables it uses.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_scope_guard;
+///
+/// fn test() {
+///     let mut log = Vec::new();
+///     {
+///         let log = &mut log;
+///         let scope = defer_scope_guard! {
+///             println!("Setup");
+///             defer! { log.push("Second"); }
+///         };
+///         println!("First");
+///         drop(scope);
+///     }
+///     assert_eq!(log, ["Second"]);
+/// }
+/// ```
+///
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! defer_scope_guard {
+	( @dollar ($d:tt) $($tt:tt)* ) => {
+		{
+			let mut scope = $crate::ScopeDeferGuard::new();
+			{
+				#[allow(unused_macros)]
+				macro_rules! defer {
+					( $d($d inner:tt)* ) => {
+						scope.push($crate::__private::trace("defer", file!(), line!(), || { $d($d inner)* }));
+					};
+				}
+				#[allow(unused_macros)]
+				macro_rules! defer_move {
+					( $d($d inner:tt)* ) => {
+						scope.push($crate::__private::trace("defer_move", file!(), line!(), move || { $d($d inner)* }));
+					};
+				}
+				#[allow(unused_macros)]
+				macro_rules! defer_outer {
+					( $d($d inner:tt)* ) => {
+						scope.push($crate::__private::trace("defer_outer", file!(), line!(), || { $d($d inner)* }));
+					};
+				}
+				#[allow(unused_macros)]
+				macro_rules! defer_move_outer {
+					( $d($d inner:tt)* ) => {
+						scope.push($crate::__private::trace("defer_move_outer", file!(), line!(), move || { $d($d inner)* }));
+					};
+				}
+				$($tt)*
+			}
+			scope
+		}
+	};
+	( $($tt:tt)* ) => {
+		$crate::defer_scope_guard!(@dollar ($) $($tt)*)
+	};
+}
+
+/// Registers a block of code with the enclosing `defer_scope_guard!` instead of the current scope.
+///
+/// In the body of a `defer_scope_guard!`, `defer_outer!` and `defer!` are equivalent,
+/// as are `defer_move_outer!` and `defer_move!`. All of them register the code with the returned `ScopeDeferGuard`,
+/// so it is executed when that guard is dropped, no matter how deeply nested in loops and blocks it is.
+/// This includes `defer!` in a loop of the body, it does not run at the end of each iteration.
+/// A guard that ends with the iteration needs a path invocation like `defer_heavy::defer!`, which is not replaced.
+///
+/// Unlike `defer!`, `defer_outer!` is a compile error outside of a `defer_scope_guard!`,
+/// so code that must outlive the loop cannot silently become a per iteration guard when it is moved out of the body.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_scope_guard;
+///
+/// fn open_all(paths: &[&str]) {
+///     let _close_all = defer_scope_guard! {
+///         for path in paths {
+///             println!("Open {path}");
+///             //Not replaced, so this runs at the end of every iteration.
+///             defer_heavy::defer! { println!("Iteration done"); }
+///             //Registered with the returned guard, `defer_move!` would do the same here.
+///             //The closure must not borrow variables of the loop, so it moves them.
+///             defer_move_outer! { println!("Close {path}"); }
+///         }
+///     };
+///     //All files are closed here in reverse order.
+/// }
+/// ```
+///
+/// Outside of a `defer_scope_guard!` the macro does not compile:
+/// ```rust,compile_fail
+/// use defer_heavy::defer_outer;
+///
+/// fn test() {
+///     defer_outer! { println!("Cleanup"); }
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! defer_outer {
+    ( $($tt:tt)* ) => {
+        compile_error!("defer_outer! can only be used in the body of a defer_scope_guard!")
+    };
+}
+
+/// `defer_outer!` with a `move` closure.
+///
+/// Registers a block of code with the enclosing `defer_scope_guard!` and is a compile error everywhere else.
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! defer_move_outer {
+    ( $($tt:tt)* ) => {
+        compile_error!("defer_move_outer! can only be used in the body of a defer_scope_guard!")
+    };
+}
+
+/// Executes a block of code when the surrounding scope ends unless a "done" flag is set at that time.
+///
+/// The flag is a borrowed `AtomicBool` that is checked with `Acquire` when the guard is dropped.
+/// The macro returns a `DoneFlagDeferGuard`.
+/// `cancel_and_mark` of the guard sets the flag and cancels the guard.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::atomic::AtomicBool;
+/// use std::sync::atomic::Ordering::Release;
+/// use defer_heavy::defer_guarded_by;
+///
+/// fn test() {
+///     let committed = AtomicBool::new(false);
+///     let _rollback = defer_guarded_by!(&committed, { println!("Rollback"); });
+///     //Commit...
+///     committed.store(true, Release);
+///     //Does not print "Rollback"
+/// }
+/// ```
+#[cfg(target_has_atomic = "8")]
+#[macro_export]
+macro_rules! defer_guarded_by {
+	( $done:expr, $body:block ) => {
+		$crate::DoneFlagDeferGuard::new($done, $crate::__private::trace("defer_guarded_by", file!(), line!(), || $body))
+	};
+	( $done:expr, $($tt:tt)* ) => {
+		$crate::DoneFlagDeferGuard::new($done, $crate::__private::trace("defer_guarded_by", file!(), line!(), || { $($tt)* }))
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends if a condition is true.
+///
+/// The condition is evaluated once when the macro is invoked.
+/// The macro returns a guard like `defer_guard!`. If the condition is false the guard is already canceled.
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_conditioned;
+///
+/// fn test(verbose: bool) {
+///     let _guard = defer_conditioned!(verbose, { println!("Second"); });
+///     println!("First");
+/// }
+/// ```
+#[macro_export]
+macro_rules! defer_conditioned {
+	( $cond:expr, $($tt:tt)* ) => {
+		{
+			let cond: bool = $cond;
+			let mut guard = $crate::DeferGuard::new($crate::__private::trace("defer_conditioned", file!(), line!(), || { $($tt)* }));
+			if !cond {
+				guard.cancel_ref();
+			}
+			guard
+		}
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends if a condition is true.
+/// This macro moves all captured variables.
+///
+/// The condition is evaluated once when the macro is invoked, before any variable is moved.
+/// The macro returns a guard like `defer_move_guard!`. If the condition is false the guard is already canceled
+/// and the captured variables are dropped immediately.
+///
+/// # Examples
+///
+/// ```rust
+/// use defer_heavy::defer_conditioned_move;
+///
+/// fn test(verbose: bool) {
+///     let n = 1;
+///     let _guard = defer_conditioned_move!(verbose, { println!("Second n={}", n); });
+///     println!("First");
+/// }
+/// ```
+///
+/// Captured variables are always moved, even if the condition is false:
+///
+/// ```rust,compile_fail
+/// use defer_heavy::defer_conditioned_move;
+///
+/// fn test() {
+///     let s = String::from("moved");
+///     let _guard = defer_conditioned_move!(false, { println!("{}", s); });
+///     println!("{}", s);
+/// }
+/// ```
+#[macro_export]
+macro_rules! defer_conditioned_move {
+	( $cond:expr, $($tt:tt)* ) => {
+		{
+			let cond: bool = $cond;
+			let mut guard = $crate::DeferGuard::new($crate::__private::trace("defer_conditioned_move", file!(), line!(), move || { $($tt)* }));
+			if !cond {
+				guard.cancel_ref();
+			}
+			guard
+		}
+	};
+}
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+pub use mt::{ArcDefer, ArcDeferGuard, ArcDeferGuardState, ErasedArcDeferGuard};
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[cfg(not(feature = "triomphe"))]
+pub use mt::WeakArcDeferGuard;
+
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[cfg(feature = "std")]
+pub use mt::CompletionHandle;
+
+/// Executes a block of code when the surrounding scope ends.
+///
+/// The macro returns a guard that defines the scope of the deferment.
+/// The guard can be shared with other threads, and it will only execute the block of code when
+/// no more references to the guard exist.
+///
+/// The code closure must be 'Send'.
+///
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use std::time::Duration;
+/// use defer_heavy::defer_arc;
+///
+/// pub fn test() {
+///     let deferred = defer_arc! { println!("Executed in {:?}", thread::current().id());};
+///     println!("Main thread {:?}", thread::current().id());
+///     let th;
+///     {
+///         let deferred = deferred.clone();
+///         th = thread::spawn(move ||{
+///             println!("Spawned thread {:?}", thread::current().id());
+///             let _deferred = deferred.own();
+///             thread::sleep(Duration::from_millis(2000)); //SIMULATE work
+///        });
+///    }
+///    thread::sleep(Duration::from_millis(2000)); //SIMULATE WORK
+///    drop(deferred);
+///    th.join().unwrap();
+///  }
+/// ```
+/// Prints:
+/// ```text
+/// Main thread Thread(1)
+/// Spawned thread Thread(2)
+/// "Executed in Thread(1)" or "Executed in Thread(2)"
+/// ```
+///
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[macro_export]
+macro_rules! defer_arc {
+	( $($tt:tt)* ) => {
+		$crate::arc_guard($crate::__private::trace("defer_arc", file!(), line!(), || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends.
+///
+/// The macro returns a guard that defines the scope of the deferment.
+/// The guard can be shared with other threads, and it will only execute the block of code when
+/// no more references to the guard exist.
+///
+/// The code closure must be 'Send'.
+/// The closure moves all used variables. Each used variable must be 'Send' or the closure won't be 'Send'.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use std::time::Duration;
+/// use defer_heavy::defer_move_arc;
+///
+/// pub fn test() {
+///     let v = 1;
+///     let deferred = defer_move_arc! { println!("Executed {} in {:?}", v, thread::current().id());};
+///     println!("Main thread {:?}", thread::current().id());
+///     let th;
+///     {
+///         let deferred = deferred.clone();
+///         th = thread::spawn(move ||{
+///             println!("Spawned thread {:?}", thread::current().id());
+///             let _deferred = deferred.own();
+///             thread::sleep(Duration::from_millis(2000)); //SIMULATE work
+///        });
+///    }
+///    thread::sleep(Duration::from_millis(2000)); //SIMULATE WORK
+///    drop(deferred);
+///    th.join().unwrap();
+///  }
+/// ```
+/// Prints:
+/// ```text
+/// Main thread Thread(1)
+/// Spawned thread Thread(2)
+/// "Executed in Thread(1)" or "Executed in Thread(2)"
+/// ```
+///
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[macro_export]
+macro_rules! defer_move_arc {
+	( $($tt:tt)* ) => {
+		$crate::arc_guard($crate::__private::trace("defer_move_arc", file!(), line!(), move || { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the last reference to the returned `ArcDefer` is dropped.
+///
+/// Unlike `defer_arc!` the closure cannot be canceled.
+/// In exchange, the shared allocation is smaller and dropping a reference is cheaper.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use defer_heavy::defer_arc_final;
+///
+/// pub fn test() {
+///     let deferred = defer_arc_final! { println!("All workers finished"); };
+///     let workers: Vec<_> = (0..4)
+///         .map(|_| {
+///             let deferred = deferred.clone();
+///             thread::spawn(move || {
+///                 let _deferred = deferred.own();
+///                 //SIMULATE work
+///             })
+///         })
+///         .collect();
+///     drop(deferred);
+///     for w in workers {
+///         w.join().unwrap();
+///     }
+/// }
+/// ```
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[macro_export]
+macro_rules! defer_arc_final {
+	( $($tt:tt)* ) => {
+		$crate::ArcDefer::new($crate::__private::trace("defer_arc_final", file!(), line!(), || { $($tt)* }))
+	};
+}
+
+/// Same as `defer_arc_final!` but moves all captured variables into the closure.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_move_arc_final;
+///
+/// pub fn test() {
+///     let name = String::from("job");
+///     let deferred = defer_move_arc_final! { println!("{} finished", name); };
+///     drop(deferred);
+/// }
+/// ```
+#[cfg(any(target_has_atomic = "8", feature = "parking_lot"))]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+#[macro_export]
+macro_rules! defer_move_arc_final {
+	( $($tt:tt)* ) => {
+		$crate::ArcDefer::new($crate::__private::trace("defer_move_arc_final", file!(), line!(), move || { $($tt)* }))
+	};
+}
+
+/// Arms a `DeferSlotCell` with a block of code.
+///
+/// The macro returns the result of `StaticDeferGuard::init`.
+/// The block of code can not capture any variables, since it is coerced to a `fn()` pointer.
+/// Use `StaticDeferGuard::init` directly for other closure types.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{defer_static, DeferSlotCell};
+///
+/// static SLOT: DeferSlotCell<fn()> = DeferSlotCell::new();
+///
+/// fn test() {
+///     let guard = defer_static!(SLOT, { println!("Released"); }).unwrap();
+///     println!("Working");
+///     guard.fire();
+/// }
+/// ```
+#[cfg(target_has_atomic = "ptr")]
+#[macro_export]
+macro_rules! defer_static {
+	( $slot:expr, $($tt:tt)* ) => {
+		$crate::StaticDeferGuard::init(&$slot, || { $($tt)* })
+	};
+}
+
+/// Declares a `static` `DeferPool` with the given closure type and number of slots.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{defer_pool, PoolDeferGuard};
+///
+/// type RadioCleanup = fn();
+///
+/// defer_pool!(RADIO_POOL: RadioCleanup; 8);
+///
+/// fn test() {
+///     let guard = PoolDeferGuard::try_new(&RADIO_POOL, (|| println!("Released")) as RadioCleanup).unwrap();
+///     println!("Working");
+///     drop(guard);
+/// }
+/// ```
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "pool")]
+#[macro_export]
+macro_rules! defer_pool {
+	( $(#[$meta:meta])* $vis:vis $name:ident : $ty:ty ; $size:expr ) => {
+		$(#[$meta])*
+		$vis static $name: $crate::DeferPool<$ty, { $size }> = $crate::DeferPool::new();
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends or when the timeout elapses, whichever comes first.
+/// This macro moves all captured variables.
+///
+/// The macro returns a `TimeoutDeferGuard`.
+/// If the timeout elapses first, the block of code is executed on a shared timer thread.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use defer_heavy::defer_timeout;
+///
+/// fn test() {
+///     let lock = "resource";
+///     let _guard = defer_timeout!(Duration::from_secs(30), { println!("Released {}", lock); });
+///     println!("Working");
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! defer_timeout {
+	( $timeout:expr, $($tt:tt)* ) => {
+		$crate::TimeoutDeferGuard::new($timeout, move || { $($tt)* })
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends, unless the timeout elapses first.
+/// This macro moves all captured variables.
+///
+/// The macro returns a `TimeoutDeferGuard` created with `TimeoutDeferGuard::new_expiring`.
+/// If the timeout elapses first, the block of code is dropped on a shared timer thread without being executed.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use defer_heavy::defer_expiring;
+///
+/// fn test() {
+///     let _guard = defer_expiring!(Duration::from_secs(5), { println!("Rollback"); });
+///     println!("Working");
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! defer_expiring {
+	( $timeout:expr, $body:block ) => {
+		$crate::TimeoutDeferGuard::new_expiring($timeout, move || $body)
+	};
+	( $timeout:expr, $($tt:tt)* ) => {
+		$crate::TimeoutDeferGuard::new_expiring($timeout, move || { $($tt)* })
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends and logs a message when doing so.
+///
+/// The macro returns a `LogDeferGuard`. The message is logged with the level `Trace`,
+/// another level can be selected with `level = debug` (or `info`, `warn`, `error`).
+/// Canceling the guard also logs the message.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::AtomicBool;
+/// use std::sync::atomic::Ordering::Release;
+/// use defer_heavy::defer_log;
+///
+/// fn test(spin: &AtomicBool) {
+///     let _guard = defer_log!("releasing spinlock", { spin.store(false, Release); });
+///     let _guard = defer_log!(level = debug, "second defer", { println!("Hi"); });
+/// }
+/// ```
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! defer_log {
+	( level = trace, $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Trace, || { $($tt)* })
+	};
+	( level = debug, $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Debug, || { $($tt)* })
+	};
+	( level = info, $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Info, || { $($tt)* })
+	};
+	( level = warn, $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Warn, || { $($tt)* })
+	};
+	( level = error, $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Error, || { $($tt)* })
+	};
+	( $name:expr, $($tt:tt)* ) => {
+		$crate::LogDeferGuard::new($name, $crate::__LogLevel::Trace, || { $($tt)* })
+	};
+}
+
+/// Executes a block of code once the guard and all threads spawned through it are done.
+///
+/// The first argument is the `std::thread::Scope` the guard is bound to.
+/// The block may borrow data that outlives the scope.
+/// See `ThreadScopeDeferGuard`.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use defer_heavy::scoped_defer;
+///
+/// let finished = AtomicUsize::new(0);
+/// std::thread::scope(|s| {
+///     let guard = scoped_defer!(s, { assert_eq!(finished.load(Ordering::SeqCst), 4); });
+///     for _ in 0..4 {
+///         guard.spawn(|| finished.fetch_add(1, Ordering::SeqCst));
+///     }
+/// });
+/// ```
+///
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! scoped_defer {
+	( $scope:expr, $body:block ) => {
+		$crate::ThreadScopeDeferGuard::new($scope, $crate::__private::trace("scoped_defer", file!(), line!(), || $body))
+	};
+	( $scope:expr, $($tt:tt)* ) => {
+		$crate::ThreadScopeDeferGuard::new($scope, $crate::__private::trace("scoped_defer", file!(), line!(), || { $($tt)* }))
+	};
+}
+
+/// Executes a fallible block of code when the surrounding scope ends and stores its error.
+///
+/// The first argument is a `&mut Option<E>` and the block must evaluate to a `Result<(), E>`.
+/// If the block returns `Err`, the error is written to the slot.
+/// The slot is borrowed by the guard, so it can be checked once the guard was dropped.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_result_guard;
+///
+/// fn flush() -> Result<(), String> {
+///     Err("disk full".to_string())
+/// }
+///
+/// fn test() {
+///     let mut error = None;
+///     {
+///         let _guard = defer_result_guard!(&mut error, { flush() });
+///     }
+///     assert_eq!(error.as_deref(), Some("disk full"));
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! defer_result_guard {
+	( $slot:expr, $body:block ) => {
+		$crate::DeferGuard::new($crate::__private::trace("defer_result_guard", file!(), line!(), $crate::__private::store_err($slot, || $body)))
+	};
+	( $slot:expr, $($tt:tt)* ) => {
+		$crate::DeferGuard::new($crate::__private::trace("defer_result_guard", file!(), line!(), $crate::__private::store_err($slot, || { $($tt)* })))
+	};
+}
+
+/// Executes a closure when the surrounding scope ends and tells it how the scope ended.
 ///
-/// Simplest example:
+/// The macro returns a `StatusDeferGuard`. The closure receives an `ExitKind`:
+/// * `Normal` if the guard was dropped normally.
+/// * `Unwinding` if the guard was dropped while the thread is panicking.
+/// * `Explicit` if the guard was executed with `destroy()`.
 ///
+/// # Examples
 /// ```rust
-/// use defer_heavy::defer;
+/// use defer_heavy::{defer_with_status, ExitKind};
 ///
 /// fn test() {
-///     defer! { println!("Second"); }
-///     println!("First");
+///     let _guard = defer_with_status!(|kind| {
+///         if kind == ExitKind::Unwinding {
+///             println!("Failed");
+///         }
+///     });
 /// }
 /// ```
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+#[macro_export]
+macro_rules! defer_with_status {
+	( |$kind:pat_param| $($tt:tt)* ) => {
+		$crate::StatusDeferGuard::new(|$kind: $crate::ExitKind| { $($tt)* })
+	};
+}
+
+/// Executes a closure when the surrounding scope ends and tells it how the scope ended.
+/// This macro moves all captured variables.
 ///
-/// Multiple statements:
+/// See `defer_with_status!`.
 ///
+/// # Examples
 /// ```rust
-/// use defer_heavy::defer;
+/// use defer_heavy::{defer_move_with_status, ExitKind};
 ///
 /// fn test() {
-///     defer! {
-///         println!("Second");
-///         println!("Third");
-///     }
-///     println!("First");
+///     let path = String::from("/tmp/work");
+///     let _guard = defer_move_with_status!(|kind| {
+///         if kind != ExitKind::Unwinding {
+///             println!("Removing {}", path);
+///         }
+///     });
 /// }
 /// ```
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+#[macro_export]
+macro_rules! defer_move_with_status {
+	( |$kind:pat_param| $($tt:tt)* ) => {
+		$crate::StatusDeferGuard::new(move |$kind: $crate::ExitKind| { $($tt)* })
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends and executes it again if it panics.
 ///
-/// In Go, the `defer` code runs when the function exits. In this Rust
-/// implementation, however, the code runs when the surrounding scope ends –
-/// this makes it possible to use `defer` inside loops:
+/// The first argument is the maximum number of attempts, it must be a constant.
+/// The macro returns a `RetryDeferGuard`. `cancel()` prevents all attempts.
+/// If all attempts panic, the last panic is re-raised unless another panic is active.
+/// The block may be executed multiple times and must be `UnwindSafe`, so it cannot capture a `Cell` or `RefCell` by reference.
 ///
+/// # Examples
 /// ```rust
-/// use defer_heavy::defer;
+/// use defer_heavy::defer_retry;
 ///
 /// fn test() {
-///     defer! { println!("End"); }
-///     println!("Before");
+///     let _guard = defer_retry!(3, {
+///         println!("Removing lock file");
+///     });
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! defer_retry {
+	( $attempts:expr, $body:block ) => {
+		$crate::RetryDeferGuard::<_, { $attempts }>::new(|| $body)
+	};
+	( $attempts:expr, $($tt:tt)* ) => {
+		$crate::RetryDeferGuard::<_, { $attempts }>::new(|| { $($tt)* })
+	};
+}
+
+/// Executes a block of code when the surrounding scope ends if the target of a `Weak` is still alive.
 ///
-///     for i in 0..2 {
-///         defer! { println!("Defer {}", i); }
-///         println!("Loop {}", i);
-///     }
+/// The first argument is an `alloc::sync::Weak`. It is upgraded when the scope ends,
+/// the block receives the `Arc` and the target is released right after the block.
+/// The macro does not keep the target alive. If the target was dropped, the block is not executed.
+/// See `DeferGuard::new_with_weak`.
 ///
-///     println!("After");
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+/// use defer_heavy::defer_weak;
+///
+/// struct Session;
+///
+/// fn test(session: &Arc<Session>) {
+///     defer_weak!(Arc::downgrade(session), |_session| {
+///         println!("Session still alive, flushing");
+///     });
 /// }
 /// ```
+#[cfg(feature = "alloc")]
+#[cfg(target_has_atomic = "ptr")]
 #[macro_export]
-macro_rules! defer {
-	( $($tt:tt)* ) => {
-		let _deferred = $crate::DeferGuard::new(|| { $($tt)* });
+macro_rules! defer_weak {
+	( $weak:expr, |$target:pat_param| $body:block ) => {
+		let _deferred = $crate::DeferGuard::new_with_weak($weak, |$target| $body);
+	};
+	( $weak:expr, |$target:pat_param| $($tt:tt)* ) => {
+		let _deferred = $crate::DeferGuard::new_with_weak($weak, |$target| { $($tt)* });
 	};
 }
 
-/// Executes a block of code when the surrounding scope ends.
+/// Executes a block of code when the surrounding scope ends if the target of a `Weak` is still alive.
 /// This macro moves all captured variables.
 ///
-/// # Examples
+/// See `defer_weak!`.
 ///
+/// # Examples
 /// ```rust
-/// use defer_heavy::defer_move;
+/// use std::sync::Arc;
+/// use defer_heavy::defer_move_weak;
 ///
-/// fn test() {
-///     let n = 1;
-///     defer_move! { println!("Second n={}", n); }
-///     println!("First");
+/// struct Session;
+///
+/// fn test(session: &Arc<Session>) {
+///     let id = 7;
+///     defer_move_weak!(Arc::downgrade(session), |_session| {
+///         println!("Closing session {}", id);
+///     });
 /// }
 /// ```
+#[cfg(feature = "alloc")]
+#[cfg(target_has_atomic = "ptr")]
 #[macro_export]
-macro_rules! defer_move {
-	( $($tt:tt)* ) => {
-		let _deferred = $crate::DeferGuard::new(move || { $($tt)* });
+macro_rules! defer_move_weak {
+	( $weak:expr, |$target:pat_param| $body:block ) => {
+		let _deferred = $crate::DeferGuard::new_with_weak($weak, move |$target| $body);
+	};
+	( $weak:expr, |$target:pat_param| $($tt:tt)* ) => {
+		let _deferred = $crate::DeferGuard::new_with_weak($weak, move |$target| { $($tt)* });
 	};
 }
 
-/// Executes a block of code when the surrounding scope ends.
+/// Executes a block of code when the value of a variable is dropped instead of when the surrounding scope ends.
 ///
-/// The macro returns a guard that defines the scope of the deferment.
-/// The guard can be used to immediately execute the deferred closure or cancel it and
-/// prevent execution of the closure altogether
+/// The variable is shadowed by a `WithDefer` that owns the value and the guard.
+/// Moving the variable moves the guard with it, so the code is executed wherever the value is finally dropped.
+/// The value is dropped before the code is executed.
+/// `guard_mut` of the `WithDefer` returns the guard, for example to cancel it.
 ///
 /// # Examples
-///
 /// ```rust
-/// use defer_heavy::defer_guard;
-///
-/// fn test() {
-///
-///     let defer1 = defer_guard! { unreachable!("Wont be executed"); };
-///     let defer2 = defer_guard! { println!("Second"); };
+/// use defer_heavy::defer_on_drop_of;
 ///
-///     println!("First");
-///     defer1.cancel();
+/// fn open() -> impl std::ops::Deref<Target = Vec<u8>> {
+///     let connection = vec![1, 2, 3];
+///     defer_on_drop_of!(connection, {
+///         println!("Connection closed");
+///     });
+///     //Not executed here, the connection is returned.
+///     connection
 /// }
 /// ```
+#[macro_export]
+macro_rules! defer_on_drop_of {
+	( $value:ident, $body:block ) => {
+		#[allow(unused_mut)]
+		let mut $value = $crate::WithDefer::new($value, $crate::guard(|| $body));
+	};
+	( $value:ident, $($tt:tt)* ) => {
+		#[allow(unused_mut)]
+		let mut $value = $crate::WithDefer::new($value, $crate::guard(|| { $($tt)* }));
+	};
+}
+
+/// Executes a block of code when the value of a variable is dropped instead of when the surrounding scope ends.
+/// This macro moves all captured variables.
 ///
+/// See `defer_on_drop_of!`.
+///
+/// # Examples
 /// ```rust
-/// use defer_heavy::defer_guard;
+/// use defer_heavy::defer_move_on_drop_of;
 ///
 /// fn test() {
-///
-///     let defer1 = defer_guard! { unreachable!("Wont be executed"); };
-///     let defer2 = defer_guard! { println!("Fourth"); };
-///     let defer3 = defer_guard! { println!("Second"); };
-///
-///     println!("First");
-///     defer3.destroy();
-///     println!("Third");
-///     defer1.cancel();
+///     let name = String::from("db");
+///     let connection = vec![1, 2, 3];
+///     defer_move_on_drop_of!(connection, {
+///         println!("Connection {} closed", name);
+///     });
+///     let connections = vec![connection];
+///     //Prints "Connection db closed"
+///     drop(connections);
 /// }
 /// ```
-///
 #[macro_export]
-macro_rules! defer_guard {
-	( $($tt:tt)* ) => {
-		$crate::DeferGuard::new(|| { $($tt)* });
+macro_rules! defer_move_on_drop_of {
+	( $value:ident, $body:block ) => {
+		#[allow(unused_mut)]
+		let mut $value = $crate::WithDefer::new($value, $crate::guard(move || $body));
+	};
+	( $value:ident, $($tt:tt)* ) => {
+		#[allow(unused_mut)]
+		let mut $value = $crate::WithDefer::new($value, $crate::guard(move || { $($tt)* }));
 	};
 }
 
-/// Executes a block of code when the surrounding scope ends.
+/// Executes a closure when the surrounding scope ends and passes it the last value set during the scope.
 ///
-/// The macro returns a guard that defines the scope of the deferment.
-/// The guard can be used to immediately execute the deferred closure or cancel it and
-/// prevent execution of the closure altogether
+/// The macro returns a `DeferWith`.
+/// The closure receives `Some` with the last value passed to `set`, or `None` if no value was set.
+/// A type annotation on the parameter names the type of the value, the parameter itself is an `Option` of it.
 ///
 /// # Examples
-///
 /// ```rust
-/// use defer_heavy::defer_move_guard;
+/// use defer_heavy::defer_with;
 ///
 /// fn test() {
-///     let n = 1;
-///     let defer1 = defer_move_guard! { unreachable!("Wont be executed {}", n); };
-///     let n = 2;
-///     let defer2 = defer_move_guard! { println!("Second {}", n); };
-///
-///     println!("First");
-///     defer1.cancel();
+///     let mut status = defer_with!(|code| {
+///         println!("Exited with {:?}", code);
+///     });
+///     status.set(1);
+///     //Prints "Exited with Some(1)"
 /// }
 /// ```
-/// Prints:
-/// ```text
-/// First
-/// Second 2
-/// ```
+///
+/// # Mutable access in the scope and in the block
+/// `defer_with!(var => { ... })` moves the value of `var` into a `Defer` and shadows `var` with it.
+/// The rest of the scope uses the value through `Deref` and `DerefMut`,
+/// and the block receives the value by value when the scope ends, so both can mutate it.
+/// No `RefCell` is involved, so there is nothing that can panic because of a conflicting borrow.
+/// Borrows of `var` in the scope are checked by the compiler and always end before the block is executed.
+/// `Defer::into_inner(var)` returns the value without executing the block.
 ///
 /// ```rust
-/// use defer_heavy::defer_move_guard;
+/// use defer_heavy::defer_with;
 ///
 /// fn test() {
-///
-///     let n = 1;
-///     let defer1 = defer_move_guard! { unreachable!("Wont be executed {}", n); };
-///     let n = 2;
-///     let defer2 = defer_move_guard! { println!("Fourth {}", n); };
-///     let n = 3;
-///     let defer3 = defer_move_guard! { println!("Second {}", n); };
-///
-///     println!("First");
-///     defer3.destroy(); //Same as drop(defer3);
-///     println!("Third");
-///     defer1.cancel();
+///     let buffer = Vec::new();
+///     defer_with!(buffer => {
+///         println!("Discarding {} bytes", buffer.len());
+///         buffer.clear();
+///     });
+///     buffer.push(1u8);
+///     buffer.push(2u8);
+///     //Prints "Discarding 2 bytes"
 /// }
 /// ```
-/// Prints:
-/// ```text
-/// First
-/// Second 3
-/// Third
-/// Fourth 2
-/// ```
+#[macro_export]
+macro_rules! defer_with {
+	( $var:ident => $body:block ) => {
+		#[allow(unused_mut)]
+		let mut $var = $crate::Defer::new($var, |$var| {
+			#[allow(unused_mut)]
+			let mut $var = $var;
+			$body
+		});
+	};
+	( $var:ident => $($tt:tt)* ) => {
+		#[allow(unused_mut)]
+		let mut $var = $crate::Defer::new($var, |$var| {
+			#[allow(unused_mut)]
+			let mut $var = $var;
+			$($tt)*
+		});
+	};
+	( |$arg:ident : $ty:ty| $body:block ) => {
+		$crate::DeferWith::new(|$arg: Option<$ty>| $body)
+	};
+	( |$arg:ident : $ty:ty| $($tt:tt)* ) => {
+		$crate::DeferWith::new(|$arg: Option<$ty>| { $($tt)* })
+	};
+	( |$arg:pat_param| $body:block ) => {
+		$crate::DeferWith::new(|$arg| $body)
+	};
+	( |$arg:pat_param| $($tt:tt)* ) => {
+		$crate::DeferWith::new(|$arg| { $($tt)* })
+	};
+}
+
+/// Executes a block of code with mutable access to a context variable when the surrounding scope ends.
 ///
+/// `defer_with_context!(ctx: &mut Type, { ... })` borrows `ctx` mutably and shadows it with a `ContextDeferGuard`.
+/// The rest of the scope uses the context through `Deref` and `DerefMut`,
+/// and the block receives it as `ctx` when the scope ends, so it sees the values set during the scope.
+/// Capturing `&mut ctx` in a `defer!` would prevent the scope from using `ctx` until the closure was called.
+/// The borrow is checked by the compiler, the guard cannot outlive the context.
+/// `ctx` can be a value or a `&mut Type`, the type can be omitted if it can be inferred.
+///
+/// # Examples
 /// ```rust
+/// use defer_heavy::defer_with_context;
+///
+/// #[derive(Default)]
+/// struct CleanupContext {
+///     rollback_id: Option<u32>,
+/// }
+///
+/// impl CleanupContext {
+///     fn do_rollback(&mut self) {
+///         if let Some(id) = self.rollback_id.take() {
+///             println!("Rollback {}", id);
+///         }
+///     }
+/// }
+///
+/// fn insert(ctx: &mut CleanupContext, id: u32) {
+///     defer_with_context!(ctx: &mut CleanupContext, {
+///         ctx.do_rollback();
+///     });
+///     ctx.rollback_id = Some(id);
+///     //Insert...
+///     //Prints "Rollback" with the id if the insert did not clear it.
+/// }
 ///
+/// let mut ctx = CleanupContext::default();
+/// insert(&mut ctx, 7);
+/// assert_eq!(ctx.rollback_id, None);
+/// ```
 #[macro_export]
-macro_rules! defer_move_guard {
-	( $($tt:tt)* ) => {
-		$crate::DeferGuard::new(move || { $($tt)* });
+macro_rules! defer_with_context {
+	( $ctx:ident : &mut $ty:ty, $body:block ) => {
+		#[allow(unused_mut)]
+		let mut $ctx = {
+			use $crate::__private::ContextSlot as _;
+			$crate::ContextDeferGuard::<'_, $ty, _>::new($ctx.__defer_heavy_context(), |$ctx: &mut $ty| $body)
+		};
+	};
+	( $ctx:ident : &mut $ty:ty, $($tt:tt)* ) => {
+		#[allow(unused_mut)]
+		let mut $ctx = {
+			use $crate::__private::ContextSlot as _;
+			$crate::ContextDeferGuard::<'_, $ty, _>::new($ctx.__defer_heavy_context(), |$ctx: &mut $ty| { $($tt)* })
+		};
+	};
+	( $ctx:ident, $body:block ) => {
+		#[allow(unused_mut)]
+		let mut $ctx = {
+			use $crate::__private::ContextSlot as _;
+			$crate::ContextDeferGuard::new($ctx.__defer_heavy_context(), |$ctx| $body)
+		};
+	};
+	( $ctx:ident, $($tt:tt)* ) => {
+		#[allow(unused_mut)]
+		let mut $ctx = {
+			use $crate::__private::ContextSlot as _;
+			$crate::ContextDeferGuard::new($ctx.__defer_heavy_context(), |$ctx| { $($tt)* })
+		};
 	};
 }
 
-#[cfg(target_has_atomic = "8")]
-#[cfg(target_has_atomic = "ptr")]
-#[cfg(feature = "mt")]
-pub use mt::ArcDeferGuard;
-
-/// Executes a block of code when the surrounding scope ends.
-///
-/// The macro returns a guard that defines the scope of the deferment.
-/// The guard can be shared with other threads, and it will only execute the block of code when
-/// no more references to the guard exist.
+/// Asserts a condition when the surrounding scope ends.
 ///
-/// The code closure must be 'Send'.
+/// The condition and the message are evaluated when the scope ends, not when the macro is invoked.
+/// Just like with `defer!`, the captured variables are borrowed until the scope ends.
+/// If the condition is false, the macro panics with the message and the location of the macro invocation.
 ///
+/// If the scope ends because the thread is already panicking, a failed assertion does not panic again,
+/// which would abort the process. With the `std` feature the failure is printed to stderr instead.
+/// Without the `std` feature a panic in progress is detected with the `panic_tracking` module,
+/// see its documentation for the integration that is needed on `no_std` targets.
 ///
 /// # Examples
 /// ```rust
-/// use std::thread;
-/// use std::time::Duration;
-/// use defer_heavy::defer_arc;
+/// use std::cell::RefCell;
+/// use defer_heavy::defer_assert;
 ///
-/// pub fn test() {
-///     let deferred = defer_arc! { println!("Executed in {:?}", thread::current().id());};
-///     println!("Main thread {:?}", thread::current().id());
-///     let th;
-///     {
-///         let deferred = deferred.clone();
-///         th = thread::spawn(move ||{
-///             println!("Spawned thread {:?}", thread::current().id());
-///             let _deferred = deferred.own();
-///             thread::sleep(Duration::from_millis(2000)); //SIMULATE work
-///        });
-///    }
-///    thread::sleep(Duration::from_millis(2000)); //SIMULATE WORK
-///    drop(deferred);
-///    th.join().unwrap();
-///  }
-/// ```
-/// Prints:
-/// ```text
-/// Main thread Thread(1)
-/// Spawned thread Thread(2)
-/// "Executed in Thread(1)" or "Executed in Thread(2)"
+/// fn handle(queue: &RefCell<Vec<u32>>) {
+///     defer_assert!(queue.borrow().is_empty(), "queue has {} remaining entries", queue.borrow().len());
+///     queue.borrow_mut().clear();
+/// }
 /// ```
-///
-#[cfg(target_has_atomic = "8")]
-#[cfg(target_has_atomic = "ptr")]
-#[cfg(feature = "mt")]
 #[macro_export]
-macro_rules! defer_arc {
-	( $($tt:tt)* ) => {
-		$crate::ArcDeferGuard::new(|| { $($tt)* });
+macro_rules! defer_assert {
+	( $cond:expr $(,)? ) => {
+		let _deferred_assert = $crate::DeferAssertGuard::new(|location| {
+			if !$cond {
+				$crate::__private::defer_assert_failed(location, format_args!("assertion failed: {}", stringify!($cond)));
+			}
+		});
+	};
+	( $cond:expr, $($arg:tt)+ ) => {
+		let _deferred_assert = $crate::DeferAssertGuard::new(|location| {
+			if !$cond {
+				$crate::__private::defer_assert_failed(location, format_args!($($arg)+));
+			}
+		});
 	};
 }
 
-/// Executes a block of code when the surrounding scope ends.
+/// Same as `defer_assert!` but only enabled with debug assertions.
 ///
-/// The macro returns a guard that defines the scope of the deferment.
-/// The guard can be shared with other threads, and it will only execute the block of code when
-/// no more references to the guard exist.
+/// Without debug assertions no guard is created and the condition is never evaluated.
 ///
-/// The code closure must be 'Send'.
-/// The closure moves all used variables. Each used variable must be 'Send' or the closure won't be 'Send'.
+/// # Examples
+/// ```rust
+/// use std::cell::Cell;
+/// use defer_heavy::defer_debug_assert;
+///
+/// fn handle(depth: &Cell<u32>) {
+///     let initial = depth.get();
+///     defer_debug_assert!(depth.get() == initial);
+///     depth.set(initial + 1);
+///     //Work...
+///     depth.set(initial);
+/// }
+/// ```
+#[macro_export]
+macro_rules! defer_debug_assert {
+	( $cond:expr $(,)? ) => {
+		let _deferred_assert = if cfg!(debug_assertions) {
+			Some($crate::DeferAssertGuard::new(|location| {
+				if !$cond {
+					$crate::__private::defer_assert_failed(location, format_args!("assertion failed: {}", stringify!($cond)));
+				}
+			}))
+		} else {
+			None
+		};
+	};
+	( $cond:expr, $($arg:tt)+ ) => {
+		let _deferred_assert = if cfg!(debug_assertions) {
+			Some($crate::DeferAssertGuard::new(|location| {
+				if !$cond {
+					$crate::__private::defer_assert_failed(location, format_args!($($arg)+));
+				}
+			}))
+		} else {
+			None
+		};
+	};
+}
+
+/// Measures the time until the surrounding scope ends and passes it to the closure.
+///
+/// The macro returns a `TimerGuard`.
+/// The elapsed time can be read earlier with `lap()`.
 ///
 /// # Examples
 /// ```rust
-/// use std::thread;
 /// use std::time::Duration;
-/// use defer_heavy::defer_move_arc;
+/// use defer_heavy::defer_time;
 ///
-/// pub fn test() {
-///     let v = 1;
-///     let deferred = defer_move_arc! { println!("Executed {} in {:?}", v, thread::current().id());};
-///     println!("Main thread {:?}", thread::current().id());
-///     let th;
-///     {
-///         let deferred = deferred.clone();
-///         th = thread::spawn(move ||{
-///             println!("Spawned thread {:?}", thread::current().id());
-///             let _deferred = deferred.own();
-///             thread::sleep(Duration::from_millis(2000)); //SIMULATE work
-///        });
-///    }
-///    thread::sleep(Duration::from_millis(2000)); //SIMULATE WORK
-///    drop(deferred);
-///    th.join().unwrap();
-///  }
-/// ```
-/// Prints:
-/// ```text
-/// Main thread Thread(1)
-/// Spawned thread Thread(2)
-/// "Executed in Thread(1)" or "Executed in Thread(2)"
+/// fn test() {
+///     let _timer = defer_time!(|elapsed: Duration| println!("Took {:?}", elapsed));
+///     //Work...
+/// }
 /// ```
-///
-#[cfg(target_has_atomic = "8")]
-#[cfg(target_has_atomic = "ptr")]
-#[cfg(feature = "mt")]
+#[cfg(feature = "std")]
 #[macro_export]
-macro_rules! defer_move_arc {
-	( $($tt:tt)* ) => {
-		$crate::ArcDeferGuard::new(move || { $($tt)* });
-	};
-}
\ No newline at end of file
+macro_rules! defer_time {
+    ( $func:expr ) => {
+        $crate::TimerGuard::new($func)
+    };
+}
@@ -0,0 +1,288 @@
+use crate::DeferGuard;
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+///
+/// One of two guards of which at most one calls its closure.
+///
+/// Created by `link`.
+/// The first of the two guards that executes, by being dropped or destroyed, claims the shared flag
+/// and calls its closure. The closure of the other guard is dropped without being called when it executes.
+/// Canceling a guard does not claim the flag, so the other guard still calls its closure.
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct LinkedGuard<F: FnOnce()>(Option<DeferGuard<F>>, Rc<Cell<bool>>);
+
+///
+/// Links two guards, so at most one of them calls its closure.
+///
+/// This is useful for alternative cleanups where only the first one that is needed should run.
+/// Guards in the same scope are dropped in reverse order of declaration,
+/// so if both are dropped normally the closure of the guard that was declared later is called.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{defer_guard, link};
+///
+/// fn upload(fail_early: bool) {
+///     let (delete_blob, rollback_row) = link(
+///         defer_guard! { println!("Delete blob"); },
+///         defer_guard! { println!("Rollback row"); },
+///     );
+///     if fail_early {
+///         //Prints "Delete blob", "Rollback row" is not printed when it is dropped.
+///         delete_blob.destroy();
+///     }
+///     drop(rollback_row);
+/// }
+/// ```
+///
+#[must_use]
+pub fn link<F: FnOnce(), G: FnOnce()>(
+    a: DeferGuard<F>,
+    b: DeferGuard<G>,
+) -> (LinkedGuard<F>, LinkedGuard<G>) {
+    let flag = Rc::new(Cell::new(false));
+    (
+        LinkedGuard(Some(a), flag.clone()),
+        LinkedGuard(Some(b), flag),
+    )
+}
+
+impl<F: FnOnce()> LinkedGuard<F> {
+    ///
+    /// Will call the closure now, unless the other guard already called its closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was canceled or the other guard called its closure.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.execute()
+    }
+
+    ///
+    /// Will cancel running the closure.
+    /// The other guard still calls its closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already canceled.
+    ///
+    #[inline(always)]
+    #[track_caller]
+    pub fn cancel(mut self) -> bool {
+        self.0.take().is_some_and(DeferGuard::cancel)
+    }
+
+    ///
+    /// Returns true if one of the two guards called its closure.
+    ///
+    #[inline(always)]
+    pub fn is_claimed(&self) -> bool {
+        self.1.get()
+    }
+
+    fn execute(&mut self) -> bool {
+        let Some(guard) = self.0.take() else {
+            return false;
+        };
+
+        if !crate::Deferrable::is_armed(&guard) {
+            return false;
+        }
+
+        if self.1.replace(true) {
+            guard.cancel();
+            return false;
+        }
+
+        guard.destroy()
+    }
+}
+
+impl<F: FnOnce()> Drop for LinkedGuard<F> {
+    fn drop(&mut self) {
+        self.execute();
+    }
+}
+
+impl<F: FnOnce()> core::fmt::Debug for LinkedGuard<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LinkedGuard")
+            .field("armed", &crate::Deferrable::is_armed(self))
+            .field("claimed", &self.1.get())
+            .finish()
+    }
+}
+
+impl<F: FnOnce()> crate::Deferrable for LinkedGuard<F> {
+    #[inline(always)]
+    #[track_caller]
+    fn cancel(self) {
+        LinkedGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        LinkedGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        !self.1.get() && self.0.as_ref().is_some_and(crate::Deferrable::is_armed)
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+pub use arc::{link_arc, ArcLinkedGuard};
+
+#[cfg(target_has_atomic = "8")]
+#[cfg(target_has_atomic = "ptr")]
+#[cfg(feature = "mt")]
+mod arc {
+    use crate::DeferGuard;
+    use alloc::sync::Arc;
+    use core::sync::atomic::AtomicBool;
+    use core::sync::atomic::Ordering::SeqCst;
+
+    ///
+    /// One of two guards of which at most one calls its closure, the guards can be sent to other threads.
+    ///
+    /// Created by `link_arc`.
+    /// The first of the two guards that executes claims the shared flag with a single atomic operation,
+    /// so even if both are dropped on different threads at the same time exactly one of them calls its closure.
+    /// Canceling a guard does not claim the flag, so the other guard still calls its closure.
+    ///
+    #[cfg_attr(
+        all(feature = "lint-attrs", defer_heavy_nightly),
+        must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+    )]
+    #[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+    pub struct ArcLinkedGuard<F: FnOnce() + Send>(Option<DeferGuard<F>>, Arc<AtomicBool>);
+
+    ///
+    /// Links two guards with `Send` closures, so at most one of them calls its closure.
+    ///
+    /// This is the thread safe variant of `link`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::thread;
+    /// use defer_heavy::{link_arc, DeferGuard};
+    ///
+    /// let (first, second) = link_arc(
+    ///     DeferGuard::new(|| println!("First")),
+    ///     DeferGuard::new(|| println!("Second")),
+    /// );
+    /// let worker = thread::spawn(move || drop(first));
+    /// drop(second);
+    /// worker.join().unwrap();
+    /// //Printed either "First" or "Second", but not both.
+    /// ```
+    ///
+    #[must_use]
+    pub fn link_arc<F: FnOnce() + Send, G: FnOnce() + Send>(
+        a: DeferGuard<F>,
+        b: DeferGuard<G>,
+    ) -> (ArcLinkedGuard<F>, ArcLinkedGuard<G>) {
+        let flag = Arc::new(AtomicBool::new(false));
+        (
+            ArcLinkedGuard(Some(a), flag.clone()),
+            ArcLinkedGuard(Some(b), flag),
+        )
+    }
+
+    impl<F: FnOnce() + Send> ArcLinkedGuard<F> {
+        ///
+        /// Will call the closure now, unless the other guard already called its closure.
+        ///
+        /// # Returns
+        /// * true: closure was called.
+        /// * false: closure was not called because it was canceled or the other guard called its closure.
+        ///
+        #[inline(always)]
+        pub fn destroy(mut self) -> bool {
+            self.execute()
+        }
+
+        ///
+        /// Will cancel running the closure.
+        /// The other guard still calls its closure.
+        ///
+        /// # Returns
+        /// * true: closure was dropped and will not be called anymore.
+        /// * false: closure was already canceled.
+        ///
+        #[inline(always)]
+        #[track_caller]
+        pub fn cancel(mut self) -> bool {
+            self.0.take().is_some_and(DeferGuard::cancel)
+        }
+
+        ///
+        /// Returns true if one of the two guards called its closure.
+        ///
+        #[inline(always)]
+        pub fn is_claimed(&self) -> bool {
+            self.1.load(SeqCst)
+        }
+
+        fn execute(&mut self) -> bool {
+            let Some(guard) = self.0.take() else {
+                return false;
+            };
+
+            if !crate::Deferrable::is_armed(&guard) {
+                return false;
+            }
+
+            if self.1.swap(true, SeqCst) {
+                guard.cancel();
+                return false;
+            }
+
+            guard.destroy()
+        }
+    }
+
+    impl<F: FnOnce() + Send> Drop for ArcLinkedGuard<F> {
+        fn drop(&mut self) {
+            self.execute();
+        }
+    }
+
+    impl<F: FnOnce() + Send> core::fmt::Debug for ArcLinkedGuard<F> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("ArcLinkedGuard")
+                .field("armed", &crate::Deferrable::is_armed(self))
+                .field("claimed", &self.1.load(SeqCst))
+                .finish()
+        }
+    }
+
+    impl<F: FnOnce() + Send> crate::Deferrable for ArcLinkedGuard<F> {
+        #[inline(always)]
+        #[track_caller]
+        fn cancel(self) {
+            ArcLinkedGuard::cancel(self);
+        }
+
+        #[inline(always)]
+        fn destroy(self) -> bool {
+            ArcLinkedGuard::destroy(self)
+        }
+
+        #[inline(always)]
+        fn is_armed(&self) -> bool {
+            !self.1.load(SeqCst) && self.0.as_ref().is_some_and(crate::Deferrable::is_armed)
+        }
+    }
+}
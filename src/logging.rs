@@ -0,0 +1,113 @@
+///
+/// Guard that logs a message when its closure is executed or canceled.
+///
+/// Created by the `defer_log!` macro. The messages are logged with the `log` crate,
+/// using the level `Trace` unless another level was given to the macro.
+/// * `"{name} [defer_execute]"` right before the closure is executed.
+/// * `"{name} [defer_cancelled]"` when the closure is canceled.
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct LogDeferGuard<F: FnOnce()>(Option<F>, &'static str, log::Level);
+
+impl<F: FnOnce()> LogDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(name: &'static str, level: log::Level, func: F) -> Self {
+        Self(Some(func), name, level)
+    }
+
+    ///
+    /// Returns the name that is logged by this guard.
+    ///
+    #[inline(always)]
+    pub fn name(&self) -> &'static str {
+        self.1
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.0.take() else {
+            return false;
+        };
+
+        log::log!(self.2, "{} [defer_execute]", self.1);
+        func();
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    pub fn cancel_ref(&mut self) -> bool {
+        if self.0.take().is_none() {
+            return false;
+        }
+
+        log::log!(self.2, "{} [defer_cancelled]", self.1);
+        true
+    }
+}
+
+impl<F: FnOnce()> crate::Deferrable for LogDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        LogDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        LogDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<F: FnOnce()> Drop for LogDeferGuard<F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
@@ -0,0 +1,154 @@
+///
+/// Guard that holds up to `N` closures of the same type without allocating.
+///
+/// The closures are stored inline, so the guard can be used where there is no heap.
+/// They are called in reverse order of registration when `destroy_all` is called or when the guard is dropped,
+/// like multiple `defer!` in the same scope. If a closure panics, the remaining closures are still called.
+/// `cancel_all` cancels all of them at once.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::MultiDeferGuard;
+///
+/// fn power_off_led() {}
+/// fn release_bus() {}
+///
+/// fn init(fail: bool) {
+///     let mut cleanup = MultiDeferGuard::<4, fn()>::new();
+///     cleanup.push(release_bus).unwrap();
+///     cleanup.push(power_off_led).unwrap();
+///
+///     if !fail {
+///         //Initialization succeeded, keep the peripherals.
+///         cleanup.cancel_all();
+///     }
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct MultiDeferGuard<const N: usize, F: FnOnce()>([Option<F>; N], usize);
+
+impl<const N: usize, F: FnOnce()> MultiDeferGuard<N, F> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self([const { None }; N], 0)
+    }
+
+    ///
+    /// Adds a closure to the guard.
+    ///
+    /// # Returns
+    /// * Ok: the closure was stored.
+    /// * Err: the guard already holds `N` closures, the closure is returned.
+    ///
+    #[inline(always)]
+    pub fn push(&mut self, func: F) -> Result<(), F> {
+        if self.1 >= N {
+            return Err(func);
+        }
+
+        self.0[self.1] = Some(func);
+        self.1 += 1;
+        Ok(())
+    }
+
+    ///
+    /// Returns the number of closures that were not called or canceled yet.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.1
+    }
+
+    ///
+    /// Returns true if there are no closures in the guard.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.1 == 0
+    }
+
+    ///
+    /// Returns true if no more closures can be added.
+    ///
+    #[inline(always)]
+    pub fn is_full(&self) -> bool {
+        self.1 >= N
+    }
+
+    ///
+    /// Will call all closures now in reverse order of registration.
+    /// The guard is empty afterward and can be reused.
+    ///
+    /// # Returns
+    /// The number of closures that were called.
+    ///
+    pub fn destroy_all(&mut self) -> usize {
+        let mut called = 0;
+        while self.1 > 0 {
+            self.1 -= 1;
+            let Some(func) = self.0[self.1].take() else {
+                continue;
+            };
+
+            //Calls the remaining closures if this one panics.
+            let rest = Rest(self);
+            func();
+            core::mem::forget(rest);
+            called += 1;
+        }
+
+        called
+    }
+
+    ///
+    /// Will cancel all closures, so they cannot be called anymore.
+    /// This drops the closures.
+    ///
+    /// # Returns
+    /// The number of closures that were canceled.
+    ///
+    pub fn cancel_all(&mut self) -> usize {
+        let canceled = self.1;
+        while self.1 > 0 {
+            self.1 -= 1;
+            self.0[self.1] = None;
+        }
+
+        canceled
+    }
+}
+
+struct Rest<'a, const N: usize, F: FnOnce()>(&'a mut MultiDeferGuard<N, F>);
+
+impl<const N: usize, F: FnOnce()> Drop for Rest<'_, N, F> {
+    fn drop(&mut self) {
+        self.0.destroy_all();
+    }
+}
+
+impl<const N: usize, F: FnOnce()> Default for MultiDeferGuard<N, F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, F: FnOnce()> Drop for MultiDeferGuard<N, F> {
+    fn drop(&mut self) {
+        self.destroy_all();
+    }
+}
+
+impl<const N: usize, F: FnOnce()> core::fmt::Debug for MultiDeferGuard<N, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MultiDeferGuard")
+            .field("len", &self.1)
+            .field("capacity", &N)
+            .finish()
+    }
+}
@@ -0,0 +1,631 @@
+//! Ready made guards for operating system resources.
+//!
+//! `defer_remove_file` and `defer_remove_dir_all` remove a path when the guard is dropped.
+//! A path that does not exist anymore is not an error, other errors are passed to the `error_hook`.
+//! `destroy` removes the path now and returns the error to the caller instead.
+//!
+//! `defer_close_fd` and `defer_close_handle` close a file descriptor or a Windows handle when the guard is dropped.
+//! `into_inner` returns it to keep it open.
+//!
+//! `defer_restore_env` and `set_env_scoped` restore an environment variable when the guard is dropped,
+//! `defer_restore_cwd` and `chdir_scoped` restore the working directory.
+//! Both are global to the process, so changing them is not thread safe:
+//! other threads observe the changed value until the guard is dropped, and a guard restores the value it saw
+//! even if another thread changed it in the meantime.
+//! The guards of this module serialize their own changes with an internal mutex,
+//! but that does not protect against other code that changes or reads the environment, like `getenv` in C.
+//! The guards should be dropped in reverse order of creation, which is the case for guards in nested scopes.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::os::defer_remove_file;
+//!
+//! fn export(path: &str) -> std::io::Result<()> {
+//!     let partial = defer_remove_file(path);
+//!     std::fs::write(&*partial, b"data")?;
+//!     //Keep the file, it is complete.
+//!     partial.cancel();
+//!     Ok(())
+//! }
+//! ```
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+//Serializes the changes of the environment and the working directory made by the guards.
+static PROCESS_LOCK: Mutex<()> = Mutex::new(());
+
+fn with_process_lock<R>(func: impl FnOnce() -> R) -> R {
+    let _lock = PROCESS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    func()
+}
+
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+///
+/// Guard that removes a file when it is dropped.
+///
+/// Created by `defer_remove_file`.
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the file is not removed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct RemoveFileGuard(Option<PathBuf>);
+
+///
+/// Removes the file when the returned guard is dropped.
+///
+/// If the file does not exist at that time nothing happens, other errors are passed to the `error_hook`.
+///
+#[inline(always)]
+#[must_use]
+pub fn defer_remove_file(path: impl Into<PathBuf>) -> RemoveFileGuard {
+    RemoveFileGuard(Some(path.into()))
+}
+
+impl RemoveFileGuard {
+    ///
+    /// Returns the path that is removed.
+    ///
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        self.0.as_deref().unwrap_or(Path::new(""))
+    }
+
+    ///
+    /// Will remove the file now.
+    ///
+    /// # Returns
+    /// * Ok: the file was removed or did not exist.
+    /// * Err: the file could not be removed.
+    ///
+    pub fn destroy(mut self) -> io::Result<()> {
+        match self.0.take() {
+            Some(path) => ignore_not_found(std::fs::remove_file(path)),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Will cancel removing the file.
+    ///
+    /// # Returns
+    /// The path of the file.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> PathBuf {
+        self.0.take().unwrap_or_default()
+    }
+}
+
+impl Deref for RemoveFileGuard {
+    type Target = Path;
+
+    #[inline(always)]
+    fn deref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Drop for RemoveFileGuard {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+
+        if let Err(error) = ignore_not_found(std::fs::remove_file(&path)) {
+            crate::error_hook::report(&(path, error));
+        }
+    }
+}
+
+impl crate::Deferrable for RemoveFileGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        RemoveFileGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        RemoveFileGuard::destroy(self).is_ok()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+///
+/// Guard that removes a directory and all of its content when it is dropped.
+///
+/// Created by `defer_remove_dir_all`.
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the directory is not removed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct RemoveDirAllGuard(Option<PathBuf>);
+
+///
+/// Removes the directory and all of its content when the returned guard is dropped.
+///
+/// If the directory does not exist at that time nothing happens, other errors are passed to the `error_hook`.
+///
+#[inline(always)]
+#[must_use]
+pub fn defer_remove_dir_all(path: impl Into<PathBuf>) -> RemoveDirAllGuard {
+    RemoveDirAllGuard(Some(path.into()))
+}
+
+impl RemoveDirAllGuard {
+    ///
+    /// Returns the path that is removed.
+    ///
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        self.0.as_deref().unwrap_or(Path::new(""))
+    }
+
+    ///
+    /// Will remove the directory now.
+    ///
+    /// # Returns
+    /// * Ok: the directory was removed or did not exist.
+    /// * Err: the directory could not be removed completely.
+    ///
+    pub fn destroy(mut self) -> io::Result<()> {
+        match self.0.take() {
+            Some(path) => ignore_not_found(std::fs::remove_dir_all(path)),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Will cancel removing the directory.
+    ///
+    /// # Returns
+    /// The path of the directory.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> PathBuf {
+        self.0.take().unwrap_or_default()
+    }
+}
+
+impl Deref for RemoveDirAllGuard {
+    type Target = Path;
+
+    #[inline(always)]
+    fn deref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Drop for RemoveDirAllGuard {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+
+        if let Err(error) = ignore_not_found(std::fs::remove_dir_all(&path)) {
+            crate::error_hook::report(&(path, error));
+        }
+    }
+}
+
+impl crate::Deferrable for RemoveDirAllGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        RemoveDirAllGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        RemoveDirAllGuard::destroy(self).is_ok()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+///
+/// Guard that restores an environment variable when it is dropped.
+///
+/// Created by `defer_restore_env` and `set_env_scoped`.
+/// A variable that was not set is removed again, so it is not confused with a variable that is set to an empty value.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::os::set_env_scoped;
+///
+/// fn test_with_debug_logging() {
+///     let _env = set_env_scoped("MY_APP_LOG", "debug");
+///     assert_eq!(std::env::var("MY_APP_LOG").unwrap(), "debug");
+///     //MY_APP_LOG has its previous value or is not set again after this.
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the environment variable stays changed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct EnvGuard(Option<(OsString, Option<OsString>)>);
+
+///
+/// Restores the current value of the environment variable when the returned guard is dropped.
+/// If the variable is not set it is removed again.
+///
+/// # Panics
+/// if the key is empty or contains `=` or a NUL character.
+///
+#[must_use]
+pub fn defer_restore_env(key: impl AsRef<OsStr>) -> EnvGuard {
+    let key = key.as_ref();
+    let previous = with_process_lock(|| std::env::var_os(key));
+    EnvGuard(Some((key.to_os_string(), previous)))
+}
+
+///
+/// Sets the environment variable and restores its previous value when the returned guard is dropped.
+/// If the variable was not set it is removed again.
+///
+/// # Panics
+/// if the key is empty or contains `=` or a NUL character, or the value contains a NUL character.
+///
+#[must_use]
+pub fn set_env_scoped(key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> EnvGuard {
+    let key = key.as_ref();
+    let previous = with_process_lock(|| {
+        let previous = std::env::var_os(key);
+        std::env::set_var(key, value);
+        previous
+    });
+    EnvGuard(Some((key.to_os_string(), previous)))
+}
+
+impl EnvGuard {
+    ///
+    /// Returns the name of the environment variable.
+    ///
+    #[inline(always)]
+    pub fn key(&self) -> &OsStr {
+        self.0.as_ref().map_or(OsStr::new(""), |(key, _)| key)
+    }
+
+    ///
+    /// Returns the value that is restored, `None` if the variable is removed.
+    ///
+    #[inline(always)]
+    pub fn previous(&self) -> Option<&OsStr> {
+        self.0
+            .as_ref()
+            .and_then(|(_, previous)| previous.as_deref())
+    }
+
+    ///
+    /// Will restore the environment variable now.
+    ///
+    #[inline(always)]
+    pub fn restore_now(mut self) {
+        self.restore();
+    }
+
+    ///
+    /// Will cancel restoring the environment variable, it keeps its current value.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) {
+        self.0 = None;
+    }
+
+    fn restore(&mut self) {
+        let Some((key, previous)) = self.0.take() else {
+            return;
+        };
+
+        with_process_lock(|| match previous {
+            Some(value) => std::env::set_var(key, value),
+            None => std::env::remove_var(key),
+        });
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+impl crate::Deferrable for EnvGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        EnvGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        let armed = self.0.is_some();
+        self.restore_now();
+        armed
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+///
+/// Guard that restores the working directory of the process when it is dropped.
+///
+/// Created by `defer_restore_cwd` and `chdir_scoped`.
+/// If the directory cannot be restored when the guard is dropped the error is passed to the `error_hook`.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::os::chdir_scoped;
+///
+/// fn build(project: &str) -> std::io::Result<()> {
+///     let _cwd = chdir_scoped(project)?;
+///     //Relative paths are relative to the project here.
+///     Ok(())
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the working directory stays changed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct CwdGuard(Option<PathBuf>);
+
+///
+/// Restores the current working directory when the returned guard is dropped.
+///
+/// # Errors
+/// if the current working directory cannot be determined.
+///
+pub fn defer_restore_cwd() -> io::Result<CwdGuard> {
+    let previous = with_process_lock(std::env::current_dir)?;
+    Ok(CwdGuard(Some(previous)))
+}
+
+///
+/// Changes the working directory and restores the previous one when the returned guard is dropped.
+///
+/// # Errors
+/// if the current working directory cannot be determined or changed.
+/// The working directory is not changed in that case.
+///
+pub fn chdir_scoped(path: impl AsRef<Path>) -> io::Result<CwdGuard> {
+    let previous = with_process_lock(|| {
+        let previous = std::env::current_dir()?;
+        std::env::set_current_dir(path)?;
+        io::Result::Ok(previous)
+    })?;
+    Ok(CwdGuard(Some(previous)))
+}
+
+impl CwdGuard {
+    ///
+    /// Returns the working directory that is restored.
+    ///
+    #[inline(always)]
+    pub fn previous(&self) -> &Path {
+        self.0.as_deref().unwrap_or(Path::new(""))
+    }
+
+    ///
+    /// Will restore the working directory now.
+    ///
+    /// # Returns
+    /// * Ok: the working directory was restored.
+    /// * Err: the working directory could not be changed.
+    ///
+    pub fn restore_now(mut self) -> io::Result<()> {
+        match self.0.take() {
+            Some(path) => with_process_lock(|| std::env::set_current_dir(path)),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Will cancel restoring the working directory.
+    ///
+    /// # Returns
+    /// The working directory that would have been restored.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> PathBuf {
+        self.0.take().unwrap_or_default()
+    }
+}
+
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+
+        if let Err(error) = with_process_lock(|| std::env::set_current_dir(&path)) {
+            crate::error_hook::report(&(path, error));
+        }
+    }
+}
+
+impl crate::Deferrable for CwdGuard {
+    #[inline(always)]
+    fn cancel(self) {
+        CwdGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        CwdGuard::restore_now(self).is_ok()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{defer_close_fd, FdGuard};
+
+#[cfg(unix)]
+mod unix {
+    use std::ops::Deref;
+    use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+    ///
+    /// Guard that closes a file descriptor when it is dropped.
+    ///
+    /// Created by `defer_close_fd`.
+    /// Errors of `close` cannot be observed, because `OwnedFd` ignores them.
+    ///
+    #[derive(Debug)]
+    pub struct FdGuard(OwnedFd);
+
+    ///
+    /// Closes the file descriptor when the returned guard is dropped.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub fn defer_close_fd(fd: OwnedFd) -> FdGuard {
+        FdGuard(fd)
+    }
+
+    impl FdGuard {
+        ///
+        /// Will close the file descriptor now.
+        ///
+        #[inline(always)]
+        pub fn destroy(self) {
+            drop(self.0)
+        }
+
+        ///
+        /// Will cancel closing the file descriptor.
+        ///
+        /// # Returns
+        /// The file descriptor, which is closed when it is dropped.
+        ///
+        #[inline(always)]
+        pub fn into_inner(self) -> OwnedFd {
+            self.0
+        }
+    }
+
+    impl Deref for FdGuard {
+        type Target = OwnedFd;
+
+        #[inline(always)]
+        fn deref(&self) -> &OwnedFd {
+            &self.0
+        }
+    }
+
+    impl AsFd for FdGuard {
+        #[inline(always)]
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.0.as_fd()
+        }
+    }
+
+    impl AsRawFd for FdGuard {
+        #[inline(always)]
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use windows::{defer_close_handle, HandleGuard};
+
+#[cfg(windows)]
+mod windows {
+    use std::ops::Deref;
+    use std::os::windows::io::{AsHandle, AsRawHandle, BorrowedHandle, OwnedHandle, RawHandle};
+
+    ///
+    /// Guard that closes a Windows handle when it is dropped.
+    ///
+    /// Created by `defer_close_handle`.
+    ///
+    #[derive(Debug)]
+    pub struct HandleGuard(OwnedHandle);
+
+    ///
+    /// Closes the handle when the returned guard is dropped.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub fn defer_close_handle(handle: OwnedHandle) -> HandleGuard {
+        HandleGuard(handle)
+    }
+
+    impl HandleGuard {
+        ///
+        /// Will close the handle now.
+        ///
+        #[inline(always)]
+        pub fn destroy(self) {
+            drop(self.0)
+        }
+
+        ///
+        /// Will cancel closing the handle.
+        ///
+        /// # Returns
+        /// The handle, which is closed when it is dropped.
+        ///
+        #[inline(always)]
+        pub fn into_inner(self) -> OwnedHandle {
+            self.0
+        }
+    }
+
+    impl Deref for HandleGuard {
+        type Target = OwnedHandle;
+
+        #[inline(always)]
+        fn deref(&self) -> &OwnedHandle {
+            &self.0
+        }
+    }
+
+    impl AsHandle for HandleGuard {
+        #[inline(always)]
+        fn as_handle(&self) -> BorrowedHandle<'_> {
+            self.0.as_handle()
+        }
+    }
+
+    impl AsRawHandle for HandleGuard {
+        #[inline(always)]
+        fn as_raw_handle(&self) -> RawHandle {
+            self.0.as_raw_handle()
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::any::Any;
+use std::boxed::Box;
+use std::panic::UnwindSafe;
+
+///
+/// Guard that catches a panic of its closure.
+///
+/// A closure that panics while the thread is already panicking aborts the process.
+/// This guard only re-raises the panic of the closure when it is dropped and no other panic is active.
+/// If another panic is active, the panic of the closure is discarded.
+/// With the `log` feature the discarded panic is logged.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::PanicSafeDeferGuard;
+///
+/// let mut guard = PanicSafeDeferGuard::new(|| panic!("cleanup failed"));
+/// guard.destroy_ref();
+/// assert!(guard.take_panic().is_some());
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct PanicSafeDeferGuard<F: FnOnce() + UnwindSafe>(Option<F>, Option<Box<dyn Any + Send>>);
+
+impl<F: FnOnce() + UnwindSafe> PanicSafeDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(Some(func), None)
+    }
+
+    ///
+    /// Will call the closure now.
+    /// A panic of the closure is re-raised, unless another panic is active.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure.
+    /// A panic of the closure is caught and can be retrieved with `take_panic`.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.0.take() else {
+            return false;
+        };
+
+        if let Err(payload) = std::panic::catch_unwind(func) {
+            self.1 = Some(payload);
+        }
+
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+
+    ///
+    /// Returns the payload of the panic that was caught by `destroy_ref`.
+    /// A panic that was taken is not re-raised when the guard is dropped.
+    ///
+    #[inline(always)]
+    pub fn take_panic(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.1.take()
+    }
+}
+
+impl<F: FnOnce() + UnwindSafe> crate::Deferrable for PanicSafeDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        PanicSafeDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        PanicSafeDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<F: FnOnce() + UnwindSafe> Drop for PanicSafeDeferGuard<F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+        let Some(payload) = self.1.take() else {
+            return;
+        };
+
+        if !std::thread::panicking() {
+            std::panic::resume_unwind(payload);
+        }
+
+        #[cfg(feature = "log")]
+        log::error!("panic of deferred closure discarded because another panic is active");
+    }
+}
@@ -0,0 +1,98 @@
+//! Tells the unwind-conditional guards whether a panic is in progress.
+//!
+//! `OnUnwind`, `OnSuccess` and `StatusDeferGuard` ask `is_panicking()` when they are dropped.
+//!
+//! With the `std` feature `is_panicking()` delegates to `std::thread::panicking()`
+//! and the counter of this module is ignored, no integration is needed.
+//!
+//! Without the `std` feature the crate cannot know that a panic is in progress.
+//! `is_panicking()` returns true while the panic counter is not zero.
+//! The integration contract is:
+//! * Call `enter_panic()` once when a panic starts, before any destructor runs.
+//!   This is usually done at the start of the `#[panic_handler]` or in the unwinding shim before it starts unwinding.
+//! * Call `exit_panic()` once when the panic ends, after all destructors ran.
+//!   This is usually done when the unwind was caught. A panic that aborts never needs to call it.
+//! * Every `enter_panic()` must be paired with exactly one `exit_panic()`.
+//!   Nested panics, for example a panic in a destructor during unwinding, increment the counter again.
+//!
+//! The counter is global, it is not tracked per thread or per core.
+//! While the counter is not zero, guards on every thread or in every interrupt handler see a panic in progress.
+//! The counter requires atomic pointer sized integers.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::panic_tracking::{enter_panic, exit_panic};
+//!
+//! //Called by the unwinding shim of a no_std target before the first destructor runs.
+//! fn unwind_started() {
+//!     enter_panic();
+//! }
+//!
+//! //Called by the unwinding shim of a no_std target after the unwind was caught.
+//! fn unwind_caught() {
+//!     exit_panic();
+//! }
+//! ```
+
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::AtomicUsize;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::Ordering::SeqCst;
+
+#[cfg(target_has_atomic = "ptr")]
+static PANIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+///
+/// Marks the start of a panic.
+///
+/// Must be paired with exactly one call to `exit_panic`.
+/// This has no effect on `is_panicking()` with the `std` feature.
+///
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub fn enter_panic() {
+    PANIC_COUNT.fetch_add(1, SeqCst);
+}
+
+///
+/// Marks the end of a panic that was started with `enter_panic`.
+///
+/// Calling this more often than `enter_panic` does not underflow the counter.
+///
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub fn exit_panic() {
+    _ = PANIC_COUNT.fetch_update(SeqCst, SeqCst, |count| count.checked_sub(1));
+}
+
+///
+/// Returns the number of panics that were entered with `enter_panic` but not exited yet.
+///
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub fn panic_count() -> usize {
+    PANIC_COUNT.load(SeqCst)
+}
+
+///
+/// Returns true if a panic is in progress.
+///
+/// Delegates to `std::thread::panicking()` with the `std` feature.
+///
+#[cfg(feature = "std")]
+#[inline(always)]
+pub fn is_panicking() -> bool {
+    std::thread::panicking()
+}
+
+///
+/// Returns true if a panic is in progress.
+///
+/// Returns true while `enter_panic` was called more often than `exit_panic`.
+///
+#[cfg(not(feature = "std"))]
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub fn is_panicking() -> bool {
+    panic_count() > 0
+}
@@ -0,0 +1,183 @@
+use std::boxed::Box;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::mpsc::{Sender, SyncSender};
+use std::sync::Arc;
+use std::thread::ThreadId;
+
+///
+/// Runs closures on the home thread of a `PinnedArcDeferGuard`.
+///
+/// Implemented for the senders of `std::sync::mpsc`. Implement it to hand the closure to an event loop,
+/// for example by sending it through a proxy of the event loop or an async channel.
+///
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot send closures to the home thread",
+    note = "use a `std::sync::mpsc::Sender<Box<dyn FnOnce() + Send>>` or implement `HomeExecutor`"
+)]
+pub trait HomeExecutor: Send + Sync {
+    ///
+    /// Hands the closure to the home thread, which is expected to call it.
+    /// This is called on the thread that dropped the last reference to the guard.
+    ///
+    fn execute(&self, func: Box<dyn FnOnce() + Send>);
+}
+
+///
+/// If the receiver was dropped the closure is dropped without being called.
+///
+impl HomeExecutor for Sender<Box<dyn FnOnce() + Send>> {
+    fn execute(&self, func: Box<dyn FnOnce() + Send>) {
+        _ = self.send(func);
+    }
+}
+
+///
+/// Blocks while the channel is full.
+/// If the receiver was dropped the closure is dropped without being called.
+///
+impl HomeExecutor for SyncSender<Box<dyn FnOnce() + Send>> {
+    fn execute(&self, func: Box<dyn FnOnce() + Send>) {
+        _ = self.send(func);
+    }
+}
+
+struct PinnedInner<F: FnOnce() + Send + 'static, E: HomeExecutor> {
+    canceled: AtomicBool,
+    func: Option<F>,
+    home: ThreadId,
+    executor: E,
+}
+
+// The closure is only accessed in Drop, which requires exclusive ownership.
+// The closure may be dropped on any thread holding a reference, so it must be Send.
+unsafe impl<F: FnOnce() + Send + 'static, E: HomeExecutor> Sync for PinnedInner<F, E> {}
+
+impl<F: FnOnce() + Send + 'static, E: HomeExecutor> Drop for PinnedInner<F, E> {
+    fn drop(&mut self) {
+        if self.canceled.load(SeqCst) {
+            return;
+        }
+
+        let Some(func) = self.func.take() else {
+            return;
+        };
+
+        if std::thread::current().id() == self.home {
+            func();
+        } else {
+            self.executor.execute(Box::new(func));
+        }
+    }
+}
+
+///
+/// Shared guard that calls the closure on its home thread when the last reference to it is dropped.
+///
+/// The home thread is the thread that created the guard.
+/// If the last reference is dropped on the home thread, the closure is called immediately.
+/// If it is dropped on any other thread, the closure is handed to the `HomeExecutor`,
+/// which sends it to the home thread. The home thread must call the closures it receives.
+/// Use this for cleanups that must run on a specific thread, like releasing resources of a GUI toolkit.
+///
+/// Any reference can cancel the closure for all references.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use defer_heavy::PinnedArcDeferGuard;
+///
+/// let (sender, receiver) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+/// let guard = PinnedArcDeferGuard::new_with_sender(|| println!("Destroy window"), sender);
+///
+/// let worker = guard.clone();
+/// drop(guard);
+/// thread::spawn(move || drop(worker.own())).join().unwrap();
+///
+/// //Event loop of the home thread.
+/// for task in receiver.try_iter() {
+///     task();
+/// }
+/// ```
+///
+pub struct PinnedArcDeferGuard<
+    F: FnOnce() + Send + 'static,
+    E: HomeExecutor = Sender<Box<dyn FnOnce() + Send>>,
+>(Arc<PinnedInner<F, E>>);
+
+impl<F: FnOnce() + Send + 'static, E: HomeExecutor> PinnedArcDeferGuard<F, E> {
+    ///
+    /// Creates a guard whose home thread is the current thread.
+    /// Closures that are not called on the home thread are handed to `sender`.
+    ///
+    #[must_use]
+    pub fn new_with_sender(func: F, sender: E) -> Self {
+        Self(Arc::new(PinnedInner {
+            canceled: AtomicBool::new(false),
+            func: Some(func),
+            home: std::thread::current().id(),
+            executor: sender,
+        }))
+    }
+
+    ///
+    /// Utility function to ensure ownership is transferred to a thread/closure.
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub fn own(self) -> Self {
+        self
+    }
+
+    ///
+    /// Returns the id of the thread that calls the closure.
+    ///
+    #[inline(always)]
+    pub fn home_thread(&self) -> ThreadId {
+        self.0.home
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once no thread has a reference to it anymore.
+    /// This may be any thread.
+    ///
+    #[inline(always)]
+    pub fn cancel(self) {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once no thread has a reference to it anymore.
+    /// This may be any thread.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.0.canceled.store(true, SeqCst)
+    }
+
+    ///
+    /// Returns true if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.canceled.load(SeqCst)
+    }
+}
+
+impl<F: FnOnce() + Send + 'static, E: HomeExecutor> Clone for PinnedArcDeferGuard<F, E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F: FnOnce() + Send + 'static, E: HomeExecutor> core::fmt::Debug for PinnedArcDeferGuard<F, E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PinnedArcDeferGuard")
+            .field("home", &self.0.home)
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
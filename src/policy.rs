@@ -0,0 +1,239 @@
+use crate::tracker::Tracker;
+use crate::DeferGuard;
+
+///
+/// Decides whether the closure of a `PolicyGuard` is executed when the guard is dropped.
+///
+/// Implement this trait to define custom execution conditions.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering::SeqCst;
+/// use defer_heavy::{DeferPolicy, PolicyGuard};
+///
+/// static FAILURES: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct OnFailures;
+///
+/// impl DeferPolicy for OnFailures {
+///     fn should_run(&self) -> bool {
+///         FAILURES.load(SeqCst) > 0
+///     }
+/// }
+///
+/// fn test() {
+///     let _guard = PolicyGuard::with_policy(|| println!("Rollback"), OnFailures);
+///     FAILURES.fetch_add(1, SeqCst);
+/// }
+/// ```
+///
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a `DeferPolicy`",
+    note = "use `Always`, `Never`, `OnUnwind`, `OnSuccess` or implement `DeferPolicy`"
+)]
+pub trait DeferPolicy {
+    ///
+    /// Called when the guard is dropped.
+    /// The closure is only executed if this returns true.
+    ///
+    fn should_run(&self) -> bool;
+
+    ///
+    /// Called right before the closure is executed.
+    /// This is only called if `should_run` returned true.
+    ///
+    #[inline(always)]
+    fn before_run(&self) {}
+}
+
+///
+/// Policy that always executes the closure. This is the behavior of `DeferGuard`.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Always;
+
+impl DeferPolicy for Always {
+    #[inline(always)]
+    fn should_run(&self) -> bool {
+        true
+    }
+}
+
+///
+/// Policy that never executes the closure when the guard is dropped.
+/// The closure can still be executed explicitly with `destroy`.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Never;
+
+impl DeferPolicy for Never {
+    #[inline(always)]
+    fn should_run(&self) -> bool {
+        false
+    }
+}
+
+///
+/// Policy that only executes the closure if the guard is dropped while a panic is in progress.
+///
+/// See the `panic_tracking` module for targets without `std`.
+///
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OnUnwind;
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+impl DeferPolicy for OnUnwind {
+    #[inline(always)]
+    fn should_run(&self) -> bool {
+        crate::panic_tracking::is_panicking()
+    }
+}
+
+///
+/// Policy that only executes the closure if the guard is dropped while no panic is in progress.
+///
+/// See the `panic_tracking` module for targets without `std`.
+///
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OnSuccess;
+
+#[cfg(any(feature = "std", target_has_atomic = "ptr"))]
+impl DeferPolicy for OnSuccess {
+    #[inline(always)]
+    fn should_run(&self) -> bool {
+        !crate::panic_tracking::is_panicking()
+    }
+}
+
+///
+/// Guard that executes its closure when dropped if its `DeferPolicy` allows it.
+///
+/// `DeferGuard<F>` behaves exactly like `PolicyGuard<F, Always>` and can be converted into it.
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct PolicyGuard<F: FnOnce(), P: DeferPolicy>(Option<F>, P);
+
+impl<F: FnOnce(), P: DeferPolicy + Default> PolicyGuard<F, P> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(Some(func), P::default())
+    }
+}
+
+impl<F: FnOnce(), P: DeferPolicy> PolicyGuard<F, P> {
+    #[inline(always)]
+    #[must_use]
+    pub fn with_policy(func: F, policy: P) -> Self {
+        Self(Some(func), policy)
+    }
+
+    ///
+    /// Returns the policy of the guard.
+    ///
+    #[inline(always)]
+    pub fn policy(&self) -> &P {
+        &self.1
+    }
+
+    ///
+    /// Will call the closure now regardless of the policy.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now regardless of the policy.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        self.0.take().map(|f| f()).is_some()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+}
+
+impl<F: FnOnce(), P: DeferPolicy> crate::Deferrable for PolicyGuard<F, P> {
+    #[inline(always)]
+    fn cancel(self) {
+        PolicyGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        PolicyGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<F: FnOnce(), P: DeferPolicy> Drop for PolicyGuard<F, P> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            if self.1.should_run() {
+                self.1.before_run();
+                f()
+            }
+        }
+    }
+}
+
+impl<F: FnOnce()> From<DeferGuard<F>> for PolicyGuard<F, Always> {
+    fn from(mut value: DeferGuard<F>) -> Self {
+        let func = value.0.take();
+        value.1.untrack();
+        Self(func, Always)
+    }
+}
+
+impl<F: FnOnce()> From<PolicyGuard<F, Always>> for DeferGuard<F> {
+    #[track_caller]
+    fn from(mut value: PolicyGuard<F, Always>) -> Self {
+        DeferGuard(value.0.take(), Tracker::new())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::DeferGuard;
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize};
+
+///
+/// A single slot of a `DeferPool`.
+///
+#[derive(Debug)]
+struct PoolSlot<F: FnOnce() + Send + 'static> {
+    used: AtomicBool,
+    canceled: AtomicBool,
+    refs: AtomicUsize,
+    func: UnsafeCell<Option<F>>,
+}
+
+// The closure is only accessed by the thread that claimed the slot or by the thread that drops the last handle.
+unsafe impl<F: FnOnce() + Send + 'static> Sync for PoolSlot<F> {}
+
+impl<F: FnOnce() + Send + 'static> PoolSlot<F> {
+    const fn new() -> Self {
+        Self {
+            used: AtomicBool::new(false),
+            canceled: AtomicBool::new(false),
+            refs: AtomicUsize::new(0),
+            func: UnsafeCell::new(None),
+        }
+    }
+
+    fn claim(&self, func: F) -> Result<(), F> {
+        if self
+            .used
+            .compare_exchange(false, true, Acquire, Relaxed)
+            .is_err()
+        {
+            return Err(func);
+        }
+
+        //SAFETY: we claimed the slot and no handle exists yet, nobody else can access the closure.
+        unsafe { *self.func.get() = Some(func) };
+        self.canceled.store(false, Relaxed);
+        self.refs.store(1, Release);
+        Ok(())
+    }
+
+    fn release(&self) {
+        if self.refs.fetch_sub(1, Release) != 1 {
+            return;
+        }
+
+        fence(Acquire);
+        //Return the slot to the pool even if the closure panics.
+        let _release = DeferGuard(
+            Some(|| self.used.store(false, Release)),
+            crate::tracker::Tracker::empty(),
+        );
+
+        //SAFETY: this was the last handle, nobody else can access the closure.
+        let func = unsafe { (*self.func.get()).take() };
+        if let Some(func) = func {
+            if !self.canceled.load(Relaxed) {
+                func();
+            }
+        }
+    }
+}
+
+///
+/// Static storage for `N` closures of `PoolDeferGuard`.
+///
+/// The closure type must be nameable to declare the static, this is usually a `fn()` pointer.
+/// Each slot is used by at most one closure at a time.
+/// The slot is returned to the pool once the last handle to its closure is dropped.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{DeferPool, PoolDeferGuard};
+///
+/// static POOL: DeferPool<fn(), 2> = DeferPool::new();
+///
+/// fn release_radio() {
+///     println!("Radio released");
+/// }
+///
+/// let guard = PoolDeferGuard::try_new(&POOL, release_radio as fn()).unwrap();
+/// let handle = guard.clone();
+/// assert_eq!(POOL.available(), 1);
+/// drop(guard);
+/// drop(handle); //Prints "Radio released"
+/// assert_eq!(POOL.available(), 2);
+/// ```
+///
+#[derive(Debug)]
+pub struct DeferPool<F: FnOnce() + Send + 'static, const N: usize> {
+    slots: [PoolSlot<F>; N],
+}
+
+impl<F: FnOnce() + Send + 'static, const N: usize> DeferPool<F, N> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { PoolSlot::new() }; N],
+        }
+    }
+
+    ///
+    /// Returns the number of slots that currently hold no closure.
+    ///
+    pub fn available(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| !slot.used.load(Relaxed))
+            .count()
+    }
+
+    ///
+    /// Returns the total number of slots of the pool.
+    ///
+    #[inline(always)]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<F: FnOnce() + Send + 'static, const N: usize> Default for DeferPool<F, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Error of `PoolDeferGuard::try_new` if every slot of the pool is in use.
+/// Contains the closure that could not be stored, it was not called.
+///
+pub struct PoolFull<F>(pub F);
+
+impl<F> PoolFull<F> {
+    ///
+    /// Returns the closure that could not be stored.
+    ///
+    #[inline(always)]
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+}
+
+impl<F> core::fmt::Debug for PoolFull<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("PoolFull")
+    }
+}
+
+impl<F> core::fmt::Display for PoolFull<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("every slot of the defer pool is in use")
+    }
+}
+
+///
+/// Shared handle to a closure stored in a `DeferPool`.
+///
+/// This works without alloc and only requires core atomics.
+/// Cloning a handle increments the reference count of the slot.
+/// The closure is called when the last handle is dropped, unless one of the handles canceled it.
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct PoolDeferGuard<F: FnOnce() + Send + 'static>(&'static PoolSlot<F>);
+
+impl<F: FnOnce() + Send + 'static> PoolDeferGuard<F> {
+    ///
+    /// Stores the closure in a free slot of the pool.
+    ///
+    /// # Returns
+    /// * Ok: the closure was stored.
+    /// * Err: every slot of the pool is in use, the closure is returned.
+    ///
+    pub fn try_new<const N: usize>(
+        pool: &'static DeferPool<F, N>,
+        func: F,
+    ) -> Result<Self, PoolFull<F>> {
+        let mut func = func;
+        for slot in &pool.slots {
+            match slot.claim(func) {
+                Ok(()) => return Ok(Self(slot)),
+                Err(returned) => func = returned,
+            }
+        }
+
+        Err(PoolFull(func))
+    }
+
+    ///
+    /// Will cancel running the closure.
+    /// The closure is dropped and the slot returned to the pool once the last handle is dropped.
+    ///
+    #[inline(always)]
+    pub fn cancel(self) {
+        self.cancel_ref();
+    }
+
+    ///
+    /// Will cancel running the closure for all handles.
+    /// The closure is dropped and the slot returned to the pool once the last handle is dropped.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.0.canceled.store(true, Relaxed);
+    }
+
+    ///
+    /// Returns true if the closure was canceled by one of the handles.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.canceled.load(Relaxed)
+    }
+
+    ///
+    /// Returns the number of handles that currently exist for the closure.
+    ///
+    #[inline(always)]
+    pub fn ref_count(&self) -> usize {
+        self.0.refs.load(Relaxed)
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Clone for PoolDeferGuard<F> {
+    fn clone(&self) -> Self {
+        if self.0.refs.fetch_add(1, Relaxed) > usize::MAX / 2 {
+            panic!("PoolDeferGuard reference count overflow");
+        }
+
+        Self(self.0)
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> core::fmt::Debug for PoolDeferGuard<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PoolDeferGuard")
+            .field("canceled", &self.is_canceled())
+            .field("refs", &self.ref_count())
+            .finish()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Drop for PoolDeferGuard<F> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
@@ -0,0 +1,148 @@
+use std::any::Any;
+use std::boxed::Box;
+use std::panic::{AssertUnwindSafe, UnwindSafe};
+
+///
+/// Guard that calls its closure again if it panics, up to `N` attempts in total.
+///
+/// The closure is called until one call returns without panicking.
+/// If all `N` attempts panic, the payload of the last panic is re-raised,
+/// unless another panic is active. In that case the panic is discarded.
+/// Use this only for idempotent cleanups.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::RetryDeferGuard;
+///
+/// fn test() {
+///     let _guard = RetryDeferGuard::<_, 3>::new(|| println!("Releasing lock file"));
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct RetryDeferGuard<F: FnMut() + UnwindSafe, const N: usize>(Option<F>, usize);
+
+impl<F: FnMut() + UnwindSafe, const N: usize> RetryDeferGuard<F, N> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        const { assert!(N > 0, "at least 1 attempt is required") };
+        Self(Some(func), 0)
+    }
+
+    ///
+    /// Returns the number of times the closure was called.
+    ///
+    #[inline(always)]
+    pub fn attempts(&self) -> usize {
+        self.1
+    }
+
+    ///
+    /// Will call the closure now until it succeeds or all attempts panicked.
+    /// The last panic is re-raised, unless another panic is active.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now until it succeeds or all attempts panicked.
+    /// This drops the closure.
+    /// The last panic is re-raised, unless another panic is active.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.0.take() else {
+            return false;
+        };
+
+        let Err(payload) = self.run(func) else {
+            return true;
+        };
+
+        if !std::thread::panicking() {
+            std::panic::resume_unwind(payload);
+        }
+
+        #[cfg(feature = "log")]
+        log::error!(
+            "panic of deferred closure discarded after {} attempts because another panic is active",
+            N
+        );
+        true
+    }
+
+    fn run(&mut self, mut func: F) -> Result<(), Box<dyn Any + Send>> {
+        loop {
+            self.1 += 1;
+            //&mut F is never UnwindSafe, F being UnwindSafe is what makes calling it again acceptable.
+            match std::panic::catch_unwind(AssertUnwindSafe(&mut func)) {
+                Ok(()) => return Ok(()),
+                Err(payload) if self.1 >= N => return Err(payload),
+                Err(_) => {}
+            }
+        }
+    }
+
+    ///
+    /// Will cancel running the closure, so no attempt is made anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so no attempt is made anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+}
+
+impl<F: FnMut() + UnwindSafe, const N: usize> crate::Deferrable for RetryDeferGuard<F, N> {
+    #[inline(always)]
+    fn cancel(self) {
+        RetryDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        RetryDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<F: FnMut() + UnwindSafe, const N: usize> Drop for RetryDeferGuard<F, N> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
@@ -0,0 +1,138 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+enum CtxEntry<'a, T> {
+    Value(Box<dyn FnOnce(&mut T) + 'a>),
+    Always(Box<dyn FnOnce() + 'a>),
+}
+
+///
+/// Registers the deferred closures of a `run_with_defers` body.
+///
+/// `defer` registers a closure that receives the return value of the body.
+/// `always` registers a closure that is also called if the body panics.
+/// All closures are called in reverse order of registration.
+///
+pub struct DeferCtx<'a, T>(Vec<CtxEntry<'a, T>>);
+
+impl<'a, T> DeferCtx<'a, T> {
+    ///
+    /// Registers a closure that can modify the return value after the body returned.
+    /// It is not called if the body panics, because there is no return value.
+    ///
+    #[inline(always)]
+    pub fn defer(&mut self, func: impl FnOnce(&mut T) + 'a) {
+        self.0.push(CtxEntry::Value(Box::new(func)));
+    }
+
+    ///
+    /// Registers a closure that is called after the body returned or panicked.
+    ///
+    #[inline(always)]
+    pub fn always(&mut self, func: impl FnOnce() + 'a) {
+        self.0.push(CtxEntry::Always(Box::new(func)));
+    }
+
+    ///
+    /// Returns the number of registered closures.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    ///
+    /// Returns true if no closures are registered.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn finish(&mut self, value: &mut T) {
+        //The entry is removed before it is called,
+        //so if it panics the remaining `always` closures are called when the context is dropped.
+        while let Some(entry) = self.0.pop() {
+            match entry {
+                CtxEntry::Value(func) => func(value),
+                CtxEntry::Always(func) => func(),
+            }
+        }
+    }
+}
+
+impl<T> Drop for DeferCtx<'_, T> {
+    fn drop(&mut self) {
+        //Only reached with entries if the body or a closure panicked.
+        while let Some(entry) = self.0.pop() {
+            if let CtxEntry::Always(func) = entry {
+                func();
+            }
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for DeferCtx<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferCtx")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+///
+/// Runs the body and afterward the closures it registered in the `DeferCtx`,
+/// which can modify the return value before it is returned.
+///
+/// This is the equivalent of a deferred function in Go that modifies a named return value.
+/// The closures are called in reverse order of registration.
+/// If the body panics, only the closures registered with `always` are called.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::run_with_defers;
+///
+/// let len = run_with_defers(|ctx| {
+///     let mut buffer = vec![1, 2, 3];
+///     ctx.defer(|len: &mut usize| *len *= 10);
+///     ctx.always(|| println!("Done"));
+///     buffer.push(4);
+///     buffer.len()
+/// });
+/// assert_eq!(len, 40);
+/// ```
+///
+pub fn run_with_defers<'a, T>(body: impl FnOnce(&mut DeferCtx<'a, T>) -> T) -> T {
+    let mut ctx = DeferCtx(Vec::new());
+    let mut value = body(&mut ctx);
+    ctx.finish(&mut value);
+    value
+}
+
+///
+/// `run_with_defers` for a body that returns a `Result`.
+///
+/// The closures registered with `defer` are called for `Ok` and `Err`,
+/// so they can for example add context to the error.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::run_with_defers_try;
+///
+/// let result = run_with_defers_try(|ctx| {
+///     ctx.defer(|result: &mut Result<u32, String>| {
+///         if let Err(error) = result {
+///             error.insert_str(0, "loading config: ");
+///         }
+///     });
+///     Err(String::from("file not found"))
+/// });
+/// assert_eq!(result.unwrap_err(), "loading config: file not found");
+/// ```
+///
+#[inline(always)]
+pub fn run_with_defers_try<'a, T, E>(
+    body: impl FnOnce(&mut DeferCtx<'a, Result<T, E>>) -> Result<T, E>,
+) -> Result<T, E> {
+    run_with_defers(body)
+}
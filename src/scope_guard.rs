@@ -0,0 +1,139 @@
+use crate::ErasedDeferGuard;
+use alloc::vec::Vec;
+
+///
+/// Guard that owns all closures registered with `defer!` or `defer_move!` in the body of a `defer_scope_guard!`.
+///
+/// The closures are called in reverse order of registration when `destroy` is called or when the guard is dropped.
+/// `cancel` cancels all of them at once.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::defer_scope_guard;
+///
+/// fn setup(fail: bool) {
+///     let scope = defer_scope_guard! {
+///         defer! { println!("Remove temp dir"); }
+///         defer! { println!("Close socket"); }
+///     };
+///
+///     if !fail {
+///         //Setup succeeded, keep the resources.
+///         scope.cancel();
+///     }
+/// }
+/// ```
+///
+#[derive(Default)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct ScopeDeferGuard<'a>(Vec<ErasedDeferGuard<'a>>);
+
+impl<'a> ScopeDeferGuard<'a> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    ///
+    /// Registers a closure. This is what `defer!` expands to in the body of a `defer_scope_guard!`.
+    ///
+    #[track_caller]
+    pub fn push(&mut self, func: impl FnOnce() + 'a) {
+        self.0.push(crate::guard(func).erase());
+    }
+
+    ///
+    /// Returns the number of registered closures that were not called or canceled yet.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    ///
+    /// Returns true if there are no registered closures.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    ///
+    /// Will call all closures now in reverse order of registration.
+    ///
+    /// # Returns
+    /// The number of closures that were called.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> usize {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call all closures now in reverse order of registration.
+    /// This drops the closures.
+    ///
+    /// # Returns
+    /// The number of closures that were called.
+    ///
+    pub fn destroy_ref(&mut self) -> usize {
+        let mut called = 0;
+        //The guard is removed before its closure is called,
+        //so if a closure panics the remaining closures are called when this guard is dropped.
+        while let Some(guard) = self.0.pop() {
+            if guard.destroy() {
+                called += 1;
+            }
+        }
+
+        called
+    }
+
+    ///
+    /// Will cancel all closures, so they cannot be called anymore.
+    ///
+    /// # Returns
+    /// The number of closures that were canceled.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> usize {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel all closures, so they cannot be called anymore.
+    /// This drops the closures.
+    ///
+    /// # Returns
+    /// The number of closures that were canceled.
+    ///
+    pub fn cancel_ref(&mut self) -> usize {
+        let mut canceled = 0;
+        while let Some(guard) = self.0.pop() {
+            if guard.cancel() {
+                canceled += 1;
+            }
+        }
+
+        canceled
+    }
+}
+
+impl Drop for ScopeDeferGuard<'_> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+impl core::fmt::Debug for ScopeDeferGuard<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ScopeDeferGuard")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
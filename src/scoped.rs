@@ -0,0 +1,219 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering::{Acquire, Release, SeqCst};
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize};
+
+///
+/// A deferred closure that can be shared with threads spawned by `std::thread::scope`
+/// without any heap allocation and without requiring the closure to be `'static`.
+///
+/// The guard itself lives on the stack frame that created it and acts as the owner of the closure.
+/// Threads receive `ScopedDeferRef` handles that borrow the owner.
+/// The closure is executed once the owner and all handles are gone.
+/// Since handles borrow the owner, the borrow checker guarantees that the owner is never dropped
+/// while a handle still exists.
+///
+/// Calling `release` gives up the reference held by the owner itself.
+/// This allows the closure to run on the thread that drops the last handle
+/// instead of when the owner goes out of scope.
+///
+/// # Examples
+/// ```rust
+/// use std::thread;
+/// use defer_heavy::ScopedDeferGuard;
+///
+/// let data = vec![1, 2, 3];
+/// let guard = ScopedDeferGuard::new(|| println!("Done with {:?}", data));
+/// thread::scope(|s| {
+///     for _ in 0..3 {
+///         let handle = guard.handle();
+///         s.spawn(move || {
+///             let _handle = handle;
+///             //SIMULATE work
+///         });
+///     }
+///     guard.release();
+/// });
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct ScopedDeferGuard<F: FnOnce() + Send> {
+    refs: AtomicUsize,
+    released: AtomicBool,
+    canceled: AtomicBool,
+    func: UnsafeCell<Option<F>>,
+}
+
+// The closure is only ever accessed by whoever decrements the reference count to zero,
+// which happens exactly once. The closure may run on any thread holding a handle, so it must be Send.
+unsafe impl<F: FnOnce() + Send> Sync for ScopedDeferGuard<F> {}
+
+impl<F: FnOnce() + Send> ScopedDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self {
+            refs: AtomicUsize::new(1),
+            released: AtomicBool::new(false),
+            canceled: AtomicBool::new(false),
+            func: UnsafeCell::new(Some(func)),
+        }
+    }
+
+    ///
+    /// Creates a new handle that keeps the closure from executing until it is dropped.
+    ///
+    /// # Panics
+    /// if the owner was released and the last handle was already dropped, see `try_handle`.
+    ///
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn handle(&self) -> ScopedDeferRef<'_, F> {
+        self.try_handle()
+            .expect("ScopedDeferGuard::handle called after the closure was released")
+    }
+
+    ///
+    /// Creates a new handle that keeps the closure from executing until it is dropped.
+    ///
+    /// # Returns
+    /// * Some: the new handle.
+    /// * None: the owner was released and the last handle was already dropped,
+    ///   so the closure was already executed or is being executed by another thread.
+    ///
+    #[must_use]
+    pub fn try_handle(&self) -> Option<ScopedDeferRef<'_, F>> {
+        let mut refs = self.refs.load(SeqCst);
+        loop {
+            //Incrementing from zero would let a second thread take the closure.
+            if refs == 0 {
+                return None;
+            }
+
+            match self
+                .refs
+                .compare_exchange_weak(refs, refs + 1, SeqCst, SeqCst)
+            {
+                Ok(_) => return Some(ScopedDeferRef(self)),
+                Err(current) => refs = current,
+            }
+        }
+    }
+
+    ///
+    /// Gives up the reference held by the owner.
+    /// If no handles exist the closure is executed immediately,
+    /// otherwise the closure is executed by whichever thread drops the last handle.
+    ///
+    /// # Returns
+    /// * true: the reference was released by this call.
+    /// * false: the reference was already released previously.
+    ///
+    pub fn release(&self) -> bool {
+        if self.released.swap(true, SeqCst) {
+            return false;
+        }
+
+        self.release_ref();
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once the owner and all handles are gone,
+    /// however it is guaranteed to not get called anymore.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.canceled.store(true, SeqCst)
+    }
+
+    ///
+    /// Returns true if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.canceled.load(SeqCst)
+    }
+
+    fn release_ref(&self) {
+        if self.refs.fetch_sub(1, Release) != 1 {
+            return;
+        }
+
+        fence(Acquire);
+        //SAFETY: the reference count reached zero, nobody else can access the closure anymore.
+        let func = unsafe { (*self.func.get()).take() };
+        if let Some(f) = func {
+            if !self.canceled.load(SeqCst) {
+                f()
+            }
+        }
+    }
+}
+
+impl<F: FnOnce() + Send> Drop for ScopedDeferGuard<F> {
+    fn drop(&mut self) {
+        if !*self.released.get_mut() {
+            *self.released.get_mut() = true;
+            self.release_ref();
+        }
+    }
+}
+
+///
+/// Handle to a `ScopedDeferGuard`.
+///
+/// The closure of the guard does not execute while any handle exists.
+/// Handles can be cloned and sent to other scoped threads.
+/// They are not `Copy` because dropping a handle must release its reference.
+///
+#[derive(Debug)]
+pub struct ScopedDeferRef<'g, F: FnOnce() + Send>(&'g ScopedDeferGuard<F>);
+
+impl<'g, F: FnOnce() + Send> ScopedDeferRef<'g, F> {
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped once the owner and all handles are gone,
+    /// however it is guaranteed to not get called anymore.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&self) {
+        self.0.cancel_ref()
+    }
+
+    ///
+    /// Returns true if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.is_canceled()
+    }
+
+    ///
+    /// Returns the guard this handle refers to.
+    ///
+    #[inline(always)]
+    pub fn guard(&self) -> &'g ScopedDeferGuard<F> {
+        self.0
+    }
+}
+
+impl<F: FnOnce() + Send> Clone for ScopedDeferRef<'_, F> {
+    fn clone(&self) -> Self {
+        //This handle holds a reference, so the count cannot be zero.
+        self.0.refs.fetch_add(1, SeqCst);
+        Self(self.0)
+    }
+}
+
+impl<F: FnOnce() + Send> Drop for ScopedDeferRef<'_, F> {
+    fn drop(&mut self) {
+        self.0.release_ref();
+    }
+}
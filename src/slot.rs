@@ -0,0 +1,209 @@
+use alloc::boxed::Box;
+
+///
+/// A slot that starts disarmed and can be armed with a closure later.
+///
+/// The armed closure is executed when the slot is dropped.
+/// The slot does not depend on the type of the closure,
+/// so it can be used as a struct field that is armed once a resource is acquired.
+/// Use `SendDeferSlot` if the slot must be `Send`.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferSlot;
+///
+/// fn test(create_dir: bool) {
+///     let mut cleanup = DeferSlot::new();
+///     if create_dir {
+///         cleanup.arm(|| println!("Remove dir"));
+///     }
+/// }
+/// ```
+///
+#[derive(Default)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferSlot<'a>(Option<Box<dyn FnOnce() + 'a>>);
+
+impl<'a> DeferSlot<'a> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(None)
+    }
+
+    ///
+    /// Arms the slot with the closure.
+    ///
+    /// # Returns
+    /// The closure the slot was previously armed with. It is not called.
+    ///
+    #[inline(always)]
+    pub fn arm(&mut self, func: impl FnOnce() + 'a) -> Option<Box<dyn FnOnce() + 'a>> {
+        self.0.replace(Box::new(func))
+    }
+
+    ///
+    /// Will call the armed closure now and disarm the slot.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: the slot was not armed.
+    ///
+    #[inline(always)]
+    pub fn destroy(&mut self) -> bool {
+        self.0.take().map(|f| f()).is_some()
+    }
+
+    ///
+    /// Will cancel the armed closure and disarm the slot.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called.
+    /// * false: the slot was not armed.
+    ///
+    #[inline(always)]
+    pub fn cancel(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+
+    ///
+    /// Returns true if the slot is armed.
+    ///
+    #[inline(always)]
+    pub fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl crate::Deferrable for DeferSlot<'_> {
+    #[inline(always)]
+    fn cancel(mut self) {
+        DeferSlot::cancel(&mut self);
+    }
+
+    #[inline(always)]
+    fn destroy(mut self) -> bool {
+        DeferSlot::destroy(&mut self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        DeferSlot::is_armed(self)
+    }
+}
+
+impl Drop for DeferSlot<'_> {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+impl core::fmt::Debug for DeferSlot<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferSlot")
+            .field("armed", &self.is_armed())
+            .finish()
+    }
+}
+
+///
+/// Same as `DeferSlot` but only accepts `Send` closures, so the slot is `Send`.
+///
+#[derive(Default)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct SendDeferSlot<'a>(Option<Box<dyn FnOnce() + Send + 'a>>);
+
+impl<'a> SendDeferSlot<'a> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(None)
+    }
+
+    ///
+    /// Arms the slot with the closure.
+    ///
+    /// # Returns
+    /// The closure the slot was previously armed with. It is not called.
+    ///
+    #[inline(always)]
+    pub fn arm(
+        &mut self,
+        func: impl FnOnce() + Send + 'a,
+    ) -> Option<Box<dyn FnOnce() + Send + 'a>> {
+        self.0.replace(Box::new(func))
+    }
+
+    ///
+    /// Will call the armed closure now and disarm the slot.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: the slot was not armed.
+    ///
+    #[inline(always)]
+    pub fn destroy(&mut self) -> bool {
+        self.0.take().map(|f| f()).is_some()
+    }
+
+    ///
+    /// Will cancel the armed closure and disarm the slot.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called.
+    /// * false: the slot was not armed.
+    ///
+    #[inline(always)]
+    pub fn cancel(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+
+    ///
+    /// Returns true if the slot is armed.
+    ///
+    #[inline(always)]
+    pub fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl crate::Deferrable for SendDeferSlot<'_> {
+    #[inline(always)]
+    fn cancel(mut self) {
+        SendDeferSlot::cancel(&mut self);
+    }
+
+    #[inline(always)]
+    fn destroy(mut self) -> bool {
+        SendDeferSlot::destroy(&mut self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        SendDeferSlot::is_armed(self)
+    }
+}
+
+impl Drop for SendDeferSlot<'_> {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+impl core::fmt::Debug for SendDeferSlot<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SendDeferSlot")
+            .field("armed", &self.is_armed())
+            .finish()
+    }
+}
@@ -0,0 +1,139 @@
+//! Reports deferred closures that take longer than a time budget.
+//!
+//! The budget of a guard is set with `DeferGuard::new_with_budget`.
+//! Guards without their own budget use the threshold set with `set_threshold`, which is disabled by default.
+//! If a closure takes longer than its budget, the hook is called with the elapsed time and the location where the guard was created.
+//! The default hook emits a `tracing` warning with the `tracing` feature and prints to stderr otherwise.
+//!
+//! Measuring a closure costs two calls to `Instant::now()`. Closures without a budget are not measured.
+//! Only `DeferGuard` and the macros that use it are measured.
+//!
+//! # Examples
+//! ```rust
+//! use std::time::Duration;
+//! use defer_heavy::{defer, slow_defer};
+//!
+//! slow_defer::set_threshold(Some(Duration::from_millis(100)));
+//!
+//! fn handle_request() {
+//!     defer! {
+//!         //Reported if this takes longer than 100ms.
+//!         println!("Closing connection");
+//!     }
+//! }
+//! ```
+
+use core::panic::Location;
+use core::sync::atomic::Ordering::SeqCst;
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+use std::time::{Duration, Instant};
+
+///
+/// Signature of a slow closure hook.
+/// Receives the time the closure took and the location where its guard was created.
+///
+pub type SlowDeferHook = fn(Duration, &'static Location<'static>);
+
+const DISABLED: usize = usize::MAX;
+
+//In microseconds, so that targets without 64 bit atomics can still hold about an hour.
+static THRESHOLD: AtomicUsize = AtomicUsize::new(DISABLED);
+
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+///
+/// Sets the budget of guards that were created without their own budget.
+/// `None` disables the measurement of these guards.
+///
+/// The threshold is rounded up to whole microseconds.
+/// Thresholds that do not fit into a `usize` of microseconds are clamped to the largest one that does,
+/// which is a little over an hour on 32 bit targets.
+///
+pub fn set_threshold(threshold: Option<Duration>) {
+    let micros = threshold.map_or(DISABLED, |t| {
+        usize::try_from(t.as_nanos().div_ceil(1000))
+            .map_or(DISABLED - 1, |micros| micros.min(DISABLED - 1))
+    });
+    THRESHOLD.store(micros, SeqCst);
+}
+
+///
+/// Returns the budget of guards that were created without their own budget.
+///
+pub fn threshold() -> Option<Duration> {
+    match THRESHOLD.load(SeqCst) {
+        DISABLED => None,
+        micros => Some(Duration::from_micros(micros as u64)),
+    }
+}
+
+///
+/// The hook that is used until `set_hook` is called.
+/// Emits a `tracing` warning with the `tracing` feature, prints to stderr otherwise.
+///
+pub fn default_hook(elapsed: Duration, location: &'static Location<'static>) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        ?elapsed,
+        file = location.file(),
+        line = location.line(),
+        "deferred closure was slow"
+    );
+
+    #[cfg(not(feature = "tracing"))]
+    std::eprintln!(
+        "deferred closure created at {} took {:?}",
+        location,
+        elapsed
+    );
+}
+
+///
+/// Replaces the hook.
+///
+/// # Returns
+/// The previous hook.
+///
+pub fn set_hook(hook: SlowDeferHook) -> SlowDeferHook {
+    let previous = HOOK.swap(hook as *mut (), SeqCst);
+    from_ptr(previous)
+}
+
+///
+/// Restores the default hook.
+///
+pub fn reset_hook() {
+    HOOK.store(core::ptr::null_mut(), SeqCst);
+}
+
+#[inline(always)]
+fn from_ptr(ptr: *mut ()) -> SlowDeferHook {
+    if ptr.is_null() {
+        return default_hook;
+    }
+
+    //SAFETY: only SlowDeferHook function pointers are stored in HOOK.
+    unsafe { core::mem::transmute::<*mut (), SlowDeferHook>(ptr) }
+}
+
+///
+/// Calls the closure and reports it if it takes longer than its budget.
+///
+#[inline(always)]
+pub(crate) fn call(
+    budget: Option<Duration>,
+    location: Option<&'static Location<'static>>,
+    func: impl FnOnce(),
+) {
+    let (Some(budget), Some(location)) = (budget.or_else(threshold), location) else {
+        func();
+        return;
+    };
+
+    let start = Instant::now();
+    func();
+    let elapsed = start.elapsed();
+    if elapsed > budget {
+        from_ptr(HOOK.load(SeqCst))(elapsed, location);
+    }
+}
@@ -0,0 +1,537 @@
+use crate::{DeferGuard, ErasedDeferGuard};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+enum StackFunc<'a, E> {
+    Plain(ErasedDeferGuard<'a>),
+    Fallible(Box<dyn FnOnce() -> Result<(), E> + 'a>),
+}
+
+impl<E> StackFunc<'_, E> {
+    #[inline(always)]
+    fn call(self) -> Result<(), E> {
+        match self {
+            StackFunc::Plain(guard) => {
+                guard.destroy();
+                Ok(())
+            }
+            StackFunc::Fallible(func) => func(),
+        }
+    }
+
+    #[inline(always)]
+    fn cancel(self) {
+        if let StackFunc::Plain(guard) = self {
+            drop(guard.into_inner());
+        }
+    }
+}
+
+struct StackEntry<'a, E> {
+    priority: i32,
+    seq: u64,
+    name: Option<Cow<'static, str>>,
+    func: StackFunc<'a, E>,
+}
+
+fn ignore_error<E>(_: &E) {}
+
+///
+/// Calls the rest of the stack if an entry of `run_all` panics.
+///
+#[cfg(not(feature = "std"))]
+struct Rest<'s, 'a, E>(&'s mut DeferStack<'a, E>);
+
+#[cfg(not(feature = "std"))]
+impl<E> Drop for Rest<'_, '_, E> {
+    fn drop(&mut self) {
+        self.0.run_all();
+    }
+}
+
+///
+/// Result of `DeferStack::run_all_catching`.
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct RunReport {
+    ///
+    /// Number of closures that were called, including the closures that panicked.
+    ///
+    pub called: usize,
+    ///
+    /// Number of closures that panicked.
+    ///
+    pub panicked: usize,
+    ///
+    /// Payload of the first panic. The payloads of later panics are dropped.
+    ///
+    pub first_panic: Option<Box<dyn core::any::Any + Send>>,
+}
+
+#[cfg(feature = "std")]
+impl RunReport {
+    ///
+    /// Returns true if no closure panicked.
+    ///
+    #[inline(always)]
+    pub fn is_ok(&self) -> bool {
+        self.panicked == 0
+    }
+
+    ///
+    /// Re-raises the first panic if a closure panicked.
+    ///
+    pub fn resume_unwind(self) {
+        if let Some(payload) = self.first_panic {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+fn report_error<E: core::fmt::Debug>(error: &E) {
+    crate::error_hook::report(error)
+}
+
+///
+/// Key of an entry in a `DeferStack` or `AsyncDeferStack`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeferKey(pub(crate) u64);
+
+///
+/// A stack of deferred closures with different types.
+///
+/// The closures are executed when `run_all` is called or when the stack is dropped.
+/// Entries with a higher priority are executed first.
+/// Entries with the same priority are executed in reverse order of registration,
+/// just like multiple `defer!` in the same scope.
+/// Entries pushed without a priority have the priority 0.
+///
+/// Entries can also be registered by name with `insert_named`.
+/// Inserting an entry with a name that is already in the stack replaces the old entry.
+/// The replacement is executed according to its own registration, not in the slot of the replaced entry.
+///
+/// Closures that return a `Result<(), E>` are pushed with `push_fallible`.
+/// `run_all_collect` and `run_all_fail_fast` return their errors.
+/// Errors of closures that are called by `run_all`, `destroy` or when the stack is dropped are passed to the `error_hook`.
+/// Stacks created with `new` have the error type `Infallible`, use `new_fallible` for other error types.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferStack;
+///
+/// fn test() {
+///     let mut stack = DeferStack::new();
+///     stack.push(|| println!("Third"));
+///     stack.push(|| println!("Second"));
+///     stack.push_with_priority(10, || println!("First"));
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closures are delayed until the stack is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferStack<'a, E = Infallible> {
+    entries: Vec<StackEntry<'a, E>>,
+    next_seq: u64,
+    report: fn(&E),
+}
+
+impl DeferStack<'_> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::new_fallible()
+    }
+}
+
+impl<E> Default for DeferStack<'_, E> {
+    fn default() -> Self {
+        Self::new_fallible()
+    }
+}
+
+impl<'a, E> DeferStack<'a, E> {
+    ///
+    /// Creates a stack whose fallible closures return errors of type `E`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferStack;
+    ///
+    /// fn teardown() {
+    ///     let mut stack = DeferStack::new_fallible();
+    ///     stack.push_fallible(|| Err("flush failed"));
+    ///     stack.push(|| println!("Close socket"));
+    ///     stack.push_fallible(|| Err("unmount failed"));
+    ///     assert_eq!(stack.run_all_collect(), Err(vec![(2, "unmount failed"), (0, "flush failed")]));
+    /// }
+    /// ```
+    ///
+    #[inline(always)]
+    #[must_use]
+    pub const fn new_fallible() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_seq: 0,
+            report: ignore_error::<E>,
+        }
+    }
+
+    ///
+    /// Pushes a closure with the priority 0.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel or execute the closure.
+    ///
+    #[inline(always)]
+    pub fn push(&mut self, func: impl FnOnce() + 'a) -> DeferKey {
+        self.push_with_priority(0, func)
+    }
+
+    ///
+    /// Pushes a closure with a priority. Closures with a higher priority are executed first.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel or execute the closure.
+    ///
+    #[inline(always)]
+    pub fn push_with_priority(&mut self, priority: i32, func: impl FnOnce() + 'a) -> DeferKey {
+        self.push_entry(
+            priority,
+            None,
+            StackFunc::Plain(DeferGuard::new(func).erase()),
+        )
+    }
+
+    ///
+    /// Pushes a closure that can fail with the priority 0.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel or execute the closure.
+    ///
+    #[inline(always)]
+    pub fn push_fallible(&mut self, func: impl FnOnce() -> Result<(), E> + 'a) -> DeferKey
+    where
+        E: core::fmt::Debug,
+    {
+        self.push_fallible_with_priority(0, func)
+    }
+
+    ///
+    /// Pushes a closure that can fail with a priority. Closures with a higher priority are executed first.
+    ///
+    /// # Returns
+    /// The key that can be used to cancel or execute the closure.
+    ///
+    pub fn push_fallible_with_priority(
+        &mut self,
+        priority: i32,
+        func: impl FnOnce() -> Result<(), E> + 'a,
+    ) -> DeferKey
+    where
+        E: core::fmt::Debug,
+    {
+        self.report = report_error::<E>;
+        self.push_entry(priority, None, StackFunc::Fallible(Box::new(func)))
+    }
+
+    fn push_entry(
+        &mut self,
+        priority: i32,
+        name: Option<Cow<'static, str>>,
+        func: StackFunc<'a, E>,
+    ) -> DeferKey {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.push(StackEntry {
+            priority,
+            seq,
+            name,
+            func,
+        });
+        DeferKey(seq)
+    }
+
+    ///
+    /// Pushes a closure with a name and the priority 0.
+    /// If the stack already contains a closure with the same name, that closure is removed from the stack.
+    /// The new closure is ordered as if it was pushed now.
+    ///
+    /// # Returns
+    /// The closure that was replaced. It is not called.
+    ///
+    pub fn insert_named(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        func: impl FnOnce() + 'a,
+    ) -> Option<Box<dyn FnOnce() + 'a>> {
+        let name = name.into();
+        let replaced =
+            self.position_named(&name)
+                .and_then(|index| match self.entries.remove(index).func {
+                    StackFunc::Plain(guard) => guard.into_inner(),
+                    //Named entries are always plain.
+                    StackFunc::Fallible(_) => None,
+                });
+        self.push_entry(
+            0,
+            Some(name),
+            StackFunc::Plain(DeferGuard::new(func).erase()),
+        );
+        replaced
+    }
+
+    fn position_named(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.name.as_deref() == Some(name))
+    }
+
+    ///
+    /// Will cancel running the closure with the name, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: there is no closure with this name in the stack.
+    ///
+    pub fn cancel_named(&mut self, name: &str) -> bool {
+        self.position_named(name)
+            .map(|index| self.entries.remove(index).func.cancel())
+            .is_some()
+    }
+
+    ///
+    /// Will call the closure with the name now and remove it from the stack.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: there is no closure with this name in the stack.
+    ///
+    pub fn destroy_named(&mut self, name: &str) -> bool {
+        self.position_named(name)
+            .map(|index| {
+                let entry = self.entries.remove(index);
+                self.call(entry)
+            })
+            .is_some()
+    }
+
+    ///
+    /// Returns true if the stack contains a closure with the name.
+    ///
+    pub fn contains(&self, name: &str) -> bool {
+        self.position_named(name).is_some()
+    }
+
+    fn take(&mut self, key: DeferKey) -> Option<StackEntry<'a, E>> {
+        let index = self.entries.iter().position(|e| e.seq == key.0)?;
+        Some(self.entries.remove(index))
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: there is no closure with this key in the stack.
+    ///
+    pub fn cancel(&mut self, key: DeferKey) -> bool {
+        self.take(key).map(|e| e.func.cancel()).is_some()
+    }
+
+    ///
+    /// Will call the closure now and remove it from the stack.
+    /// If the closure fails, the error is passed to the `error_hook`.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: there is no closure with this key in the stack.
+    ///
+    pub fn destroy(&mut self, key: DeferKey) -> bool {
+        self.take(key).map(|e| self.call(e)).is_some()
+    }
+
+    #[inline(always)]
+    fn call(&self, entry: StackEntry<'a, E>) {
+        if let Err(error) = entry.func.call() {
+            (self.report)(&error);
+        }
+    }
+
+    ///
+    /// Returns true if the closure with this key is still in the stack.
+    ///
+    pub fn contains_key(&self, key: DeferKey) -> bool {
+        self.entries.iter().any(|e| e.seq == key.0)
+    }
+
+    ///
+    /// Returns the amount of closures in the stack.
+    ///
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///
+    /// Returns true if there are no closures in the stack.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// Will cancel all closures.
+    ///
+    pub fn cancel_all(&mut self) {
+        for entry in self.entries.drain(..) {
+            entry.func.cancel();
+        }
+    }
+
+    ///
+    /// Will call all closures now. Closures with a higher priority are called first,
+    /// closures with the same priority are called in reverse order of registration.
+    /// Errors are passed to the `error_hook`.
+    /// The stack is empty afterward and can be reused.
+    ///
+    /// If a closure panics, the remaining closures are still called.
+    /// With the `std` feature the first panic is re-raised after all closures were called,
+    /// unless the thread is already panicking, then it is discarded.
+    /// Without the `std` feature the remaining closures are called while the panic unwinds,
+    /// so a second panic aborts the process.
+    ///
+    pub fn run_all(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            let report = self.run_all_catching();
+            if !std::thread::panicking() {
+                report.resume_unwind();
+            }
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            self.sort();
+            while let Some(entry) = self.entries.pop() {
+                let rest = Rest(self);
+                rest.0.call(entry);
+                core::mem::forget(rest);
+            }
+        }
+    }
+
+    ///
+    /// Will call all closures now in the same order as `run_all` and catches their panics.
+    /// Errors are passed to the `error_hook`.
+    /// The stack is empty afterward and can be reused.
+    ///
+    /// # Returns
+    /// How many closures were called and panicked, and the payload of the first panic.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferStack;
+    ///
+    /// let mut stack = DeferStack::new();
+    /// stack.push(|| println!("Release lock 1"));
+    /// stack.push(|| panic!("Assertion in cleanup failed"));
+    /// stack.push(|| println!("Release lock 2"));
+    ///
+    /// let report = stack.run_all_catching();
+    /// assert_eq!(report.called, 3);
+    /// assert_eq!(report.panicked, 1);
+    /// ```
+    ///
+    #[cfg(feature = "std")]
+    pub fn run_all_catching(&mut self) -> RunReport {
+        let mut report = RunReport::default();
+        self.sort();
+        while let Some(entry) = self.entries.pop() {
+            report.called += 1;
+            let result =
+                std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| self.call(entry)));
+            if let Err(payload) = result {
+                report.panicked += 1;
+                report.first_panic.get_or_insert(payload);
+            }
+        }
+
+        report
+    }
+
+    ///
+    /// Will call all closures now in the same order as `run_all`, regardless of failures.
+    /// The stack is empty afterward and can be reused.
+    ///
+    /// # Returns
+    /// * Ok: no closure failed.
+    /// * Err: the errors in the order they occurred,
+    ///   each with the registration index of its closure, which is the number of closures pushed before it.
+    ///
+    pub fn run_all_collect(&mut self) -> Result<(), Vec<(usize, E)>> {
+        let mut errors = Vec::new();
+        self.sort();
+        while let Some(entry) = self.entries.pop() {
+            let seq = entry.seq;
+            if let Err(error) = entry.func.call() {
+                errors.push((seq as usize, error));
+            }
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(errors)
+    }
+
+    ///
+    /// Will call the closures in the same order as `run_all` until one fails.
+    /// The closures that were not called stay in the stack.
+    /// Call `cancel_all` to drop them or `run_all` to call them anyway.
+    ///
+    /// # Returns
+    /// * Ok: no closure failed, the stack is empty.
+    /// * Err: the error of the first closure that failed, with its registration index.
+    ///
+    pub fn run_all_fail_fast(&mut self) -> Result<(), (usize, E)> {
+        self.sort();
+        while let Some(entry) = self.entries.pop() {
+            let seq = entry.seq;
+            entry.func.call().map_err(|error| (seq as usize, error))?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Sorts the entries so that the next closure to call is at the end.
+    /// Each entry is removed before it is called, so it is never called twice if it panics.
+    ///
+    #[inline(always)]
+    fn sort(&mut self) {
+        self.entries.sort_unstable_by_key(|e| (e.priority, e.seq));
+    }
+}
+
+impl<E> Drop for DeferStack<'_, E> {
+    fn drop(&mut self) {
+        self.run_all();
+    }
+}
+
+impl<E> core::fmt::Debug for DeferStack<'_, E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferStack")
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
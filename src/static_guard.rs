@@ -0,0 +1,192 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+const EMPTY: usize = 0;
+const ARMING: usize = 1;
+const ARMED: usize = 2;
+const RUNNING: usize = 3;
+const STATE_MASK: usize = 0b11;
+const GENERATION_STEP: usize = 0b100;
+
+///
+/// Static storage for a `StaticDeferGuard`.
+///
+/// The closure type must be nameable to declare the static, this is usually a `fn()` pointer.
+///
+/// The slot is a small state machine:
+/// * Empty: the slot holds no closure. `StaticDeferGuard::init` is only possible in this state.
+/// * Arming: `StaticDeferGuard::init` is currently storing the closure.
+/// * Armed: the slot holds a closure that can be canceled or fired.
+/// * Running: a handle is currently firing or canceling the closure.
+///
+/// Firing or canceling the closure returns the slot to Empty and increments the generation of the slot.
+/// Handles remember the generation they were created in,
+/// so handles of a previous arming cannot fire or cancel a later arming.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{DeferSlotCell, StaticDeferGuard};
+///
+/// static SLOT: DeferSlotCell<fn()> = DeferSlotCell::new();
+///
+/// fn release_radio() {
+///     println!("Radio released");
+/// }
+///
+/// let guard = StaticDeferGuard::init(&SLOT, release_radio as fn()).unwrap();
+/// let handle = guard; //Handles are Copy
+/// assert!(handle.fire());
+/// assert!(!guard.cancel());
+/// ```
+///
+#[derive(Debug)]
+pub struct DeferSlotCell<F: FnOnce() + Send + 'static> {
+    state: AtomicUsize,
+    func: UnsafeCell<Option<F>>,
+}
+
+// The closure is only accessed by the thread/interrupt that moved the slot into the Arming or Running state.
+unsafe impl<F: FnOnce() + Send + 'static> Sync for DeferSlotCell<F> {}
+
+impl<F: FnOnce() + Send + 'static> DeferSlotCell<F> {
+    #[inline(always)]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(EMPTY),
+            func: UnsafeCell::new(None),
+        }
+    }
+
+    ///
+    /// Returns true if the slot currently holds no closure and can be initialized.
+    ///
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.state.load(Acquire) & STATE_MASK == EMPTY
+    }
+
+    fn take(&self, generation: usize) -> Option<F> {
+        if self
+            .state
+            .compare_exchange(generation | ARMED, generation | RUNNING, Acquire, Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        //SAFETY: we moved the slot into the running state, nobody else can access the closure.
+        let func = unsafe { (*self.func.get()).take() };
+        self.state
+            .store(generation.wrapping_add(GENERATION_STEP) | EMPTY, Release);
+        func
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Default for DeferSlotCell<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Handle to a closure stored in a `DeferSlotCell`.
+///
+/// This works without alloc and only requires core atomics.
+/// Handles are `Copy`, any handle can `fire` or `cancel` the closure exactly once.
+/// Dropping a handle does nothing.
+///
+#[derive(Debug)]
+pub struct StaticDeferGuard<F: FnOnce() + Send + 'static> {
+    slot: &'static DeferSlotCell<F>,
+    generation: usize,
+}
+
+impl<F: FnOnce() + Send + 'static> StaticDeferGuard<F> {
+    ///
+    /// Arms the slot with the closure.
+    ///
+    /// # Returns
+    /// * Ok: the slot was armed.
+    /// * Err: the slot is not empty, the closure is returned.
+    ///
+    pub fn init(slot: &'static DeferSlotCell<F>, func: F) -> Result<Self, F> {
+        let current = slot.state.load(Relaxed);
+        if current & STATE_MASK != EMPTY {
+            return Err(func);
+        }
+
+        if slot
+            .state
+            .compare_exchange(current, current | ARMING, Acquire, Relaxed)
+            .is_err()
+        {
+            return Err(func);
+        }
+
+        //SAFETY: we moved the slot into the arming state, nobody else can access the closure.
+        unsafe { *slot.func.get() = Some(func) };
+        slot.state.store(current | ARMED, Release);
+        Ok(Self {
+            slot,
+            generation: current,
+        })
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because it was already fired or canceled.
+    ///
+    pub fn fire(self) -> bool {
+        self.slot.take(self.generation).map(|f| f()).is_some()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// The closure is dropped and the slot can be initialized again.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already fired or canceled.
+    ///
+    pub fn cancel(self) -> bool {
+        self.slot.take(self.generation).is_some()
+    }
+
+    ///
+    /// Returns true if the closure of this handle can still be fired or canceled.
+    ///
+    #[inline(always)]
+    pub fn is_armed(self) -> bool {
+        self.slot.state.load(Acquire) == self.generation | ARMED
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Clone for StaticDeferGuard<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Copy for StaticDeferGuard<F> {}
+
+impl<F: FnOnce() + Send + 'static> crate::Deferrable for StaticDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        StaticDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        self.fire()
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        StaticDeferGuard::is_armed(*self)
+    }
+}
@@ -0,0 +1,92 @@
+//! Global counters of created, executed and canceled guards.
+//!
+//! `DeferGuard`, `ArcDeferGuard` and the collections built on them, like `DeferStack`, are counted.
+//! An `ArcDeferGuard` is counted once, regardless of how often it was cloned.
+//! The counters only use pointer sized atomics from `core`, so this works without `std` and on 32-bit targets.
+//! On 32-bit targets the counters wrap after 2^32 guards.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::{defer_guard, stats};
+//!
+//! let before = stats::snapshot();
+//! let guard = defer_guard! { println!("Cleanup"); };
+//! assert!(stats::snapshot().currently_live > before.currently_live);
+//! drop(guard);
+//! assert!(stats::snapshot().executed > before.executed);
+//! ```
+
+use crate::tracker::Outcome;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+static EXECUTED: AtomicUsize = AtomicUsize::new(0);
+static CANCELED: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+///
+/// Counts of all guards at the time `snapshot()` was called.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeferStats {
+    /// Number of guards created.
+    pub created: u64,
+    /// Number of guards whose closure was called.
+    pub executed: u64,
+    /// Number of guards whose closure was canceled.
+    pub canceled: u64,
+    /// Number of guards that were neither executed nor canceled.
+    /// This includes forgotten guards.
+    pub currently_live: u64,
+}
+
+#[inline(always)]
+fn decrement(counter: &AtomicUsize) {
+    //Saturates because reset() may have been called while the guard was alive.
+    let _ = counter.fetch_update(Relaxed, Relaxed, |count| count.checked_sub(1));
+}
+
+pub(crate) fn record(outcome: Outcome) {
+    match outcome {
+        Outcome::Created => {
+            CREATED.fetch_add(1, Relaxed);
+            LIVE.fetch_add(1, Relaxed);
+        }
+        Outcome::Executed => {
+            EXECUTED.fetch_add(1, Relaxed);
+            decrement(&LIVE);
+        }
+        Outcome::Canceled => {
+            CANCELED.fetch_add(1, Relaxed);
+            decrement(&LIVE);
+        }
+        Outcome::Untracked => {
+            decrement(&CREATED);
+            decrement(&LIVE);
+        }
+    }
+}
+
+///
+/// Returns the current counts.
+/// The counters are read one after another, so guards of other threads may be counted in between.
+///
+pub fn snapshot() -> DeferStats {
+    DeferStats {
+        created: CREATED.load(Relaxed) as u64,
+        executed: EXECUTED.load(Relaxed) as u64,
+        canceled: CANCELED.load(Relaxed) as u64,
+        currently_live: LIVE.load(Relaxed) as u64,
+    }
+}
+
+///
+/// Sets the created, executed and canceled counters to 0.
+/// `currently_live` is not reset, because it describes guards that still exist.
+///
+pub fn reset() {
+    CREATED.store(0, Relaxed);
+    EXECUTED.store(0, Relaxed);
+    CANCELED.store(0, Relaxed);
+}
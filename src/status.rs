@@ -0,0 +1,126 @@
+///
+/// Describes how the scope of a `StatusDeferGuard` ended.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitKind {
+    /// The guard was dropped normally.
+    Normal,
+    /// The guard was dropped while a panic was in progress.
+    ///
+    /// See the `panic_tracking` module for targets without `std`.
+    Unwinding,
+    /// The closure was executed by calling `destroy` or `destroy_ref`.
+    Explicit,
+}
+
+///
+/// Guard that passes the reason for its execution to the closure.
+///
+/// This allows a single cleanup to behave differently on success and failure.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{ExitKind, StatusDeferGuard};
+///
+/// fn test() {
+///     let _guard = StatusDeferGuard::new(|kind| match kind {
+///         ExitKind::Unwinding => println!("Keeping temp dir for post-mortem"),
+///         _ => println!("Removing temp dir"),
+///     });
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct StatusDeferGuard<F: FnOnce(ExitKind)>(Option<F>);
+
+impl<F: FnOnce(ExitKind)> StatusDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(Some(func))
+    }
+
+    ///
+    /// Will call the closure now with `ExitKind::Explicit`.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now with `ExitKind::Explicit`.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_ref(&mut self) -> bool {
+        self.0.take().map(|f| f(ExitKind::Explicit)).is_some()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+}
+
+impl<F: FnOnce(ExitKind)> crate::Deferrable for StatusDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        StatusDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        StatusDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<F: FnOnce(ExitKind)> Drop for StatusDeferGuard<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            if crate::panic_tracking::is_panicking() {
+                f(ExitKind::Unwinding)
+            } else {
+                f(ExitKind::Normal)
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Helpers for tests of code that uses deferred closures.
+//!
+//! `CallTracker` records the order in which closures were called.
+//! `DropCounter` counts how often its clones were dropped, for example because a closure was dropped.
+//! `ExecProbe` creates closures that record if they were called or dropped.
+//!
+//! `CallTracker` only requires `alloc`.
+//! `SyncCallTracker` requires `std`, the other types require `alloc::sync::Arc`.
+//!
+//! # Examples
+//! ```rust
+//! use defer_heavy::defer_guard;
+//! use defer_heavy::testing::CallTracker;
+//!
+//! let tracker = CallTracker::new();
+//! {
+//!     let _first = defer_guard! { tracker.record("first"); };
+//!     let _second = tracker.guard("second");
+//! }
+//! tracker.assert_order(&["second", "first"]);
+//! ```
+
+use crate::DeferGuard;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+///
+/// A labeled event recorded by a `CallTracker` or `SyncCallTracker`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallEvent {
+    /// Position of the event, starting at 0 for the first recorded event.
+    pub sequence: usize,
+    /// Label passed to `record`.
+    pub label: &'static str,
+}
+
+///
+/// Records labeled events in the order they happen.
+/// Clones share the same records.
+///
+/// This is not thread safe, use `SyncCallTracker` to record events of multiple threads.
+///
+#[derive(Debug, Clone, Default)]
+pub struct CallTracker(Rc<RefCell<Vec<&'static str>>>);
+
+impl CallTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Records an event and returns its sequence number.
+    ///
+    pub fn record(&self, label: &'static str) -> usize {
+        let mut events = self.0.borrow_mut();
+        events.push(label);
+        events.len() - 1
+    }
+
+    ///
+    /// Returns a closure that records the label when called.
+    ///
+    pub fn recorder(&self, label: &'static str) -> impl FnOnce() + 'static {
+        let tracker = self.clone();
+        move || {
+            tracker.record(label);
+        }
+    }
+
+    ///
+    /// Returns a guard that records the label when it is executed.
+    ///
+    #[track_caller]
+    pub fn guard(&self, label: &'static str) -> DeferGuard<impl FnOnce() + 'static> {
+        DeferGuard::new(self.recorder(label))
+    }
+
+    ///
+    /// Returns all recorded events.
+    ///
+    pub fn events(&self) -> Vec<CallEvent> {
+        to_events(&self.0.borrow())
+    }
+
+    ///
+    /// Returns the labels of all recorded events in the order they were recorded.
+    ///
+    pub fn labels(&self) -> Vec<&'static str> {
+        self.0.borrow().clone()
+    }
+
+    ///
+    /// Removes all recorded events.
+    ///
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    ///
+    /// Panics unless exactly these labels were recorded in this order.
+    ///
+    #[track_caller]
+    pub fn assert_order(&self, expected: &[&'static str]) {
+        assert_eq!(
+            self.0.borrow().as_slice(),
+            expected,
+            "unexpected call order"
+        );
+    }
+}
+
+fn to_events(labels: &[&'static str]) -> Vec<CallEvent> {
+    labels
+        .iter()
+        .enumerate()
+        .map(|(sequence, label)| CallEvent { sequence, label })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+mod sync {
+    use super::{to_events, CallEvent};
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    ///
+    /// Thread safe version of `CallTracker`.
+    /// Clones share the same records and can be sent to other threads.
+    ///
+    #[derive(Debug, Clone, Default)]
+    pub struct SyncCallTracker(Arc<Mutex<Vec<&'static str>>>);
+
+    impl SyncCallTracker {
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, Vec<&'static str>> {
+            self.0.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        ///
+        /// Records an event and returns its sequence number.
+        ///
+        pub fn record(&self, label: &'static str) -> usize {
+            let mut events = self.lock();
+            events.push(label);
+            events.len() - 1
+        }
+
+        ///
+        /// Returns a closure that records the label when called.
+        ///
+        pub fn recorder(&self, label: &'static str) -> impl FnOnce() + Send + 'static {
+            let tracker = self.clone();
+            move || {
+                tracker.record(label);
+            }
+        }
+
+        ///
+        /// Returns all recorded events.
+        ///
+        pub fn events(&self) -> Vec<CallEvent> {
+            to_events(&self.lock())
+        }
+
+        ///
+        /// Returns the labels of all recorded events in the order they were recorded.
+        ///
+        pub fn labels(&self) -> Vec<&'static str> {
+            self.lock().clone()
+        }
+
+        ///
+        /// Removes all recorded events.
+        ///
+        pub fn clear(&self) {
+            self.lock().clear();
+        }
+
+        ///
+        /// Panics unless exactly these labels were recorded in this order.
+        ///
+        #[track_caller]
+        pub fn assert_order(&self, expected: &[&'static str]) {
+            let labels = self.labels();
+            assert_eq!(labels.as_slice(), expected, "unexpected call order");
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use sync::SyncCallTracker;
+
+#[cfg(target_has_atomic = "ptr")]
+mod counter {
+    use alloc::sync::Arc;
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering::SeqCst;
+
+    ///
+    /// Counts how often it or one of its clones was dropped.
+    ///
+    /// Move a clone into a closure to detect when the closure is dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_move_guard;
+    /// use defer_heavy::testing::DropCounter;
+    ///
+    /// let counter = DropCounter::new();
+    /// let token = counter.clone();
+    /// let guard = defer_move_guard! { let _token = &token; };
+    /// assert_eq!(counter.count(), 0);
+    /// guard.cancel();
+    /// assert_eq!(counter.count(), 1);
+    /// ```
+    ///
+    #[derive(Debug, Clone, Default)]
+    pub struct DropCounter(Arc<AtomicUsize>);
+
+    impl DropCounter {
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        ///
+        /// Returns how often this counter or one of its clones was dropped.
+        ///
+        pub fn count(&self) -> usize {
+            self.0.load(SeqCst)
+        }
+    }
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    ///
+    /// Creates closures that record if they were called or dropped.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::DeferGuard;
+    /// use defer_heavy::testing::ExecProbe;
+    ///
+    /// let probe = ExecProbe::new();
+    /// let guard = DeferGuard::new(probe.closure());
+    /// guard.cancel();
+    /// assert_eq!(probe.calls(), 0);
+    /// assert_eq!(probe.dropped(), 1);
+    /// ```
+    ///
+    #[derive(Debug, Clone, Default)]
+    pub struct ExecProbe {
+        calls: Arc<AtomicUsize>,
+        drops: Arc<AtomicUsize>,
+    }
+
+    ///
+    /// Increments the drop count of an `ExecProbe` when the closure is dropped.
+    ///
+    struct ProbeToken(Arc<AtomicUsize>);
+
+    impl Drop for ProbeToken {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    impl ExecProbe {
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        ///
+        /// Returns a closure that increments `calls` when called and `dropped` when dropped.
+        /// A closure that is called is also dropped.
+        ///
+        pub fn closure(&self) -> impl FnOnce() + Send + 'static {
+            let calls = self.calls.clone();
+            let token = ProbeToken(self.drops.clone());
+            move || {
+                let _token = token;
+                calls.fetch_add(1, SeqCst);
+            }
+        }
+
+        ///
+        /// Returns how often closures of this probe were called.
+        ///
+        pub fn calls(&self) -> usize {
+            self.calls.load(SeqCst)
+        }
+
+        ///
+        /// Returns how often closures of this probe were dropped, including the closures that were called.
+        ///
+        pub fn dropped(&self) -> usize {
+            self.drops.load(SeqCst)
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+pub use counter::{DropCounter, ExecProbe};
@@ -0,0 +1,84 @@
+use crate::DeferGuard;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{Scope, ScopedJoinHandle};
+
+///
+/// Guard that is bound to a `std::thread::scope` and runs its closure after the threads it spawned finished.
+///
+/// Created by the `scoped_defer!` macro.
+/// The closure only has to live as long as the scope, so it can borrow data from outside the scope.
+/// Threads spawned with `spawn` keep the closure from executing until they finished.
+/// The closure is executed once the guard was dropped and all of these threads finished.
+/// If the guard is dropped at the end of the scope closure, this happens while the scope joins its threads.
+/// Threads spawned directly on the scope are not waited for.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Mutex;
+/// use defer_heavy::scoped_defer;
+///
+/// let results = Mutex::new(Vec::new());
+/// std::thread::scope(|s| {
+///     let guard = scoped_defer!(s, { println!("Collected {:?}", results.lock().unwrap()); });
+///     for i in 0..3 {
+///         let results = &results;
+///         guard.spawn(move || results.lock().unwrap().push(i));
+///     }
+/// });
+/// ```
+///
+#[derive(Debug)]
+pub struct ThreadScopeDeferGuard<'scope, 'env, F: FnOnce() + Send + 'scope> {
+    scope: &'scope Scope<'scope, 'env>,
+    guard: Arc<Mutex<DeferGuard<F>>>,
+}
+
+impl<'scope, 'env, F: FnOnce() + Send + 'scope> ThreadScopeDeferGuard<'scope, 'env, F> {
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new(scope: &'scope Scope<'scope, 'env>, func: F) -> Self {
+        Self {
+            scope,
+            guard: Arc::new(Mutex::new(DeferGuard::new(func))),
+        }
+    }
+
+    ///
+    /// Spawns a thread on the scope. The closure is not executed before this thread finished.
+    ///
+    pub fn spawn<T: Send + 'scope>(
+        &self,
+        func: impl FnOnce() -> T + Send + 'scope,
+    ) -> ScopedJoinHandle<'scope, T> {
+        let guard = self.guard.clone();
+        self.scope.spawn(move || {
+            let _guard = guard;
+            func()
+        })
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    /// Threads spawned by this guard can still be running.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already canceled previously.
+    ///
+    pub fn cancel_ref(&self) -> bool {
+        self.lock().cancel_ref()
+    }
+
+    ///
+    /// Returns the scope this guard is bound to.
+    ///
+    #[inline(always)]
+    pub fn scope(&self) -> &'scope Scope<'scope, 'env> {
+        self.scope
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DeferGuard<F>> {
+        self.guard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,121 @@
+use core::time::Duration;
+use std::time::Instant;
+
+///
+/// Guard that measures the time since its creation and passes it to the closure.
+///
+/// Created by the `defer_time!` macro.
+/// With the `tracing` feature an event containing the elapsed time is emitted when the closure is called.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use defer_heavy::TimerGuard;
+///
+/// fn test() {
+///     let _timer = TimerGuard::new(|elapsed: Duration| println!("Took {:?}", elapsed));
+///     //Work...
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct TimerGuard<F: FnOnce(Duration)> {
+    func: Option<F>,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    location: &'static core::panic::Location<'static>,
+}
+
+impl<F: FnOnce(Duration)> TimerGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    #[track_caller]
+    pub fn new(func: F) -> Self {
+        Self {
+            func: Some(func),
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            location: core::panic::Location::caller(),
+        }
+    }
+
+    ///
+    /// Returns the time since the guard was created.
+    ///
+    #[inline(always)]
+    pub fn lap(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// The time since the guard was created.
+    /// It is also returned if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> Duration {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// The time since the guard was created.
+    /// It is also returned if the closure was canceled or called previously.
+    ///
+    pub fn destroy_ref(&mut self) -> Duration {
+        let elapsed = self.lap();
+        if let Some(f) = self.func.take() {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                name = "defer_time",
+                file = self.location.file(),
+                line = self.location.line(),
+                elapsed = ?elapsed,
+                "defer executing"
+            );
+            f(elapsed);
+        }
+        elapsed
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.func.take().is_some()
+    }
+}
+
+impl<F: FnOnce(Duration)> Drop for TimerGuard<F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
@@ -0,0 +1,338 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::any::Any;
+use core::cmp::{Ordering, Reverse};
+use core::time::Duration;
+use std::collections::BinaryHeap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+trait TimeoutTask: Send + Sync {
+    fn deadline_passed(&self);
+}
+
+///
+/// The closure and whether it is canceled instead of called when the deadline passes.
+///
+struct TimeoutShared<F: FnOnce() + Send>(Mutex<Option<F>>, bool);
+
+impl<F: FnOnce() + Send> TimeoutShared<F> {
+    fn take(&self) -> Option<F> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    fn fire(&self) -> bool {
+        //The lock is released before the closure is called.
+        let func = self.take();
+        func.map(|f| f()).is_some()
+    }
+}
+
+impl<F: FnOnce() + Send> TimeoutTask for TimeoutShared<F> {
+    fn deadline_passed(&self) {
+        if self.1 {
+            drop(self.take());
+        } else {
+            self.fire();
+        }
+    }
+}
+
+struct TimerEntry {
+    deadline: Instant,
+    id: u64,
+    task: Arc<dyn TimeoutTask>,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deadline, self.id).cmp(&(other.deadline, other.id))
+    }
+}
+
+#[derive(Default)]
+struct TimerState {
+    next_id: u64,
+    entries: BinaryHeap<Reverse<TimerEntry>>,
+}
+
+struct Timer {
+    state: Mutex<TimerState>,
+    cond: Condvar,
+}
+
+impl Timer {
+    ///
+    /// Returns the shared timer, the timer thread is spawned on first use.
+    ///
+    fn get() -> &'static Timer {
+        static TIMER: OnceLock<Timer> = OnceLock::new();
+        let mut spawn = false;
+        let timer = TIMER.get_or_init(|| {
+            spawn = true;
+            Timer {
+                state: Mutex::new(TimerState::default()),
+                cond: Condvar::new(),
+            }
+        });
+
+        if spawn {
+            thread::Builder::new()
+                .name("defer-heavy-timeout".into())
+                .spawn(move || timer.run())
+                .expect("failed to spawn defer-heavy timeout thread");
+        }
+
+        timer
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TimerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn register(&self, deadline: Instant, task: Arc<dyn TimeoutTask>) -> u64 {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state
+            .entries
+            .push(Reverse(TimerEntry { deadline, id, task }));
+        drop(state);
+        self.cond.notify_one();
+        id
+    }
+
+    fn deregister(&self, id: u64) {
+        self.lock().entries.retain(|e| e.0.id != id);
+    }
+
+    fn run(&self) {
+        let mut state = self.lock();
+        loop {
+            let Some(next) = state.entries.peek().map(|e| e.0.deadline) else {
+                state = self.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            };
+
+            let now = Instant::now();
+            if next > now {
+                state = self
+                    .cond
+                    .wait_timeout(state, next - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            }
+
+            if let Some(Reverse(entry)) = state.entries.pop() {
+                drop(state);
+                //A panicking closure must not end the timer thread, the thread is never spawned again.
+                let result =
+                    std::panic::catch_unwind(AssertUnwindSafe(|| entry.task.deadline_passed()));
+                if let Err(payload) = result {
+                    report_panic(payload);
+                }
+                state = self.lock();
+            }
+        }
+    }
+}
+
+///
+/// Passes the message of a panic of a closure called by the timer thread to the `error_hook`.
+///
+fn report_panic(payload: Box<dyn Any + Send>) {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    crate::error_hook::report(&("timeout closure panicked", message));
+}
+
+///
+/// Guard that executes its closure when it is dropped or when its deadline passes, whichever comes first.
+/// Guards created with `new_expiring` cancel the closure when the deadline passes instead.
+///
+/// The deadlines of all guards are tracked by a single timer thread that is spawned on first use.
+/// If the deadline passes first, the closure is executed on the timer thread.
+/// The closure is guaranteed to be executed at most once, even if the deadline passes
+/// concurrently with the guard being dropped.
+/// If the closure panics on the timer thread, the panic is passed to the `error_hook`
+/// and the timer keeps serving the other guards.
+/// A timeout that is too large to be represented as an `Instant`, like `Duration::MAX`, means that there is no deadline.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use defer_heavy::TimeoutDeferGuard;
+///
+/// fn test() {
+///     let _guard = TimeoutDeferGuard::new(Duration::from_secs(30), || println!("Unlocked"));
+///     //Blocking work...
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct TimeoutDeferGuard<F: FnOnce() + Send + 'static> {
+    shared: Arc<TimeoutShared<F>>,
+    //None if the deadline is too far in the future to be represented.
+    id: Option<u64>,
+}
+
+impl<F: FnOnce() + Send + 'static> TimeoutDeferGuard<F> {
+    #[must_use]
+    pub fn new(timeout: Duration, func: F) -> Self {
+        Self::register(timeout, func, false)
+    }
+
+    ///
+    /// Creates a guard that cancels its closure when the deadline passes.
+    /// The closure is only called if the guard is dropped or destroyed before the deadline,
+    /// for example "roll back unless committed within 5 seconds" is expressed
+    /// by canceling the guard on commit.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use defer_heavy::TimeoutDeferGuard;
+    ///
+    /// fn test() {
+    ///     let _guard = TimeoutDeferGuard::new_expiring(Duration::from_secs(5), || println!("Rollback"));
+    ///     //Prints "Rollback" if this scope ends within 5 seconds
+    /// }
+    /// ```
+    ///
+    #[must_use]
+    pub fn new_expiring(timeout: Duration, func: F) -> Self {
+        Self::register(timeout, func, true)
+    }
+
+    fn register(timeout: Duration, func: F, expire: bool) -> Self {
+        let shared = Arc::new(TimeoutShared(Mutex::new(Some(func)), expire));
+        let task: Arc<dyn TimeoutTask> = shared.clone();
+        let id = Instant::now()
+            .checked_add(timeout)
+            .map(|deadline| Timer::get().register(deadline, task));
+        Self { shared, id }
+    }
+
+    ///
+    /// Will call the closure now.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because the deadline already passed or it was canceled.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now.
+    /// This drops the closure and removes it from the timer.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because the deadline already passed or it was canceled.
+    ///
+    pub fn destroy_ref(&mut self) -> bool {
+        self.deregister();
+        self.shared.fire()
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was not dropped because the deadline already passed or it was canceled.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure and removes it from the timer.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was not dropped because the deadline already passed or it was canceled.
+    ///
+    pub fn cancel_ref(&mut self) -> bool {
+        self.deregister();
+        self.shared.take().is_some()
+    }
+
+    fn deregister(&mut self) {
+        if let Some(id) = self.id.take() {
+            Timer::get().deregister(id);
+        }
+    }
+
+    ///
+    /// Returns true if the closure has neither been called nor canceled yet.
+    ///
+    pub fn is_pending(&self) -> bool {
+        self.shared
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> crate::Deferrable for TimeoutDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        TimeoutDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        TimeoutDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        self.is_pending()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Drop for TimeoutDeferGuard<F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> core::fmt::Debug for TimeoutDeferGuard<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TimeoutDeferGuard")
+            .field("id", &self.id)
+            .field("pending", &self.is_pending())
+            .finish()
+    }
+}
@@ -0,0 +1,278 @@
+#[cfg(all(feature = "alloc", target_has_atomic = "8", target_has_atomic = "ptr"))]
+mod enabled {
+    extern crate alloc;
+    use crate::DeferGuard;
+    use alloc::sync::Arc;
+    use core::sync::atomic::AtomicBool;
+    use core::sync::atomic::Ordering::SeqCst;
+
+    ///
+    /// Shared state of a guard that a `CancelToken` can cancel without keeping the closure alive.
+    ///
+    #[cfg(all(feature = "mt", not(feature = "triomphe")))]
+    pub(crate) trait SharedCancel: Send + Sync {
+        fn cancel(&self);
+
+        fn is_canceled(&self) -> bool;
+    }
+
+    #[derive(Clone)]
+    enum TokenInner {
+        Flag(Arc<AtomicBool>),
+        #[cfg(all(feature = "mt", not(feature = "triomphe")))]
+        Shared(alloc::sync::Weak<dyn SharedCancel>),
+    }
+
+    ///
+    /// Handle that cancels a guard without owning it.
+    ///
+    /// Created by `CancelableDeferGuard::cancel_token` or `ArcDeferGuard::cancel_token`.
+    /// Tokens are cheap to clone and do not keep the closure alive.
+    /// Canceling a guard whose closure was already called does nothing.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::defer_guard;
+    ///
+    /// fn test() {
+    ///     let mut guard = defer_guard! { unreachable!(); }.with_token();
+    ///     let token = guard.cancel_token();
+    ///     token.cancel();
+    ///     assert!(token.is_canceled());
+    /// }
+    /// ```
+    ///
+    #[derive(Clone)]
+    pub struct CancelToken(TokenInner);
+
+    impl CancelToken {
+        #[cfg(all(feature = "mt", not(feature = "triomphe")))]
+        pub(crate) fn shared(target: alloc::sync::Weak<dyn SharedCancel>) -> Self {
+            Self(TokenInner::Shared(target))
+        }
+
+        ///
+        /// Cancels the guard, so its closure is not called anymore.
+        /// Does nothing if the closure was already called.
+        ///
+        pub fn cancel(&self) {
+            match &self.0 {
+                TokenInner::Flag(flag) => flag.store(true, SeqCst),
+                #[cfg(all(feature = "mt", not(feature = "triomphe")))]
+                TokenInner::Shared(target) => {
+                    if let Some(target) = target.upgrade() {
+                        target.cancel();
+                    }
+                }
+            }
+        }
+
+        ///
+        /// Returns true if the guard was canceled.
+        /// A token of an `ArcDeferGuard` returns false once the last reference to the guard was dropped.
+        ///
+        pub fn is_canceled(&self) -> bool {
+            match &self.0 {
+                TokenInner::Flag(flag) => flag.load(SeqCst),
+                #[cfg(all(feature = "mt", not(feature = "triomphe")))]
+                TokenInner::Shared(target) => target.upgrade().is_some_and(|t| t.is_canceled()),
+            }
+        }
+    }
+
+    impl core::fmt::Debug for CancelToken {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("CancelToken")
+                .field("canceled", &self.is_canceled())
+                .finish()
+        }
+    }
+
+    ///
+    /// Guard whose closure can also be canceled by `CancelToken`s.
+    ///
+    /// Created by `DeferGuard::with_token`.
+    /// The shared cancel flag is only allocated once the first token is requested.
+    /// A plain `DeferGuard` does not carry this state, so it stays as small as its closure.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use defer_heavy::{defer_guard, CancelToken};
+    ///
+    /// fn arm() -> (impl Drop, CancelToken) {
+    ///     let mut guard = defer_guard! { println!("Cleanup"); }.with_token();
+    ///     let token = guard.cancel_token();
+    ///     (guard, token)
+    /// }
+    ///
+    /// let (guard, token) = arm();
+    /// //A component that does not own the guard decides that the cleanup is not needed.
+    /// token.cancel();
+    /// drop(guard);
+    /// ```
+    ///
+    #[derive(Debug)]
+    #[cfg_attr(
+        all(feature = "lint-attrs", defer_heavy_nightly),
+        must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+    )]
+    #[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+    pub struct CancelableDeferGuard<F: FnOnce()>(DeferGuard<F>, Option<Arc<AtomicBool>>);
+
+    impl<F: FnOnce()> CancelableDeferGuard<F> {
+        #[inline(always)]
+        pub(crate) fn new(guard: DeferGuard<F>) -> Self {
+            Self(guard, None)
+        }
+
+        ///
+        /// Returns a token that can cancel this guard without owning it.
+        /// All tokens of a guard share the same state.
+        ///
+        pub fn cancel_token(&mut self) -> CancelToken {
+            let flag = self.1.get_or_insert_with(Default::default);
+            CancelToken(TokenInner::Flag(flag.clone()))
+        }
+
+        ///
+        /// Returns true if a token canceled the guard.
+        ///
+        #[inline(always)]
+        pub fn is_canceled(&self) -> bool {
+            self.1.as_ref().is_some_and(|flag| flag.load(SeqCst))
+        }
+
+        ///
+        /// Drops the closure if a token canceled the guard.
+        ///
+        #[inline(always)]
+        fn apply_token(&mut self) {
+            if self.is_canceled() && self.0 .0.take().is_some() {
+                self.0 .1.canceled();
+            }
+        }
+
+        ///
+        /// Will call the closure now.
+        ///
+        /// # Returns
+        /// * true: closure was called.
+        /// * false: closure was not called because it was canceled or called previously.
+        ///
+        #[inline(always)]
+        pub fn destroy(mut self) -> bool {
+            self.destroy_ref()
+        }
+
+        ///
+        /// Will call the closure now.
+        /// This drops the closure.
+        ///
+        /// # Returns
+        /// * true: closure was called.
+        /// * false: closure was not called because it was canceled or called previously.
+        ///
+        #[inline(always)]
+        pub fn destroy_ref(&mut self) -> bool {
+            self.apply_token();
+            self.0.destroy_ref()
+        }
+
+        ///
+        /// Will cancel running the closure, the tokens observe the guard as canceled.
+        ///
+        /// # Returns
+        /// * true: closure was dropped and will not be called anymore.
+        /// * false: closure was already canceled or called previously.
+        ///
+        #[inline(always)]
+        #[track_caller]
+        pub fn cancel(mut self) -> bool {
+            self.cancel_ref()
+        }
+
+        ///
+        /// Will cancel running the closure, the tokens observe the guard as canceled.
+        /// This drops the closure.
+        ///
+        /// # Returns
+        /// * true: closure was dropped and will not be called anymore.
+        /// * false: closure was already canceled or called previously.
+        ///
+        #[inline(always)]
+        #[track_caller]
+        pub fn cancel_ref(&mut self) -> bool {
+            self.apply_token();
+            let live = self.0.cancel_ref();
+            if let Some(flag) = &self.1 {
+                flag.store(true, SeqCst);
+            }
+            live
+        }
+
+        ///
+        /// Returns a reference to the closure.
+        ///
+        /// # Returns
+        /// * Some: the closure was not called or canceled yet.
+        /// * None: the closure was called or canceled, possibly by a token.
+        ///
+        #[inline(always)]
+        pub fn peek(&self) -> Option<&F> {
+            self.0.peek().filter(|_| !self.is_canceled())
+        }
+
+        ///
+        /// Upgrade the guard to a reference counted one.
+        /// Tokens of this guard keep working after the upgrade.
+        ///
+        #[cfg(feature = "mt")]
+        pub fn upgrade(mut self) -> crate::ArcDeferGuard<F>
+        where
+            F: FnOnce() + Send,
+        {
+            self.apply_token();
+            let mut guard = crate::ArcDeferGuard::new_opt(self.0 .0.take(), self.0 .1.take());
+            guard.set_external_flag(self.1.take());
+            guard
+        }
+    }
+
+    impl<F: FnOnce()> From<DeferGuard<F>> for CancelableDeferGuard<F> {
+        #[inline(always)]
+        fn from(value: DeferGuard<F>) -> Self {
+            Self::new(value)
+        }
+    }
+
+    impl<F: FnOnce()> Drop for CancelableDeferGuard<F> {
+        #[inline(always)]
+        fn drop(&mut self) {
+            self.apply_token();
+        }
+    }
+
+    impl<F: FnOnce()> crate::Deferrable for CancelableDeferGuard<F> {
+        #[inline(always)]
+        #[track_caller]
+        fn cancel(self) {
+            CancelableDeferGuard::cancel(self);
+        }
+
+        #[inline(always)]
+        fn destroy(self) -> bool {
+            CancelableDeferGuard::destroy(self)
+        }
+
+        #[inline(always)]
+        fn is_armed(&self) -> bool {
+            self.peek().is_some()
+        }
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "8", target_has_atomic = "ptr"))]
+#[cfg(all(feature = "mt", not(feature = "triomphe")))]
+pub(crate) use enabled::SharedCancel;
+#[cfg(all(feature = "alloc", target_has_atomic = "8", target_has_atomic = "ptr"))]
+pub use enabled::{CancelToken, CancelableDeferGuard};
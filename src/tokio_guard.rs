@@ -0,0 +1,142 @@
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+///
+/// Guard for blocking closures in async code.
+///
+/// Dropping the guard inside a tokio runtime passes the closure to `tokio::task::spawn_blocking`
+/// instead of calling it, so a closure that does blocking I/O does not block the executor thread.
+/// The spawned task is detached.
+/// If tokio drops the task without executing it, because the runtime is shutting down,
+/// the closure is called on the thread that drops the task instead, so the cleanup is never lost.
+/// Outside a tokio runtime the closure is called directly.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::TokioDeferGuard;
+///
+/// async fn write_report(file: std::fs::File) {
+///     let _guard = TokioDeferGuard::new(move || {
+///         //Blocking, runs on the blocking thread pool.
+///         let _ = file.sync_all();
+///     });
+///     //...
+/// }
+/// ```
+///
+#[derive(Debug)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct TokioDeferGuard<F: FnOnce() + Send + 'static>(Option<F>);
+
+impl<F: FnOnce() + Send + 'static> TokioDeferGuard<F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(Some(func))
+    }
+
+    ///
+    /// Will call the closure now and wait for it to finish.
+    ///
+    /// On a multi thread runtime the closure is called in `tokio::task::block_in_place`,
+    /// so other tasks of the worker thread are moved to another worker while it runs.
+    /// On a current thread runtime and outside a runtime the closure is called directly.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` was called previously.
+    ///
+    pub fn destroy(mut self) -> bool {
+        let Some(func) = self.0.take() else {
+            return false;
+        };
+
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(func),
+            _ => func(),
+        }
+
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.0.take().is_some()
+    }
+
+    ///
+    /// Returns true if the closure was canceled.
+    ///
+    #[inline(always)]
+    pub fn is_canceled(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> crate::Deferrable for TokioDeferGuard<F> {
+    #[inline(always)]
+    fn cancel(self) {
+        TokioDeferGuard::cancel(self);
+    }
+
+    #[inline(always)]
+    fn destroy(self) -> bool {
+        TokioDeferGuard::destroy(self)
+    }
+
+    #[inline(always)]
+    fn is_armed(&self) -> bool {
+        !self.is_canceled()
+    }
+}
+
+impl<F: FnOnce() + Send + 'static> Drop for TokioDeferGuard<F> {
+    fn drop(&mut self) {
+        let Some(func) = self.0.take() else {
+            return;
+        };
+
+        match Handle::try_current() {
+            //The join handle is dropped, which detaches the task.
+            Ok(handle) => {
+                let pending = CallOnDrop(Some(func));
+                drop(handle.spawn_blocking(move || drop(pending)));
+            }
+            Err(_) => func(),
+        }
+    }
+}
+
+///
+/// Calls the closure when dropped.
+/// The blocking task drops it to call the closure. If tokio drops the task without executing it,
+/// the closure is called by the drop of the task instead.
+///
+struct CallOnDrop<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for CallOnDrop<F> {
+    fn drop(&mut self) {
+        if let Some(func) = self.0.take() {
+            func();
+        }
+    }
+}
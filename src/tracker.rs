@@ -0,0 +1,133 @@
+///
+/// What happened to a guard.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub(crate) enum Outcome {
+    Created,
+    Executed,
+    Canceled,
+    Untracked,
+}
+
+///
+/// Records the creation and outcome of a guard for `leakcheck` and `stats`
+/// and holds the time budget for `slow_defer`.
+/// Without the `leak-detect`, `stats` and `slow-defer-warning` features this is a zero sized type
+/// and all methods do nothing.
+///
+#[derive(Debug)]
+pub(crate) struct Tracker {
+    #[cfg(any(feature = "leak-detect", feature = "slow-defer-warning"))]
+    location: Option<&'static core::panic::Location<'static>>,
+    #[cfg(feature = "stats")]
+    live: bool,
+    #[cfg(feature = "slow-defer-warning")]
+    budget: Option<std::time::Duration>,
+}
+
+impl Tracker {
+    #[inline(always)]
+    #[track_caller]
+    pub(crate) fn new() -> Self {
+        let tracker = Self {
+            #[cfg(any(feature = "leak-detect", feature = "slow-defer-warning"))]
+            location: Some(core::panic::Location::caller()),
+            #[cfg(feature = "stats")]
+            live: true,
+            #[cfg(feature = "slow-defer-warning")]
+            budget: None,
+        };
+        tracker.record(Outcome::Created);
+        tracker
+    }
+
+    ///
+    /// Returns a tracker that does not record anything.
+    ///
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub(crate) const fn empty() -> Self {
+        Self {
+            #[cfg(any(feature = "leak-detect", feature = "slow-defer-warning"))]
+            location: None,
+            #[cfg(feature = "stats")]
+            live: false,
+            #[cfg(feature = "slow-defer-warning")]
+            budget: None,
+        }
+    }
+
+    ///
+    /// Sets the time budget of the closure.
+    ///
+    #[cfg(feature = "slow-defer-warning")]
+    #[inline(always)]
+    pub(crate) fn set_budget(&mut self, budget: std::time::Duration) {
+        self.budget = Some(budget);
+    }
+
+    ///
+    /// Returns the time budget and the location of the guard for `slow_defer::call`.
+    /// Call this before `executed`, which clears them.
+    ///
+    #[cfg(feature = "slow-defer-warning")]
+    #[inline(always)]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn timing(
+        &self,
+    ) -> (
+        Option<std::time::Duration>,
+        Option<&'static core::panic::Location<'static>>,
+    ) {
+        (self.budget, self.location)
+    }
+
+    ///
+    /// Moves the record to the returned tracker.
+    ///
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub(crate) fn take(&mut self) -> Self {
+        core::mem::replace(self, Self::empty())
+    }
+
+    ///
+    /// Records that the closure was called.
+    ///
+    #[inline(always)]
+    pub(crate) fn executed(&mut self) {
+        self.take().record(Outcome::Executed)
+    }
+
+    ///
+    /// Records that the closure was canceled.
+    ///
+    #[inline(always)]
+    pub(crate) fn canceled(&mut self) {
+        self.take().record(Outcome::Canceled)
+    }
+
+    ///
+    /// Removes the guard from the records, because its closure was moved to a type that is not tracked.
+    ///
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub(crate) fn untrack(&mut self) {
+        self.take().record(Outcome::Untracked)
+    }
+
+    #[inline(always)]
+    #[allow(unused_variables)]
+    fn record(&self, outcome: Outcome) {
+        #[cfg(feature = "leak-detect")]
+        if let Some(location) = self.location {
+            crate::leakcheck::record(location, outcome);
+        }
+
+        #[cfg(feature = "stats")]
+        if self.live {
+            crate::stats::record(outcome);
+        }
+    }
+}
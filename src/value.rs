@@ -0,0 +1,166 @@
+use crate::{Always, DeferPolicy};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+///
+/// Guard that owns a value and passes it to the closure when dropped.
+///
+/// The value can be used through `Deref` and `DerefMut`.
+/// `into_inner` returns the value without calling the closure.
+/// The closure is only called if the `DeferPolicy` allows it, otherwise the value is just dropped.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::Defer;
+///
+/// fn test() {
+///     let mut buffer = Defer::new(Vec::new(), |b: Vec<u8>| println!("Discarded {} bytes", b.len()));
+///     buffer.push(1);
+///     assert_eq!(buffer.len(), 1);
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct Defer<T, F: FnOnce(T), P: DeferPolicy = Always> {
+    value: ManuallyDrop<T>,
+    func: ManuallyDrop<F>,
+    policy: P,
+}
+
+///
+/// Guard that passes the value to the closure only if it is dropped while the thread is panicking.
+/// Otherwise the value is dropped normally.
+///
+#[cfg(feature = "std")]
+pub type OnUnwindValueGuard<T, F> = Defer<T, F, crate::OnUnwind>;
+
+impl<T, F: FnOnce(T)> Defer<T, F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(value: T, func: F) -> Self {
+        Self::with_policy(value, func, Always)
+    }
+}
+
+impl<T, F: FnOnce(T), P: DeferPolicy> Defer<T, F, P> {
+    #[inline(always)]
+    #[must_use]
+    pub fn with_policy(value: T, func: F, policy: P) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            func: ManuallyDrop::new(func),
+            policy,
+        }
+    }
+
+    ///
+    /// Returns the value without calling the closure.
+    /// The closure is dropped.
+    ///
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        //SAFETY: this is never dropped, so each field is taken or dropped exactly once.
+        unsafe {
+            let value = ManuallyDrop::take(&mut this.value);
+            ManuallyDrop::drop(&mut this.func);
+            core::ptr::drop_in_place(&mut this.policy);
+            value
+        }
+    }
+
+    ///
+    /// Returns the policy of the guard.
+    ///
+    #[inline(always)]
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+}
+
+impl<T, F: FnOnce(T), P: DeferPolicy> Deref for Defer<T, F, P> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, F: FnOnce(T), P: DeferPolicy> DerefMut for Defer<T, F, P> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T, F: FnOnce(T), P: DeferPolicy> Drop for Defer<T, F, P> {
+    fn drop(&mut self) {
+        //SAFETY: the fields are not used after drop.
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        let func = unsafe { ManuallyDrop::take(&mut self.func) };
+        if self.policy.should_run() {
+            self.policy.before_run();
+            func(value)
+        }
+    }
+}
+
+impl<T: core::fmt::Debug, F: FnOnce(T), P: DeferPolicy + core::fmt::Debug> core::fmt::Debug
+    for Defer<T, F, P>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Defer")
+            .field("value", &*self.value)
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+///
+/// Extension trait to create a `Defer` guard from any value in method position.
+///
+/// The trait is implemented for every sized type and cannot be implemented for other types.
+/// It is not sealed, because the blanket implementation already covers every possible type.
+///
+/// # Examples
+/// ```rust
+/// use std::fs::{remove_file, File};
+/// use defer_heavy::DeferExt;
+///
+/// fn create(path: &str) -> std::io::Result<File> {
+///     let file = File::create(path)?.defer(|f| {
+///         drop(f);
+///         let _ = remove_file(path);
+///     });
+///     file.sync_all()?;
+///     //Keep the file on success.
+///     Ok(file.into_inner())
+/// }
+/// ```
+///
+pub trait DeferExt: Sized {
+    ///
+    /// Moves the value into a guard that passes it to the closure when dropped.
+    ///
+    #[inline(always)]
+    #[must_use]
+    fn defer<F: FnOnce(Self)>(self, func: F) -> Defer<Self, F> {
+        Defer::new(self, func)
+    }
+
+    ///
+    /// Moves the value into a guard that passes it to the closure when dropped while the thread is panicking.
+    ///
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    #[must_use]
+    fn defer_on_unwind<F: FnOnce(Self)>(self, func: F) -> OnUnwindValueGuard<Self, F> {
+        Defer::with_policy(self, func, crate::OnUnwind)
+    }
+}
+
+impl<T> DeferExt for T {}
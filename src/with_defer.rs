@@ -0,0 +1,89 @@
+use crate::DeferGuard;
+use core::ops::{Deref, DerefMut};
+
+///
+/// Pairs a value with a guard, so the closure is called when the value is dropped instead of when a scope ends.
+///
+/// The value is dropped first, then the closure is called.
+/// Moving the pair moves the value and the guard together, so moving the value does not call the closure.
+/// The value can be used through `Deref` and `DerefMut`.
+/// This is the type `defer_on_drop_of!` converts the variable to.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::{guard, WithDefer};
+///
+/// fn connect() -> WithDefer<Vec<u8>, impl FnOnce()> {
+///     WithDefer::new(Vec::new(), guard(|| println!("Connection closed")))
+/// }
+///
+/// fn test() {
+///     let mut connection = connect();
+///     connection.push(1);
+///     //Prints "Connection closed" when connection is dropped.
+/// }
+/// ```
+///
+pub struct WithDefer<T, F: FnOnce()> {
+    //The value is declared first, so it is dropped before the guard.
+    value: T,
+    guard: DeferGuard<F>,
+}
+
+impl<T, F: FnOnce()> WithDefer<T, F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(value: T, guard: DeferGuard<F>) -> Self {
+        Self { value, guard }
+    }
+
+    ///
+    /// Returns the guard, for example to cancel it.
+    ///
+    #[inline(always)]
+    pub fn guard_mut(&mut self) -> &mut DeferGuard<F> {
+        &mut self.guard
+    }
+
+    ///
+    /// Separates the value from the guard.
+    /// The closure is called when the returned guard is dropped.
+    ///
+    #[inline(always)]
+    pub fn into_parts(self) -> (T, DeferGuard<F>) {
+        (self.value, self.guard)
+    }
+
+    ///
+    /// Returns the value and cancels the guard.
+    ///
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        self.guard.cancel();
+        self.value
+    }
+}
+
+impl<T, F: FnOnce()> Deref for WithDefer<T, F> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, F: FnOnce()> DerefMut for WithDefer<T, F> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: core::fmt::Debug, F: FnOnce()> core::fmt::Debug for WithDefer<T, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WithDefer")
+            .field("value", &self.value)
+            .finish_non_exhaustive()
+    }
+}
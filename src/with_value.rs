@@ -0,0 +1,160 @@
+use core::cell::Cell;
+
+///
+/// Guard that passes the last value set during the scope to the closure when dropped.
+///
+/// The closure receives `Some` with the last value passed to `set` or `set_ref`,
+/// or `None` if no value was set.
+/// `set_ref` only needs a shared reference, so the guard can stay at the top of the scope
+/// while other code holds a reference to it.
+///
+/// # Examples
+/// ```rust
+/// use defer_heavy::DeferWith;
+///
+/// fn copy(data: &[u8]) {
+///     let mut report = DeferWith::new(|copied: Option<usize>| match copied {
+///         Some(n) => println!("Copied {} bytes", n),
+///         None => println!("Copy failed"),
+///     });
+///     //Copy...
+///     report.set(data.len());
+/// }
+/// ```
+///
+#[cfg_attr(
+    all(feature = "lint-attrs", defer_heavy_nightly),
+    must_not_suspend = "the closure is delayed until the guard is dropped after the suspension"
+)]
+#[cfg_attr(feature = "lint-attrs", clippy::has_significant_drop)]
+pub struct DeferWith<T, F: FnOnce(Option<T>)>(Cell<Option<T>>, Option<F>);
+
+impl<T, F: FnOnce(Option<T>)> DeferWith<T, F> {
+    #[inline(always)]
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self(Cell::new(None), Some(func))
+    }
+
+    ///
+    /// Sets the value that is passed to the closure.
+    ///
+    /// # Returns
+    /// The previously set value.
+    ///
+    #[inline(always)]
+    pub fn set(&mut self, value: T) -> Option<T> {
+        self.0.get_mut().replace(value)
+    }
+
+    ///
+    /// Sets the value that is passed to the closure through a shared reference.
+    ///
+    /// # Returns
+    /// The previously set value.
+    ///
+    #[inline(always)]
+    pub fn set_ref(&self, value: T) -> Option<T> {
+        self.0.replace(Some(value))
+    }
+
+    ///
+    /// Removes the value, so the closure receives `None` unless a value is set again.
+    ///
+    /// # Returns
+    /// The previously set value.
+    ///
+    #[inline(always)]
+    pub fn take_value(&mut self) -> Option<T> {
+        self.0.get_mut().take()
+    }
+
+    ///
+    /// Will call the closure now with the current value.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy(mut self) -> bool {
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now with the given value, the current value is dropped.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn destroy_with(mut self, value: T) -> bool {
+        self.set(value);
+        self.destroy_ref()
+    }
+
+    ///
+    /// Will call the closure now with the current value.
+    /// This drops the closure.
+    ///
+    /// # Returns
+    /// * true: closure was called.
+    /// * false: closure was not called because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    pub fn destroy_ref(&mut self) -> bool {
+        let Some(func) = self.1.take() else {
+            return false;
+        };
+
+        func(self.take_value());
+        true
+    }
+
+    ///
+    /// Will cancel running the closure, so it cannot be called anymore.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel(mut self) -> bool {
+        self.cancel_ref()
+    }
+
+    ///
+    /// Will cancel the closure, so it cannot be called anymore.
+    /// This drops the closure, the value is kept until the guard is dropped.
+    ///
+    /// # Returns
+    /// * true: closure was dropped and will not be called anymore.
+    /// * false: closure was already dropped previously because `cancel_ref` or `destroy_ref` was called previously.
+    ///
+    #[inline(always)]
+    pub fn cancel_ref(&mut self) -> bool {
+        self.1.take().is_some()
+    }
+
+    ///
+    /// Returns true if the closure was neither called nor canceled.
+    ///
+    #[inline(always)]
+    pub fn is_pending(&self) -> bool {
+        self.1.is_some()
+    }
+}
+
+impl<T, F: FnOnce(Option<T>)> Drop for DeferWith<T, F> {
+    fn drop(&mut self) {
+        self.destroy_ref();
+    }
+}
+
+impl<T, F: FnOnce(Option<T>)> core::fmt::Debug for DeferWith<T, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferWith")
+            .field("pending", &self.is_pending())
+            .finish_non_exhaustive()
+    }
+}
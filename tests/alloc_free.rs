@@ -0,0 +1,109 @@
+//leak-detect allocates to record the guards.
+#![cfg(not(feature = "leak-detect"))]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer, defer_conditioned, defer_guard, defer_move, DeferGuard};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+pub fn test_scope_macros_do_not_allocate() {
+    let count = Cell::new(0);
+    let allocated = allocations(|| {
+        defer! { count.set(count.get() + 1); }
+        let data = [1u8; 64];
+        let count = &count;
+        defer_move! { count.set(count.get() + data.len()); }
+    });
+    assert_eq!(allocated, 0);
+    assert_eq!(count.get(), 65);
+}
+
+#[test]
+pub fn test_guards_do_not_allocate() {
+    let count = Cell::new(0);
+    let allocated = allocations(|| {
+        let guard = defer_guard! { count.set(count.get() + 1); };
+        assert_eq!(guard.cancel(), true);
+        let guard = defer_guard! { count.set(count.get() + 1); };
+        assert_eq!(guard.destroy(), true);
+        let _guard = defer_conditioned!(false, {
+            count.set(count.get() + 1);
+        });
+        let _guard = DeferGuard::new(|| count.set(count.get() + 1));
+    });
+    assert_eq!(allocated, 0);
+    assert_eq!(count.get(), 2);
+}
+
+#[test]
+pub fn test_multi_guard_does_not_allocate() {
+    use defer_heavy::MultiDeferGuard;
+
+    static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    fn increment() {
+        COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    let allocated = allocations(|| {
+        let mut guard = MultiDeferGuard::<4, fn()>::new();
+        for _ in 0..4 {
+            assert_eq!(guard.push(increment).is_ok(), true);
+        }
+        assert_eq!(guard.push(increment).is_err(), true);
+    });
+    assert_eq!(allocated, 0);
+    assert_eq!(COUNT.load(std::sync::atomic::Ordering::SeqCst), 4);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_arc_guard_allocates_once() {
+    use defer_heavy::ArcDeferGuard;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+
+    let count = AtomicUsize::new(0);
+    let mut guard = None;
+    let allocated = allocations(|| {
+        guard = Some(ArcDeferGuard::new(|| {
+            count.fetch_add(1, SeqCst);
+        }));
+    });
+    assert_eq!(allocated, 1);
+
+    let guard = guard.unwrap();
+    let allocated = allocations(|| {
+        let clones: [_; 8] = core::array::from_fn(|_| guard.clone());
+        drop(clones);
+        drop(guard);
+    });
+    assert_eq!(allocated, 0);
+    assert_eq!(count.load(SeqCst), 1);
+}
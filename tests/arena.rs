@@ -0,0 +1,90 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::DeferArena;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+pub fn test_arena_order_and_reuse() {
+    let order = RefCell::new(Vec::new());
+    let mut arena = DeferArena::with_capacity(4, 16);
+    assert_eq!(arena.capacity(), 4);
+    assert_eq!(arena.max_closure_bytes(), 16);
+
+    for i in 0..3 {
+        let order = &order;
+        arena.push(move || order.borrow_mut().push(i * 10 + 1));
+        arena.push(move || order.borrow_mut().push(i * 10 + 2));
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.slots_used(), 2);
+        assert_eq!(arena.boxed(), 0);
+        arena.run_all();
+        assert_eq!(arena.is_empty(), true);
+    }
+
+    assert_eq!(order.borrow().as_slice(), &[2, 1, 12, 11, 22, 21]);
+}
+
+#[test]
+pub fn test_arena_fallback() {
+    let order = RefCell::new(Vec::new());
+    {
+        let mut arena = DeferArena::with_capacity(1, 8);
+        let large = [7u64; 4];
+        let large = move || large.iter().sum::<u64>();
+        let order = &order;
+        assert_eq!(
+            arena
+                .try_push(move || order.borrow_mut().push(large()))
+                .is_err(),
+            true
+        );
+        arena.push(move || order.borrow_mut().push(large()));
+        assert_eq!(arena.boxed(), 1);
+
+        //The second slot does not exist, so this closure is boxed too.
+        assert_eq!(arena.try_push(|| order.borrow_mut().push(1)).is_ok(), false);
+        arena.push(|| order.borrow_mut().push(1));
+        assert_eq!(arena.boxed(), 2);
+        assert_eq!(arena.slots_used(), 0);
+    }
+
+    assert_eq!(order.borrow().as_slice(), &[1, 28]);
+}
+
+#[test]
+pub fn test_arena_reset_drops_captures() {
+    let capture = Rc::new(());
+    let mut arena = DeferArena::with_capacity(2, 16);
+    let inline = capture.clone();
+    arena.push(move || unreachable!("Wont be executed {:?}", inline));
+    let boxed = (capture.clone(), [0u8; 64]);
+    arena.push(move || unreachable!("Wont be executed {:?}", boxed));
+    assert_eq!(Rc::strong_count(&capture), 3);
+    assert_eq!(arena.slots_used(), 1);
+    assert_eq!(arena.boxed(), 1);
+
+    arena.reset();
+    assert_eq!(Rc::strong_count(&capture), 1);
+    assert_eq!(arena.is_empty(), true);
+    assert_eq!(arena.boxed(), 0);
+}
+
+#[test]
+pub fn test_arena_panic() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let o = order.clone();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+        let mut arena = DeferArena::with_capacity(4, 16);
+        let first = o.clone();
+        arena.push(move || first.borrow_mut().push(1));
+        arena.push(|| panic!("cleanup failed"));
+        let third = o.clone();
+        arena.push(move || third.borrow_mut().push(3));
+        arena.run_all();
+    }));
+
+    assert_eq!(result.is_err(), true);
+    assert_eq!(order.borrow().as_slice(), &[3, 1]);
+}
@@ -0,0 +1,79 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_assert, defer_debug_assert};
+use std::cell::{Cell, RefCell};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+fn panic_message(err: Box<dyn std::any::Any + Send>) -> String {
+    err.downcast_ref::<String>()
+        .cloned()
+        .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap()
+}
+
+#[test]
+pub fn test_defer_assert_pass() {
+    let queue = RefCell::new(vec![1, 2, 3]);
+    let evaluated = Cell::new(false);
+    {
+        defer_assert!({
+            evaluated.set(true);
+            queue.borrow().is_empty()
+        });
+        assert_eq!(evaluated.get(), false);
+        queue.borrow_mut().clear();
+    }
+    assert_eq!(evaluated.get(), true);
+}
+
+#[test]
+pub fn test_defer_assert_fail() {
+    let err = catch_unwind(|| {
+        defer_assert!(1 + 1 == 3);
+    })
+    .unwrap_err();
+    let msg = panic_message(err);
+    assert_eq!(msg.contains("assertion failed: 1 + 1 == 3"), true);
+    assert_eq!(msg.contains(file!()), true);
+
+    let err = catch_unwind(|| {
+        let remaining = 2;
+        defer_assert!(remaining == 0, "{} entries remaining", remaining);
+    })
+    .unwrap_err();
+    assert_eq!(panic_message(err).contains("2 entries remaining"), true);
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_defer_assert_fail_during_unwind() {
+    let evaluated = Cell::new(false);
+    let err = catch_unwind(AssertUnwindSafe(|| {
+        defer_assert!({
+            evaluated.set(true);
+            false
+        });
+        panic!("original");
+    }))
+    .unwrap_err();
+    assert_eq!(evaluated.get(), true);
+    assert_eq!(panic_message(err), "original");
+}
+
+#[test]
+pub fn test_defer_debug_assert() {
+    let evaluated = Cell::new(false);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        defer_debug_assert!(
+            {
+                evaluated.set(true);
+                false
+            },
+            "debug only"
+        );
+    }));
+    assert_eq!(result.is_err(), cfg!(debug_assertions));
+    assert_eq!(evaluated.get(), cfg!(debug_assertions));
+
+    defer_debug_assert!(true);
+}
@@ -0,0 +1,158 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(all(feature = "async", feature = "alloc"))]
+
+use defer_heavy::{AsyncDeferStack, AsyncDropPolicy, BoxCleanup};
+use std::cell::RefCell;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+///
+/// Returns Pending on the first poll and Ready on the second.
+///
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+///
+/// Polls the future until it completes.
+///
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn push<'a>(stack: &mut AsyncDeferStack<'a>, log: &'a RefCell<Vec<String>>, id: u32) {
+    stack.push(move || async move {
+        log.borrow_mut().push(format!("start {id}"));
+        YieldOnce(false).await;
+        log.borrow_mut().push(format!("end {id}"));
+    });
+}
+
+#[test]
+pub fn test_async_stack_run_all() {
+    let log = RefCell::new(Vec::new());
+    let mut stack = AsyncDeferStack::new();
+    push(&mut stack, &log, 1);
+    let key = stack.push(|| async { panic!("canceled cleanup was run") });
+    push(&mut stack, &log, 2);
+    stack.push_boxed(Box::pin(async { log.borrow_mut().push("boxed".into()) }));
+    assert_eq!(stack.len(), 4);
+
+    assert_eq!(stack.cancel(key), true);
+    assert_eq!(stack.cancel(key), false);
+    assert_eq!(stack.contains_key(key), false);
+
+    //Nothing runs until the stack is awaited.
+    assert_eq!(log.borrow().len(), 0);
+    block_on(stack.run_all());
+    assert_eq!(stack.is_empty(), true);
+    assert_eq!(
+        *log.borrow(),
+        ["boxed", "start 2", "end 2", "start 1", "end 1"]
+    );
+}
+
+#[test]
+pub fn test_async_stack_run_all_concurrent() {
+    let log = RefCell::new(Vec::new());
+    let mut stack = AsyncDeferStack::new();
+    for id in 1..=5 {
+        push(&mut stack, &log, id);
+    }
+
+    block_on(stack.run_all_concurrent(NonZeroUsize::new(2).unwrap()));
+    assert_eq!(stack.is_empty(), true);
+    assert_eq!(
+        *log.borrow(),
+        [
+            "start 5", "start 4", "end 5", "end 4", "start 3", "start 2", "end 3", "end 2",
+            "start 1", "end 1"
+        ]
+    );
+}
+
+#[test]
+pub fn test_async_stack_dropped_run_all() {
+    let log = RefCell::new(Vec::new());
+    let mut stack = AsyncDeferStack::with_drop_policy(AsyncDropPolicy::Discard);
+    push(&mut stack, &log, 1);
+    push(&mut stack, &log, 2);
+    push(&mut stack, &log, 3);
+
+    //The cleanup that was started is dropped, the others stay in the stack.
+    {
+        let mut run = pin!(stack.run_all());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(run.as_mut().poll(&mut cx).is_pending(), true);
+    }
+
+    assert_eq!(*log.borrow(), ["start 3"]);
+    assert_eq!(stack.len(), 2);
+    block_on(stack.run_all());
+    assert_eq!(
+        *log.borrow(),
+        ["start 3", "start 2", "end 2", "start 1", "end 1"]
+    );
+}
+
+#[test]
+pub fn test_async_stack_drop_policy() {
+    let log = RefCell::new(Vec::new());
+    let pending = Rc::new(RefCell::new(Vec::<BoxCleanup>::new()));
+    {
+        let fallback = pending.clone();
+        let policy = AsyncDropPolicy::Fallback(Box::new(move |cleanup| {
+            fallback.borrow_mut().push(cleanup);
+        }));
+        let mut stack = AsyncDeferStack::with_drop_policy(policy);
+        push(&mut stack, &log, 1);
+        push(&mut stack, &log, 2);
+    }
+
+    assert_eq!(log.borrow().len(), 0);
+    for cleanup in pending.take() {
+        block_on(cleanup);
+    }
+    assert_eq!(*log.borrow(), ["start 2", "end 2", "start 1", "end 1"]);
+
+    log.borrow_mut().clear();
+    let mut stack = AsyncDeferStack::new();
+    push(&mut stack, &log, 1);
+    let previous = stack.set_drop_policy(AsyncDropPolicy::Discard);
+    assert_eq!(matches!(previous, AsyncDropPolicy::DebugPanic), true);
+    drop(stack);
+    assert_eq!(log.borrow().len(), 0);
+
+    //An empty stack never triggers the policy.
+    drop(AsyncDeferStack::new());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "AsyncDeferStack dropped with 1 cleanups that were not run")]
+pub fn test_async_stack_debug_panic() {
+    let mut stack = AsyncDeferStack::new();
+    stack.push(|| async {});
+    drop(stack);
+}
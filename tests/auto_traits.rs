@@ -0,0 +1,64 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "mt")]
+
+use defer_heavy::{ArcDeferGuard, DeferGuard};
+use std::cell::Cell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::OnceLock;
+use std::thread;
+
+const fn assert_send<T: Send>() {}
+const fn assert_sync<T: Sync>() {}
+
+fn assert_send_val<T: Send>(_: &T) {}
+fn assert_sync_val<T: Sync>(_: &T) {}
+
+type BoxedSend = Box<dyn FnOnce() + Send>;
+
+const _: () = {
+    assert_send::<DeferGuard<fn()>>();
+    assert_sync::<DeferGuard<fn()>>();
+    assert_send::<DeferGuard<BoxedSend>>();
+    assert_send::<ArcDeferGuard<BoxedSend>>();
+    assert_sync::<ArcDeferGuard<BoxedSend>>();
+};
+
+#[cfg(not(feature = "triomphe"))]
+const _: () = {
+    assert_send::<defer_heavy::WeakArcDeferGuard<BoxedSend>>();
+    assert_sync::<defer_heavy::WeakArcDeferGuard<BoxedSend>>();
+};
+
+#[test]
+pub fn test_arc_guard_sync_with_non_sync_closure() {
+    let cell = Cell::new(1u32);
+    let guard = ArcDeferGuard::new(move || assert_eq!(cell.get(), 1));
+    assert_send_val(&guard);
+    assert_sync_val(&guard);
+
+    let guard = DeferGuard::new(move || {});
+    assert_send_val(&guard);
+    assert_sync_val(&guard);
+}
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+static GUARD: OnceLock<ArcDeferGuard<BoxedSend>> = OnceLock::new();
+
+#[test]
+pub fn test_arc_guard_in_static() {
+    let guard = ArcDeferGuard::new(Box::new(|| {
+        CALLS.fetch_add(1, SeqCst);
+    }) as BoxedSend);
+    assert_eq!(GUARD.set(guard.clone()).is_ok(), true);
+
+    let threads: Vec<_> = (0..2)
+        .map(|_| thread::spawn(|| GUARD.get().unwrap().cancel_ref()))
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert_eq!(guard.try_destroy().is_err(), true);
+    assert_eq!(CALLS.load(SeqCst), 0);
+}
@@ -0,0 +1,48 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::{cancel_vec, execute_vec, DeferGuard, DeferIterator};
+use std::cell::RefCell;
+
+fn guards(order: &RefCell<Vec<u32>>) -> Vec<DeferGuard<impl FnOnce() + '_>> {
+    let mut guards: Vec<_> = (0..5)
+        .map(|n| DeferGuard::new(move || order.borrow_mut().push(n)))
+        .collect();
+    assert_eq!(guards[1].cancel_ref(), true);
+    assert_eq!(guards[3].cancel_ref(), true);
+    guards
+}
+
+#[test]
+pub fn test_execute_vec() {
+    let order = RefCell::new(Vec::new());
+    execute_vec(guards(&order));
+    assert_eq!(order.borrow().as_slice(), &[4, 2, 0]);
+}
+
+#[test]
+pub fn test_cancel_vec() {
+    let order = RefCell::new(Vec::new());
+    cancel_vec(guards(&order));
+    assert_eq!(order.borrow().is_empty(), true);
+}
+
+#[test]
+pub fn test_defer_iterator() {
+    let order = RefCell::new(Vec::new());
+    let mut iter = DeferIterator::new(guards(&order));
+    assert_eq!(iter.next(), Some(()));
+    assert_eq!(order.borrow().as_slice(), &[4]);
+    assert_eq!(iter.count(), 2);
+    assert_eq!(order.borrow().as_slice(), &[4, 2, 0]);
+
+    order.borrow_mut().clear();
+    let mut iter = DeferIterator::from(guards(&order));
+    assert_eq!(iter.next(), Some(()));
+    drop(iter);
+    assert_eq!(order.borrow().as_slice(), &[4, 2, 0]);
+
+    order.borrow_mut().clear();
+    let executed: Vec<()> = DeferIterator::new(guards(&order)).collect();
+    assert_eq!(executed.len(), 3);
+}
@@ -0,0 +1,73 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{acquire_deferred, bracket, try_bracket};
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[test]
+pub fn test_bracket() {
+    let released = RefCell::new(Vec::new());
+    let result = bracket(
+        || vec![1],
+        |v| released.borrow_mut().extend(v),
+        |v| {
+            v.push(2);
+            v.len()
+        },
+    );
+    assert_eq!(result, 2);
+    assert_eq!(released.borrow().as_slice(), &[1, 2]);
+}
+
+#[test]
+pub fn test_bracket_panic() {
+    let released = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        bracket(
+            || vec![1],
+            |v| released.borrow_mut().extend(v),
+            |v| {
+                v.push(2);
+                panic!("body failed")
+            },
+        )
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(released.borrow().as_slice(), &[1, 2]);
+}
+
+#[test]
+pub fn test_try_bracket() {
+    let released = RefCell::new(Vec::new());
+    let result: Result<(), &str> = try_bracket(
+        || Ok(vec![1]),
+        |v| released.borrow_mut().extend(v),
+        |v| {
+            v.push(2);
+            Err("body failed")
+        },
+    );
+    assert_eq!(result, Err("body failed"));
+    assert_eq!(released.borrow().as_slice(), &[1, 2]);
+
+    let result: Result<(), &str> = try_bracket(
+        || Err("acquire failed"),
+        |_: Vec<u8>| unreachable!(),
+        |_| unreachable!(),
+    );
+    assert_eq!(result, Err("acquire failed"));
+
+    let result: Result<usize, &str> = try_bracket(|| Ok(3), |_| {}, |v| Ok(*v + 1));
+    assert_eq!(result, Ok(4));
+}
+
+#[test]
+pub fn test_acquire_deferred() {
+    let released = RefCell::new(Vec::new());
+    {
+        let mut buffer = acquire_deferred(|| vec![1], |v| released.borrow_mut().extend(v));
+        buffer.push(2);
+        assert_eq!(released.borrow().is_empty(), true);
+    }
+    assert_eq!(released.borrow().as_slice(), &[1, 2]);
+}
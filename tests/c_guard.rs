@@ -0,0 +1,53 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use core::ffi::c_void;
+use defer_heavy::CDeferGuard;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+
+unsafe extern "C" fn flip(ctx: *mut c_void) {
+    (*(ctx as *const AtomicBool)).store(true, SeqCst);
+}
+
+fn ctx(flag: &AtomicBool) -> *mut c_void {
+    flag as *const AtomicBool as *mut c_void
+}
+
+#[test]
+pub fn test_c_guard_drop() {
+    let flag = AtomicBool::new(false);
+    let guard = unsafe { CDeferGuard::new(flip, ctx(&flag)) };
+    assert_eq!(guard.context(), ctx(&flag));
+    assert_eq!(flag.load(SeqCst), false);
+    drop(guard);
+    assert_eq!(flag.load(SeqCst), true);
+}
+
+#[test]
+pub fn test_c_guard_cancel() {
+    let flag = AtomicBool::new(false);
+    let mut guard = unsafe { CDeferGuard::new(flip, ctx(&flag)) };
+    assert_eq!(guard.cancel_ref(), true);
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(guard.cancel(), false);
+    assert_eq!(flag.load(SeqCst), false);
+}
+
+#[test]
+pub fn test_c_guard_destroy() {
+    let flag = AtomicBool::new(false);
+    let mut guard = unsafe { CDeferGuard::new(flip, ctx(&flag)) };
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(flag.load(SeqCst), true);
+    flag.store(false, SeqCst);
+    assert_eq!(guard.destroy(), false);
+    assert_eq!(flag.load(SeqCst), false);
+}
+
+#[test]
+pub fn test_c_guard_send() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+    let guard = unsafe { CDeferGuard::new(flip, ctx(&FLAG)).assert_send() };
+    std::thread::spawn(move || drop(guard)).join().unwrap();
+    assert_eq!(FLAG.load(SeqCst), true);
+}
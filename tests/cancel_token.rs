@@ -0,0 +1,100 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::defer_guard;
+use std::cell::Cell;
+
+#[test]
+pub fn test_cancel_token_before_drop() {
+    let executed = Cell::new(false);
+    let mut guard = defer_guard! { executed.set(true); }.with_token();
+    let token = guard.cancel_token();
+    assert_eq!(token.is_canceled(), false);
+    assert_eq!(guard.peek().is_some(), true);
+    token.cancel();
+    assert_eq!(guard.peek().is_none(), true);
+    drop(guard);
+    assert_eq!(executed.get(), false);
+    assert_eq!(token.is_canceled(), true);
+}
+
+#[test]
+pub fn test_cancel_token_after_execution() {
+    let executed = Cell::new(0);
+    let mut guard = defer_guard! { executed.set(executed.get() + 1); }.with_token();
+    let token = guard.cancel_token();
+    assert_eq!(guard.destroy(), true);
+    token.cancel();
+    assert_eq!(executed.get(), 1);
+}
+
+#[test]
+pub fn test_cancel_token_shared_state() {
+    let mut guard = defer_guard! { unreachable!(); }.with_token();
+    let first = guard.cancel_token();
+    let second = guard.cancel_token();
+    let third = first.clone();
+    second.cancel();
+    assert_eq!(first.is_canceled(), true);
+    assert_eq!(third.is_canceled(), true);
+    assert_eq!(guard.cancel_ref(), false);
+
+    let mut guard = defer_guard! { unreachable!(); }.with_token();
+    let token = guard.cancel_token();
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(token.is_canceled(), true);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_cancel_token_upgrade() {
+    let mut guard = defer_guard! { unreachable!(); }.with_token();
+    let token = guard.cancel_token();
+    let guard = guard.upgrade();
+    let clone = guard.clone();
+    std::thread::spawn(move || token.cancel()).join().unwrap();
+    drop(guard);
+    drop(clone);
+}
+
+#[cfg(all(feature = "mt", not(feature = "triomphe")))]
+#[test]
+pub fn test_cancel_token_arc() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let dropped_clone = dropped.clone();
+    let guard = defer_heavy::defer_move_arc! {
+        let _ = &dropped_clone;
+        unreachable!();
+    };
+    let token = guard.cancel_token();
+    token.cancel();
+    assert_eq!(token.is_canceled(), true);
+    drop(guard);
+    assert_eq!(token.is_canceled(), false);
+    assert_eq!(Arc::strong_count(&dropped), 1);
+    token.cancel();
+}
+
+#[cfg(not(any(
+    feature = "leak-detect",
+    feature = "stats",
+    feature = "slow-defer-warning"
+)))]
+#[test]
+pub fn test_cancel_token_guard_size() {
+    use defer_heavy::{CancelableDeferGuard, DeferGuard};
+
+    fn noop() {}
+    //The token state lives in CancelableDeferGuard, a plain DeferGuard stays as small as its closure.
+    assert_eq!(size_of::<DeferGuard<fn()>>(), size_of::<Option<fn()>>());
+    assert_eq!(
+        size_of::<CancelableDeferGuard<fn()>>() > size_of::<DeferGuard<fn()>>(),
+        true
+    );
+    let mut guard = DeferGuard::new(noop as fn()).with_token();
+    let _token = guard.cancel_token();
+    assert_eq!(guard.destroy(), true);
+}
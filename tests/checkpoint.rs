@@ -0,0 +1,46 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::Checkpoint;
+
+#[allow(dead_code)]
+fn panic_message(err: Box<dyn std::any::Any + Send>) -> String {
+    err.downcast_ref::<String>()
+        .cloned()
+        .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap()
+}
+
+#[cfg(any(debug_assertions, not(feature = "checkpoint_lenient")))]
+#[test]
+pub fn test_checkpoint_unresolved() {
+    let err = std::panic::catch_unwind(|| {
+        let _checkpoint = Checkpoint::new("close connection");
+    })
+    .unwrap_err();
+    let msg = panic_message(err);
+    assert_eq!(msg.contains("close connection"), true);
+    assert_eq!(msg.contains(file!()), true);
+}
+
+#[test]
+pub fn test_checkpoint_resolved() {
+    let checkpoint = Checkpoint::new("resolved");
+    assert_eq!(checkpoint.label(), "resolved");
+    assert_eq!(checkpoint.location().file(), file!());
+    checkpoint.resolve();
+
+    Checkpoint::new("defused").defuse();
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_checkpoint_during_unwind() {
+    use std::panic::AssertUnwindSafe;
+
+    let err = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let _checkpoint = Checkpoint::new("unwinding");
+        panic!("original");
+    }))
+    .unwrap_err();
+    assert_eq!(panic_message(err), "original");
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(all(feature = "scopeguard-compat", feature = "std"))]
+
+use defer_heavy::compat;
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+fn with_scopeguard(log: &RefCell<Vec<&'static str>>, fail: bool) {
+    scopeguard::defer! { log.borrow_mut().push("defer"); }
+    let mut value = scopeguard::guard(vec!["value"], |v| log.borrow_mut().extend(v));
+    value.push("pushed");
+    scopeguard::defer_on_unwind! { log.borrow_mut().push("unwind"); }
+    scopeguard::defer_on_success! { log.borrow_mut().push("success"); }
+    let _unwind = scopeguard::guard_on_unwind((), |_| log.borrow_mut().push("guard unwind"));
+    let _success = scopeguard::guard_on_success((), |_| log.borrow_mut().push("guard success"));
+    if fail {
+        panic!("fail");
+    }
+}
+
+fn with_compat(log: &RefCell<Vec<&'static str>>, fail: bool) {
+    compat::defer! { log.borrow_mut().push("defer"); }
+    let mut value = compat::guard(vec!["value"], |v| log.borrow_mut().extend(v));
+    value.push("pushed");
+    compat::defer_on_unwind! { log.borrow_mut().push("unwind"); }
+    compat::defer_on_success! { log.borrow_mut().push("success"); }
+    let _unwind = compat::guard_on_unwind((), |_| log.borrow_mut().push("guard unwind"));
+    let _success = compat::guard_on_success((), |_| log.borrow_mut().push("guard success"));
+    if fail {
+        panic!("fail");
+    }
+}
+
+fn run(func: fn(&RefCell<Vec<&'static str>>, bool), fail: bool) -> Vec<&'static str> {
+    let log = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| func(&log, fail)));
+    assert_eq!(result.is_err(), fail);
+    log.into_inner()
+}
+
+#[test]
+pub fn test_compat_same_behavior() {
+    assert_eq!(run(with_compat, false), run(with_scopeguard, false));
+    assert_eq!(run(with_compat, true), run(with_scopeguard, true));
+    assert_eq!(
+        run(with_compat, true),
+        vec!["guard unwind", "unwind", "value", "pushed", "defer"]
+    );
+}
+
+#[test]
+pub fn test_compat_from_scopeguard() {
+    let log = RefCell::new(Vec::new());
+    {
+        let guard = scopeguard::guard(vec![1], |v| log.borrow_mut().extend(v));
+        let mut converted = compat::ScopeGuardDefer::from(guard);
+        converted.push(2);
+        assert_eq!(converted.len(), 2);
+        assert_eq!(log.borrow().is_empty(), true);
+    }
+    assert_eq!(log.borrow().as_slice(), &[1, 2]);
+
+    let guard = scopeguard::guard(vec![1], |v| log.borrow_mut().extend(v));
+    let converted: compat::ScopeGuardDefer<_, _> = guard.into();
+    let guard = converted.into_inner();
+    assert_eq!(scopeguard::ScopeGuard::into_inner(guard), vec![1]);
+    assert_eq!(log.borrow().as_slice(), &[1, 2]);
+}
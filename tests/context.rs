@@ -0,0 +1,73 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_with_context, ContextDeferGuard, Deferrable};
+
+#[derive(Default, Debug)]
+struct CleanupContext {
+    rollback_id: Option<u32>,
+    rolled_back: Vec<u32>,
+}
+
+impl CleanupContext {
+    fn do_rollback(&mut self) {
+        if let Some(id) = self.rollback_id.take() {
+            self.rolled_back.push(id);
+        }
+    }
+}
+
+fn insert(ctx: &mut CleanupContext, id: u32, fail: bool) {
+    defer_with_context!(ctx: &mut CleanupContext, {
+        ctx.do_rollback();
+    });
+    ctx.rollback_id = Some(id);
+    if !fail {
+        ctx.rollback_id = None;
+    }
+}
+
+#[test]
+pub fn test_context_macro() {
+    let mut ctx = CleanupContext::default();
+    insert(&mut ctx, 1, true);
+    insert(&mut ctx, 2, false);
+    insert(&mut ctx, 3, true);
+    assert_eq!(ctx.rolled_back, [1, 3]);
+    assert_eq!(ctx.rollback_id, None);
+}
+
+#[test]
+pub fn test_context_macro_owned() {
+    let mut ctx = CleanupContext::default();
+    {
+        defer_with_context!(ctx, {
+            ctx.do_rollback();
+        });
+        ctx.rollback_id = Some(4);
+        //The scope can pass the context on while the guard exists.
+        let inner: &mut CleanupContext = &mut ctx;
+        inner.rolled_back.push(0);
+    }
+    assert_eq!(ctx.rolled_back, [0, 4]);
+}
+
+#[test]
+pub fn test_context_guard() {
+    let mut count = 0u32;
+    {
+        let mut guard = ContextDeferGuard::new(&mut count, |count: &mut u32| *count *= 10);
+        *guard += 1;
+        assert_eq!(guard.is_armed(), true);
+    }
+    assert_eq!(count, 10);
+
+    let mut guard = ContextDeferGuard::new(&mut count, |count: &mut u32| *count *= 10);
+    *guard += 1;
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(count, 11);
+
+    let mut guard = ContextDeferGuard::new(&mut count, |count: &mut u32| *count = 0);
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(*guard, 0);
+    assert_eq!(guard.destroy(), false);
+}
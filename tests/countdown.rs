@@ -0,0 +1,72 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "mt")]
+
+use defer_heavy::CountdownDeferGuard;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+pub fn test_countdown() {
+    let executed = AtomicUsize::new(0);
+    let countdown = CountdownDeferGuard::new(2, || {
+        executed.fetch_add(1, SeqCst);
+    });
+    let clones: Vec<_> = (0..4).map(|_| countdown.clone()).collect();
+    assert_eq!(countdown.remaining(), 2);
+
+    let mut clones = clones.into_iter();
+    drop(clones.next());
+    assert_eq!(executed.load(SeqCst), 0);
+    assert_eq!(countdown.remaining(), 1);
+    drop(clones.next());
+    assert_eq!(executed.load(SeqCst), 1);
+    assert_eq!(countdown.remaining(), 0);
+    drop(clones);
+    drop(countdown);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_countdown_threads() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let countdown = CountdownDeferGuard::new(8, move || {
+        exe.fetch_add(1, SeqCst);
+    });
+    let threads: Vec<_> = (0..16)
+        .map(|_| {
+            let countdown = countdown.clone();
+            thread::spawn(move || drop(countdown.own()))
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(executed.load(SeqCst), 1);
+    drop(countdown);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_countdown_cancel() {
+    let countdown = CountdownDeferGuard::new(1, || unreachable!("Wont be executed"));
+    let clone = countdown.clone();
+    clone.cancel();
+    assert_eq!(countdown.is_canceled(), true);
+    drop(countdown);
+}
+
+#[test]
+pub fn test_countdown_not_reached() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let capture = drops.clone();
+    let countdown = CountdownDeferGuard::new(3, move || {
+        let _capture = &capture;
+        unreachable!("Wont be executed");
+    });
+    drop(countdown.clone());
+    drop(countdown);
+    assert_eq!(Arc::strong_count(&drops), 1);
+}
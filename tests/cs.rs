@@ -0,0 +1,100 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "critical-section")]
+
+use defer_heavy::{CsDeferGuard, CsDeferSlot};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread;
+
+static FIRED: AtomicUsize = AtomicUsize::new(0);
+
+fn cleanup() {
+    FIRED.fetch_add(1, SeqCst);
+}
+
+#[test]
+pub fn test_cs_release_on_last_drop() {
+    static SLOT: CsDeferSlot<fn()> = CsDeferSlot::new();
+    let before = FIRED.load(SeqCst);
+    assert_eq!(SLOT.is_empty(), true);
+
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    let handles: Vec<_> = (0..8).map(|_| guard.clone()).collect();
+    assert_eq!(guard.ref_count(), 9);
+    drop(guard);
+    assert_eq!(FIRED.load(SeqCst), before);
+
+    thread::scope(|scope| {
+        for handle in handles {
+            scope.spawn(move || drop(handle));
+        }
+    });
+
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+    assert_eq!(SLOT.is_empty(), true);
+}
+
+#[test]
+pub fn test_cs_cancel() {
+    static SLOT: CsDeferSlot<fn()> = CsDeferSlot::new();
+    let before = FIRED.load(SeqCst);
+
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    let handle = guard.clone();
+    assert_eq!(guard.is_canceled(), false);
+    handle.cancel();
+    assert_eq!(guard.is_canceled(), true);
+    drop(guard);
+    assert_eq!(FIRED.load(SeqCst), before);
+    assert_eq!(SLOT.is_empty(), true);
+
+    //Cancel state does not carry over to the next closure.
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    assert_eq!(guard.is_canceled(), false);
+    drop(guard);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+}
+
+#[test]
+pub fn test_cs_fire_now() {
+    static SLOT: CsDeferSlot<fn()> = CsDeferSlot::new();
+    let before = FIRED.load(SeqCst);
+
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    let handle = guard.clone();
+    assert_eq!(guard.fire_now(), true);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+    assert_eq!(SLOT.is_empty(), false);
+    assert_eq!(handle.clone().fire_now(), false);
+    drop(handle);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+    assert_eq!(SLOT.is_empty(), true);
+
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    guard.cancel_ref();
+    assert_eq!(guard.fire_now(), false);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+}
+
+#[test]
+pub fn test_cs_slot_reuse() {
+    static SLOT: CsDeferSlot<fn()> = CsDeferSlot::new();
+    let before = FIRED.load(SeqCst);
+
+    let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    let Err(func) = CsDeferGuard::init(&SLOT, cleanup as fn()) else {
+        panic!("slot was armed twice");
+    };
+    func();
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+
+    drop(guard);
+    assert_eq!(FIRED.load(SeqCst), before + 2);
+
+    for n in 0..3 {
+        let guard = CsDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+        drop(guard.clone());
+        drop(guard);
+        assert_eq!(FIRED.load(SeqCst), before + 3 + n);
+    }
+}
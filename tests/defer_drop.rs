@@ -0,0 +1,43 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "proc-macro")]
+
+use defer_heavy::defer_drop;
+use std::cell::RefCell;
+use std::fmt::Debug;
+
+struct Cleanup<'a, T: Debug>(&'a RefCell<Vec<String>>, T);
+
+#[defer_drop(cleanup)]
+impl<'a, T: Debug> Cleanup<'a, T> {
+    fn cleanup(&mut self) {
+        self.0.borrow_mut().push(format!("{:?}", self.1));
+    }
+}
+
+struct SharedCleanup<'a>(&'a RefCell<u32>);
+
+#[defer_drop(cleanup)]
+impl SharedCleanup<'_> {
+    fn cleanup(&self) {
+        *self.0.borrow_mut() += 1;
+    }
+}
+
+#[test]
+pub fn test_defer_drop() {
+    let log = RefCell::new(Vec::new());
+    {
+        let _a = Cleanup(&log, 1);
+        let _b = Cleanup(&log, "b");
+    }
+    assert_eq!(log.borrow().as_slice(), &["\"b\"", "1"]);
+
+    let count = RefCell::new(0);
+    drop(SharedCleanup(&count));
+    assert_eq!(*count.borrow(), 1);
+}
+
+#[test]
+pub fn test_defer_drop_compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/defer_drop_*.rs");
+}
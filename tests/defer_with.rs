@@ -0,0 +1,87 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_with, DeferWith};
+use std::cell::RefCell;
+
+#[test]
+pub fn test_defer_with_set() {
+    let received = RefCell::new(Vec::new());
+    {
+        let mut guard = defer_with!(|value| received.borrow_mut().push(value));
+        assert_eq!(guard.set(1), None);
+        assert_eq!(guard.set(2), Some(1));
+    }
+
+    {
+        let _guard = DeferWith::new(|value| received.borrow_mut().push(value));
+    }
+
+    assert_eq!(received.borrow().as_slice(), &[Some(2), None]);
+}
+
+#[test]
+pub fn test_defer_with_set_ref() {
+    let received = RefCell::new(None);
+    {
+        let guard = defer_with!(|value: &str| {
+            *received.borrow_mut() = value;
+        });
+        let handle = &guard;
+        assert_eq!(handle.set_ref("first"), None);
+        assert_eq!(handle.set_ref("second"), Some("first"));
+    }
+
+    assert_eq!(*received.borrow(), Some("second"));
+}
+
+#[test]
+pub fn test_defer_with_destroy_cancel() {
+    let received = RefCell::new(Vec::new());
+    let mut guard = DeferWith::new(|value| received.borrow_mut().push(value));
+    guard.set(1);
+    assert_eq!(guard.destroy_with(5), true);
+
+    let mut guard = DeferWith::new(|value| received.borrow_mut().push(value));
+    guard.set(1);
+    assert_eq!(guard.take_value(), Some(1));
+    assert_eq!(guard.destroy(), true);
+
+    let mut guard = DeferWith::new(|value: Option<i32>| received.borrow_mut().push(value));
+    guard.set(1);
+    assert_eq!(guard.is_pending(), true);
+    assert_eq!(guard.cancel_ref(), true);
+    assert_eq!(guard.is_pending(), false);
+    assert_eq!(guard.destroy_ref(), false);
+    drop(guard);
+
+    assert_eq!(received.borrow().as_slice(), &[Some(5), None]);
+}
+
+#[test]
+pub fn test_defer_with_var() {
+    let seen = RefCell::new(Vec::new());
+    {
+        let buffer = vec![1];
+        defer_with!(buffer => {
+            buffer.push(4);
+            seen.borrow_mut().extend_from_slice(&buffer);
+        });
+        buffer.push(2);
+        let last = buffer.last_mut().unwrap();
+        *last += 1;
+        assert_eq!(*buffer, [1, 3]);
+        assert_eq!(seen.borrow().len(), 0);
+    }
+    assert_eq!(*seen.borrow(), [1, 3, 4]);
+
+    let count = 0;
+    {
+        defer_with!(count => assert_eq!(count, 2));
+        *count += 2;
+    }
+
+    let value = String::from("kept");
+    defer_with!(value => unreachable!("{}", value));
+    let value = defer_heavy::Defer::into_inner(value);
+    assert_eq!(value, "kept");
+}
@@ -0,0 +1,105 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_guard, DeferGuard, Deferrable, FnMutDeferGuard};
+use std::cell::RefCell;
+
+fn finish<D: Deferrable>(cleanup: D, success: bool) -> bool {
+    assert_eq!(cleanup.is_armed(), true);
+    if success {
+        cleanup.cancel();
+        return false;
+    }
+
+    cleanup.destroy()
+}
+
+#[test]
+pub fn test_deferrable_generic() {
+    let log = RefCell::new(Vec::new());
+    let push = |n: u32| {
+        let log = &log;
+        move || log.borrow_mut().push(n)
+    };
+
+    assert_eq!(finish(push(1), false), true);
+    assert_eq!(finish(push(2), true), false);
+    assert_eq!(finish(DeferGuard::new(push(3)), false), true);
+    assert_eq!(finish(DeferGuard::new(push(4)), true), false);
+    assert_eq!(finish(FnMutDeferGuard::new(push(5)), false), true);
+    assert_eq!(finish(FnMutDeferGuard::new(push(6)), true), false);
+    assert_eq!(*log.borrow(), [1, 3, 5]);
+
+    let mut guard = defer_guard! { log.borrow_mut().push(7); };
+    guard.cancel_ref();
+    assert_eq!(Deferrable::is_armed(&guard), false);
+    assert_eq!(Deferrable::destroy(guard), false);
+    assert_eq!(*log.borrow(), [1, 3, 5]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+pub fn test_deferrable_cancel_token() {
+    let called = std::cell::Cell::new(false);
+    let mut guard = DeferGuard::new(|| called.set(true)).with_token();
+    let token = guard.cancel_token();
+    token.cancel();
+    assert_eq!(Deferrable::is_armed(&guard), false);
+    assert_eq!(Deferrable::destroy(guard), false);
+    assert_eq!(called.get(), false);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_deferrable_arc() {
+    use defer_heavy::ArcDeferGuard;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+
+    let called = std::sync::Arc::new(AtomicUsize::new(0));
+    let counter = called.clone();
+    let guard = ArcDeferGuard::new(move || {
+        counter.fetch_add(1, SeqCst);
+    });
+    let other = guard.clone();
+    //Another reference exists, so only this reference is released.
+    assert_eq!(finish(guard, false), false);
+    assert_eq!(called.load(SeqCst), 0);
+    assert_eq!(finish(other, false), true);
+    assert_eq!(called.load(SeqCst), 1);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+pub fn test_deferrable_dyn() {
+    use defer_heavy::{DeferSlot, DeferrableDyn};
+
+    let log = RefCell::new(Vec::new());
+    let push = |n: u32| {
+        let log = &log;
+        move || log.borrow_mut().push(n)
+    };
+
+    let mut slot = DeferSlot::new();
+    slot.arm(push(4));
+    let cleanups: Vec<Box<dyn DeferrableDyn>> = vec![
+        Box::new(push(1)),
+        Box::new(DeferGuard::new(push(2))),
+        Box::new(FnMutDeferGuard::new(push(3))),
+        Box::new(slot),
+    ];
+    assert_eq!(cleanups.iter().all(|c| c.is_armed()), true);
+
+    for (index, cleanup) in cleanups.into_iter().enumerate() {
+        if index % 2 == 0 {
+            assert_eq!(cleanup.destroy_boxed(), true);
+        } else {
+            cleanup.cancel_boxed();
+        }
+    }
+    assert_eq!(*log.borrow(), [1, 3]);
+
+    //Boxed guards are Deferrable themselves.
+    let boxed: Box<dyn DeferrableDyn> = Box::new(DeferGuard::new(push(5)));
+    assert_eq!(finish(boxed, false), true);
+    assert_eq!(*log.borrow(), [1, 3, 5]);
+}
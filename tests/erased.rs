@@ -0,0 +1,83 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::{execute_vec, DeferGuard, ErasedDeferGuard};
+use std::cell::RefCell;
+
+#[test]
+pub fn test_erased_vec() {
+    let order = RefCell::new(Vec::new());
+    let name = String::from("third");
+    let mut guards: Vec<ErasedDeferGuard> = Vec::new();
+    guards.push(DeferGuard::new(|| order.borrow_mut().push(String::from("first"))).erase());
+    guards.push(DeferGuard::new(|| order.borrow_mut().push(format!("{}", 2))).erase());
+    let third = &order;
+    guards.push(DeferGuard::new(move || third.borrow_mut().push(name)).erase());
+
+    let mut canceled = DeferGuard::new(|| unreachable!("Wont be executed"));
+    canceled.cancel_ref();
+    guards.push(canceled.erase());
+
+    execute_vec(guards);
+    assert_eq!(order.borrow().as_slice(), &["third", "2", "first"]);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_erased_arc() {
+    use defer_heavy::{ArcDeferGuard, ErasedArcDeferGuard};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let first = ArcDeferGuard::new(move || {
+        exe.fetch_add(1, SeqCst);
+    });
+    let exe = executed.clone();
+    let second = ArcDeferGuard::new(move || {
+        exe.fetch_add(10, SeqCst);
+    });
+
+    let clone = first.clone();
+    let first = first.try_erase().unwrap_err();
+    drop(clone);
+
+    second.cancel_ref();
+    let guards: Vec<ErasedArcDeferGuard> =
+        vec![first.try_erase().unwrap(), second.try_erase().unwrap()];
+    assert_eq!(executed.load(SeqCst), 0);
+    drop(guards);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_erased_arc_boxed() {
+    use defer_heavy::ErasedArcDeferGuard;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::thread;
+
+    let executed = Arc::new(AtomicUsize::new(0));
+    let mut guards = Vec::new();
+    for i in 0..4 {
+        let exe = executed.clone();
+        guards.push(ErasedArcDeferGuard::boxed(move || {
+            exe.fetch_add(i, SeqCst);
+        }));
+    }
+    let exe = executed.clone();
+    guards.push(ErasedArcDeferGuard::boxed(move || {
+        exe.fetch_add(100, SeqCst);
+    }));
+    guards[4].cancel_ref();
+
+    let clones = guards.to_vec();
+    let worker = thread::spawn(move || drop(clones));
+    drop(guards);
+    worker.join().unwrap();
+    assert_eq!(executed.load(SeqCst), 6);
+}
@@ -0,0 +1,75 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "ffi")]
+
+use defer_heavy::{
+    defer_arc_cancel, defer_arc_clone, defer_arc_destroy, defer_arc_free, ErasedArcDeferGuard,
+};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+
+fn counting_guard(counter: &Arc<AtomicUsize>) -> *const () {
+    let counter = counter.clone();
+    let func: Box<dyn FnOnce() + Send> = Box::new(move || {
+        counter.fetch_add(1, SeqCst);
+    });
+    ErasedArcDeferGuard::new(func).into_raw()
+}
+
+#[test]
+pub fn test_ffi_destroy() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let ptr = counting_guard(&counter);
+    let clone = unsafe { defer_arc_clone(ptr) };
+    unsafe { defer_arc_destroy(ptr) };
+    assert_eq!(counter.load(SeqCst), 0);
+    unsafe { defer_arc_destroy(clone) };
+    assert_eq!(counter.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_ffi_cancel() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let ptr = counting_guard(&counter);
+    let clone = unsafe { defer_arc_clone(ptr) };
+    unsafe { defer_arc_cancel(clone) };
+    unsafe { defer_arc_destroy(ptr) };
+    assert_eq!(counter.load(SeqCst), 0);
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+pub fn test_ffi_free() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let ptr = counting_guard(&counter);
+    let clone = unsafe { defer_arc_clone(ptr) };
+    unsafe { defer_arc_free(ptr) };
+    unsafe { defer_arc_free(clone) };
+    assert_eq!(counter.load(SeqCst), 0);
+    assert_eq!(Arc::strong_count(&counter), 1);
+
+    let ptr = counting_guard(&counter);
+    let clone = unsafe { defer_arc_clone(ptr) };
+    unsafe { defer_arc_free(ptr) };
+    unsafe { defer_arc_destroy(clone) };
+    assert_eq!(counter.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_ffi_null() {
+    unsafe {
+        defer_arc_cancel(std::ptr::null());
+        defer_arc_destroy(std::ptr::null());
+        defer_arc_free(std::ptr::null());
+        assert_eq!(defer_arc_clone(std::ptr::null()).is_null(), true);
+    }
+}
+
+#[test]
+pub fn test_ffi_from_raw() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let ptr = counting_guard(&counter);
+    let guard = unsafe { ErasedArcDeferGuard::from_raw(ptr) };
+    assert_eq!(guard.try_destroy().is_ok(), true);
+    assert_eq!(counter.load(SeqCst), 1);
+}
@@ -0,0 +1,139 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{
+    cell_flag_guard, defer_guarded_by, flag_guard, try_cell_flag_guard, try_flag_guard,
+    DoneFlagDeferGuard, FlagGuard,
+};
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::{Acquire, Release, SeqCst};
+use std::thread;
+
+fn reentrant(busy: &AtomicBool, depth: u32, entered: &Cell<u32>) {
+    let Some(_guard) = try_flag_guard(busy) else {
+        return;
+    };
+    entered.set(entered.get() + 1);
+    if depth > 0 {
+        reentrant(busy, depth - 1, entered);
+    }
+}
+
+#[test]
+pub fn test_flag_guard() {
+    let flag = AtomicBool::new(false);
+    {
+        let _guard = flag_guard(&flag);
+        assert_eq!(flag.load(SeqCst), true);
+    }
+    assert_eq!(flag.load(SeqCst), false);
+
+    let guard = FlagGuard::new(&flag, Release);
+    assert_eq!(flag.load(Acquire), true);
+    guard.release();
+    assert_eq!(flag.load(Acquire), false);
+
+    flag_guard(&flag).forget();
+    assert_eq!(flag.load(SeqCst), true);
+}
+
+#[test]
+pub fn test_try_flag_guard_reentrancy() {
+    let flag = AtomicBool::new(false);
+    let entered = Cell::new(0);
+    reentrant(&flag, 3, &entered);
+    assert_eq!(entered.get(), 1);
+    assert_eq!(flag.load(SeqCst), false);
+
+    let outer = try_flag_guard(&flag).unwrap();
+    assert_eq!(try_flag_guard(&flag).is_none(), true);
+    assert_eq!(FlagGuard::try_new(&flag, Release).is_none(), true);
+    //The failed attempts do not clear the flag of the outer guard.
+    assert_eq!(flag.load(SeqCst), true);
+    drop(outer);
+    assert_eq!(flag.load(SeqCst), false);
+    assert_eq!(FlagGuard::try_new(&flag, Release).is_some(), true);
+}
+
+#[test]
+#[should_panic]
+pub fn test_try_flag_guard_acquire() {
+    let flag = AtomicBool::new(false);
+    let _guard = FlagGuard::try_new(&flag, Acquire);
+}
+
+#[test]
+pub fn test_flag_guard_panic() {
+    let flag = AtomicBool::new(false);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = try_flag_guard(&flag).unwrap();
+        panic!("scope failed");
+    }));
+    assert!(result.is_err());
+    assert_eq!(flag.load(SeqCst), false);
+}
+
+#[test]
+pub fn test_cell_flag_guard() {
+    let flag = Cell::new(false);
+    {
+        let _guard = cell_flag_guard(&flag);
+        assert_eq!(flag.get(), true);
+        assert_eq!(try_cell_flag_guard(&flag).is_none(), true);
+        assert_eq!(flag.get(), true);
+    }
+    assert_eq!(flag.get(), false);
+
+    let guard = try_cell_flag_guard(&flag).unwrap();
+    guard.release();
+    assert_eq!(flag.get(), false);
+
+    try_cell_flag_guard(&flag).unwrap().forget();
+    assert_eq!(flag.get(), true);
+    flag.set(false);
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = try_cell_flag_guard(&flag).unwrap();
+        panic!("scope failed");
+    }));
+    assert!(result.is_err());
+    assert_eq!(flag.get(), false);
+}
+
+#[test]
+pub fn test_defer_guarded_by() {
+    let done = AtomicBool::new(false);
+    let calls = Cell::new(0);
+    {
+        let _guard = defer_guarded_by!(&done, calls.set(calls.get() + 1));
+    }
+    assert_eq!(calls.get(), 1);
+
+    {
+        let guard = defer_guarded_by!(&done, {
+            calls.set(calls.get() + 1);
+        });
+        assert_eq!(guard.is_done(), false);
+        //Set by the code that completes the work.
+        thread::scope(|s| {
+            s.spawn(|| done.store(true, SeqCst));
+        });
+        assert_eq!(guard.is_done(), true);
+    }
+    assert_eq!(calls.get(), 1);
+
+    done.store(false, SeqCst);
+    let guard = DoneFlagDeferGuard::new(&done, || calls.set(calls.get() + 1));
+    let other = DoneFlagDeferGuard::new(&done, || calls.set(calls.get() + 1));
+    guard.cancel_and_mark();
+    assert_eq!(done.load(SeqCst), true);
+    drop(other);
+    assert_eq!(calls.get(), 1);
+
+    done.store(false, SeqCst);
+    let guard = DoneFlagDeferGuard::new(&done, || calls.set(calls.get() + 1));
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(done.load(SeqCst), false);
+    assert_eq!(calls.get(), 1);
+}
@@ -0,0 +1,82 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "async")]
+
+use defer_heavy::FutureDeferExt;
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+
+///
+/// Returns Pending on the first poll and Ready on the second.
+///
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = u32;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u32> {
+        if self.0 {
+            return Poll::Ready(7);
+        }
+
+        self.0 = true;
+        Poll::Pending
+    }
+}
+
+fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+#[test]
+pub fn test_on_cancel_dropped_early() {
+    let called = Cell::new(false);
+    {
+        let future = pin!(YieldOnce(false).defer_on_cancel(|| called.set(true)));
+        assert_eq!(poll_once(future).is_pending(), true);
+        assert_eq!(called.get(), false);
+    }
+
+    assert_eq!(called.get(), true);
+}
+
+#[test]
+pub fn test_on_cancel_completed() {
+    let called = Cell::new(false);
+    let mut future = YieldOnce(false).defer_on_cancel(|| called.set(true));
+    //OnCancel is Unpin if the inner future is Unpin.
+    assert_eq!(poll_once(Pin::new(&mut future)).is_pending(), true);
+    assert_eq!(poll_once(Pin::new(&mut future)), Poll::Ready(7));
+    drop(future);
+    assert_eq!(called.get(), false);
+}
+
+#[test]
+pub fn test_on_cancel_async_block() {
+    let called = Cell::new(false);
+    {
+        let future =
+            pin!(async { YieldOnce(false).await + 1 }.defer_on_cancel(|| called.set(true)));
+        assert_eq!(poll_once(future).is_pending(), true);
+    }
+
+    assert_eq!(called.get(), true);
+}
+
+#[test]
+pub fn test_on_complete() {
+    let called = Cell::new(false);
+    {
+        let future = pin!(YieldOnce(false).defer_on_complete(|| called.set(true)));
+        assert_eq!(poll_once(future).is_pending(), true);
+    }
+    assert_eq!(called.get(), false);
+
+    let mut future = YieldOnce(false).defer_on_complete(|| called.set(true));
+    assert_eq!(poll_once(Pin::new(&mut future)).is_pending(), true);
+    assert_eq!(poll_once(Pin::new(&mut future)), Poll::Ready(7));
+    assert_eq!(called.get(), false);
+    drop(future);
+    assert_eq!(called.get(), true);
+}
@@ -0,0 +1,67 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "futures-channel")]
+
+use defer_heavy::ArcDeferGuard;
+use futures_executor::block_on;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+
+#[test]
+pub fn test_arc_completion_channel() {
+    let called = Arc::new(AtomicUsize::new(0));
+    let counter = called.clone();
+    let (guard, mut done) = ArcDeferGuard::new_with_completion(move || {
+        counter.fetch_add(1, SeqCst);
+    });
+    let second = guard.clone();
+
+    drop(guard);
+    assert_eq!(done.try_recv().unwrap(), None);
+
+    drop(second);
+    assert_eq!(done.try_recv().unwrap(), Some(()));
+    assert_eq!(called.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_arc_completion_channel_threads() {
+    let called = Arc::new(AtomicUsize::new(0));
+    let counter = called.clone();
+    let (guard, done) = ArcDeferGuard::new_with_completion(move || {
+        counter.fetch_add(1, SeqCst);
+    });
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let guard = guard.clone();
+            std::thread::spawn(move || drop(guard))
+        })
+        .collect();
+    drop(guard);
+
+    block_on(done).unwrap();
+    //The closure returned before the receiver resolved.
+    assert_eq!(called.load(SeqCst), 1);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+pub fn test_arc_completion_channel_cancel() {
+    let (guard, done) = ArcDeferGuard::new_with_completion(|| unreachable!());
+    let second = guard.clone();
+    guard.cancel();
+    drop(second);
+    assert_eq!(block_on(done).is_ok(), true);
+
+    //A dropped receiver does not prevent the closure from being called.
+    let called = Arc::new(AtomicUsize::new(0));
+    let counter = called.clone();
+    let (guard, done) = ArcDeferGuard::new_with_completion(move || {
+        counter.fetch_add(1, SeqCst);
+    });
+    drop(done);
+    drop(guard);
+    assert_eq!(called.load(SeqCst), 1);
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "alloc")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::DeferGroup;
+use std::sync::Mutex;
+
+#[test]
+pub fn test_group_order() {
+    let order = Mutex::new(Vec::new());
+    {
+        let mut group = DeferGroup::new();
+        for i in 0..4 {
+            let order = &order;
+            group.add(move || order.lock().unwrap().push(i));
+        }
+        assert_eq!(group.len(), 4);
+    }
+    assert_eq!(*order.lock().unwrap(), [3, 2, 1, 0]);
+
+    let mut group = DeferGroup::new();
+    group.add(|| order.lock().unwrap().push(4));
+    group.cancel_all();
+    assert_eq!(group.is_empty(), true);
+    group.add(|| order.lock().unwrap().push(5));
+    group.destroy_all();
+    assert_eq!(group.is_empty(), true);
+    drop(group);
+    assert_eq!(*order.lock().unwrap(), [3, 2, 1, 0, 5]);
+}
+
+#[cfg(feature = "std")]
+mod parallel {
+    use defer_heavy::DeferGroup;
+    use std::num::NonZeroUsize;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const SLEEP: Duration = Duration::from_millis(200);
+
+    fn sleeping_group(count: usize, done: &AtomicUsize) -> DeferGroup<'_> {
+        let mut group = DeferGroup::new();
+        for _ in 0..count {
+            group.add(move || {
+                thread::sleep(SLEEP);
+                done.fetch_add(1, SeqCst);
+            });
+        }
+        group
+    }
+
+    #[test]
+    pub fn test_group_parallel() {
+        let done = AtomicUsize::new(0);
+        let start = Instant::now();
+        sleeping_group(10, &done).destroy_all_parallel(None);
+        let elapsed = start.elapsed();
+        assert_eq!(done.load(SeqCst), 10);
+        //Sequential would take 2 seconds.
+        assert!(elapsed >= SLEEP);
+        assert!(elapsed < SLEEP * 5, "took {:?}", elapsed);
+    }
+
+    #[test]
+    pub fn test_group_parallel_bounded() {
+        let done = AtomicUsize::new(0);
+        let start = Instant::now();
+        sleeping_group(4, &done).destroy_all_parallel(NonZeroUsize::new(2));
+        let elapsed = start.elapsed();
+        assert_eq!(done.load(SeqCst), 4);
+        assert!(elapsed >= SLEEP * 2);
+        assert!(elapsed < SLEEP * 4, "took {:?}", elapsed);
+
+        sleeping_group(2, &done).destroy_all_parallel(NonZeroUsize::new(1));
+        assert_eq!(done.load(SeqCst), 6);
+        DeferGroup::new().destroy_all_parallel(None);
+    }
+
+    #[test]
+    pub fn test_group_parallel_panic() {
+        let done = AtomicUsize::new(0);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut group = DeferGroup::new();
+            for i in 0..6 {
+                let done = &done;
+                group.add(move || {
+                    if i == 2 {
+                        panic!("cleanup {} failed", i);
+                    }
+                    done.fetch_add(1, SeqCst);
+                });
+            }
+            group.destroy_all_parallel(NonZeroUsize::new(3));
+        }));
+        assert_eq!(done.load(SeqCst), 5);
+        let payload = result.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "cleanup 2 failed"
+        );
+    }
+}
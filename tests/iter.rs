@@ -0,0 +1,75 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{IterDeferExt, IterEnd};
+use std::cell::RefCell;
+
+///
+/// Returns None once, then continues with the next item.
+///
+struct Resuming(u32);
+
+impl Iterator for Resuming {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        self.0 += 1;
+        if self.0 == 2 {
+            return None;
+        }
+
+        Some(self.0)
+    }
+}
+
+#[test]
+pub fn test_iter_exhausted() {
+    let ends = RefCell::new(Vec::new());
+    let mut iter = [1, 2, 3]
+        .into_iter()
+        .defer_cleanup(|end| ends.borrow_mut().push(end));
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.size_hint(), (3, Some(3)));
+    assert_eq!(iter.by_ref().sum::<i32>(), 6);
+    assert_eq!(iter.is_exhausted(), true);
+    assert_eq!(ends.borrow().as_slice(), &[IterEnd::Exhausted]);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+    drop(iter);
+    assert_eq!(ends.borrow().as_slice(), &[IterEnd::Exhausted]);
+}
+
+#[test]
+pub fn test_iter_dropped() {
+    let ends = RefCell::new(Vec::new());
+    let taken: Vec<_> = (0..10)
+        .defer_cleanup(|end| ends.borrow_mut().push(end))
+        .take(3)
+        .collect();
+    assert_eq!(taken, [0, 1, 2]);
+    assert_eq!(ends.borrow().as_slice(), &[IterEnd::Dropped]);
+}
+
+#[test]
+pub fn test_iter_double_ended() {
+    let ends = RefCell::new(Vec::new());
+    let mut iter = [1, 2]
+        .into_iter()
+        .defer_cleanup(|end| ends.borrow_mut().push(end));
+    assert_eq!(iter.next_back(), Some(2));
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(ends.borrow().is_empty(), true);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(ends.borrow().as_slice(), &[IterEnd::Exhausted]);
+}
+
+#[test]
+pub fn test_iter_fused() {
+    let ends = RefCell::new(Vec::new());
+    let mut iter = Resuming(0).defer_cleanup(|end| ends.borrow_mut().push(end));
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.next(), None);
+    //The inner iterator would resume, but the cleanup already ran.
+    assert_eq!(iter.next(), None);
+    drop(iter);
+    assert_eq!(ends.borrow().as_slice(), &[IterEnd::Exhausted]);
+}
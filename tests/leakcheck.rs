@@ -0,0 +1,86 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "leak-detect")]
+
+use defer_heavy::{defer_guard, leakcheck, DeferGuard};
+
+//The records are global, so everything is checked in a single test.
+#[test]
+pub fn test_leakcheck() {
+    leakcheck::reset();
+
+    let executed_line = line!() + 1;
+    let guard = defer_guard! {};
+    drop(guard);
+
+    let canceled_line = line!() + 1;
+    let guard = defer_guard! {};
+    assert_eq!(guard.cancel(), true);
+
+    let forgotten_line = line!() + 1;
+    let guard = DeferGuard::new(|| {});
+    assert_eq!(guard.forget(), true);
+
+    let report = leakcheck::report();
+    let line_of = |line: u32| {
+        report
+            .locations
+            .iter()
+            .find(|l| l.location.file() == file!() && l.location.line() == line)
+            .copied()
+            .unwrap()
+    };
+
+    let executed = line_of(executed_line);
+    assert_eq!(executed.created, 1);
+    assert_eq!(executed.executed, 1);
+    assert_eq!(executed.forgotten(), 0);
+
+    let canceled = line_of(canceled_line);
+    assert_eq!(canceled.created, 1);
+    assert_eq!(canceled.canceled, 1);
+    assert_eq!(canceled.forgotten(), 0);
+
+    let forgotten = line_of(forgotten_line);
+    assert_eq!(forgotten.created, 1);
+    assert_eq!(forgotten.executed, 0);
+    assert_eq!(forgotten.forgotten(), 1);
+
+    assert_eq!(report.failures().count(), 2);
+    assert_eq!(
+        std::panic::catch_unwind(leakcheck::assert_all_executed).is_err(),
+        true
+    );
+
+    #[cfg(feature = "mt")]
+    {
+        leakcheck::reset();
+        let arc_line = line!() + 1;
+        let guard = defer_heavy::defer_arc! {};
+        drop(guard.clone());
+        drop(guard);
+
+        let downgraded_line = line!() + 1;
+        let guard = defer_heavy::defer_arc! {};
+        guard.try_downgrade().unwrap().destroy();
+
+        let report = leakcheck::report();
+        assert_eq!(report.locations.len(), 2);
+        let arc = report
+            .locations
+            .iter()
+            .find(|l| l.location.line() == arc_line)
+            .unwrap();
+        assert_eq!(arc.created, 1);
+        assert_eq!(arc.executed, 1);
+        let downgraded = report
+            .locations
+            .iter()
+            .find(|l| l.location.line() == downgraded_line)
+            .unwrap();
+        assert_eq!(downgraded.executed, 1);
+    }
+
+    leakcheck::reset();
+    leakcheck::assert_all_executed();
+    assert_eq!(leakcheck::report().locations.is_empty(), true);
+}
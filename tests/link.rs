@@ -0,0 +1,83 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::{link, DeferGuard, Deferrable};
+use std::cell::RefCell;
+
+#[test]
+pub fn test_link_drop_both() {
+    let log = RefCell::new(Vec::new());
+    {
+        let (_a, _b) = link(
+            DeferGuard::new(|| log.borrow_mut().push("a")),
+            DeferGuard::new(|| log.borrow_mut().push("b")),
+        );
+    }
+    //The later declared guard is dropped first and wins.
+    assert_eq!(*log.borrow(), ["b"]);
+}
+
+#[test]
+pub fn test_link_destroy() {
+    let log = RefCell::new(Vec::new());
+    let (a, b) = link(
+        DeferGuard::new(|| log.borrow_mut().push("a")),
+        DeferGuard::new(|| log.borrow_mut().push("b")),
+    );
+    assert_eq!(b.is_armed(), true);
+    assert_eq!(a.destroy(), true);
+    assert_eq!(b.is_claimed(), true);
+    assert_eq!(b.is_armed(), false);
+    assert_eq!(b.destroy(), false);
+    assert_eq!(*log.borrow(), ["a"]);
+}
+
+#[test]
+pub fn test_link_cancel() {
+    let log = RefCell::new(Vec::new());
+    let (a, b) = link(
+        DeferGuard::new(|| log.borrow_mut().push("a")),
+        DeferGuard::new(|| log.borrow_mut().push("b")),
+    );
+    assert_eq!(a.cancel(), true);
+    assert_eq!(b.is_claimed(), false);
+    drop(b);
+    assert_eq!(*log.borrow(), ["b"]);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_link_arc_threads() {
+    use defer_heavy::link_arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::{Arc, Barrier};
+
+    for _ in 0..100 {
+        let called = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+        let (a, b) = link_arc(
+            DeferGuard::new({
+                let called = called.clone();
+                move || {
+                    called.fetch_add(1, SeqCst);
+                }
+            }),
+            DeferGuard::new({
+                let called = called.clone();
+                move || {
+                    called.fetch_add(1, SeqCst);
+                }
+            }),
+        );
+        let other = barrier.clone();
+        let worker = std::thread::spawn(move || {
+            other.wait();
+            drop(a);
+        });
+        barrier.wait();
+        drop(b);
+        worker.join().unwrap();
+        assert_eq!(called.load(SeqCst), 1);
+    }
+}
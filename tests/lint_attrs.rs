@@ -0,0 +1,7 @@
+#![cfg(all(feature = "lint-attrs", defer_heavy_nightly))]
+
+//Only runs on nightly: cargo +nightly test --features lint-attrs --test lint_attrs
+#[test]
+pub fn test_must_not_suspend() {
+    trybuild::TestCases::new().compile_fail("tests/ui_nightly/must_not_suspend_*.rs");
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "log")]
+
+use defer_heavy::defer_log;
+use log::{Level, Log, Metadata, Record};
+use std::cell::Cell;
+use std::sync::Mutex;
+
+struct TestLogger(Mutex<Vec<(Level, String)>>);
+
+impl Log for TestLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: TestLogger = TestLogger(Mutex::new(Vec::new()));
+
+fn take_logs() -> Vec<(Level, String)> {
+    std::mem::take(&mut *LOGGER.0.lock().unwrap())
+}
+
+#[test]
+pub fn test_defer_log() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let executed = Cell::new(0);
+    {
+        let _guard = defer_log!("releasing spinlock", {
+            executed.set(executed.get() + 1);
+        });
+        let mut guard = defer_log!(level = debug, "rollback", {
+            executed.set(executed.get() + 10);
+        });
+        assert_eq!(guard.name(), "rollback");
+        assert!(guard.cancel_ref());
+        assert!(!guard.cancel_ref());
+        assert_eq!(
+            take_logs(),
+            vec![(Level::Debug, "rollback [defer_cancelled]".to_string())]
+        );
+        assert_eq!(executed.get(), 0);
+
+        let guard = defer_log!(level = warn, "early", {
+            executed.set(executed.get() + 100);
+        });
+        assert!(guard.destroy());
+        assert_eq!(
+            take_logs(),
+            vec![(Level::Warn, "early [defer_execute]".to_string())]
+        );
+    }
+
+    assert_eq!(executed.get(), 101);
+    assert_eq!(
+        take_logs(),
+        vec![(
+            Level::Trace,
+            "releasing spinlock [defer_execute]".to_string()
+        )]
+    );
+}
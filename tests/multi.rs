@@ -0,0 +1,69 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::MultiDeferGuard;
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[test]
+pub fn test_multi_order() {
+    let log = RefCell::new(Vec::new());
+    let push = |value| {
+        let log = &log;
+        move || log.borrow_mut().push(value)
+    };
+
+    {
+        let mut guard = MultiDeferGuard::<3, _>::new();
+        for i in 0..3 {
+            assert_eq!(guard.push(push(i)).is_ok(), true);
+        }
+        assert_eq!(guard.is_full(), true);
+        assert_eq!(guard.push(push(3)).is_err(), true);
+        assert_eq!(guard.len(), 3);
+    }
+    assert_eq!(*log.borrow(), [2, 1, 0]);
+}
+
+#[test]
+pub fn test_multi_cancel_and_reuse() {
+    let log = RefCell::new(Vec::new());
+    let push = |value| {
+        let log = &log;
+        move || log.borrow_mut().push(value)
+    };
+
+    let mut guard = MultiDeferGuard::<2, _>::new();
+    assert_eq!(guard.push(push(1)).is_ok(), true);
+    assert_eq!(guard.push(push(2)).is_ok(), true);
+    assert_eq!(guard.cancel_all(), 2);
+    assert_eq!(guard.is_empty(), true);
+
+    assert_eq!(guard.push(push(3)).is_ok(), true);
+    assert_eq!(guard.destroy_all(), 1);
+    assert_eq!(guard.destroy_all(), 0);
+    assert_eq!(guard.push(push(4)).is_ok(), true);
+    drop(guard);
+    assert_eq!(*log.borrow(), [3, 4]);
+}
+
+#[test]
+pub fn test_multi_panic() {
+    let log = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = MultiDeferGuard::<3, Box<dyn FnOnce()>>::new();
+        _ = guard.push(Box::new(|| log.borrow_mut().push(1)));
+        _ = guard.push(Box::new(|| panic!("second failed")));
+        _ = guard.push(Box::new(|| log.borrow_mut().push(3)));
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(*log.borrow(), [3, 1]);
+}
+
+#[test]
+pub fn test_multi_size() {
+    //Stored inline, the function pointers use the niche of Option.
+    assert_eq!(
+        size_of::<MultiDeferGuard<4, fn()>>(),
+        5 * size_of::<usize>()
+    );
+}
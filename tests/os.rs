@@ -0,0 +1,186 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "std")]
+
+use defer_heavy::error_hook;
+use defer_heavy::os::{
+    chdir_scoped, defer_remove_dir_all, defer_remove_file, defer_restore_cwd, defer_restore_env,
+    set_env_scoped,
+};
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Mutex;
+
+//The error hook, the environment and the working directory are global and file descriptor numbers are reused,
+//so the tests run one after the other.
+static LOCK: Mutex<()> = Mutex::new(());
+
+static REPORTED: AtomicUsize = AtomicUsize::new(0);
+
+fn count_error(_: &dyn Debug) {
+    REPORTED.fetch_add(1, SeqCst);
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("defer-heavy-{}-{}", std::process::id(), name))
+}
+
+#[test]
+pub fn test_os_remove_file() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = temp_path("remove-file");
+    std::fs::write(&path, b"data").unwrap();
+    {
+        let guard = defer_remove_file(&path);
+        assert_eq!(guard.exists(), true);
+    }
+    assert_eq!(path.exists(), false);
+
+    std::fs::write(&path, b"data").unwrap();
+    let kept = defer_remove_file(&path).cancel();
+    assert_eq!(kept, path);
+    assert_eq!(path.exists(), true);
+
+    assert_eq!(defer_remove_file(&path).destroy().is_ok(), true);
+    assert_eq!(path.exists(), false);
+}
+
+#[test]
+pub fn test_os_remove_dir_all() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = temp_path("remove-dir");
+    std::fs::create_dir_all(path.join("nested")).unwrap();
+    std::fs::write(path.join("nested").join("file"), b"data").unwrap();
+    {
+        let guard = defer_remove_dir_all(path.clone());
+        assert_eq!(guard.path(), path);
+    }
+    assert_eq!(path.exists(), false);
+
+    std::fs::create_dir_all(&path).unwrap();
+    defer_remove_dir_all(&path).cancel();
+    assert_eq!(path.exists(), true);
+    assert_eq!(defer_remove_dir_all(&path).destroy().is_ok(), true);
+    assert_eq!(path.exists(), false);
+}
+
+#[test]
+pub fn test_os_not_found_and_errors() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    error_hook::set(count_error);
+    let before = REPORTED.load(SeqCst);
+
+    //Removing twice is not an error.
+    let path = temp_path("not-found");
+    std::fs::write(&path, b"data").unwrap();
+    drop(defer_remove_file(&path));
+    drop(defer_remove_file(&path));
+    assert_eq!(defer_remove_file(&path).destroy().is_ok(), true);
+    drop(defer_remove_dir_all(&path));
+    assert_eq!(REPORTED.load(SeqCst), before);
+
+    //A directory cannot be removed with remove_file.
+    let dir = temp_path("not-a-file");
+    std::fs::create_dir_all(&dir).unwrap();
+    assert_eq!(defer_remove_file(&dir).destroy().is_err(), true);
+    drop(defer_remove_file(&dir));
+    assert_eq!(REPORTED.load(SeqCst), before + 1);
+
+    error_hook::reset();
+    std::fs::remove_dir(&dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+pub fn test_os_close_fd() {
+    use defer_heavy::os::defer_close_fd;
+    use std::io::Write;
+    use std::os::fd::{AsRawFd, OwnedFd};
+
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = temp_path("close-fd");
+    let _remove = defer_remove_file(&path);
+    let is_open = |fd: i32| PathBuf::from(format!("/proc/self/fd/{fd}")).exists();
+
+    let guard = defer_close_fd(OwnedFd::from(std::fs::File::create(&path).unwrap()));
+    let fd = guard.as_raw_fd();
+    assert_eq!(is_open(fd), true);
+    drop(guard);
+    assert_eq!(is_open(fd), false);
+
+    let guard = defer_close_fd(OwnedFd::from(std::fs::File::create(&path).unwrap()));
+    let mut file = std::fs::File::from(guard.into_inner());
+    file.write_all(b"kept open").unwrap();
+    drop(file);
+    assert_eq!(std::fs::read(&path).unwrap(), b"kept open");
+}
+
+#[test]
+pub fn test_os_env_unset_variable() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let key = "DEFER_HEAVY_TEST_UNSET";
+    std::env::remove_var(key);
+    {
+        let guard = set_env_scoped(key, "");
+        assert_eq!(guard.previous(), None);
+        assert_eq!(std::env::var_os(key).unwrap(), "");
+    }
+    //Removed again instead of being left empty.
+    assert_eq!(std::env::var_os(key), None);
+
+    let guard = defer_restore_env(key);
+    std::env::set_var(key, "changed");
+    guard.restore_now();
+    assert_eq!(std::env::var_os(key), None);
+
+    set_env_scoped(key, "kept").cancel();
+    assert_eq!(std::env::var(key).unwrap(), "kept");
+    std::env::remove_var(key);
+}
+
+#[test]
+pub fn test_os_env_nested() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let key = "DEFER_HEAVY_TEST_NESTED";
+    std::env::set_var(key, "outer");
+    {
+        let _first = set_env_scoped(key, "first");
+        {
+            let second = set_env_scoped(key, "second");
+            assert_eq!(second.previous().unwrap(), "first");
+            assert_eq!(std::env::var(key).unwrap(), "second");
+        }
+        assert_eq!(std::env::var(key).unwrap(), "first");
+    }
+    assert_eq!(std::env::var(key).unwrap(), "outer");
+    std::env::remove_var(key);
+}
+
+#[test]
+pub fn test_os_cwd() {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let original = std::env::current_dir().unwrap();
+    let path = temp_path("cwd");
+    std::fs::create_dir_all(&path).unwrap();
+    let path = path.canonicalize().unwrap();
+
+    let result = std::panic::catch_unwind(|| {
+        let guard = chdir_scoped(&path).unwrap();
+        assert_eq!(guard.previous(), original);
+        assert_eq!(std::env::current_dir().unwrap(), path);
+        panic!("restored during unwinding");
+    });
+    assert_eq!(result.is_err(), true);
+    assert_eq!(std::env::current_dir().unwrap(), original);
+
+    let guard = defer_restore_cwd().unwrap();
+    std::env::set_current_dir(&path).unwrap();
+    assert_eq!(guard.restore_now().is_ok(), true);
+    assert_eq!(std::env::current_dir().unwrap(), original);
+
+    assert_eq!(chdir_scoped(path.join("missing")).is_err(), true);
+    assert_eq!(std::env::current_dir().unwrap(), original);
+
+    std::fs::remove_dir(&path).unwrap();
+}
@@ -0,0 +1,51 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "std")]
+
+use defer_heavy::PanicSafeDeferGuard;
+use std::panic::catch_unwind;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+
+#[test]
+pub fn test_panic_safe_take_panic() {
+    let mut guard = PanicSafeDeferGuard::new(|| panic!("cleanup failed"));
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(guard.destroy_ref(), false);
+    let payload = guard.take_panic().unwrap();
+    assert_eq!(*payload.downcast::<&str>().unwrap(), "cleanup failed");
+    assert_eq!(guard.take_panic().is_none(), true);
+    drop(guard);
+}
+
+#[test]
+pub fn test_panic_safe_reraise() {
+    let result = catch_unwind(|| {
+        let _guard = PanicSafeDeferGuard::new(|| panic!("cleanup failed"));
+    });
+    assert_eq!(
+        *result.unwrap_err().downcast::<&str>().unwrap(),
+        "cleanup failed"
+    );
+}
+
+#[test]
+pub fn test_panic_safe_during_panic() {
+    static EXECUTED: AtomicBool = AtomicBool::new(false);
+    let result = catch_unwind(|| {
+        let _guard = PanicSafeDeferGuard::new(|| {
+            EXECUTED.store(true, SeqCst);
+            panic!("cleanup failed");
+        });
+        panic!("original");
+    });
+    assert_eq!(EXECUTED.load(SeqCst), true);
+    assert_eq!(*result.unwrap_err().downcast::<&str>().unwrap(), "original");
+}
+
+#[test]
+pub fn test_panic_safe_cancel() {
+    let guard = PanicSafeDeferGuard::new(|| panic!("canceled"));
+    assert_eq!(guard.cancel(), true);
+    let guard = PanicSafeDeferGuard::new(|| {});
+    assert_eq!(guard.destroy(), true);
+}
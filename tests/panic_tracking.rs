@@ -0,0 +1,103 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::panic_tracking::{enter_panic, exit_panic, is_panicking, panic_count};
+use defer_heavy::{ExitKind, OnSuccess, OnUnwind, PolicyGuard, StatusDeferGuard};
+use std::cell::{Cell, RefCell};
+
+//The counter is global, so the counter based path is tested in a single test.
+#[cfg(not(feature = "std"))]
+#[test]
+pub fn test_counter_hooks() {
+    assert_eq!(panic_count(), 0);
+    assert_eq!(is_panicking(), false);
+
+    let unwind = Cell::new(0);
+    let success = Cell::new(0);
+    let status = RefCell::new(Vec::new());
+    {
+        let _unwind = PolicyGuard::with_policy(|| unwind.set(unwind.get() + 1), OnUnwind);
+        let _success = PolicyGuard::with_policy(|| success.set(success.get() + 1), OnSuccess);
+        let _status = StatusDeferGuard::new(|kind| status.borrow_mut().push(kind));
+    }
+    assert_eq!(unwind.get(), 0);
+    assert_eq!(success.get(), 1);
+    assert_eq!(*status.borrow(), vec![ExitKind::Normal]);
+
+    {
+        let _unwind = PolicyGuard::with_policy(|| unwind.set(unwind.get() + 1), OnUnwind);
+        let _success = PolicyGuard::with_policy(|| success.set(success.get() + 1), OnSuccess);
+        let _status = StatusDeferGuard::new(|kind| status.borrow_mut().push(kind));
+        enter_panic();
+        assert_eq!(is_panicking(), true);
+    }
+    assert_eq!(unwind.get(), 1);
+    assert_eq!(success.get(), 1);
+    assert_eq!(
+        *status.borrow(),
+        vec![ExitKind::Normal, ExitKind::Unwinding]
+    );
+
+    //Nested panic
+    enter_panic();
+    assert_eq!(panic_count(), 2);
+    exit_panic();
+    assert_eq!(is_panicking(), true);
+    exit_panic();
+    assert_eq!(is_panicking(), false);
+
+    //Unpaired exit does not underflow
+    exit_panic();
+    assert_eq!(panic_count(), 0);
+    assert_eq!(is_panicking(), false);
+
+    {
+        let _unwind = PolicyGuard::with_policy(|| unwind.set(unwind.get() + 1), OnUnwind);
+        let _status = StatusDeferGuard::new(|kind| status.borrow_mut().push(kind));
+    }
+    assert_eq!(unwind.get(), 1);
+    assert_eq!(
+        *status.borrow(),
+        vec![ExitKind::Normal, ExitKind::Unwinding, ExitKind::Normal]
+    );
+
+    //A failed deferred assertion is skipped while the counter reports a panic.
+    let evaluated = Cell::new(false);
+    enter_panic();
+    {
+        defer_heavy::defer_assert!({
+            evaluated.set(true);
+            false
+        });
+    }
+    exit_panic();
+    assert_eq!(evaluated.get(), true);
+    let result = std::panic::catch_unwind(|| {
+        defer_heavy::defer_assert!(false);
+    });
+    assert_eq!(result.is_err(), true);
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_std_ignores_counter() {
+    let unwind = Cell::new(0);
+    let status = RefCell::new(Vec::new());
+    {
+        let _unwind = PolicyGuard::with_policy(|| unwind.set(unwind.get() + 1), OnUnwind);
+        let _success = PolicyGuard::with_policy(|| (), OnSuccess);
+        let _status = StatusDeferGuard::new(|kind| status.borrow_mut().push(kind));
+        enter_panic();
+        assert_eq!(panic_count(), 1);
+        assert_eq!(is_panicking(), false);
+    }
+    exit_panic();
+    assert_eq!(panic_count(), 0);
+    assert_eq!(unwind.get(), 0);
+    assert_eq!(*status.borrow(), vec![ExitKind::Normal]);
+
+    let result = std::panic::catch_unwind(|| {
+        let _unwind = PolicyGuard::with_policy(|| assert_eq!(is_panicking(), true), OnUnwind);
+        panic!("test");
+    });
+    assert!(result.is_err());
+}
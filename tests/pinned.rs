@@ -0,0 +1,110 @@
+#![cfg(feature = "std")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::PinnedArcDeferGuard;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::ThreadId;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+fn recording_guard(
+    executed_on: &Arc<Mutex<Vec<ThreadId>>>,
+) -> (
+    PinnedArcDeferGuard<impl FnOnce() + Send + 'static>,
+    mpsc::Receiver<Task>,
+) {
+    let (sender, receiver) = mpsc::channel::<Task>();
+    let executed_on = executed_on.clone();
+    let guard = PinnedArcDeferGuard::new_with_sender(
+        move || executed_on.lock().unwrap().push(thread::current().id()),
+        sender,
+    );
+    (guard, receiver)
+}
+
+#[test]
+pub fn test_pinned_foreign_drop() {
+    let executed_on = Arc::new(Mutex::new(Vec::new()));
+    let (guard, receiver) = recording_guard(&executed_on);
+    let home = thread::current().id();
+    assert_eq!(guard.home_thread(), home);
+
+    let worker = guard.clone();
+    drop(guard);
+    thread::spawn(move || drop(worker.own())).join().unwrap();
+    assert_eq!(executed_on.lock().unwrap().len(), 0);
+
+    for task in receiver.try_iter() {
+        task();
+    }
+    assert_eq!(*executed_on.lock().unwrap(), [home]);
+}
+
+#[test]
+pub fn test_pinned_home_drop() {
+    let executed_on = Arc::new(Mutex::new(Vec::new()));
+    let (guard, receiver) = recording_guard(&executed_on);
+    let home = thread::current().id();
+
+    let worker = guard.clone();
+    thread::spawn(move || drop(worker.own())).join().unwrap();
+    drop(guard);
+    assert_eq!(*executed_on.lock().unwrap(), [home]);
+    assert_eq!(receiver.try_iter().count(), 0);
+}
+
+#[test]
+pub fn test_pinned_home_on_other_thread() {
+    let executed_on = Arc::new(Mutex::new(Vec::new()));
+    let (guard_sender, guard_receiver) = mpsc::channel();
+    let eo = executed_on.clone();
+    //The home thread runs an event loop until the channel is closed.
+    let home = thread::spawn(move || {
+        let (guard, receiver) = recording_guard(&eo);
+        guard_sender.send(guard).unwrap();
+        drop(guard_sender);
+        for task in receiver {
+            task();
+        }
+        thread::current().id()
+    });
+
+    let guard = guard_receiver.recv().unwrap();
+    assert_ne!(guard.home_thread(), thread::current().id());
+    let clones: Vec<_> = (0..4).map(|_| guard.clone()).collect();
+    drop(guard);
+    let workers: Vec<_> = clones
+        .into_iter()
+        .map(|clone| thread::spawn(move || drop(clone.own())))
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let home = home.join().unwrap();
+    assert_eq!(*executed_on.lock().unwrap(), [home]);
+}
+
+#[test]
+pub fn test_pinned_cancel() {
+    let (sender, receiver) = mpsc::channel::<Task>();
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    let guard = PinnedArcDeferGuard::new_with_sender(
+        move || {
+            c.fetch_add(1, SeqCst);
+        },
+        sender,
+    );
+
+    let worker = guard.clone();
+    thread::spawn(move || worker.cancel()).join().unwrap();
+    assert_eq!(guard.is_canceled(), true);
+    drop(guard);
+    assert_eq!(receiver.try_iter().count(), 0);
+    assert_eq!(count.load(SeqCst), 0);
+}
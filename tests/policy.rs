@@ -0,0 +1,102 @@
+#![cfg(feature = "std")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{
+    defer_guard, Always, DeferGuard, DeferPolicy, Never, OnSuccess, OnUnwind, PolicyGuard,
+};
+use std::cell::{Cell, RefCell};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+struct OnFailures<'a> {
+    failures: &'a Cell<u32>,
+    hook_calls: &'a Cell<u32>,
+}
+
+impl DeferPolicy for OnFailures<'_> {
+    fn should_run(&self) -> bool {
+        self.failures.get() > 0
+    }
+
+    fn before_run(&self) {
+        self.hook_calls.set(self.hook_calls.get() + 1);
+    }
+}
+
+#[test]
+pub fn test_custom_policy() {
+    let failures = Cell::new(0);
+    let hook_calls = Cell::new(0);
+    let executed = Cell::new(0);
+
+    {
+        let _guard = PolicyGuard::with_policy(
+            || executed.set(executed.get() + 1),
+            OnFailures {
+                failures: &failures,
+                hook_calls: &hook_calls,
+            },
+        );
+    }
+    assert_eq!(executed.get(), 0);
+    assert_eq!(hook_calls.get(), 0);
+
+    {
+        let _guard = PolicyGuard::with_policy(
+            || executed.set(executed.get() + 1),
+            OnFailures {
+                failures: &failures,
+                hook_calls: &hook_calls,
+            },
+        );
+        failures.set(1);
+    }
+    assert_eq!(executed.get(), 1);
+    assert_eq!(hook_calls.get(), 1);
+}
+
+#[test]
+pub fn test_builtin_policies() {
+    let executed = RefCell::new(Vec::new());
+    {
+        let _always = PolicyGuard::<_, Always>::new(|| executed.borrow_mut().push("always"));
+        let _never = PolicyGuard::<_, Never>::new(|| executed.borrow_mut().push("never"));
+        let _success = PolicyGuard::<_, OnSuccess>::new(|| executed.borrow_mut().push("success"));
+        let _unwind = PolicyGuard::<_, OnUnwind>::new(|| executed.borrow_mut().push("unwind"));
+    }
+    assert_eq!(executed.borrow().as_slice(), &["success", "always"]);
+
+    executed.borrow_mut().clear();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _always = PolicyGuard::<_, Always>::new(|| executed.borrow_mut().push("always"));
+        let _success = PolicyGuard::<_, OnSuccess>::new(|| executed.borrow_mut().push("success"));
+        let _unwind = PolicyGuard::<_, OnUnwind>::new(|| executed.borrow_mut().push("unwind"));
+        panic!("fail");
+    }));
+    assert!(result.is_err());
+    assert_eq!(executed.borrow().as_slice(), &["unwind", "always"]);
+}
+
+#[test]
+pub fn test_never_destroy() {
+    let executed = Cell::new(false);
+    let guard = PolicyGuard::<_, Never>::new(|| executed.set(true));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(executed.get(), true);
+}
+
+#[test]
+pub fn test_macros_are_always() {
+    let executed = Cell::new(0);
+    {
+        let guard: PolicyGuard<_, Always> =
+            defer_guard! { executed.set(executed.get() + 1); }.into();
+        let _guard: DeferGuard<_> = guard.into();
+    }
+    assert_eq!(executed.get(), 1);
+
+    let mut guard: PolicyGuard<_, Always> =
+        defer_guard! { executed.set(executed.get() + 1); }.into();
+    assert_eq!(guard.cancel_ref(), true);
+    drop(guard);
+    assert_eq!(executed.get(), 1);
+}
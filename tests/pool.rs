@@ -0,0 +1,121 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "pool")]
+
+use defer_heavy::{defer_pool, DeferPool, PoolDeferGuard};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread;
+
+static FIRED: AtomicUsize = AtomicUsize::new(0);
+
+fn cleanup() {
+    FIRED.fetch_add(1, SeqCst);
+}
+
+#[test]
+pub fn test_pool_exhaust_and_recycle() {
+    static POOL: DeferPool<fn(), 2> = DeferPool::new();
+    let before = FIRED.load(SeqCst);
+    assert_eq!(POOL.capacity(), 2);
+    assert_eq!(POOL.available(), 2);
+
+    let first = PoolDeferGuard::try_new(&POOL, cleanup as fn()).unwrap();
+    let second = PoolDeferGuard::try_new(&POOL, cleanup as fn()).unwrap();
+    assert_eq!(POOL.available(), 0);
+    let full = PoolDeferGuard::try_new(&POOL, cleanup as fn()).unwrap_err();
+    full.into_inner()();
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+
+    let handle = first.clone();
+    assert_eq!(handle.ref_count(), 2);
+    drop(first);
+    assert_eq!(POOL.available(), 0);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+    drop(handle);
+    assert_eq!(POOL.available(), 1);
+    assert_eq!(FIRED.load(SeqCst), before + 2);
+
+    let third = PoolDeferGuard::try_new(&POOL, cleanup as fn()).unwrap();
+    assert_eq!(POOL.available(), 0);
+    drop(second);
+    drop(third);
+    assert_eq!(POOL.available(), 2);
+    assert_eq!(FIRED.load(SeqCst), before + 4);
+}
+
+#[test]
+pub fn test_pool_cancel() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    defer_pool!(POOL: fn(); 1);
+
+    let guard = PoolDeferGuard::try_new(
+        &POOL,
+        (|| {
+            COUNT.fetch_add(1, SeqCst);
+        }) as fn(),
+    )
+    .unwrap();
+    let handle = guard.clone();
+    handle.cancel();
+    assert_eq!(guard.is_canceled(), true);
+    drop(guard);
+    assert_eq!(POOL.available(), 1);
+    assert_eq!(COUNT.load(SeqCst), 0);
+
+    //A recycled slot is not canceled anymore.
+    let guard = PoolDeferGuard::try_new(
+        &POOL,
+        (|| {
+            COUNT.fetch_add(1, SeqCst);
+        }) as fn(),
+    )
+    .unwrap();
+    assert_eq!(guard.is_canceled(), false);
+    drop(guard);
+    assert_eq!(COUNT.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_pool_panic_returns_slot() {
+    defer_pool!(POOL: fn(); 1);
+
+    let guard = PoolDeferGuard::try_new(&POOL, (|| panic!("cleanup failed")) as fn()).unwrap();
+    let result = thread::spawn(move || drop(guard)).join();
+    assert_eq!(result.is_err(), true);
+    assert_eq!(POOL.available(), 1);
+}
+
+#[test]
+pub fn test_pool_threads() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    defer_pool!(POOL: fn(); 4);
+
+    for _ in 0..100 {
+        let guards: Vec<_> = (0..4)
+            .map(|_| {
+                PoolDeferGuard::try_new(
+                    &POOL,
+                    (|| {
+                        COUNT.fetch_add(1, SeqCst);
+                    }) as fn(),
+                )
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(POOL.available(), 0);
+
+        let workers: Vec<_> = guards
+            .iter()
+            .flat_map(|guard| (0..4).map(move |_| guard.clone()))
+            .map(|handle| thread::spawn(move || drop(handle)))
+            .collect();
+        drop(guards);
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(POOL.available(), 4);
+    }
+
+    assert_eq!(COUNT.load(SeqCst), 400);
+}
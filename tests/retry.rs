@@ -0,0 +1,73 @@
+#![cfg(feature = "std")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_retry, RetryDeferGuard};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+
+#[test]
+pub fn test_retry_succeeds_on_third_attempt() {
+    let calls = AtomicUsize::new(0);
+    {
+        let guard = defer_retry!(3, {
+            calls.fetch_add(1, SeqCst);
+            if calls.load(SeqCst) < 3 {
+                panic!("attempt {} failed", calls.load(SeqCst));
+            }
+        });
+        assert_eq!(guard.attempts(), 0);
+    }
+    assert_eq!(calls.load(SeqCst), 3);
+
+    calls.store(0, SeqCst);
+    let mut guard = defer_retry!(5, {
+        calls.fetch_add(1, SeqCst);
+        if calls.load(SeqCst) < 3 {
+            panic!("attempt {} failed", calls.load(SeqCst));
+        }
+    });
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(guard.attempts(), 3);
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(calls.load(SeqCst), 3);
+}
+
+#[test]
+pub fn test_retry_exhausted() {
+    let calls = AtomicUsize::new(0);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = defer_retry!(2, {
+            calls.fetch_add(1, SeqCst);
+            panic!("attempt {}", calls.load(SeqCst));
+        });
+    }));
+    assert_eq!(calls.load(SeqCst), 2);
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<String>().unwrap(), "attempt 2");
+}
+
+#[test]
+pub fn test_retry_cancel() {
+    let calls = AtomicUsize::new(0);
+    let guard = RetryDeferGuard::<_, 3>::new(|| {
+        calls.fetch_add(1, SeqCst);
+    });
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(calls.load(SeqCst), 0);
+}
+
+#[test]
+pub fn test_retry_while_panicking() {
+    let calls = AtomicUsize::new(0);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = defer_retry!(3, {
+            calls.fetch_add(1, SeqCst);
+            panic!("cleanup failed");
+        });
+        panic!("scope failed");
+    }));
+    assert_eq!(calls.load(SeqCst), 3);
+    let payload = result.unwrap_err();
+    assert_eq!(*payload.downcast_ref::<&str>().unwrap(), "scope failed");
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "alloc")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{run_with_defers, run_with_defers_try};
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[test]
+pub fn test_run_with_defers_value() {
+    let log = RefCell::new(Vec::new());
+    let value = run_with_defers(|ctx| {
+        ctx.defer(|value: &mut Vec<u32>| {
+            log.borrow_mut().push("first");
+            value.push(2);
+        });
+        ctx.always(|| log.borrow_mut().push("always"));
+        ctx.defer(|value| {
+            log.borrow_mut().push("last");
+            value.push(1);
+        });
+        assert_eq!(ctx.len(), 3);
+        vec![0]
+    });
+    assert_eq!(value, [0, 1, 2]);
+    assert_eq!(*log.borrow(), ["last", "always", "first"]);
+}
+
+#[test]
+pub fn test_run_with_defers_try() {
+    let context = |result: &mut Result<u32, String>| {
+        if let Err(error) = result {
+            error.insert_str(0, "reading: ");
+        }
+    };
+
+    let result = run_with_defers_try(|ctx| {
+        ctx.defer(context);
+        Err("eof".to_string())
+    });
+    assert_eq!(result, Err("reading: eof".to_string()));
+
+    let result = run_with_defers_try(|ctx| {
+        ctx.defer(context);
+        ctx.defer(|result| {
+            if let Ok(value) = result {
+                *value += 1;
+            }
+        });
+        Ok(1)
+    });
+    assert_eq!(result, Ok(2));
+}
+
+#[test]
+pub fn test_run_with_defers_panic() {
+    let log = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        run_with_defers(|ctx| {
+            ctx.always(|| log.borrow_mut().push("first always"));
+            ctx.defer(|_: &mut u32| log.borrow_mut().push("value"));
+            ctx.always(|| log.borrow_mut().push("second always"));
+            panic!("body failed");
+        })
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(*log.borrow(), ["second always", "first always"]);
+
+    //A panic in a value closure still calls the remaining always closures.
+    log.borrow_mut().clear();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        run_with_defers(|ctx| {
+            ctx.always(|| log.borrow_mut().push("always"));
+            ctx.defer(|_: &mut u32| log.borrow_mut().push("skipped"));
+            ctx.defer(|_: &mut u32| panic!("defer failed"));
+            0
+        })
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(*log.borrow(), ["always"]);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "alloc")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer, defer_scope_guard, ScopeDeferGuard};
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[test]
+pub fn test_scope_guard_drop_order() {
+    let log = RefCell::new(Vec::new());
+    {
+        defer! { log.borrow_mut().push("outer"); }
+        let scope = defer_scope_guard! {
+            defer! { log.borrow_mut().push("first registered"); }
+            let log = &log;
+            let name = "second registered";
+            defer_move! { log.borrow_mut().push(name); }
+        };
+        assert_eq!(scope.len(), 2);
+        assert_eq!(log.borrow().len(), 0);
+        log.borrow_mut().push("body done");
+    }
+    assert_eq!(
+        *log.borrow(),
+        [
+            "body done",
+            "second registered",
+            "first registered",
+            "outer"
+        ]
+    );
+}
+
+#[test]
+pub fn test_scope_guard_cancel_and_destroy() {
+    let log = RefCell::new(Vec::new());
+    let scope = defer_scope_guard! {
+        defer! { log.borrow_mut().push(1); }
+        defer! { log.borrow_mut().push(2); }
+    };
+    assert_eq!(scope.cancel(), 2);
+    assert_eq!(log.borrow().len(), 0);
+
+    let scope = defer_scope_guard! {
+        defer! { log.borrow_mut().push(1); }
+        defer! { log.borrow_mut().push(2); }
+    };
+    assert_eq!(scope.destroy(), 2);
+    assert_eq!(*log.borrow(), [2, 1]);
+
+    let mut scope = defer_scope_guard! {};
+    assert_eq!(scope.is_empty(), true);
+    assert_eq!(scope.destroy_ref(), 0);
+    scope.push(|| log.borrow_mut().push(3));
+    assert_eq!(scope.cancel_ref(), 1);
+    assert_eq!(scope.cancel_ref(), 0);
+    drop(scope);
+    assert_eq!(*log.borrow(), [2, 1]);
+}
+
+#[test]
+pub fn test_scope_guard_nested() {
+    let log = RefCell::new(Vec::new());
+    let outer: ScopeDeferGuard = defer_scope_guard! {
+        defer! { log.borrow_mut().push("outer"); }
+        let inner = defer_scope_guard! {
+            defer! { log.borrow_mut().push("inner"); }
+        };
+        inner.cancel();
+        defer! { log.borrow_mut().push("outer again"); }
+    };
+    assert_eq!(outer.len(), 2);
+    drop(outer);
+    assert_eq!(*log.borrow(), ["outer again", "outer"]);
+}
+
+#[test]
+pub fn test_scope_guard_panic_in_body() {
+    let log = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _scope = defer_scope_guard! {
+            defer! { log.borrow_mut().push(1); }
+            if log.borrow().is_empty() {
+                panic!("body failed");
+            }
+        };
+    }));
+    assert!(result.is_err());
+    assert_eq!(*log.borrow(), [1]);
+}
+
+#[test]
+pub fn test_scope_guard_defer_outer() {
+    let log = RefCell::new(Vec::new());
+    let scope = defer_scope_guard! {
+        for i in 0..3 {
+            defer_heavy::defer! { log.borrow_mut().push(format!("iteration {i}")); }
+            let name = format!("outer {i}");
+            let log = &log;
+            defer_move_outer! { log.borrow_mut().push(name); }
+            defer_outer! { log.borrow_mut().push(String::from("outer borrow")); }
+        }
+    };
+    assert_eq!(scope.len(), 6);
+    assert_eq!(*log.borrow(), ["iteration 0", "iteration 1", "iteration 2"]);
+
+    log.borrow_mut().clear();
+    drop(scope);
+    assert_eq!(
+        *log.borrow(),
+        [
+            "outer borrow",
+            "outer 2",
+            "outer borrow",
+            "outer 1",
+            "outer borrow",
+            "outer 0"
+        ]
+    );
+}
+
+#[test]
+pub fn test_scope_guard_defer_in_loop_is_outer() {
+    let log = RefCell::new(Vec::new());
+    let scope = defer_scope_guard! {
+        for i in 0..2 {
+            let log = &log;
+            defer_move! { log.borrow_mut().push(format!("defer {i}")); }
+            defer_move_outer! { log.borrow_mut().push(format!("outer {i}")); }
+        }
+    };
+    //In the body defer_move! registers with the scope guard, just like defer_move_outer!.
+    assert_eq!(scope.len(), 4);
+    assert_eq!(log.borrow().is_empty(), true);
+    drop(scope);
+    assert_eq!(*log.borrow(), ["outer 1", "defer 1", "outer 0", "defer 0"]);
+}
+
+#[test]
+pub fn test_scope_guard_compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/scope_guard_*.rs");
+}
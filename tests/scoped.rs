@@ -0,0 +1,139 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::ScopedDeferGuard;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+pub fn test_scoped_owner_last() {
+    let executed = AtomicUsize::new(0);
+    let data: Vec<i32> = (1..=3).collect();
+    let guard = ScopedDeferGuard::new(|| {
+        assert_eq!(data.iter().sum::<i32>(), 6);
+        executed.fetch_add(1, SeqCst);
+    });
+
+    thread::scope(|s| {
+        for _ in 0..4 {
+            let handle = guard.handle();
+            s.spawn(move || {
+                let _handle = handle;
+                thread::sleep(Duration::from_millis(10));
+            });
+        }
+    });
+
+    assert_eq!(executed.load(SeqCst), 0);
+    drop(guard);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_scoped_last_handle_executes() {
+    let executed_by = Mutex::new(None);
+    let data: Vec<i32> = (1..=3).collect();
+    let guard = ScopedDeferGuard::new(|| {
+        assert_eq!(data.len(), 3);
+        let mut executed_by = executed_by.lock().unwrap();
+        assert!(executed_by.is_none());
+        *executed_by = Some(thread::current().id());
+    });
+
+    let worker = thread::scope(|s| {
+        let handle = guard.handle();
+        let jh = s.spawn(move || {
+            let _handle = handle;
+            thread::sleep(Duration::from_millis(200));
+            thread::current().id()
+        });
+        assert_eq!(guard.release(), true);
+        assert_eq!(guard.release(), false);
+        jh.join().unwrap()
+    });
+
+    assert_eq!(*executed_by.lock().unwrap(), Some(worker));
+    drop(guard);
+    assert_eq!(*executed_by.lock().unwrap(), Some(worker));
+}
+
+#[test]
+pub fn test_scoped_release_without_handles() {
+    let executed = AtomicUsize::new(0);
+    let guard = ScopedDeferGuard::new(|| {
+        executed.fetch_add(1, SeqCst);
+    });
+    guard.release();
+    assert_eq!(executed.load(SeqCst), 1);
+    drop(guard);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_scoped_cancel() {
+    let executed = AtomicUsize::new(0);
+    let mut data = vec![1, 2, 3];
+    {
+        let guard = ScopedDeferGuard::new(|| {
+            data.clear();
+            executed.fetch_add(1, SeqCst);
+        });
+        thread::scope(|s| {
+            for i in 0..4 {
+                let handle = guard.handle();
+                s.spawn(move || {
+                    if i == 2 {
+                        handle.cancel_ref();
+                    }
+                    let _copy = handle.clone();
+                });
+            }
+        });
+        assert_eq!(guard.is_canceled(), true);
+    }
+
+    assert_eq!(executed.load(SeqCst), 0);
+    assert_eq!(data, vec![1, 2, 3]);
+}
+
+#[test]
+pub fn test_scoped_handle_after_release() {
+    let executed = AtomicUsize::new(0);
+    let guard = ScopedDeferGuard::new(|| {
+        executed.fetch_add(1, SeqCst);
+    });
+    let handle = guard.handle();
+    guard.release();
+    assert_eq!(guard.try_handle().is_some(), true);
+    drop(handle);
+    assert_eq!(executed.load(SeqCst), 1);
+
+    //The closure was executed, new handles cannot be created anymore.
+    assert_eq!(guard.try_handle().is_none(), true);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| guard.handle()));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_scoped_handle_race_with_release() {
+    for _ in 0..100 {
+        let executed = AtomicUsize::new(0);
+        let guard = ScopedDeferGuard::new(|| {
+            executed.fetch_add(1, SeqCst);
+        });
+        thread::scope(|s| {
+            let handle = guard.handle();
+            s.spawn(move || drop(handle));
+            s.spawn(|| {
+                for _ in 0..10 {
+                    drop(guard.try_handle());
+                }
+            });
+            guard.release();
+        });
+        assert_eq!(executed.load(SeqCst), 1);
+    }
+}
@@ -0,0 +1,77 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "alloc")]
+
+use defer_heavy::{DeferSlot, SendDeferSlot};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+use std::thread;
+
+struct DropCount(Rc<RefCell<u32>>);
+
+impl Drop for DropCount {
+    fn drop(&mut self) {
+        *self.0.borrow_mut() += 1;
+    }
+}
+
+#[test]
+pub fn test_slot_never_armed() {
+    let mut slot = DeferSlot::new();
+    assert_eq!(slot.is_armed(), false);
+    assert_eq!(slot.cancel(), false);
+    assert_eq!(slot.destroy(), false);
+}
+
+#[test]
+pub fn test_slot_rearm() {
+    let executed = RefCell::new(Vec::new());
+    let drops = Rc::new(RefCell::new(0));
+    {
+        let mut slot = DeferSlot::new();
+        let capture = DropCount(drops.clone());
+        let exe = &executed;
+        let previous = slot.arm(move || {
+            let _capture = &capture;
+            exe.borrow_mut().push(1);
+        });
+        assert_eq!(previous.is_none(), true);
+        assert_eq!(slot.is_armed(), true);
+        assert_eq!(*drops.borrow(), 0);
+
+        slot.arm(|| executed.borrow_mut().push(2));
+        assert_eq!(*drops.borrow(), 1);
+        assert_eq!(executed.borrow().is_empty(), true);
+    }
+    assert_eq!(executed.borrow().as_slice(), &[2]);
+    assert_eq!(*drops.borrow(), 1);
+}
+
+#[test]
+pub fn test_slot_cancel() {
+    let mut slot = DeferSlot::new();
+    slot.arm(|| unreachable!("Wont be executed"));
+    assert_eq!(slot.cancel(), true);
+    assert_eq!(slot.is_armed(), false);
+}
+
+struct Connection {
+    cleanup: SendDeferSlot<'static>,
+}
+
+#[test]
+pub fn test_send_slot_field() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let mut conn = Connection {
+        cleanup: SendDeferSlot::new(),
+    };
+    let exe = executed.clone();
+    conn.cleanup.arm(move || {
+        exe.fetch_add(1, SeqCst);
+    });
+
+    thread::spawn(move || drop(conn)).join().unwrap();
+    assert_eq!(executed.load(SeqCst), 1);
+}
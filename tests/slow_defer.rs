@@ -0,0 +1,79 @@
+#![cfg(feature = "slow-defer-warning")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer, slow_defer, DeferGuard};
+use std::panic::Location;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+static REPORTS: Mutex<Vec<(Duration, u32)>> = Mutex::new(Vec::new());
+
+fn record(elapsed: Duration, location: &'static Location<'static>) {
+    assert_eq!(location.file(), file!());
+    REPORTS.lock().unwrap().push((elapsed, location.line()));
+}
+
+fn take_reports() -> Vec<(Duration, u32)> {
+    core::mem::take(&mut *REPORTS.lock().unwrap())
+}
+
+//The hook and the threshold are global, so everything is tested in one test.
+#[test]
+pub fn test_slow_defer() {
+    slow_defer::set_hook(record);
+
+    //Slow closure with a budget.
+    let line = line!() + 1;
+    let guard = DeferGuard::new_with_budget(Duration::from_millis(10), || {
+        thread::sleep(Duration::from_millis(50));
+    });
+    drop(guard);
+    let reports = take_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].1, line);
+    assert!(reports[0].0 >= Duration::from_millis(50));
+    assert!(reports[0].0 < Duration::from_secs(5), "{:?}", reports[0].0);
+
+    //Fast closure with a budget.
+    drop(DeferGuard::new_with_budget(Duration::from_secs(1), || {}));
+    assert_eq!(take_reports().len(), 0);
+
+    //No budget and no threshold.
+    {
+        defer! { thread::sleep(Duration::from_millis(20)); }
+    }
+    assert_eq!(take_reports().len(), 0);
+
+    //Threshold for guards without a budget.
+    slow_defer::set_threshold(Some(Duration::from_millis(5)));
+    assert_eq!(slow_defer::threshold(), Some(Duration::from_millis(5)));
+    let line = line!() + 2;
+    {
+        defer! { thread::sleep(Duration::from_millis(20)); }
+    }
+    let reports = take_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].1, line);
+
+    //The budget of the guard overrides the threshold.
+    drop(DeferGuard::new_with_budget(Duration::from_secs(1), || {
+        thread::sleep(Duration::from_millis(20));
+    }));
+    //Canceled closures are not measured.
+    DeferGuard::new(|| thread::sleep(Duration::from_millis(20))).cancel();
+    assert_eq!(take_reports().len(), 0);
+
+    //The threshold is rounded up to microseconds and clamped.
+    slow_defer::set_threshold(Some(Duration::from_nanos(1500)));
+    assert_eq!(slow_defer::threshold(), Some(Duration::from_micros(2)));
+    slow_defer::set_threshold(Some(Duration::MAX));
+    assert_eq!(
+        slow_defer::threshold(),
+        Some(Duration::from_micros(usize::MAX as u64 - 1))
+    );
+
+    slow_defer::set_threshold(None);
+    assert_eq!(slow_defer::threshold(), None);
+    slow_defer::reset_hook();
+}
@@ -0,0 +1,257 @@
+#![cfg(feature = "alloc")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{DeferKey, DeferStack};
+use std::cell::RefCell;
+
+fn push<'a>(
+    stack: &mut DeferStack<'a>,
+    order: &'a RefCell<Vec<&'static str>>,
+    prio: i32,
+    name: &'static str,
+) -> DeferKey {
+    stack.push_with_priority(prio, move || order.borrow_mut().push(name))
+}
+
+#[test]
+pub fn test_stack_lifo() {
+    let order = RefCell::new(Vec::new());
+    {
+        let mut stack = DeferStack::new();
+        for i in 0..4 {
+            let order = &order;
+            stack.push(move || order.borrow_mut().push(i));
+        }
+        assert_eq!(stack.len(), 4);
+    }
+    assert_eq!(order.borrow().as_slice(), &[3, 2, 1, 0]);
+}
+
+#[test]
+pub fn test_stack_priority() {
+    let order = RefCell::new(Vec::new());
+    let mut stack = DeferStack::new();
+    push(&mut stack, &order, 0, "close file");
+    push(&mut stack, &order, 10, "flush cache a");
+    push(&mut stack, &order, -5, "log shutdown");
+    let key = push(&mut stack, &order, 10, "flush cache b");
+    push(&mut stack, &order, 0, "close socket");
+    push(&mut stack, &order, 10, "flush cache c");
+    stack.push(|| order.borrow_mut().push("anonymous"));
+
+    assert_eq!(stack.contains_key(key), true);
+    assert_eq!(stack.cancel(key), true);
+    assert_eq!(stack.cancel(key), false);
+    assert_eq!(stack.contains_key(key), false);
+
+    stack.run_all();
+    assert_eq!(stack.is_empty(), true);
+    assert_eq!(
+        order.borrow().as_slice(),
+        &[
+            "flush cache c",
+            "flush cache a",
+            "anonymous",
+            "close socket",
+            "close file",
+            "log shutdown"
+        ]
+    );
+
+    order.borrow_mut().clear();
+    push(&mut stack, &order, 1, "reused");
+    let key = push(&mut stack, &order, 2, "destroyed");
+    assert_eq!(stack.destroy(key), true);
+    drop(stack);
+    assert_eq!(order.borrow().as_slice(), &["destroyed", "reused"]);
+}
+
+#[test]
+pub fn test_stack_named() {
+    let order = RefCell::new(Vec::new());
+    let mut stack = DeferStack::new();
+    let record = |name: &'static str| {
+        let order = &order;
+        move || order.borrow_mut().push(name)
+    };
+
+    assert!(stack
+        .insert_named("temp dir", record("temp dir old"))
+        .is_none());
+    push(&mut stack, &order, 0, "anonymous 1");
+    assert!(stack
+        .insert_named(String::from("plugin"), record("plugin"))
+        .is_none());
+    push(&mut stack, &order, 0, "anonymous 2");
+
+    //Replace, the new entry is ordered by its own registration.
+    let old = stack.insert_named("temp dir", record("temp dir new"));
+    assert_eq!(stack.len(), 4);
+    assert_eq!(stack.contains("temp dir"), true);
+    assert_eq!(order.borrow().len(), 0);
+    drop(old);
+    assert_eq!(order.borrow().len(), 0);
+
+    assert_eq!(stack.cancel_named("plugin"), true);
+    assert_eq!(stack.cancel_named("plugin"), false);
+    assert_eq!(stack.contains("plugin"), false);
+    assert_eq!(stack.destroy_named("missing"), false);
+
+    drop(stack);
+    assert_eq!(
+        order.borrow().as_slice(),
+        &["temp dir new", "anonymous 2", "anonymous 1"]
+    );
+}
+
+#[test]
+pub fn test_stack_destroy_named() {
+    let order = RefCell::new(Vec::new());
+    let mut stack = DeferStack::new();
+    stack.insert_named("a", || order.borrow_mut().push("a"));
+    stack.insert_named("b", || order.borrow_mut().push("b"));
+    assert_eq!(stack.destroy_named("a"), true);
+    assert_eq!(stack.destroy_named("a"), false);
+    drop(stack);
+    assert_eq!(order.borrow().as_slice(), &["a", "b"]);
+}
+
+fn push_result<'a>(
+    stack: &mut DeferStack<'a, &'static str>,
+    order: &'a RefCell<Vec<&'static str>>,
+    name: &'static str,
+    result: Result<(), &'static str>,
+) -> DeferKey {
+    stack.push_fallible(move || {
+        order.borrow_mut().push(name);
+        result
+    })
+}
+
+#[test]
+pub fn test_stack_fallible() {
+    let order = RefCell::new(Vec::new());
+    let mut stack = DeferStack::new_fallible();
+    push_result(&mut stack, &order, "a", Ok(()));
+    push_result(&mut stack, &order, "b", Err("b failed"));
+    stack.push(|| order.borrow_mut().push("c"));
+    push_result(&mut stack, &order, "d", Err("d failed"));
+    stack.push_fallible_with_priority(10, || {
+        order.borrow_mut().push("e");
+        Ok(())
+    });
+
+    assert_eq!(
+        stack.run_all_collect(),
+        Err(vec![(3, "d failed"), (1, "b failed")])
+    );
+    assert_eq!(order.borrow().as_slice(), &["e", "d", "c", "b", "a"]);
+    assert_eq!(stack.is_empty(), true);
+    assert_eq!(stack.run_all_collect(), Ok(()));
+
+    order.borrow_mut().clear();
+    push_result(&mut stack, &order, "a", Ok(()));
+    push_result(&mut stack, &order, "b", Err("b failed"));
+    stack.push(|| order.borrow_mut().push("c"));
+    push_result(&mut stack, &order, "d", Err("d failed"));
+    assert_eq!(stack.run_all_fail_fast(), Err((8, "d failed")));
+    assert_eq!(order.borrow().as_slice(), &["d"]);
+    assert_eq!(stack.len(), 3);
+    assert_eq!(stack.run_all_fail_fast(), Err((6, "b failed")));
+    assert_eq!(order.borrow().as_slice(), &["d", "c", "b"]);
+    assert_eq!(stack.run_all_fail_fast(), Ok(()));
+    assert_eq!(order.borrow().as_slice(), &["d", "c", "b", "a"]);
+}
+
+#[test]
+pub fn test_stack_fallible_error_hook() {
+    use std::sync::Mutex;
+
+    static REPORTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn record(error: &dyn std::fmt::Debug) {
+        REPORTED.lock().unwrap().push(format!("{:?}", error));
+    }
+
+    //The hook is global, this is the only test in this file that replaces it.
+    defer_heavy::error_hook::set(record);
+    {
+        let mut stack = DeferStack::new_fallible();
+        stack.push_fallible(|| Err("first"));
+        let key = stack.push_fallible(|| Err("destroyed"));
+        stack.push_fallible(|| Ok(()));
+        stack.push_fallible(|| Err("last"));
+        assert_eq!(stack.destroy(key), true);
+    }
+    defer_heavy::error_hook::reset();
+    assert_eq!(
+        REPORTED.lock().unwrap().as_slice(),
+        &["\"destroyed\"", "\"last\"", "\"first\""]
+    );
+}
+
+#[test]
+pub fn test_stack_panic_runs_rest() {
+    let order = RefCell::new(Vec::new());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut stack = DeferStack::new();
+        push(&mut stack, &order, 0, "fifth");
+        push(&mut stack, &order, 0, "fourth");
+        stack.push(|| panic!("third"));
+        push(&mut stack, &order, 0, "second");
+        push(&mut stack, &order, 0, "first");
+        stack.run_all();
+    }));
+
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"third"));
+    assert_eq!(
+        order.borrow().as_slice(),
+        &["first", "second", "fourth", "fifth"]
+    );
+}
+
+#[test]
+pub fn test_stack_panic_on_drop_runs_rest() {
+    let order = RefCell::new(Vec::new());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut stack = DeferStack::new();
+        push(&mut stack, &order, 0, "fifth");
+        push(&mut stack, &order, 0, "fourth");
+        stack.push(|| panic!("third"));
+        push(&mut stack, &order, 0, "second");
+        push(&mut stack, &order, 0, "first");
+    }));
+
+    assert_eq!(result.is_err(), true);
+    assert_eq!(
+        order.borrow().as_slice(),
+        &["first", "second", "fourth", "fifth"]
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_stack_run_all_catching() {
+    let order = RefCell::new(Vec::new());
+    let mut stack = DeferStack::new();
+    push(&mut stack, &order, 0, "fifth");
+    stack.push(|| panic!("fourth"));
+    push(&mut stack, &order, 0, "third");
+    stack.push(|| panic!("second"));
+    push(&mut stack, &order, 0, "first");
+
+    let report = stack.run_all_catching();
+    assert_eq!(stack.is_empty(), true);
+    assert_eq!(report.called, 5);
+    assert_eq!(report.panicked, 2);
+    assert_eq!(report.is_ok(), false);
+    let payload = report.first_panic.unwrap();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"second"));
+    assert_eq!(order.borrow().as_slice(), &["first", "third", "fifth"]);
+
+    push(&mut stack, &order, 0, "again");
+    let report = stack.run_all_catching();
+    assert_eq!(report.is_ok(), true);
+    assert_eq!(report.called, 1);
+}
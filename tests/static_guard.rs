@@ -0,0 +1,83 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_static, DeferSlotCell, StaticDeferGuard};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread;
+
+static FIRED: AtomicUsize = AtomicUsize::new(0);
+
+fn cleanup() {
+    FIRED.fetch_add(1, SeqCst);
+}
+
+#[test]
+pub fn test_static_init_fire_reinit() {
+    static SLOT: DeferSlotCell<fn()> = DeferSlotCell::new();
+    let before = FIRED.load(SeqCst);
+
+    let guard = StaticDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    assert_eq!(guard.is_armed(), true);
+    assert!(StaticDeferGuard::init(&SLOT, cleanup as fn()).is_err());
+
+    let copy = guard;
+    assert_eq!(copy.fire(), true);
+    assert_eq!(guard.fire(), false);
+    assert_eq!(guard.cancel(), false);
+    assert_eq!(guard.is_armed(), false);
+    assert_eq!(FIRED.load(SeqCst), before + 1);
+
+    let second = StaticDeferGuard::init(&SLOT, cleanup as fn()).unwrap();
+    //Handles of the previous arming can not touch the new one.
+    assert_eq!(guard.cancel(), false);
+    assert_eq!(second.is_armed(), true);
+    assert_eq!(second.fire(), true);
+    assert_eq!(FIRED.load(SeqCst), before + 2);
+}
+
+#[test]
+pub fn test_static_cancel() {
+    static SLOT: DeferSlotCell<fn()> = DeferSlotCell::new();
+    static CANCELED: AtomicUsize = AtomicUsize::new(0);
+
+    let guard = defer_static!(SLOT, {
+        CANCELED.fetch_add(1, SeqCst);
+    })
+    .unwrap();
+    assert_eq!(SLOT.is_empty(), false);
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(guard.fire(), false);
+    assert_eq!(SLOT.is_empty(), true);
+    assert_eq!(CANCELED.load(SeqCst), 0);
+
+    let guard = defer_static!(SLOT, {
+        CANCELED.fetch_add(1, SeqCst);
+    })
+    .unwrap();
+    assert_eq!(guard.fire(), true);
+    assert_eq!(CANCELED.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_static_fire_race() {
+    static SLOT: DeferSlotCell<fn()> = DeferSlotCell::new();
+    static RACED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..100 {
+        let guard = defer_static!(SLOT, {
+            RACED.fetch_add(1, SeqCst);
+        })
+        .unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| thread::spawn(move || guard.fire()))
+            .collect();
+        let fired = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .filter(|f| *f)
+            .count();
+        assert_eq!(fired, 1);
+    }
+
+    assert_eq!(RACED.load(SeqCst), 100);
+}
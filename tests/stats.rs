@@ -0,0 +1,103 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "stats")]
+
+use defer_heavy::stats::{self, DeferStats};
+use defer_heavy::{defer, defer_guard};
+
+fn delta(before: DeferStats) -> DeferStats {
+    let after = stats::snapshot();
+    DeferStats {
+        created: after.created - before.created,
+        executed: after.executed - before.executed,
+        canceled: after.canceled - before.canceled,
+        currently_live: after.currently_live - before.currently_live,
+    }
+}
+
+//The counters are global, so everything is checked in a single test.
+#[test]
+pub fn test_stats() {
+    let before = stats::snapshot();
+    {
+        defer! {}
+        let guard = defer_guard! {};
+        assert_eq!(guard.cancel(), true);
+        let live = defer_guard! {};
+        assert_eq!(
+            delta(before),
+            DeferStats {
+                created: 3,
+                executed: 0,
+                canceled: 1,
+                currently_live: 2,
+            }
+        );
+        drop(live);
+    }
+
+    assert_eq!(
+        delta(before),
+        DeferStats {
+            created: 3,
+            executed: 2,
+            canceled: 1,
+            currently_live: 0,
+        }
+    );
+
+    #[cfg(feature = "mt")]
+    {
+        let before = stats::snapshot();
+        let guard = defer_heavy::defer_arc! {};
+        let clones = vec![guard.clone(), guard.clone(), guard.clone()];
+        drop(clones);
+        assert_eq!(delta(before).currently_live, 1);
+        drop(guard);
+
+        let guard = defer_heavy::defer_arc! {};
+        guard.clone().cancel();
+        drop(guard);
+
+        let guard = defer_heavy::defer_guard! {};
+        let guard = guard.upgrade();
+        guard.try_downgrade().unwrap().destroy();
+
+        assert_eq!(
+            delta(before),
+            DeferStats {
+                created: 3,
+                executed: 2,
+                canceled: 1,
+                currently_live: 0,
+            }
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    {
+        let before = stats::snapshot();
+        let mut stack = defer_heavy::DeferStack::new();
+        stack.push(|| {});
+        stack.push(|| {});
+        stack.push(|| {});
+        assert_eq!(delta(before).currently_live, 3);
+        stack.cancel_all();
+        stack.push(|| {});
+        drop(stack);
+        assert_eq!(
+            delta(before),
+            DeferStats {
+                created: 4,
+                executed: 1,
+                canceled: 3,
+                currently_live: 0,
+            }
+        );
+    }
+
+    stats::reset();
+    let after = stats::snapshot();
+    assert_eq!(after.created, 0);
+    assert_eq!(after.executed, 0);
+    assert_eq!(after.canceled, 0);
+}
@@ -0,0 +1,34 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "std")]
+
+use defer_heavy::{defer_move_with_status, defer_with_status, ExitKind};
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[test]
+pub fn test_status_normal_and_explicit() {
+    let seen = RefCell::new(Vec::new());
+    {
+        let _guard = defer_with_status!(|kind| seen.borrow_mut().push(kind));
+        let guard = defer_with_status!(|kind| seen.borrow_mut().push(kind));
+        assert_eq!(guard.destroy(), true);
+        let guard = defer_with_status!(|kind| seen.borrow_mut().push(kind));
+        assert_eq!(guard.cancel(), true);
+    }
+    assert_eq!(
+        seen.borrow().as_slice(),
+        &[ExitKind::Explicit, ExitKind::Normal]
+    );
+}
+
+#[test]
+pub fn test_status_unwinding() {
+    let seen = RefCell::new(Vec::new());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let seen = &seen;
+        let _guard = defer_move_with_status!(|kind| seen.borrow_mut().push(kind));
+        panic!("fail");
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(seen.borrow().as_slice(), &[ExitKind::Unwinding]);
+}
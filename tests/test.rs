@@ -1,4 +1,9 @@
-use defer_heavy::{defer, defer_guard, defer_move, defer_move_guard};
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{
+    defer, defer_conditioned, defer_conditioned_move, defer_guard, defer_move, defer_move_guard,
+    DeferGuard, FnMutDeferGuard,
+};
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -6,9 +11,11 @@ use std::rc::Rc;
 mod mt_test {
 
     use defer_heavy::{
-        defer, defer_arc, defer_guard, defer_move, defer_move_arc, defer_move_guard,
+        defer, defer_arc, defer_arc_final, defer_guard, defer_move, defer_move_arc,
+        defer_move_arc_final, defer_move_guard, ArcDeferGuard,
     };
     use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicUsize;
     use std::sync::atomic::Ordering::SeqCst;
     use std::sync::Arc;
     use std::thread;
@@ -63,6 +70,375 @@ mod mt_test {
         jh.join().unwrap();
     }
 
+    #[cfg(not(feature = "triomphe"))]
+    #[test]
+    pub fn test_mt_downgrade_ref() {
+        let destroyed = Arc::new(AtomicBool::new(false));
+        let des = destroyed.clone();
+        let deferred = defer_move_arc! {
+            assert_eq!(des.swap(true, SeqCst), false)
+        };
+
+        let weak = deferred.downgrade_ref();
+        assert_eq!(weak.is_pending(), true);
+        let upgraded = weak.upgrade().unwrap();
+        drop(deferred);
+        assert_eq!(destroyed.load(SeqCst), false);
+        assert_eq!(weak.clone().is_pending(), true);
+        drop(upgraded);
+        assert_eq!(destroyed.load(SeqCst), true);
+        assert_eq!(weak.is_pending(), false);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    pub fn test_mt_try_forget() {
+        let destroyed = Arc::new(AtomicBool::new(false));
+        let des = destroyed.clone();
+        let deferred = defer_move_arc! {
+            des.store(true, SeqCst);
+        };
+
+        let clone = deferred.clone();
+        let deferred = deferred.try_forget().unwrap_err();
+        drop(clone);
+        assert_eq!(deferred.try_forget().is_ok(), true);
+        assert_eq!(destroyed.load(SeqCst), false);
+        //The captured Arc was leaked
+        assert_eq!(Arc::strong_count(&destroyed), 2);
+    }
+
+    #[test]
+    pub fn test_mt_try_map() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c = calls.clone();
+        let deferred = ArcDeferGuard::new(move || {
+            c.fetch_add(1, SeqCst);
+        });
+
+        let clone = deferred.clone();
+        let deferred = deferred.try_map(|f| f).unwrap_err();
+        drop(clone);
+        let c = calls.clone();
+        let deferred = deferred
+            .try_map(|f| {
+                move || {
+                    c.fetch_add(10, SeqCst);
+                    f();
+                }
+            })
+            .unwrap();
+        assert_eq!(calls.load(SeqCst), 0);
+        drop(deferred);
+        assert_eq!(calls.load(SeqCst), 11);
+
+        let deferred = ArcDeferGuard::new(|| unreachable!());
+        deferred.cancel_ref();
+        let mut deferred = deferred.try_map(|_| -> fn() { unreachable!() }).unwrap();
+        assert_eq!(deferred.try_peek(), Some(None));
+    }
+
+    #[test]
+    pub fn test_mt_inspect_inner() {
+        let guard = ArcDeferGuard::new(|| {});
+        let state = guard.inspect_inner();
+        assert_eq!(state.strong_count, 1);
+        assert_eq!(state.is_canceled, false);
+        assert_eq!(state.has_closure, true);
+
+        let clone = guard.clone();
+        clone.cancel_ref();
+        let state = guard.inspect_inner();
+        assert_eq!(state.strong_count, 2);
+        assert_eq!(state.is_canceled, true);
+        assert_eq!(clone.inspect_inner(), state);
+        assert_eq!(
+            state.to_string(),
+            "ArcDeferGuard { references: 2, canceled: true, closure: true }"
+        );
+        drop(clone);
+
+        let erased = guard.try_erase().unwrap();
+        assert_eq!(erased.inspect_inner().has_closure, false);
+        assert_eq!(erased.inspect_inner().strong_count, 1);
+    }
+
+    #[test]
+    pub fn test_mt_final() {
+        let destroyed = Arc::new(AtomicBool::new(false));
+        let des = destroyed.clone();
+        let deferred = defer_move_arc_final! {
+            assert_eq!(des.swap(true, SeqCst), false)
+        };
+
+        let jh;
+        {
+            let deferred = deferred.clone();
+            let destroyed = destroyed.clone();
+            jh = thread::spawn(move || {
+                let _deferred = deferred.own();
+                thread::sleep(Duration::from_millis(500));
+                assert_eq!(destroyed.load(SeqCst), false);
+            });
+        }
+
+        drop(deferred);
+        assert_eq!(destroyed.load(SeqCst), false);
+        jh.join().unwrap();
+        assert_eq!(destroyed.load(SeqCst), true);
+    }
+
+    #[test]
+    pub fn test_mt_final_try_destroy() {
+        let destroyed = AtomicBool::new(false);
+        let deferred = defer_arc_final! {
+            assert_eq!(destroyed.swap(true, SeqCst), false)
+        };
+
+        let clone = deferred.clone();
+        let deferred = deferred.try_destroy().unwrap_err();
+        assert_eq!(destroyed.load(SeqCst), false);
+        drop(clone);
+        assert_eq!(deferred.try_destroy().is_ok(), true);
+        assert_eq!(destroyed.load(SeqCst), true);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn test_mt_wait_for_completion() {
+        let destroyed = Arc::new(AtomicBool::new(false));
+        let des = destroyed.clone();
+        let deferred = defer_move_arc! {
+            des.store(true, SeqCst);
+        };
+
+        let completion = deferred.wait_for_completion();
+        let released = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let threads: Vec<_> = (0..5)
+            .map(|i| {
+                let deferred = deferred.clone();
+                let released = released.clone();
+                thread::spawn(move || {
+                    let _deferred = deferred.own();
+                    thread::sleep(Duration::from_millis(50 * (i + 1)));
+                    released.fetch_add(1, SeqCst);
+                })
+            })
+            .collect();
+        drop(deferred);
+
+        assert_eq!(completion.wait_timeout(Duration::from_millis(10)), false);
+        completion.wait();
+        assert_eq!(released.load(SeqCst), 5);
+        assert_eq!(destroyed.load(SeqCst), true);
+        assert_eq!(completion.is_complete(), true);
+        for t in threads {
+            t.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn test_mt_wait_for_completion_canceled() {
+        let deferred = defer_arc! {
+            unreachable!("Wont be executed");
+        };
+
+        let completion = deferred.wait_for_completion();
+        let clone = deferred.clone();
+        deferred.cancel();
+        assert_eq!(completion.is_complete(), false);
+        drop(clone);
+        assert_eq!(completion.wait_timeout(Duration::from_millis(10)), true);
+    }
+
+    #[test]
+    pub fn test_mt_try_peek() {
+        let mut deferred = defer_arc! {};
+        assert_eq!(deferred.try_peek().unwrap().is_some(), true);
+
+        let mut clone = deferred.clone();
+        assert_eq!(deferred.try_peek().is_none(), true);
+        clone.cancel_ref();
+        assert_eq!(clone.try_peek().is_none(), true);
+        drop(clone);
+        assert_eq!(deferred.try_peek().unwrap().is_none(), true);
+    }
+
+    #[test]
+    pub fn test_mt_external_cancel() {
+        let executed = Arc::new(AtomicBool::new(false));
+        let token = Arc::new(AtomicBool::new(false));
+
+        let executed_clone = executed.clone();
+        let deferred = defer_heavy::ArcDeferGuard::with_external_cancel(
+            move || executed_clone.store(true, SeqCst),
+            token.clone(),
+        );
+        let clone = deferred.clone();
+        token.store(true, SeqCst);
+        drop(deferred);
+        drop(clone);
+        assert_eq!(executed.load(SeqCst), false);
+
+        token.store(false, SeqCst);
+        let executed_clone = executed.clone();
+        let deferred = defer_heavy::ArcDeferGuard::with_external_cancel(
+            move || executed_clone.store(true, SeqCst),
+            token.clone(),
+        );
+        assert_eq!(deferred.clone().try_destroy().is_err(), true);
+        deferred.cancel_ref();
+        drop(deferred);
+        assert_eq!(executed.load(SeqCst), false);
+
+        let executed_clone = executed.clone();
+        let deferred = defer_heavy::ArcDeferGuard::with_external_cancel(
+            move || executed_clone.store(true, SeqCst),
+            token.clone(),
+        );
+        drop(deferred);
+        assert_eq!(executed.load(SeqCst), true);
+    }
+
+    #[test]
+    pub fn test_mt_raw() {
+        use defer_heavy::ErasedArcDeferGuard;
+        use std::sync::atomic::AtomicUsize;
+
+        let executed = Arc::new(AtomicUsize::new(0));
+        let executed_clone = executed.clone();
+        let deferred: ErasedArcDeferGuard = ErasedArcDeferGuard::new(Box::new(move || {
+            executed_clone.fetch_add(1, SeqCst);
+        }));
+        let ptr = deferred.clone().into_raw() as usize;
+
+        let handle = thread::spawn(move || {
+            for _ in 0..3 {
+                let callback = unsafe { ErasedArcDeferGuard::clone_from_raw(ptr as *const ()) };
+                drop(callback);
+            }
+            drop(unsafe { ErasedArcDeferGuard::from_raw(ptr as *const ()) });
+        });
+
+        drop(deferred);
+        handle.join().unwrap();
+        assert_eq!(executed.load(SeqCst), 1);
+
+        let deferred = ErasedArcDeferGuard::new(Box::new(|| unreachable!()));
+        let ptr = deferred.clone().into_raw();
+        deferred.cancel();
+        drop(unsafe { ErasedArcDeferGuard::from_raw(ptr) });
+    }
+
+    #[test]
+    pub fn test_mt_identity() {
+        use std::collections::HashSet;
+
+        let create = || defer_arc! {};
+        let first = create();
+        let second = create();
+        assert_eq!(first.ptr_eq(&first.clone()), true);
+        assert_eq!(first == first.clone(), true);
+        assert_eq!(first.id(), first.clone().id());
+        assert_eq!(first.ptr_eq(&second), false);
+        assert_eq!(first == second, false);
+        assert_eq!(first.id() == second.id(), false);
+
+        //The hash only depends on the identity of the guard.
+        #[allow(clippy::mutable_key_type)]
+        let mut pending = HashSet::new();
+        assert_eq!(pending.insert(first.clone()), true);
+        assert_eq!(pending.insert(first.clone()), false);
+        assert_eq!(pending.insert(second.clone()), true);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending.remove(&first), true);
+        assert_eq!(pending.contains(&second), true);
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    pub fn test_mt_drop_race_exactly_once() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Barrier;
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+        for i in 0..1000 {
+            let c = count.clone();
+            let guard = defer_move_arc! { c.fetch_add(1, SeqCst); };
+            let clone = guard.clone();
+            let b = barrier.clone();
+            let worker = thread::spawn(move || {
+                b.wait();
+                drop(clone);
+            });
+            barrier.wait();
+            drop(guard);
+            worker.join().unwrap();
+            assert_eq!(count.load(SeqCst), i + 1);
+        }
+    }
+
+    #[test]
+    pub fn test_mt_try_execute_if_sole() {
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let create = || {
+            let count = count.clone();
+            let guard = defer_move_arc! { count.fetch_add(1, SeqCst); };
+            guard
+        };
+
+        let guard = create();
+        let clone = guard.clone();
+        assert_eq!(guard.try_execute_if_sole(), false);
+        assert_eq!(count.load(SeqCst), 0);
+        drop(clone);
+        assert_eq!(count.load(SeqCst), 1);
+
+        let guard = create();
+        let clone = guard.clone();
+        drop(clone);
+        assert_eq!(guard.try_execute_if_sole(), true);
+        assert_eq!(count.load(SeqCst), 2);
+    }
+
+    #[test]
+    pub fn test_mt_execute_if() {
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let create = || {
+            let count = count.clone();
+            let guard = defer_move_arc! { count.fetch_add(1, SeqCst); };
+            guard
+        };
+
+        let guard = create();
+        let clone = guard.clone();
+        assert_eq!(guard.execute_if(|| true), false);
+        assert_eq!(count.load(SeqCst), 0);
+        assert_eq!(clone.execute_if(|| true), true);
+        assert_eq!(count.load(SeqCst), 1);
+
+        let guard = create();
+        let clone = guard.clone();
+        assert_eq!(guard.execute_if(|| false), false);
+        assert_eq!(clone.execute_if(|| false), false);
+        assert_eq!(count.load(SeqCst), 2);
+
+        let guard = create();
+        guard.cancel_ref();
+        let mut evaluated = false;
+        assert_eq!(
+            guard.execute_if(|| {
+                evaluated = true;
+                true
+            }),
+            false
+        );
+        assert_eq!(evaluated, true);
+        assert_eq!(count.load(SeqCst), 2);
+    }
+
     #[test]
     pub fn test_macros_compile() {
         defer! {
@@ -88,6 +464,14 @@ mod mt_test {
         let _guard = defer_move_arc! {
             println!("HI6");
         };
+
+        let _guard = defer_arc_final! {
+            println!("HI7");
+        };
+
+        let _guard = defer_move_arc_final! {
+            println!("HI8");
+        };
     }
 }
 
@@ -171,3 +555,660 @@ pub fn test_macros_compile() {
         println!("HI4");
     };
 }
+
+#[test]
+pub fn test_fn_mut_reset() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let push = |n: i32| {
+        let c = calls.clone();
+        move || c.borrow_mut().push(n)
+    };
+    let mut guard = FnMutDeferGuard::new(push(0));
+
+    for i in 1..=5 {
+        assert_eq!(guard.destroy_ref(), true);
+        assert_eq!(guard.destroy_ref(), false);
+        assert_eq!(guard.reset(push(i)), true);
+        assert_eq!(guard.reset(push(i * 10)), false);
+        guard.destroy_ref();
+        assert_eq!(guard.rearm(), true);
+    }
+
+    drop(guard);
+    assert_eq!(
+        calls.borrow().as_slice(),
+        &[0, 10, 10, 20, 20, 30, 30, 40, 40, 50, 50]
+    );
+}
+
+#[test]
+pub fn test_fn_mut_cancel() {
+    let calls = Rc::new(RefCell::new(0));
+    let c = calls.clone();
+    let mut guard = FnMutDeferGuard::new(move || *c.borrow_mut() += 1);
+    assert_eq!(guard.cancel_ref(), true);
+    assert_eq!(guard.is_armed(), false);
+    drop(guard);
+    assert_eq!(*calls.borrow(), 0);
+}
+
+#[test]
+pub fn test_functions() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    {
+        let c = calls.clone();
+        let _scoped = defer_heavy::scoped(move || c.borrow_mut().push("scoped"));
+        let c = calls.clone();
+        let guard = defer_heavy::guard(move || c.borrow_mut().push("guard"));
+        let c = calls.clone();
+        let canceled = defer_heavy::guard(move || c.borrow_mut().push("canceled"));
+        assert_eq!(canceled.cancel(), true);
+        drop(guard);
+    }
+
+    assert_eq!(calls.borrow().as_slice(), &["guard", "scoped"]);
+}
+
+#[test]
+pub fn test_macros_return_guard() {
+    fn is_guard<F: FnOnce()>(guard: DeferGuard<F>) -> DeferGuard<F> {
+        guard
+    }
+
+    let guard = is_guard(defer_guard! {});
+    assert_eq!(guard.cancel(), true);
+    let guard = is_guard(defer_move_guard! {});
+    assert_eq!(guard.destroy(), true);
+}
+
+#[test]
+pub fn test_fn_mut_call_count() {
+    let calls = Rc::new(RefCell::new(0));
+    let c = calls.clone();
+    let mut guard = FnMutDeferGuard::new(move || *c.borrow_mut() += 1);
+    assert_eq!(guard.call_count(), 0);
+    for _ in 0..3 {
+        guard.destroy_ref();
+        guard.rearm();
+    }
+
+    guard.cancel_ref();
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(guard.call_count(), 3);
+    guard.reset_count();
+    assert_eq!(guard.call_count(), 0);
+    assert_eq!(guard.is_armed(), false);
+    assert_eq!(*calls.borrow(), 3);
+}
+
+#[test]
+pub fn test_fn_mut_max_calls() {
+    let calls = Rc::new(RefCell::new(0));
+    let c = calls.clone();
+    let mut guard = FnMutDeferGuard::with_max_calls(move || *c.borrow_mut() += 1, 2);
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(guard.rearm(), true);
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(guard.rearm(), false);
+    assert_eq!(guard.is_armed(), false);
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(guard.call_count(), 2);
+
+    guard.reset_count();
+    assert_eq!(guard.rearm(), true);
+    drop(guard);
+    assert_eq!(*calls.borrow(), 3);
+
+    let guard = FnMutDeferGuard::with_max_calls(|| unreachable!("Wont be executed"), 0);
+    assert_eq!(guard.is_armed(), false);
+}
+
+#[test]
+pub fn test_fn_mut_call_now() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let c = calls.clone();
+    let mut guard = FnMutDeferGuard::new(move || c.borrow_mut().push("now"));
+    assert_eq!(guard.call_now(), true);
+    assert_eq!(guard.is_armed(), true);
+    assert_eq!(guard.call_count(), 1);
+
+    let result = guard.with_fn_mut_access(|f| {
+        f();
+        "accessed"
+    });
+    assert_eq!(result, "accessed");
+    assert_eq!(guard.call_count(), 1);
+    drop(guard);
+    assert_eq!(calls.borrow().as_slice(), &["now", "now", "now"]);
+
+    let count = Rc::new(RefCell::new(0));
+    let c = count.clone();
+    let mut guard = FnMutDeferGuard::with_max_calls(move || *c.borrow_mut() += 1, 1);
+    assert_eq!(guard.call_now(), true);
+    assert_eq!(guard.is_armed(), false);
+    assert_eq!(guard.call_now(), false);
+    drop(guard);
+    assert_eq!(*count.borrow(), 1);
+}
+
+#[test]
+#[ignore = "calls the closure 2^32 times, run with --release --ignored"]
+pub fn test_fn_mut_call_count_overflow() {
+    let mut guard = FnMutDeferGuard::new(|| {});
+    for _ in 0..u32::MAX {
+        guard.destroy_ref();
+        guard.rearm();
+    }
+
+    assert_eq!(guard.call_count(), u32::MAX);
+    guard.destroy_ref();
+    assert_eq!(guard.call_count(), 0);
+}
+
+struct DropCount(Rc<RefCell<u32>>);
+
+impl Drop for DropCount {
+    fn drop(&mut self) {
+        *self.0.borrow_mut() += 1;
+    }
+}
+
+#[test]
+pub fn test_defer_forget() {
+    let drops = Rc::new(RefCell::new(0));
+    let capture = DropCount(drops.clone());
+    let deferred = defer_move_guard! {
+        let _capture = &capture;
+        unreachable!("Wont be executed");
+    };
+
+    assert_eq!(deferred.forget(), true);
+    assert_eq!(*drops.borrow(), 0);
+
+    let capture = DropCount(drops.clone());
+    let mut deferred = defer_move_guard! {
+        let _capture = &capture;
+    };
+    assert_eq!(deferred.destroy_ref(), true);
+    assert_eq!(*drops.borrow(), 1);
+    assert_eq!(deferred.forget(), false);
+    assert_eq!(*drops.borrow(), 1);
+}
+
+#[test]
+pub fn test_defer_conditioned_move() {
+    let drops = Rc::new(RefCell::new(0));
+    let executed = Rc::new(RefCell::new(0));
+
+    let capture = DropCount(drops.clone());
+    let exe = executed.clone();
+    let guard = defer_conditioned_move!(false, {
+        let _capture = &capture;
+        *exe.borrow_mut() += 1;
+    });
+    //The capture was moved into the canceled closure and dropped with it.
+    assert_eq!(*drops.borrow(), 1);
+    drop(guard);
+    assert_eq!(*executed.borrow(), 0);
+
+    let capture = DropCount(drops.clone());
+    let exe = executed.clone();
+    let guard = defer_conditioned_move!(*drops.borrow() == 1, {
+        let _capture = &capture;
+        *exe.borrow_mut() += 1;
+    });
+    assert_eq!(*drops.borrow(), 1);
+    drop(guard);
+    assert_eq!(*executed.borrow(), 1);
+    assert_eq!(*drops.borrow(), 2);
+}
+
+#[test]
+pub fn test_defer_conditioned() {
+    let executed = RefCell::new(0);
+    {
+        let _guard = defer_conditioned!(true, {
+            *executed.borrow_mut() += 1;
+        });
+        let _guard = defer_conditioned!(false, {
+            *executed.borrow_mut() += 10;
+        });
+    }
+    assert_eq!(*executed.borrow(), 1);
+}
+
+#[test]
+pub fn test_defer_swap() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let make = |n: u32| {
+        let order = order.clone();
+        move || order.borrow_mut().push(n)
+    };
+
+    {
+        let mut first = DeferGuard::new(make(1));
+        let mut second = DeferGuard::new(make(2));
+        first.swap(&mut second);
+        //second is dropped first and now executes the closure of first.
+    }
+    assert_eq!(order.borrow().as_slice(), &[1, 2]);
+
+    order.borrow_mut().clear();
+    {
+        let mut guard = DeferGuard::new(make(1));
+        let mut slot = Some(make(2));
+        guard.swap_option(&mut slot);
+        (slot.unwrap())();
+        let mut empty = None;
+        guard.swap_option(&mut empty);
+        assert_eq!(guard.destroy(), false);
+        (empty.unwrap())();
+    }
+    assert_eq!(order.borrow().as_slice(), &[1, 2]);
+}
+
+#[test]
+pub fn test_defer_peek() {
+    let mut guard = DeferGuard::new(|| unreachable!("Wont be executed"));
+    assert_eq!(guard.peek().is_some(), true);
+    assert_eq!(guard.cancel_ref(), true);
+    assert_eq!(guard.peek().is_none(), true);
+
+    let value = 5;
+    let guard = DeferGuard::new(move || assert_eq!(value, 5));
+    let closure = guard.peek().unwrap();
+    (*closure)();
+    assert_eq!(guard.destroy(), true);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+pub fn test_defer_extend() {
+    let order = RefCell::new(Vec::new());
+    {
+        let mut guard =
+            DeferGuard::new(Box::new(|| order.borrow_mut().push(1)) as Box<dyn FnOnce()>);
+        guard.extend(|| order.borrow_mut().push(2));
+        guard.extend(|| order.borrow_mut().push(3));
+        assert_eq!(order.borrow().is_empty(), true);
+    }
+    assert_eq!(order.borrow().as_slice(), &[1, 2, 3]);
+
+    order.borrow_mut().clear();
+    let mut guard = DeferGuard::new(Box::new(|| order.borrow_mut().push(1)) as Box<dyn FnOnce()>);
+    assert_eq!(guard.cancel_ref(), true);
+    guard.extend(|| order.borrow_mut().push(2));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(order.borrow().as_slice(), &[2]);
+}
+
+#[test]
+pub fn test_defer_result_guard() {
+    let mut error: Option<&str> = None;
+    let attempts = RefCell::new(0);
+    {
+        let _guard = defer_heavy::defer_result_guard!(&mut error, {
+            *attempts.borrow_mut() += 1;
+            Err("flush failed")
+        });
+    }
+    assert_eq!(*attempts.borrow(), 1);
+    assert_eq!(error, Some("flush failed"));
+
+    let mut error: Option<&str> = None;
+    let slot = &mut error;
+    let guard = defer_heavy::defer_result_guard!(slot, { Ok(()) });
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(error, None);
+
+    let mut error: Option<&str> = None;
+    let guard = defer_heavy::defer_result_guard!(&mut error, { Err("canceled") });
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(error, None);
+}
+
+#[test]
+pub fn test_defer_take_or_else() {
+    let order = RefCell::new(Vec::new());
+    let first: Box<dyn FnOnce()> = Box::new(|| order.borrow_mut().push(1));
+    let guard = DeferGuard::new(first);
+    let guard = guard.take_or_else(Box::new(|| order.borrow_mut().push(2)));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(order.borrow().as_slice(), &[1]);
+
+    let first: Box<dyn FnOnce()> = Box::new(|| order.borrow_mut().push(3));
+    let mut guard = DeferGuard::new(first);
+    assert_eq!(guard.cancel_ref(), true);
+    let guard = guard.take_or_else(Box::new(|| order.borrow_mut().push(4)));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(order.borrow().as_slice(), &[1, 4]);
+}
+
+#[test]
+pub fn test_defer_guard_by_ref() {
+    let mut total = 0;
+    let mut log = Vec::new();
+    let step = 2;
+    for i in 0..3 {
+        let guard = defer_heavy::defer_guard_by_ref!(&mut total, &mut log, &step => {
+            *total += *step;
+            log.push(i);
+        });
+        if i == 1 {
+            assert_eq!(guard.cancel(), true);
+        }
+    }
+
+    let guard = defer_heavy::defer_guard_by_ref!(&step => {
+        assert_eq!(*step, 2);
+    });
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(total, 4);
+    assert_eq!(log, [0, 2]);
+}
+
+#[test]
+pub fn test_defer_zip() {
+    let log = RefCell::new(Vec::new());
+    {
+        let first = defer_guard! { log.borrow_mut().push(1); };
+        let second = defer_guard! { log.borrow_mut().push(2); };
+        let _zipped = first.zip(second);
+        assert_eq!(log.borrow().len(), 0);
+    }
+    assert_eq!(*log.borrow(), [1, 2]);
+
+    log.borrow_mut().clear();
+    let mut first = defer_guard! { log.borrow_mut().push(1); };
+    let second = defer_guard! { log.borrow_mut().push(2); };
+    first.cancel_ref();
+    assert_eq!(first.zip(second).destroy(), true);
+    assert_eq!(*log.borrow(), [2]);
+
+    log.borrow_mut().clear();
+    let first = defer_guard! { log.borrow_mut().push(1); };
+    let mut second = defer_guard! { log.borrow_mut().push(2); };
+    second.cancel_ref();
+    assert_eq!(first.zip(second).destroy(), true);
+    assert_eq!(*log.borrow(), [1]);
+
+    log.borrow_mut().clear();
+    let mut first = defer_guard! { log.borrow_mut().push(1); };
+    let mut second = defer_guard! { log.borrow_mut().push(2); };
+    first.cancel_ref();
+    second.cancel_ref();
+    let mut zipped = first.zip(second);
+    assert_eq!(zipped.peek().is_none(), true);
+    assert_eq!(zipped.cancel_ref(), false);
+    assert_eq!(log.borrow().len(), 0);
+
+    let first = defer_guard! { log.borrow_mut().push(1); };
+    let second = defer_guard! { log.borrow_mut().push(2); };
+    assert_eq!(first.zip(second).cancel(), true);
+    assert_eq!(log.borrow().len(), 0);
+
+    //The second closure is called even if the first panics.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let first = defer_guard! { panic!("first failed"); };
+        let second = defer_guard! { log.borrow_mut().push(2); };
+        drop(first.zip(second));
+    }));
+    assert_eq!(result.is_err(), true);
+    assert_eq!(*log.borrow(), [2]);
+}
+
+#[test]
+pub fn test_defer_then_if_active() {
+    let log = RefCell::new(Vec::new());
+    let checks = std::cell::Cell::new(0);
+    let check = |result: bool| {
+        let checks = &checks;
+        move || {
+            checks.set(checks.get() + 1);
+            result
+        }
+    };
+
+    //Both conditions are true.
+    let guard = defer_guard! { log.borrow_mut().push(1); };
+    let guard = guard.then_if_active(check(true));
+    assert_eq!(checks.get(), 0);
+    drop(guard);
+    assert_eq!(checks.get(), 1);
+    assert_eq!(*log.borrow(), [1]);
+
+    //The check fails.
+    let guard = defer_guard! { log.borrow_mut().push(2); };
+    drop(guard.then_if_active(check(false)));
+    assert_eq!(checks.get(), 2);
+    assert_eq!(*log.borrow(), [1]);
+
+    //The guard was canceled before, the check is not needed.
+    let mut guard = defer_guard! { log.borrow_mut().push(3); };
+    guard.cancel_ref();
+    let mut guard = guard.then_if_active(check(true));
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(checks.get(), 2);
+    assert_eq!(*log.borrow(), [1]);
+
+    //Canceling the returned guard cancels the closure.
+    let guard = defer_guard! { log.borrow_mut().push(5); };
+    assert_eq!(guard.then_if_active(check(true)).cancel(), true);
+    assert_eq!(checks.get(), 2);
+    assert_eq!(*log.borrow(), [1]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+pub fn test_defer_ensure_silent_drop_panics() {
+    let called = std::cell::Cell::new(false);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _guard = defer_heavy::defer_ensure! { called.set(true); };
+    }));
+    assert_eq!(result.is_err(), true);
+    //The closure is still called before the panic.
+    assert_eq!(called.get(), true);
+}
+
+#[cfg(not(debug_assertions))]
+#[test]
+pub fn test_defer_ensure_silent_drop_release() {
+    let called = std::cell::Cell::new(false);
+    drop(DeferGuard::ensure(|| called.set(true)));
+    assert_eq!(called.get(), true);
+}
+
+#[test]
+pub fn test_defer_ensure_handled() {
+    let log = RefCell::new(Vec::new());
+    let guard = DeferGuard::ensure(|| log.borrow_mut().push(1));
+    assert_eq!(guard.cancel(), true);
+    let guard = defer_heavy::defer_ensure! { log.borrow_mut().push(2); };
+    assert_eq!(guard.destroy(), true);
+    let mut guard = DeferGuard::ensure(|| log.borrow_mut().push(3));
+    assert_eq!(guard.destroy_ref(), true);
+    drop(guard);
+    assert_eq!(*log.borrow(), [2, 3]);
+
+    //Dropping during a panic does not panic again.
+    #[cfg(feature = "std")]
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _guard = DeferGuard::ensure(|| log.borrow_mut().push(5));
+        panic!("unrelated");
+    }));
+    #[cfg(feature = "std")]
+    {
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"unrelated"));
+        assert_eq!(*log.borrow(), [2, 3, 5]);
+    }
+}
+
+#[test]
+pub fn test_defer_with_cancel_hook() {
+    let log = RefCell::new(Vec::new());
+    let push = |entry: &'static str| {
+        let log = &log;
+        move || log.borrow_mut().push(entry)
+    };
+
+    //Executed normally, the hook is not called.
+    drop(DeferGuard::new(push("executed")).with_cancel_hook(push("hook")));
+    assert_eq!(*log.borrow(), ["executed"]);
+
+    //The returned guard is canceled.
+    log.borrow_mut().clear();
+    let guard = DeferGuard::new(push("executed")).with_cancel_hook(push("hook"));
+    assert_eq!(guard.cancel(), true);
+    assert_eq!(*log.borrow(), ["hook"]);
+
+    //The guard was canceled before the hook was added.
+    log.borrow_mut().clear();
+    let mut guard = DeferGuard::new(push("executed"));
+    guard.cancel_ref();
+    assert_eq!(guard.with_cancel_hook(push("hook")).destroy(), true);
+    assert_eq!(*log.borrow(), ["hook"]);
+
+    //Composed hooks are all called on cancel and none on execution.
+    log.borrow_mut().clear();
+    let guard = DeferGuard::new(push("executed"))
+        .with_cancel_hook(push("first hook"))
+        .with_cancel_hook(push("second hook"));
+    guard.cancel();
+    assert_eq!(*log.borrow(), ["second hook", "first hook"]);
+
+    log.borrow_mut().clear();
+    let guard = DeferGuard::new(push("executed"))
+        .with_cancel_hook(push("first hook"))
+        .with_cancel_hook(push("second hook"));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(*log.borrow(), ["executed"]);
+}
+
+#[test]
+pub fn test_defer_invert_state() {
+    let log = RefCell::new(Vec::new());
+    let log = &log;
+    let push = |value| move || log.borrow_mut().push(value);
+
+    //Active guard: canceled, closure returned without being called.
+    let guard = DeferGuard::new(push(1));
+    let Err(func) = guard.invert_state(push(2)) else {
+        panic!("guard was active");
+    };
+    assert_eq!(log.borrow().len(), 0);
+    func();
+    assert_eq!(*log.borrow(), [1]);
+
+    //Canceled guard: activated with the replacement.
+    let mut guard = DeferGuard::new(push(3));
+    guard.cancel_ref();
+    let Ok(guard) = guard.invert_state(push(4)) else {
+        panic!("guard was canceled");
+    };
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(*log.borrow(), [1, 4]);
+
+    //Executed guard: activated with the replacement.
+    let mut guard = DeferGuard::new(push(5));
+    guard.destroy_ref();
+    assert_eq!(guard.invert_state(push(6)).is_ok(), true);
+    assert_eq!(*log.borrow(), [1, 4, 5, 6]);
+}
+
+#[test]
+pub fn test_defer_cancel_if_destroy_if() {
+    let calls = Rc::new(RefCell::new(0));
+    let c = calls.clone();
+    let mut guard = defer_move_guard! { *c.borrow_mut() += 1; };
+    assert_eq!(guard.cancel_if(false), false);
+    assert_eq!(guard.destroy_if(false), false);
+    assert_eq!(*calls.borrow(), 0);
+    assert_eq!(guard.destroy_if(true), true);
+    assert_eq!(*calls.borrow(), 1);
+    assert_eq!(guard.destroy_if(true), false);
+    assert_eq!(guard.cancel_if(true), false);
+
+    let c = calls.clone();
+    let mut guard = defer_move_guard! { *c.borrow_mut() += 1; };
+    assert_eq!(guard.cancel_if(true), true);
+    drop(guard);
+    assert_eq!(*calls.borrow(), 1);
+}
+
+#[test]
+pub fn test_defer_same_as() {
+    let first = defer_guard! {};
+    let second = defer_guard! {};
+    let first_ref = &first;
+    assert_eq!(first.same_as(first_ref), true);
+    assert_eq!(first.same_as(&second), false);
+    assert_eq!(second.same_as(&second), true);
+}
+
+static CANCELED_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static CANCELED_GUARD: std::sync::Mutex<DeferGuard<fn()>> =
+    std::sync::Mutex::new(DeferGuard::canceled());
+const CANCELED_CONST: DeferGuard<fn()> = DeferGuard::canceled();
+
+fn count_canceled_call() {
+    CANCELED_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[test]
+pub fn test_defer_canceled() {
+    let guard = CANCELED_CONST;
+    assert_eq!(guard.peek().is_none(), true);
+    drop(guard);
+
+    let mut guard = CANCELED_GUARD.lock().unwrap();
+    assert_eq!(guard.destroy_ref(), false);
+    assert_eq!(guard.cancel_ref(), false);
+    assert_eq!(CANCELED_CALLS.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    *guard = DeferGuard::new(count_canceled_call);
+    assert_eq!(CANCELED_CALLS.load(std::sync::atomic::Ordering::SeqCst), 0);
+    assert_eq!(guard.destroy_ref(), true);
+    assert_eq!(CANCELED_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+pub fn test_defer_into_fn() {
+    let count = RefCell::new(0);
+    let guard = defer_guard! { *count.borrow_mut() += 1; };
+    let func = guard.into_fn();
+    assert_eq!(*count.borrow(), 0);
+    func();
+    assert_eq!(*count.borrow(), 1);
+
+    let mut guard = defer_guard! { *count.borrow_mut() += 1; };
+    guard.cancel_ref();
+    guard.into_fn()();
+    assert_eq!(*count.borrow(), 1);
+
+    let guard = defer_guard! { *count.borrow_mut() += 1; };
+    drop(guard.into_fn());
+    assert_eq!(*count.borrow(), 2);
+}
+
+#[cfg(not(any(
+    feature = "leak-detect",
+    feature = "stats",
+    feature = "slow-defer-warning"
+)))]
+#[test]
+pub fn test_defer_guard_size() {
+    fn zst() {}
+
+    const fn size_of_guard<F: FnOnce()>(_: &F) -> usize {
+        size_of::<DeferGuard<F>>()
+    }
+
+    const { assert!(size_of::<DeferGuard<fn()>>() == size_of::<Option<fn()>>()) };
+    const { assert!(size_of_guard(&zst) == size_of::<bool>()) };
+    let captured = vec![1u8];
+    assert_eq!(
+        size_of_guard(&move || drop(captured)),
+        size_of::<Option<Vec<u8>>>()
+    );
+}
@@ -0,0 +1,98 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "test-util")]
+
+use defer_heavy::testing::{CallEvent, CallTracker, DropCounter, ExecProbe};
+use defer_heavy::{defer, defer_move_guard, DeferGuard};
+
+#[test]
+pub fn test_call_tracker_order() {
+    let tracker = CallTracker::new();
+    {
+        defer! { tracker.record("first"); }
+        let _second = tracker.guard("second");
+        let third = DeferGuard::new(tracker.recorder("third"));
+        assert_eq!(third.destroy(), true);
+        tracker.assert_order(&["third"]);
+    }
+
+    tracker.assert_order(&["third", "second", "first"]);
+    assert_eq!(
+        tracker.events()[1],
+        CallEvent {
+            sequence: 1,
+            label: "second"
+        }
+    );
+    tracker.clear();
+    assert_eq!(tracker.labels().is_empty(), true);
+}
+
+#[test]
+#[should_panic(expected = "unexpected call order")]
+pub fn test_call_tracker_wrong_order() {
+    let tracker = CallTracker::new();
+    tracker.record("a");
+    tracker.record("b");
+    tracker.assert_order(&["b", "a"]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_sync_call_tracker() {
+    use defer_heavy::testing::SyncCallTracker;
+    use std::thread;
+
+    let tracker = SyncCallTracker::new();
+    let guard = defer_heavy::arc_guard(tracker.recorder("last"));
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let guard = guard.clone();
+            let tracker = tracker.clone();
+            thread::spawn(move || {
+                tracker.record("worker");
+                drop(guard);
+            })
+        })
+        .collect();
+    drop(guard);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    tracker.assert_order(&["worker", "worker", "worker", "worker", "last"]);
+}
+
+#[test]
+pub fn test_defer_forget_drop_counter() {
+    let counter = DropCounter::new();
+    let token = counter.clone();
+    let deferred = defer_move_guard! {
+        let _token = &token;
+        unreachable!("Wont be executed");
+    };
+    deferred.forget();
+    assert_eq!(counter.count(), 0);
+
+    let token = counter.clone();
+    let deferred = defer_move_guard! {
+        let _token = &token;
+    };
+    assert_eq!(deferred.destroy(), true);
+    assert_eq!(counter.count(), 1);
+}
+
+#[test]
+pub fn test_exec_probe() {
+    let probe = ExecProbe::new();
+    let executed = DeferGuard::new(probe.closure());
+    let canceled = DeferGuard::new(probe.closure());
+    assert_eq!(probe.calls(), 0);
+    assert_eq!(probe.dropped(), 0);
+
+    assert_eq!(canceled.cancel(), true);
+    assert_eq!(probe.calls(), 0);
+    assert_eq!(probe.dropped(), 1);
+    drop(executed);
+    assert_eq!(probe.calls(), 1);
+    assert_eq!(probe.dropped(), 2);
+}
@@ -0,0 +1,55 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "std")]
+
+use defer_heavy::scoped_defer;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+pub fn test_scoped_defer_after_threads() {
+    let events = Mutex::new(Vec::new());
+    thread::scope(|s| {
+        let guard = scoped_defer!(s, {
+            events.lock().unwrap().push("cleanup");
+        });
+        for i in 0..4 {
+            let events = &events;
+            guard.spawn(move || {
+                thread::sleep(Duration::from_millis(10 * i));
+                events.lock().unwrap().push("thread");
+            });
+        }
+        events.lock().unwrap().push("scope end");
+    });
+
+    let events = events.into_inner().unwrap();
+    assert_eq!(events.len(), 6);
+    assert_eq!(events.contains(&"scope end"), true);
+    assert_eq!(events[5], "cleanup");
+}
+
+#[test]
+pub fn test_scoped_defer_join_handle() {
+    let cleaned = Mutex::new(false);
+    thread::scope(|s| {
+        let guard = scoped_defer!(s, {
+            *cleaned.lock().unwrap() = true;
+        });
+        let handle = guard.spawn(|| 42);
+        assert_eq!(handle.join().unwrap(), 42);
+        assert_eq!(*cleaned.lock().unwrap(), false);
+        drop(guard);
+        assert_eq!(*cleaned.lock().unwrap(), true);
+    });
+}
+
+#[test]
+pub fn test_scoped_defer_cancel() {
+    thread::scope(|s| {
+        let guard = scoped_defer!(s, { unreachable!() });
+        guard.spawn(|| thread::sleep(Duration::from_millis(10)));
+        assert_eq!(guard.cancel_ref(), true);
+        assert_eq!(guard.cancel_ref(), false);
+    });
+}
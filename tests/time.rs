@@ -0,0 +1,40 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "std")]
+
+use defer_heavy::defer_time;
+use std::cell::Cell;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+pub fn test_defer_time() {
+    let reported = Cell::new(None);
+    {
+        let timer = defer_time!(|elapsed| reported.set(Some(elapsed)));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(timer.lap() >= Duration::from_millis(50), true);
+        assert_eq!(reported.get(), None);
+    }
+    assert_eq!(reported.get().unwrap() >= Duration::from_millis(50), true);
+}
+
+#[test]
+pub fn test_defer_time_destroy() {
+    let reported = Cell::new(None);
+    let timer = defer_time!(|elapsed| reported.set(Some(elapsed)));
+    thread::sleep(Duration::from_millis(20));
+    let elapsed = timer.destroy();
+    assert_eq!(reported.get(), Some(elapsed));
+    assert_eq!(elapsed >= Duration::from_millis(20), true);
+}
+
+#[test]
+pub fn test_defer_time_cancel() {
+    let timer = defer_time!(|_| unreachable!("Wont be executed"));
+    assert_eq!(timer.cancel(), true);
+
+    let mut timer = defer_time!(|_| unreachable!("Wont be executed"));
+    assert_eq!(timer.cancel_ref(), true);
+    assert_eq!(timer.cancel_ref(), false);
+    let _ = timer.destroy();
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "std")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_expiring, defer_timeout, TimeoutDeferGuard};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+pub fn test_timeout_deadline_first() {
+    let executed_by = Arc::new(Mutex::new(Vec::new()));
+    let exe = executed_by.clone();
+    let guard = defer_timeout!(Duration::from_millis(50), {
+        exe.lock().unwrap().push(thread::current().id());
+    });
+
+    assert_eq!(guard.is_pending(), true);
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(guard.is_pending(), false);
+    drop(guard);
+
+    let executed_by = executed_by.lock().unwrap();
+    assert_eq!(executed_by.len(), 1);
+    assert_ne!(executed_by[0], thread::current().id());
+}
+
+#[test]
+pub fn test_timeout_scope_first() {
+    let executed_by = Arc::new(Mutex::new(Vec::new()));
+    let exe = executed_by.clone();
+    let guard = defer_timeout!(Duration::from_millis(200), {
+        exe.lock().unwrap().push(thread::current().id());
+    });
+    drop(guard);
+    thread::sleep(Duration::from_millis(400));
+
+    assert_eq!(
+        executed_by.lock().unwrap().as_slice(),
+        &[thread::current().id()]
+    );
+}
+
+#[test]
+pub fn test_timeout_cancel() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let guard = TimeoutDeferGuard::new(Duration::from_millis(50), move || {
+        exe.fetch_add(1, SeqCst);
+    });
+    assert_eq!(guard.cancel(), true);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(executed.load(SeqCst), 0);
+}
+
+#[test]
+pub fn test_timeout_race() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    for i in 0..200 {
+        let exe = executed.clone();
+        let guard = defer_timeout!(Duration::from_micros(i % 20), {
+            exe.fetch_add(1, SeqCst);
+        });
+        thread::sleep(Duration::from_micros(10));
+        drop(guard);
+    }
+
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(executed.load(SeqCst), 200);
+}
+
+#[test]
+pub fn test_expiring_deadline_first() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let guard = defer_expiring!(Duration::from_millis(50), {
+        exe.fetch_add(1, SeqCst);
+    });
+
+    assert_eq!(guard.is_pending(), true);
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(guard.is_pending(), false);
+    assert_eq!(guard.destroy(), false);
+    assert_eq!(executed.load(SeqCst), 0);
+    //The closure was dropped by the timer thread.
+    assert_eq!(Arc::strong_count(&executed), 1);
+}
+
+#[test]
+pub fn test_expiring_destroy_first() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let guard = TimeoutDeferGuard::new_expiring(Duration::from_millis(200), move || {
+        exe.fetch_add(1, SeqCst);
+    });
+    assert_eq!(guard.destroy(), true);
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(executed.load(SeqCst), 1);
+}
+
+#[test]
+pub fn test_timeout_panic_keeps_timer_alive() {
+    static REPORTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    fn record(error: &dyn std::fmt::Debug) {
+        REPORTED.lock().unwrap().push(format!("{:?}", error));
+    }
+
+    let previous = defer_heavy::error_hook::set(record);
+    let panicking = defer_timeout!(Duration::from_millis(10), {
+        panic!("timeout closure failure");
+    });
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(panicking.is_pending(), false);
+    drop(panicking);
+
+    //The timer thread survived the panic and still fires later deadlines.
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let guard = defer_timeout!(Duration::from_millis(10), {
+        sender.send(thread::current().id()).unwrap();
+    });
+    let fired_by = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_ne!(fired_by, thread::current().id());
+    drop(guard);
+    defer_heavy::error_hook::set(previous);
+
+    let reported = REPORTED.lock().unwrap();
+    assert_eq!(
+        reported
+            .iter()
+            .any(|entry| entry.contains("timeout closure failure")),
+        true
+    );
+}
+
+#[test]
+pub fn test_timeout_without_deadline() {
+    let executed = Arc::new(AtomicUsize::new(0));
+    let exe = executed.clone();
+    let guard = TimeoutDeferGuard::new(Duration::MAX, move || {
+        exe.fetch_add(1, SeqCst);
+    });
+    assert_eq!(guard.is_pending(), true);
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(executed.load(SeqCst), 1);
+
+    let guard = TimeoutDeferGuard::new_expiring(Duration::MAX, || unreachable!());
+    assert_eq!(guard.cancel(), true);
+}
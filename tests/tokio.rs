@@ -0,0 +1,97 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(feature = "tokio")]
+
+use defer_heavy::TokioDeferGuard;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn slow_closure(done: &Arc<AtomicBool>) -> impl FnOnce() + Send + 'static {
+    let done = done.clone();
+    move || {
+        thread::sleep(Duration::from_millis(300));
+        done.store(true, SeqCst);
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+pub async fn test_tokio_drop_does_not_block() {
+    let done = Arc::new(AtomicBool::new(false));
+    let guard = TokioDeferGuard::new(slow_closure(&done));
+
+    let start = Instant::now();
+    drop(guard);
+    assert!(start.elapsed() < Duration::from_millis(200));
+    assert_eq!(done.load(SeqCst), false);
+
+    //The only executor thread is free to drive the timer while the closure runs.
+    let ticker = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    });
+    ticker.await.unwrap();
+    assert_eq!(done.load(SeqCst), false);
+
+    while !done.load(SeqCst) {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn test_tokio_destroy_waits() {
+    let done = Arc::new(AtomicBool::new(false));
+    let guard = TokioDeferGuard::new(slow_closure(&done));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(done.load(SeqCst), true);
+}
+
+#[tokio::test(flavor = "current_thread")]
+pub async fn test_tokio_destroy_current_thread() {
+    let done = Arc::new(AtomicBool::new(false));
+    let guard = TokioDeferGuard::new(slow_closure(&done));
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(done.load(SeqCst), true);
+}
+
+#[tokio::test]
+pub async fn test_tokio_cancel() {
+    let done = Arc::new(AtomicBool::new(false));
+    let mut guard = TokioDeferGuard::new(slow_closure(&done));
+    assert_eq!(guard.is_canceled(), false);
+    assert_eq!(guard.cancel_ref(), true);
+    assert_eq!(guard.is_canceled(), true);
+    assert_eq!(guard.destroy(), false);
+
+    let guard = TokioDeferGuard::new(slow_closure(&done));
+    assert_eq!(guard.cancel(), true);
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(done.load(SeqCst), false);
+}
+
+#[test]
+pub fn test_tokio_drop_outside_runtime() {
+    let done = Arc::new(AtomicBool::new(false));
+    drop(TokioDeferGuard::new(slow_closure(&done)));
+    assert_eq!(done.load(SeqCst), true);
+}
+
+#[test]
+pub fn test_tokio_drop_during_runtime_shutdown() {
+    let done = Arc::new(AtomicBool::new(false));
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .build()
+        .unwrap();
+    let guard = TokioDeferGuard::new(slow_closure(&done));
+    runtime.spawn(async move {
+        let _guard = guard;
+        std::future::pending::<()>().await;
+    });
+    thread::sleep(Duration::from_millis(50));
+
+    //The pending task is dropped during the shutdown, the blocking task it spawns is never executed.
+    drop(runtime);
+    assert_eq!(done.load(SeqCst), true);
+}
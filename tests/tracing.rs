@@ -0,0 +1,130 @@
+#![allow(clippy::bool_assert_comparison)]
+#![cfg(all(feature = "tracing", feature = "mt"))]
+
+use defer_heavy::{defer_arc, defer_guard};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+#[derive(Default, Clone)]
+struct Collector(
+    Arc<Mutex<Vec<(String, String, u32)>>>,
+    Arc<Mutex<Vec<String>>>,
+);
+
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    name: String,
+    line: u32,
+    elapsed: Option<String>,
+}
+
+impl Visit for EventVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "line" {
+            self.line = value as u32;
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.name = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else if field.name() == "elapsed" {
+            self.elapsed = Some(format!("{:?}", value));
+        }
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        if let Some(elapsed) = visitor.elapsed {
+            self.1.lock().unwrap().push(elapsed);
+        }
+        self.0
+            .lock()
+            .unwrap()
+            .push((visitor.name, visitor.message, visitor.line));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+pub fn test_tracing_events() {
+    let collector = Collector::default();
+    let events = collector.0.clone();
+    tracing::subscriber::with_default(collector, || {
+        let line = line!() + 1;
+        let guard = defer_guard! { println!("executed"); };
+        drop(guard);
+
+        let mut guard = defer_guard! { println!("canceled"); };
+        let cancel_line = line!() + 1;
+        guard.cancel_ref();
+        drop(guard);
+
+        let arc_line = line!() + 1;
+        let guard = defer_arc! { println!("arc executed"); };
+        drop(guard.clone());
+        drop(guard);
+
+        let events = events.lock().unwrap();
+        let expected: Vec<(String, String, u32)> = vec![
+            ("defer_guard".into(), "defer registered".into(), line),
+            ("defer_guard".into(), "defer executing".into(), line),
+            ("defer_guard".into(), "defer executed".into(), line),
+            ("defer_guard".into(), "defer registered".into(), line + 3),
+            ("defer_guard".into(), "defer canceled".into(), cancel_line),
+            ("defer_arc".into(), "defer registered".into(), arc_line),
+            ("defer_arc".into(), "defer executing".into(), arc_line),
+            ("defer_arc".into(), "defer executed".into(), arc_line),
+        ];
+        assert_eq!(events.as_slice(), expected.as_slice());
+    });
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_tracing_defer_time() {
+    let collector = Collector::default();
+    let events = collector.0.clone();
+    let elapsed = collector.1.clone();
+    tracing::subscriber::with_default(collector, || {
+        let line = line!() + 1;
+        let timer = defer_heavy::defer_time!(|_| {});
+        let reported = timer.destroy();
+
+        let events = events.lock().unwrap();
+        let expected: Vec<(String, String, u32)> =
+            vec![("defer_time".into(), "defer executing".into(), line)];
+        assert_eq!(events.as_slice(), expected.as_slice());
+        assert_eq!(elapsed.lock().unwrap().len(), 1);
+        assert_eq!(elapsed.lock().unwrap()[0].is_empty(), false);
+        assert_eq!(reported > std::time::Duration::ZERO, true);
+    });
+}
@@ -0,0 +1,14 @@
+use defer_heavy::defer_drop;
+
+struct Connection;
+
+#[defer_drop(close)]
+impl Connection {
+    fn close(&mut self) {}
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {}
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use defer_heavy::defer_drop;
+
+struct Connection;
+
+#[defer_drop(close)]
+impl Connection {
+    fn shutdown(&mut self) {}
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use defer_heavy::defer_drop;
+
+struct Connection;
+
+#[defer_drop(clone)]
+impl Clone for Connection {
+    fn clone(&self) -> Self {
+        Connection
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use defer_heavy::defer_scope_guard;
+
+fn main() {
+    let scope = defer_scope_guard! {
+        let local = String::from("dropped at the end of the body");
+        defer! { println!("{}", local); }
+    };
+    drop(scope);
+}
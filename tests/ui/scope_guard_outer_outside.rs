@@ -0,0 +1,7 @@
+use defer_heavy::defer_outer;
+
+fn main() {
+    for i in 0..3 {
+        defer_outer! { println!("{}", i); }
+    }
+}
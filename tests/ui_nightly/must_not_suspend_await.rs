@@ -0,0 +1,18 @@
+#![feature(must_not_suspend)]
+#![deny(must_not_suspend)]
+
+use defer_heavy::defer_guard;
+
+async fn commit() {}
+
+async fn transaction() {
+    let rollback = defer_guard! {
+        println!("Rollback");
+    };
+    commit().await;
+    rollback.cancel();
+}
+
+fn main() {
+    drop(transaction());
+}
@@ -0,0 +1,71 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::DeferExt;
+use std::cell::RefCell;
+
+struct Resource<'a> {
+    name: &'static str,
+    log: &'a RefCell<Vec<String>>,
+}
+
+impl<'a> Resource<'a> {
+    fn open(name: &'static str, log: &'a RefCell<Vec<String>>) -> Result<Self, ()> {
+        log.borrow_mut().push(format!("open {}", name));
+        Ok(Self { name, log })
+    }
+
+    fn cleanup(self) {
+        self.log.borrow_mut().push(format!("cleanup {}", self.name));
+    }
+}
+
+fn setup<'a>(log: &'a RefCell<Vec<String>>, fail: bool) -> Result<Resource<'a>, ()> {
+    let resource = Resource::open("a", log)?.defer(Resource::cleanup);
+    log.borrow_mut().push(format!("using {}", resource.name));
+    if fail {
+        return Err(());
+    }
+    Ok(resource.into_inner())
+}
+
+#[test]
+pub fn test_defer_ext() {
+    let log = RefCell::new(Vec::new());
+    let resource = setup(&log, false).unwrap();
+    assert_eq!(resource.name, "a");
+    assert_eq!(log.borrow().as_slice(), &["open a", "using a"]);
+
+    log.borrow_mut().clear();
+    assert_eq!(setup(&log, true).is_err(), true);
+    assert_eq!(log.borrow().as_slice(), &["open a", "using a", "cleanup a"]);
+}
+
+#[test]
+pub fn test_defer_deref_mut() {
+    let result = RefCell::new(Vec::new());
+    {
+        let mut values = vec![1].defer(|v| *result.borrow_mut() = v);
+        values.push(2);
+        assert_eq!(values.len(), 2);
+    }
+    assert_eq!(result.borrow().as_slice(), &[1, 2]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+pub fn test_defer_on_unwind() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let result = RefCell::new(Vec::new());
+    {
+        let _value = 1.defer_on_unwind(|v| result.borrow_mut().push(v));
+    }
+    assert_eq!(result.borrow().is_empty(), true);
+
+    let err = catch_unwind(AssertUnwindSafe(|| {
+        let _value = 2.defer_on_unwind(|v| result.borrow_mut().push(v));
+        panic!("fail");
+    }));
+    assert_eq!(err.is_err(), true);
+    assert_eq!(result.borrow().as_slice(), &[2]);
+}
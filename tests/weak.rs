@@ -0,0 +1,111 @@
+#![cfg(feature = "alloc")]
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_move_weak, defer_weak, DeferGuard};
+use std::cell::Cell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+
+struct Session<'a>(&'a AtomicUsize);
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, SeqCst);
+    }
+}
+
+#[test]
+pub fn test_weak_target_alive() {
+    let dropped = AtomicUsize::new(0);
+    let session = Arc::new(Session(&dropped));
+    let strong_in_closure = Cell::new(0);
+    {
+        let _guard = DeferGuard::new_with_weak(Arc::downgrade(&session), |target| {
+            strong_in_closure.set(Arc::strong_count(&target));
+        });
+        assert_eq!(Arc::strong_count(&session), 1);
+    }
+    assert_eq!(strong_in_closure.get(), 2);
+    //The temporary strong reference was released after the closure.
+    assert_eq!(Arc::strong_count(&session), 1);
+    assert_eq!(dropped.load(SeqCst), 0);
+}
+
+#[test]
+pub fn test_weak_target_dropped_first() {
+    let dropped = AtomicUsize::new(0);
+    let session = Arc::new(Session(&dropped));
+    let called = Cell::new(false);
+    let guard = DeferGuard::new_with_weak(Arc::downgrade(&session), |_| called.set(true));
+
+    //The guard does not keep the session alive.
+    drop(session);
+    assert_eq!(dropped.load(SeqCst), 1);
+    assert_eq!(guard.destroy(), true);
+    assert_eq!(called.get(), false);
+}
+
+#[test]
+pub fn test_weak_upgrade_at_drop() {
+    let dropped = AtomicUsize::new(0);
+    let session = Arc::new(Session(&dropped));
+    let weak = Arc::downgrade(&session);
+    let called = Cell::new(0);
+    let first = DeferGuard::new_with_weak(weak.clone(), |_| called.set(called.get() + 1));
+    let second = DeferGuard::new_with_weak(weak, |_| called.set(called.get() + 1));
+    drop(first);
+    drop(session);
+    drop(second);
+    assert_eq!(called.get(), 1);
+}
+
+#[test]
+pub fn test_weak_macros() {
+    let dropped = AtomicUsize::new(0);
+    let session = Arc::new(Session(&dropped));
+    let log = Cell::new(0);
+    {
+        defer_weak!(Arc::downgrade(&session), |target| {
+            log.set(log.get() + target.0.load(SeqCst) + 1);
+        });
+        let log = &log;
+        defer_move_weak!(Arc::downgrade(&session), |_target| log.set(log.get() + 10));
+    }
+    assert_eq!(log.get(), 11);
+
+    {
+        let temporary = Arc::new(Session(&dropped));
+        defer_weak!(Arc::downgrade(&temporary), |_target| log.set(0));
+        drop(temporary);
+    }
+    assert_eq!(log.get(), 11);
+    assert_eq!(dropped.load(SeqCst), 1);
+}
+
+#[cfg(feature = "mt")]
+#[test]
+pub fn test_weak_arc_guard() {
+    use defer_heavy::ArcDeferGuard;
+
+    let session = Arc::new(AtomicUsize::new(0));
+    let guard = ArcDeferGuard::new_with_weak(Arc::downgrade(&session), |target| {
+        target.fetch_add(1, SeqCst);
+    });
+    let clone = guard.clone();
+    std::thread::spawn(move || drop(clone)).join().unwrap();
+    assert_eq!(Arc::strong_count(&session), 1);
+    drop(guard);
+    assert_eq!(session.load(SeqCst), 1);
+    assert_eq!(Arc::strong_count(&session), 1);
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let target = Arc::new(AtomicUsize::new(0));
+    let count = counter.clone();
+    let guard = ArcDeferGuard::new_with_weak(Arc::downgrade(&target), move |_| {
+        count.fetch_add(1, SeqCst);
+    });
+    drop(target);
+    drop(guard);
+    assert_eq!(counter.load(SeqCst), 0);
+}
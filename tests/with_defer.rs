@@ -0,0 +1,60 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use defer_heavy::{defer_move_on_drop_of, defer_on_drop_of, guard, WithDefer};
+use std::cell::RefCell;
+
+struct Connection<'a>(&'a RefCell<Vec<&'static str>>);
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        self.0.borrow_mut().push("connection dropped");
+    }
+}
+
+#[test]
+pub fn test_defer_on_drop_of_scope() {
+    let log = RefCell::new(Vec::new());
+    {
+        let connection = Connection(&log);
+        defer_on_drop_of!(connection, {
+            log.borrow_mut().push("cleanup");
+        });
+        connection.0.borrow_mut().push("used");
+    }
+    assert_eq!(*log.borrow(), ["used", "connection dropped", "cleanup"]);
+}
+
+#[test]
+pub fn test_defer_on_drop_of_moved() {
+    let log = RefCell::new(Vec::new());
+    let mut connections = Vec::new();
+    {
+        let connection = Connection(&log);
+        defer_on_drop_of!(connection, log.borrow_mut().push("cleanup"));
+        //Moved, still alive
+        connections.push(connection);
+    }
+    assert_eq!(log.borrow().len(), 0);
+    drop(connections);
+    assert_eq!(*log.borrow(), ["connection dropped", "cleanup"]);
+}
+
+#[test]
+pub fn test_defer_move_on_drop_of() {
+    let log = RefCell::new(Vec::new());
+    let data = vec![1, 2];
+    let tag = String::from("cleanup");
+    defer_move_on_drop_of!(data, {
+        assert_eq!(tag, "cleanup");
+    });
+    data.push(3);
+    assert_eq!(data.len(), 3);
+    assert_eq!(data.guard_mut().cancel_ref(), true);
+    drop(data);
+
+    let connection = WithDefer::new(Connection(&log), guard(|| log.borrow_mut().push("cleanup")));
+    let connection = connection.into_inner();
+    assert_eq!(log.borrow().len(), 0);
+    drop(connection);
+    assert_eq!(*log.borrow(), ["connection dropped"]);
+}